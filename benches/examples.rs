@@ -0,0 +1,33 @@
+use chicken::VMBuilder;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn run(name: &str, input: &str) -> String {
+    VMBuilder::from_path(format!("examples/{}.chicken", name))
+        .unwrap()
+        .input(input)
+        .build()
+        .run()
+        .unwrap()
+}
+
+fn examples(c: &mut Criterion) {
+    c.bench_function("quine", |b| b.iter(|| run("quine", "")));
+    c.bench_function("cat", |b| b.iter(|| run("cat", "meow")));
+    c.bench_function("deadfish", |b| b.iter(|| run("deadfish", "iissiso")));
+
+    let mut group = c.benchmark_group("99chickens");
+    for bottles in [9, 99, 1024, 5000] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(bottles),
+            &bottles,
+            |b, bottles| {
+                let input = bottles.to_string();
+                b.iter(|| run("99chickens", &input))
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, examples);
+criterion_main!(benches);
@@ -0,0 +1,30 @@
+use chicken::VMBuilder;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+fn stack(c: &mut Criterion) {
+    c.bench_function("push and add 100k numbers", |b| {
+        // pushes 1, then pushes and adds 1 to it over and over
+        let mut opcodes = vec![11];
+        for _ in 0..100_000 {
+            opcodes.extend([11, 2]);
+        }
+        // and turns the result into a string so it's a valid exit value
+        opcodes.extend([1, 2]);
+
+        b.iter(|| VMBuilder::from_opcodes(opcodes.clone()).build().run())
+    });
+
+    c.bench_function("deadfish", |b| {
+        let input = std::fs::read_to_string("examples/deadfish.input").unwrap();
+        let builder = || {
+            VMBuilder::from_path("examples/deadfish.chicken")
+                .unwrap()
+                .input(input.as_str())
+        };
+
+        b.iter(|| builder().build().run())
+    });
+}
+
+criterion_group!(benches, stack);
+criterion_main!(benches);
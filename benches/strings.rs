@@ -0,0 +1,59 @@
+use chicken::VMBuilder;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+// loads the input `times` times, leaving every copy of it on the stack
+fn load_input(times: usize) -> Vec<isize> {
+    [11, 6, 0].repeat(times)
+}
+
+fn strings(c: &mut Criterion) {
+    let input = "chicken ".repeat(16 * 1024);
+
+    c.bench_function("load a 128k string 1000 times", |b| {
+        let opcodes = load_input(1000);
+        b.iter(|| {
+            VMBuilder::from_opcodes(opcodes.clone())
+                .input(input.as_str())
+                .build()
+                .run()
+        })
+    });
+
+    c.bench_function("fork with 100 128k strings on the stack", |b| {
+        let mut vm = VMBuilder::from_opcodes(load_input(100))
+            .input(input.as_str())
+            .build();
+        for _ in 0..200 {
+            vm.step().unwrap();
+        }
+
+        b.iter(|| vm.fork())
+    });
+
+    c.bench_function("error with 100 128k strings on the stack", |b| {
+        // Char fails on a string with normal_char set, copying the stack into the error
+        let mut opcodes = load_input(100);
+        opcodes.push(9);
+
+        b.iter(|| {
+            VMBuilder::from_opcodes(opcodes.clone())
+                .input(input.as_str())
+                .normal_char()
+                .build()
+                .run()
+        })
+    });
+
+    c.bench_function("99 chickens from 2000", |b| {
+        let builder = || {
+            VMBuilder::from_path("examples/99chickens.chicken")
+                .unwrap()
+                .input(2000)
+        };
+
+        b.iter(|| builder().build().run())
+    });
+}
+
+criterion_group!(benches, strings);
+criterion_main!(benches);
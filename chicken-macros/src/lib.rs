@@ -0,0 +1,72 @@
+//! compile time Chicken programs for chicken-rs. these are re-exported by chicken-rs when its `macros` feature is enabled,
+//! so there's usually no need to depend on this crate directly
+
+use proc_macro::TokenStream;
+use proc_macro2::Literal;
+use quote::quote;
+use std::path::PathBuf;
+use syn::{parse_macro_input, LitStr};
+
+/// reads a Chicken program at compile time, expanding to an array of its opcodes.
+/// the path is relative to the root of the crate using the macro, like `CARGO_MANIFEST_DIR`,
+/// and the crate is rebuilt whenever the file changes
+///
+/// # Example
+///
+/// ```rust
+/// use chicken_macros::chicken;
+///
+/// // the file ends with an empty line, which is an exit
+/// const CAT: [isize; 4] = chicken!("../examples/cat.chicken");
+/// assert_eq!(CAT, [11, 6, 0, 0]);
+/// ```
+#[proc_macro]
+pub fn chicken(input: TokenStream) -> TokenStream {
+    let path = parse_macro_input!(input as LitStr);
+
+    let mut full_path = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap_or_default());
+    full_path.push(path.value());
+
+    let code = match std::fs::read(&full_path) {
+        // zero bytes only turn up in UTF-16, which chicken-rs can decode at runtime but this doesn't bother with
+        Ok(bytes)
+            if bytes.starts_with(&[0xff, 0xfe])
+                || bytes.starts_with(&[0xfe, 0xff])
+                || bytes.contains(&0) =>
+        {
+            Err(format!(
+                "{} looks like UTF-16, save it as UTF-8 instead",
+                full_path.display()
+            ))
+        }
+        Ok(bytes) => String::from_utf8(bytes)
+            .map_err(|err| format!("couldn't read {}: {}", full_path.display(), err)),
+        Err(err) => Err(format!("couldn't read {}: {}", full_path.display(), err)),
+    };
+    let code = match code {
+        Ok(code) => code,
+        Err(message) => {
+            return syn::Error::new(path.span(), message)
+                .to_compile_error()
+                .into()
+        }
+    };
+
+    // this has to count chickens the same way Program::from_chicken does
+    let code = code.strip_prefix('\u{feff}').unwrap_or(&code);
+    let opcodes = code
+        .split("\r\n")
+        .flat_map(|line| line.split(['\r', '\n']))
+        .map(|l| Literal::isize_suffixed(l.matches("chicken").count() as isize));
+
+    let full_path = full_path.to_string_lossy();
+
+    quote! {
+        {
+            // makes cargo rebuild the crate when the program changes
+            const _: &[u8] = include_bytes!(#full_path);
+            [#(#opcodes),*]
+        }
+    }
+    .into()
+}
@@ -0,0 +1,21 @@
+#![no_main]
+
+use chicken::{Limits, Program, VMBuilder};
+use libfuzzer_sys::fuzz_target;
+
+// runs random programs with random input, which should never panic or use more memory than the limits allow.
+// errors are fine, since most random programs will do something invalid eventually
+fuzz_target!(|data: (Program, String)| {
+    let (program, input) = data;
+
+    let _ = VMBuilder::from_program(program)
+        .input(input)
+        .limits(Limits {
+            max_steps: Some(10_000),
+            max_stack: Some(10_000),
+            max_string_len: Some(1 << 20),
+            ..Default::default()
+        })
+        .build()
+        .run();
+});
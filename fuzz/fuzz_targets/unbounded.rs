@@ -0,0 +1,20 @@
+#![no_main]
+
+use chicken::{Limits, Program, VMBuilder};
+use libfuzzer_sys::fuzz_target;
+
+// runs random programs like the run target, but with nothing but a step limit, so strings can grow as long as
+// the program can make them. this is what reaches the overflows and huge allocations in the stats and the ropes,
+// which the string length limit in the run target stops long before they could happen
+fuzz_target!(|data: (Program, String)| {
+    let (program, input) = data;
+
+    let _ = VMBuilder::from_program(program)
+        .input(input)
+        .limits(Limits {
+            max_steps: Some(10_000),
+            ..Default::default()
+        })
+        .build()
+        .run();
+});
@@ -0,0 +1,364 @@
+use crate::{Instruction, Program, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Write},
+};
+
+// how many different values a stack slot can hold before it's treated as unknown
+const MAX_VALUES: usize = 16;
+
+// how many stack slots are tracked at each instruction, counting down from the top
+const MAX_DEPTH: usize = 64;
+
+/// a jump that can land on the operand of a Load, found by [Program::jump_alignment].
+/// the operand is then run as an instruction of its own, and everything after it is decoded differently than it was meant to be
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MisalignedJump {
+    /// the program index of the jump
+    pub jump: usize,
+
+    /// the program index of the Load whose operand it can land on
+    pub load: usize,
+}
+
+impl fmt::Display for MisalignedJump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "jump at program index {} can land on the operand of the load at program index {}",
+            self.jump, self.load
+        )
+    }
+}
+
+/// where each jump in a program can land, from [Program::jump_alignment]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JumpAlignment {
+    /// the program indices each reachable jump can land on, keyed by the program index of the jump.
+    /// an index equal to the length of the program is the exit appended after it
+    pub targets: BTreeMap<usize, BTreeSet<usize>>,
+
+    /// reachable jumps whose offset couldn't be worked out, like when it's read from the input
+    pub unresolved: BTreeSet<usize>,
+
+    /// jumps that can land on the operand of a Load
+    pub misaligned: Vec<MisalignedJump>,
+}
+
+impl JumpAlignment {
+    /// whether any jump can land on the operand of a Load
+    pub fn is_aligned(&self) -> bool {
+        self.misaligned.is_empty()
+    }
+}
+
+// what's known about a value on the stack: either it's one of a few values, or it could be anything
+#[derive(Debug, Clone)]
+enum Abstract {
+    OneOf(Vec<Value>),
+    Unknown,
+}
+
+impl Abstract {
+    fn of(value: Value) -> Self {
+        Self::OneOf(vec![value])
+    }
+
+    // values are compared by kind too, since 1 and "1" are equal in Chicken but don't behave the same way
+    fn insert(values: &mut Vec<Value>, value: Value) {
+        if !values
+            .iter()
+            .any(|v| v.kind() == value.kind() && *v == value)
+        {
+            values.push(value);
+        }
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Self::OneOf(a), Self::OneOf(b)) => {
+                let mut values = a.clone();
+                for value in b.iter() {
+                    Self::insert(&mut values, value.clone());
+                }
+
+                match values.len() > MAX_VALUES {
+                    true => Self::Unknown,
+                    false => Self::OneOf(values),
+                }
+            }
+            _ => Self::Unknown,
+        }
+    }
+
+    fn map2(a: Self, b: Self, f: impl Fn(Value, Value) -> Value) -> Self {
+        let (Self::OneOf(a), Self::OneOf(b)) = (a, b) else {
+            return Self::Unknown;
+        };
+        if a.len() * b.len() > MAX_VALUES {
+            return Self::Unknown;
+        }
+
+        let mut values = Vec::new();
+        for a in a.iter() {
+            for b in b.iter() {
+                Self::insert(&mut values, f(a.clone(), b.clone()));
+            }
+        }
+
+        Self::OneOf(values)
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::OneOf(values) => values.len(),
+            Self::Unknown => usize::MAX,
+        }
+    }
+}
+
+// the top of the stack at some instruction. anything below what's tracked is unknown
+#[derive(Debug, Clone, Default)]
+struct AbstractStack(Vec<Abstract>);
+
+impl AbstractStack {
+    fn push(&mut self, value: Abstract) {
+        if self.0.len() == MAX_DEPTH {
+            self.0.remove(0);
+        }
+        self.0.push(value);
+    }
+
+    fn pop(&mut self) -> Abstract {
+        self.0.pop().unwrap_or(Abstract::Unknown)
+    }
+
+    // lines both stacks up at the top and joins them slot by slot, returning whether anything about self changed
+    fn join(&mut self, other: &Self) -> bool {
+        let depth = self.0.len().min(other.0.len());
+        let mut changed = depth < self.0.len();
+
+        let joined = (self.0[self.0.len() - depth..].iter())
+            .zip(other.0[other.0.len() - depth..].iter())
+            .map(|(a, b)| {
+                let joined = a.join(b);
+                changed |= joined.len() != a.len();
+                joined
+            })
+            .collect();
+
+        self.0 = joined;
+        changed
+    }
+}
+
+impl Program {
+    /// works out every place each jump in the program can land, by following the values that can be pushed along every path through the program,
+    /// and finds the jumps that can land on the operand of a Load. Load is the only instruction that takes up two words,
+    /// so landing on its operand runs the operand as an instruction of its own and decodes everything after it differently.
+    ///
+    /// this finds more jumps than [Program::cfg], since offsets can be computed in one place and jumped with in another,
+    /// but jumps whose offsets depend on the input or on values loaded from the stack end up in [JumpAlignment::unresolved],
+    /// and stores into the program are ignored
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{MisalignedJump, Program};
+    /// use std::collections::BTreeSet;
+    ///
+    /// // push 1, push 1, push 1, push 0, jump, jump, load 0.
+    /// // the first jump lands on the second one, so its offset is pushed somewhere the control flow graph doesn't look
+    /// let alignment = Program::from_opcodes([11, 11, 11, 10, 8, 8, 6, 0]).jump_alignment();
+    /// assert_eq!(alignment.targets[&5], BTreeSet::from([7]));
+    /// assert_eq!(alignment.misaligned, vec![MisalignedJump { jump: 5, load: 6 }]);
+    /// ```
+    pub fn jump_alignment(&self) -> JumpAlignment {
+        let len = self.len();
+        let operands = (self.instructions().into_iter())
+            .filter(|(index, instruction)| {
+                matches!(instruction, Instruction::Load(_)) && index + 1 < len
+            })
+            .map(|(index, _)| index + 1)
+            .collect::<BTreeSet<_>>();
+
+        let mut alignment = JumpAlignment::default();
+        let mut states: BTreeMap<usize, AbstractStack> = BTreeMap::new();
+        let mut queue = BTreeSet::from([0]);
+        states.insert(0, AbstractStack::default());
+
+        while let Some(index) = queue.pop_first() {
+            let Some(instruction) = self.decode(index) else {
+                continue;
+            };
+            let mut stack = states[&index].clone();
+            let next = index + instruction.width();
+
+            let successors = match instruction {
+                Instruction::Exit => vec![],
+                Instruction::Chicken => {
+                    stack.push(Abstract::of("chicken".into()));
+                    vec![next]
+                }
+                Instruction::Add | Instruction::Subtract | Instruction::Multiply => {
+                    let b = stack.pop();
+                    let a = stack.pop();
+                    stack.push(Abstract::map2(a, b, |a, b| match instruction {
+                        Instruction::Add => a + b,
+                        Instruction::Subtract => a - b,
+                        _ => a * b,
+                    }));
+                    vec![next]
+                }
+                Instruction::Compare => {
+                    let b = stack.pop();
+                    let a = stack.pop();
+                    stack.push(Abstract::map2(a, b, |a, b| (a == b).into()));
+                    vec![next]
+                }
+                Instruction::Load(_) | Instruction::Char => {
+                    stack.pop();
+                    stack.push(Abstract::Unknown);
+                    vec![next]
+                }
+                Instruction::Store => {
+                    stack.pop();
+                    stack.pop();
+                    vec![next]
+                }
+                Instruction::Jump => {
+                    let offset = stack.pop();
+                    let condition = stack.pop();
+
+                    let (taken, not_taken) = match &condition {
+                        Abstract::OneOf(values) => (
+                            values.iter().any(Value::is_truthy),
+                            values.iter().any(|v| !v.is_truthy()),
+                        ),
+                        Abstract::Unknown => (true, true),
+                    };
+
+                    let mut successors = Vec::new();
+                    if not_taken {
+                        successors.push(next);
+                    }
+
+                    if taken {
+                        let targets = alignment.targets.entry(index).or_default();
+
+                        match offset {
+                            Abstract::OneOf(offsets) => {
+                                for offset in offsets.iter() {
+                                    let target = offset
+                                        .to_num_option()
+                                        .and_then(|offset| (next as isize).checked_add(offset));
+
+                                    // jumps outside of the program stop it, and are reported by the linter
+                                    if let Some(target) =
+                                        target.filter(|&t| t >= 0 && t as usize <= len)
+                                    {
+                                        targets.insert(target as usize);
+                                        successors.push(target as usize);
+                                    }
+                                }
+                            }
+                            Abstract::Unknown => {
+                                alignment.unresolved.insert(index);
+                            }
+                        }
+                    }
+
+                    successors
+                }
+                Instruction::Push(n) => {
+                    stack.push(Abstract::of(n.into()));
+                    vec![next]
+                }
+            };
+
+            for successor in successors {
+                let changed = match states.get_mut(&successor) {
+                    Some(state) => state.join(&stack),
+                    None => {
+                        states.insert(successor, stack.clone());
+                        true
+                    }
+                };
+
+                if changed {
+                    queue.insert(successor);
+                }
+            }
+        }
+
+        for (&jump, targets) in alignment.targets.iter() {
+            for target in targets.intersection(&operands) {
+                alignment.misaligned.push(MisalignedJump {
+                    jump,
+                    load: target - 1,
+                });
+            }
+        }
+
+        alignment
+    }
+
+    /// writes this program as assembly like [Program::to_asm], with comments after each jump listing where it can land
+    /// and after each Load whose operand a jump can land on, from [Program::jump_alignment].
+    /// the comments are ignored by [Program::from_asm], so the listing can still be assembled
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::Program;
+    ///
+    /// let listing = Program::from_opcodes([11, 11, 8, 6, 0]).to_asm_with_jump_targets();
+    /// assert_eq!(
+    ///     listing,
+    ///     "push 1\npush 1\njump # can land on 4\nload 0 # jump at 2 lands on its operand\n"
+    /// );
+    /// ```
+    pub fn to_asm_with_jump_targets(&self) -> String {
+        let alignment = self.jump_alignment();
+        let asm = self.to_asm();
+        let mut listing = String::new();
+
+        for ((index, _), line) in self.instructions().into_iter().zip(asm.lines()) {
+            listing += line;
+
+            if let Some(targets) = alignment.targets.get(&index) {
+                let targets = targets.iter().map(usize::to_string).collect::<Vec<_>>();
+
+                match (targets.is_empty(), alignment.unresolved.contains(&index)) {
+                    (true, _) => listing += " # can land anywhere",
+                    (false, false) => {
+                        let _ = write!(listing, " # can land on {}", targets.join(", "));
+                    }
+                    (false, true) => {
+                        let _ = write!(
+                            listing,
+                            " # can land on {}, or anywhere",
+                            targets.join(", ")
+                        );
+                    }
+                }
+            }
+
+            let jumps = (alignment.misaligned.iter())
+                .filter(|misaligned| misaligned.load == index)
+                .map(|misaligned| misaligned.jump.to_string())
+                .collect::<Vec<_>>();
+            if !jumps.is_empty() {
+                let _ = write!(
+                    listing,
+                    " # jump at {} lands on its operand",
+                    jumps.join(", ")
+                );
+            }
+
+            listing.push('\n');
+        }
+
+        listing
+    }
+}
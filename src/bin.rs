@@ -1,45 +1,3218 @@
-use clap::Parser;
+use chicken::{
+    unescape, CancellationToken, CharEntity, ChickenError, ChromeTraceGranularity, ChromeTracer,
+    ColorMode, ConformanceCase, ConformanceVectors, CostModel, EntityDecoding, ErrorKind,
+    ExitPolicy, Explanation, HtmlReport, Limits, Metadata, OutputProcessor, Program, ProgramFile,
+    Requirement, RunProgress, StackCapture, Stats, Stream, Trace, TraceSampling, Value, Visualizer,
+    Warning,
+};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use colored::{ColoredString, Colorize};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, OnceLock,
+    },
+    time::Instant,
+};
 
 #[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
-struct Args {
+#[clap(author, version, about, long_about = None, after_help = EXIT_CODES)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+
+    /// how to print errors to stderr
+    #[clap(long, global = true, value_enum, default_value = "text")]
+    error_format: ErrorFormat,
+
+    /// when to color errors and debugger output: auto, always or never. auto only colors output going to a terminal and honors NO_COLOR
+    #[clap(long, global = true, value_parser, default_value = "auto")]
+    color: ColorMode,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ErrorFormat {
+    /// human readable errors
+    Text,
+
+    /// a JSON object with the kind of error, its message and, for errors from the interpreter, where it happened
+    Json,
+}
+
+// process exit codes, so scripts can tell what went wrong without parsing the error
+const EXIT_RUNTIME_ERROR: i32 = 1;
+const EXIT_LOAD_ERROR: i32 = 2;
+const EXIT_LIMIT_EXCEEDED: i32 = 3;
+const EXIT_IO_ERROR: i32 = 4;
+const EXIT_TRACE_DIVERGED: i32 = 5;
+const EXIT_UNEXPECTED_OUTPUT: i32 = 6;
+const EXIT_TESTS_FAILED: i32 = 7;
+const EXIT_UNFORMATTED: i32 = 8;
+const EXIT_LINT_WARNINGS: i32 = 9;
+// the same code shells use for programs stopped with ctrl+c
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// the exit codes above, listed at the end of --help
+const EXIT_CODES: &str = "exit codes:
+    1    the program failed while running
+    2    the program couldn't be loaded
+    3    a limit was hit, like --max-steps or --detect-loops
+    4    something else failed to read or write
+    5    a replayed trace diverged, or chicken diff or chicken compare-modes found a difference
+    6    the output didn't match --expect
+    7    chicken test or chicken conformance had failures
+    8    chicken fmt --check found unformatted files
+    9    chicken lint found something
+    130  the program was stopped from the debugger or with Ctrl-C";
+
+/// how many values from the top of the stack are shown when a program is stopped with Ctrl-C
+const INTERRUPT_DUMP_CELLS: usize = 20;
+
+// the arguments are only ever parsed once, so there's no point boxing the big ones
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// runs a chicken program
+    Run(RunArgs),
+
+    /// writes the control flow graph of a chicken program in the Graphviz DOT format
+    Cfg(CfgArgs),
+
+    /// converts a program between chicken code, raw opcodes, assembly and JSON
+    Compile(CompileArgs),
+
+    /// assembles several .asm files into one program, where files can jump to the labels other files export
+    Link(LinkArgs),
+
+    /// prints a disassembly listing of a program, with the address of each instruction
+    Disasm(DisasmArgs),
+
+    /// runs snippets of assembly as they're typed in, asking whether to keep going when one runs for too long
+    Repl(ReplArgs),
+
+    /// rewrites chicken files into their canonical form, with single spaces between chickens and no trailing whitespace
+    Fmt(FmtArgs),
+
+    /// looks for suspicious things in chicken files, like misspelled chickens and jumps that land outside of the program
+    Lint(LintArgs),
+
+    /// runs every chicken program in a directory that has a .expected file next to it, checking that its output matches
+    Test(TestArgs),
+
+    /// runs every chicken program in a directory or zip archive with its matching input, printing a JSON summary of the results,
+    /// for things like grading a class's submissions
+    RunAll(RunAllArgs),
+
+    /// runs the conformance cases in a directory, which check that each instruction behaves like it does in the original implementation
+    Conformance(ConformanceArgs),
+
+    /// lists or runs the example programs built into chicken
+    #[clap(subcommand)]
+    Examples(ExamplesCommand),
+
+    /// opens a graphical debugger for a chicken program
+    #[cfg(feature = "gui")]
+    Gui(GuiArgs),
+
+    /// runs an HTTP server that runs programs posted to it, for backing things like online playgrounds
+    #[cfg(feature = "serve")]
+    Serve(ServeArgs),
+
+    /// listens on a Unix socket for programs to run, one JSON request per line, so tools that run lots of short programs
+    /// don't have to start a new process for each one
+    #[cfg(unix)]
+    Daemon(DaemonArgs),
+
+    /// replays a trace written by `run --trace`, checking that the program behaves the same way it did when it was recorded
+    Replay(ReplayArgs),
+
+    /// compares two traces written by `run --trace`, printing the first step where the runs did something different
+    Diff(DiffArgs),
+
+    /// runs a program with different sets of options side by side, like with and without --strict,
+    /// printing what each one output and the first step where they did something different
+    CompareModes(CompareModesArgs),
+
+    /// shrinks a program that fails with an error down to a smaller one that fails the same way,
+    /// with the same kind of error at the same kind of instruction, for bug reports against the interpreter
+    Minimize(MinimizeArgs),
+
+    /// explains what an instruction does, given its mnemonic or opcode, with a tiny example program and what it outputs.
+    /// with no instruction, lists all of them
+    Explain {
+        /// the mnemonic or opcode of the instruction, like add or 2
+        #[clap(value_parser)]
+        instruction: Option<String>,
+    },
+
+    /// prints a script that makes the shell complete chicken's subcommands and flags, to be sourced from the shell's startup file
+    Completions {
+        /// the shell to complete in
+        #[clap(value_enum)]
+        shell: clap_complete::Shell,
+    },
+}
+
+#[derive(Args, Debug)]
+struct RunArgs {
     /// file to load chicken code from
-    #[clap(short, long, value_parser)]
-    file: String,
+    #[clap(value_parser, required_unless_present = "file-flag")]
+    file: Option<PathBuf>,
 
-    /// input to be provided to the program
-    #[clap(short, long, value_parser, default_value = "")]
-    input: String,
+    /// the file to load chicken code from, the way it was passed before there were subcommands.
+    /// deprecated, pass the file on its own instead
+    #[clap(
+        short = 'f',
+        long = "file",
+        value_name = "FILE",
+        value_parser,
+        hide = true,
+        conflicts_with = "file"
+    )]
+    file_flag: Option<PathBuf>,
+
+    /// read the program as raw opcodes separated by whitespace or commas instead of chicken code.
+    /// this is the default for files ending in .opcodes
+    #[clap(long, value_parser, default_value_t = false)]
+    opcodes: bool,
+
+    /// refuse to run chicken code that doesn't follow the spec to the letter, with nothing but chickens separated by single spaces on each line,
+    /// instead of counting chickens wherever they are
+    #[clap(
+        long,
+        value_parser,
+        default_value_t = false,
+        conflicts_with = "opcodes"
+    )]
+    strict_syntax: bool,
+
+    /// input to be provided to the program. can be given more than once to pass several inputs,
+    /// which are placed on the stack one after another
+    #[clap(short, long, value_parser, action = clap::ArgAction::Append)]
+    input: Vec<String>,
+
+    /// file to read the input to the program from, instead of passing it on the command line
+    #[clap(long, value_parser, conflicts_with = "input")]
+    input_file: Option<PathBuf>,
+
+    /// ask for the input on the terminal the first time the program reads it, instead of passing it on the command line.
+    /// programs that never read their input won't ask for it
+    #[clap(long, value_parser, default_value_t = false, conflicts_with_all = &["input", "input-file"])]
+    prompt: bool,
+
+    /// whether to process escape sequences like \n, \t, \\ and \x41 in inputs before passing them to the program
+    #[clap(long, value_parser, default_value_t = false)]
+    input_escapes: bool,
 
     /// whether to provide a debugger of sorts. this lets you step through programs and view the stack
     #[clap(short, long, value_parser, default_value_t = false)]
     debug: bool,
 
+    /// run the debugger with its commands read from this file, one per line, instead of typed in.
+    /// the commands are printed along with everything else the debugger prints, so the output is a transcript of the session
+    #[clap(long, value_parser, conflicts_with = "debug")]
+    debug_script: Option<PathBuf>,
+
+    /// run the debugger with line delimited JSON on stdin and stdout instead of text, for editors and other frontends.
+    /// the result is printed as with `--format json`, with `"event": "exited"` added
+    #[clap(long, value_parser, default_value_t = false, conflicts_with_all = &["debug", "debug-script"])]
+    debug_mi: bool,
+
+    /// with --debug or --debug-mi, keep going after each instruction instead of waiting for a command,
+    /// so the debugger prints a verbose trace of how every instruction changed the stack
+    #[clap(
+        long,
+        value_parser,
+        default_value_t = false,
+        conflicts_with = "debug-script"
+    )]
+    no_pause: bool,
+
     /// whether the Char instruction should convert to actual characters instead of HTML entities.
     /// disabled by default for compatibility
     #[clap(short, long, value_parser, default_value_t = false)]
     normal_char: bool,
+
+    /// print the output as is, without converting HTML entities like `&#104;` back into characters.
+    /// the same as `--decode-entities none`
+    #[clap(
+        long,
+        value_parser,
+        default_value_t = false,
+        conflicts_with = "decode-entities"
+    )]
+    raw: bool,
+
+    /// how Char writes HTML entities when --normal-char isn't set: decimal like `&#65;` or hex like `&#x41;`, which only shows with --raw
+    #[clap(long, value_parser, default_value = "decimal")]
+    char_entity: CharEntity,
+
+    /// which HTML entities in the output to convert back into characters: all, numeric (only ones like `&#104;`, which is all Char produces) or none
+    #[clap(long, value_parser, default_value = "all")]
+    decode_entities: EntityDecoding,
+
+    /// what to do to the output once the program exits, as steps separated by commas that run in order:
+    /// decode-entities (as set by --decode-entities), trim, json-escape or none, like decode-entities,trim
+    #[clap(
+        long,
+        value_parser,
+        value_delimiter = ',',
+        default_value = "decode-entities"
+    )]
+    post_process: Vec<OutputProcessor>,
+
+    /// make the Char instruction produce single bytes and write the output to stdout as raw bytes, without a trailing newline,
+    /// so programs can output binary data
+    #[clap(long, value_parser, default_value_t = false, conflicts_with_all = &["format", "expect"])]
+    bytes: bool,
+
+    /// whether to stop with an error when the program gets stuck in an infinite loop.
+    /// this slows execution down considerably
+    #[clap(long, value_parser, default_value_t = false)]
+    detect_loops: bool,
+
+    /// whether to stop with an error when the program stores something into itself.
+    /// this catches Stores to the wrong address, but breaks programs that use their own code as variables
+    #[clap(long, value_parser, default_value_t = false)]
+    freeze_program: bool,
+
+    /// whether to stop with an error when an instruction pops more values than there are above the program,
+    /// instead of popping the program itself or undefined, when a Load would load undefined from outside of the stack or a string,
+    /// or when Char is given something that isn't a number.
+    /// programs with a load at the very end or a jump onto the operand of a load are refused before they start
+    #[clap(long, value_parser, default_value_t = false)]
+    strict: bool,
+
+    /// print a warning to stderr for things the program does that aren't errors but usually mean it's broken,
+    /// like popping values that aren't there, storing into itself or making a NaN. these are included in --format json too
+    #[clap(long, value_parser, default_value_t = false)]
+    warnings: bool,
+
+    /// turn on the extension instructions that aren't part of chicken itself, like random, which have opcodes starting at 1000
+    #[clap(long, value_parser, default_value_t = false)]
+    extensions: bool,
+
+    /// the seed for the random numbers pushed by the random extension, so a run can be repeated. a different one is picked every run if this isn't provided
+    #[clap(long, value_parser, requires = "extensions")]
+    seed: Option<u64>,
+
+    /// what to do when the program exits with something other than a string: stop with an error,
+    /// or stringify it the way javascript would so a program that exits with 5 outputs 5
+    #[clap(long, value_parser, default_value = "error")]
+    exit_policy: ExitPolicy,
+
+    /// how much of the stack to keep for the error message if the program fails: full, none,
+    /// or a number of values to keep from each end of the stack
+    #[clap(long, value_parser, default_value = "full")]
+    stack_capture: StackCapture,
+
+    /// execute every instruction on its own instead of running common pairs of instructions in one go.
+    /// this never changes what a program does, but can rule out the interpreter when debugging
+    #[clap(long, value_parser, default_value_t = false)]
+    no_fusion: bool,
+
+    /// only remember this many previous states when detecting infinite loops, to keep memory usage bounded
+    #[clap(long, value_parser)]
+    loop_window: Option<usize>,
+
+    /// whether to profile the program, printing which instructions were executed the most and how long they took to stderr
+    #[clap(long, value_parser, default_value_t = false)]
+    profile: bool,
+
+    /// whether to print an annotated disassembly of the program to stderr, showing which instructions were executed
+    #[clap(long, value_parser, default_value_t = false)]
+    coverage: bool,
+
+    /// file to write a trace of every executed instruction to, as one JSON object per line.
+    /// the trace can be replayed later with the replay subcommand
+    #[clap(long, value_parser)]
+    trace: Option<PathBuf>,
+
+    /// only write some of the steps to the trace, to keep traces of very long runs small: all, every nth step given as a number,
+    /// jumps for only the steps that took a jump or stores for only the steps that stored something
+    #[clap(long, value_parser, default_value = "all", requires = "trace")]
+    trace_sample: TraceSampling,
+
+    /// file to write a trace in the chrome://tracing format to, which can be opened in Perfetto
+    #[clap(long, value_parser)]
+    chrome_trace: Option<PathBuf>,
+
+    /// make the chrome trace contain one event per basic block instead of one per instruction
+    #[clap(long, value_parser, default_value_t = false, requires = "chrome-trace")]
+    chrome_trace_blocks: bool,
+
+    /// file to write an HTML page to that shows the disassembly and the stack at every step of the run, with a slider to move between steps
+    #[clap(long, value_parser)]
+    report: Option<PathBuf>,
+
+    /// file to write a markdown report of the run to, with its output, stats and a disassembly annotated with how many times
+    /// each instruction ran and how long each kind of instruction took, for sharing in issues and classrooms
+    #[clap(long, value_parser)]
+    report_md: Option<PathBuf>,
+
+    /// file to write where the program was up to when it's stopped with Ctrl-C, instead of printing it to stderr.
+    /// this has the program counter, the code around it, the top of the stack and the stats so far
+    #[clap(long, value_parser)]
+    interrupt_dump: Option<PathBuf>,
+
+    /// file to write a checkpoint of the VM to every --checkpoint-every steps, and when it's stopped with Ctrl-C,
+    /// so a long run can be picked up where it left off with --resume
+    #[clap(long, value_parser)]
+    checkpoint: Option<PathBuf>,
+
+    /// how many steps go by between checkpoints. accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count, default_value = "10M", requires = "checkpoint")]
+    checkpoint_every: u64,
+
+    /// carry on from a checkpoint written with --checkpoint instead of starting the program from the beginning.
+    /// the program file is still read for its source map, and the other flags should be the same as the run that wrote the checkpoint
+    #[clap(long, value_parser)]
+    resume: Option<PathBuf>,
+
+    /// only record every nth step in the HTML report, to keep reports of long runs small
+    #[clap(long, value_parser, default_value_t = 1, requires = "report")]
+    report_every: u64,
+
+    /// stop recording steps in the HTML report after this many. accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count, default_value = "1000", requires = "report")]
+    report_max_steps: u64,
+
+    /// the maximum number of instructions the program can execute. accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count)]
+    max_steps: Option<u64>,
+
+    /// print how many instructions have been executed and how big the stack is to stderr every this many steps,
+    /// to see that a long running program is still going. accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count)]
+    progress: Option<u64>,
+
+    /// the maximum number of values that can be on the stack, including the program itself. accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count)]
+    max_stack: Option<u64>,
+
+    /// the maximum amount of time the program can run for, in seconds like 2.5 or milliseconds like 500ms
+    #[clap(long, value_parser = parse_duration)]
+    max_time: Option<std::time::Duration>,
+
+    /// the maximum size of the output in bytes, checked as the program builds it up. accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count)]
+    max_output: Option<u64>,
+
+    /// the maximum amount of memory the stack can take up in bytes, counting each value and the bytes of every string on it.
+    /// accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count)]
+    max_memory: Option<u64>,
+
+    /// the maximum amount of fuel the program can use up, counted with --costs. every instruction costs 1 without it.
+    /// accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count)]
+    max_fuel: Option<u64>,
+
+    /// how much fuel each instruction costs, like add=2,load=3,byte=1. byte is charged for every byte of each string made by an add,
+    /// so --max-fuel reflects how much work programs that build up long strings do. instructions that aren't listed cost 1
+    #[clap(long, value_parser)]
+    costs: Option<CostModel>,
+
+    /// run a program that can't be trusted, with conservative step, stack, string length, memory and time limits plus --strict.
+    /// --max-steps, --max-stack, --max-memory and --max-time override the limits this sets
+    #[clap(long, value_parser, default_value_t = false)]
+    sandbox: bool,
+
+    /// whether to print statistics about the run, like the number of steps and how long it took, to stderr
+    #[clap(long, value_parser, default_value_t = false)]
+    stats: bool,
+
+    /// how to print the result of running the program
+    #[clap(long, value_enum, default_value = "text")]
+    format: Format,
+
+    /// exit with an error unless the output of the program is exactly this, for using chicken in shell based test suites
+    #[clap(long, value_parser)]
+    expect: Option<String>,
+
+    /// treat --expect as a regular expression that has to match the entire output
+    #[clap(long, value_parser, default_value_t = false, requires = "expect")]
+    expect_regex: bool,
+
+    /// animate the stack in the terminal as the program runs, on stderr
+    #[clap(long, value_parser, default_value_t = false, conflicts_with_all = &["debug", "debug-script", "debug-mi"])]
+    visualize: bool,
+
+    /// how many steps per second to run at when visualizing
+    #[clap(long, value_parser, default_value_t = 10.0, requires = "visualize")]
+    visualize_speed: f64,
+
+    /// how many cells from the top of the stack to show when visualizing
+    #[clap(long, value_parser, default_value_t = 16, requires = "visualize")]
+    visualize_rows: usize,
+
+    /// run the program again every time it or its input file changes, clearing the screen first
+    #[clap(long, value_parser, default_value_t = false, conflicts_with_all = &["debug", "debug-script", "debug-mi"])]
+    watch: bool,
 }
 
-fn main() {
-    let args = Args::parse();
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    /// prints the output of the program, or a human readable error
+    Text,
 
-    let code = match std::fs::read_to_string(&args.file) {
-        Ok(code) => code,
-        Err(err) => {
-            eprintln!("error reading file {:?}: {:?}", args.file, err);
-            std::process::exit(1);
+    /// prints a JSON object with the output of the program, its statistics and any error
+    Json,
+}
+
+#[derive(Args, Debug)]
+struct CfgArgs {
+    /// file to load chicken code from
+    #[clap(value_parser)]
+    file: PathBuf,
+
+    /// read the program as raw opcodes separated by whitespace or commas instead of chicken code.
+    /// this is the default for files ending in .opcodes
+    #[clap(long, value_parser, default_value_t = false)]
+    opcodes: bool,
+
+    /// file to write the graph to. the graph is written to stdout if this isn't provided
+    #[clap(short, long, value_parser)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct ReplArgs {
+    /// the input each snippet is run with, which can be changed with :input
+    #[clap(short, long, value_parser, default_value = "")]
+    input: String,
+
+    /// how many instructions a snippet can run before asking whether to keep going. accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count, default_value = "1M")]
+    fuel: u64,
+
+    /// how long a snippet can run for before asking whether to keep going, in seconds like 2.5 or milliseconds like 500ms
+    #[clap(long, value_parser = parse_duration, default_value = "1s")]
+    time_budget: std::time::Duration,
+
+    /// decode the instructions added by chicken-rs, which aren't part of the original language
+    #[clap(long, value_parser, default_value_t = false)]
+    extensions: bool,
+}
+
+#[derive(Args, Debug)]
+struct DisasmArgs {
+    /// file to load the program from
+    #[clap(value_parser)]
+    file: PathBuf,
+
+    /// the format the program is written in. detected from the file extension if this isn't provided, defaulting to chicken
+    #[clap(long, value_enum)]
+    from: Option<ProgramFormat>,
+
+    /// how to print the listing
+    #[clap(long, value_enum, default_value = "text")]
+    format: DisasmFormat,
+
+    /// file to write the listing to. it's written to stdout if this isn't provided
+    #[clap(short, long, value_parser)]
+    output: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum DisasmFormat {
+    /// one instruction per line after its address, with labels on lines of their own
+    Text,
+
+    /// one JSON object per line for each instruction, with its address, opcode, mnemonic, operand and label,
+    /// for comparing two versions of a program with other tools
+    Json,
+}
+
+#[derive(Args, Debug)]
+struct LinkArgs {
+    /// the assembly files to link, with the first one at the start of the program
+    #[clap(value_parser, required = true)]
+    files: Vec<PathBuf>,
+
+    /// the format to write the program in. json output has a source map of which file and line each instruction came from
+    #[clap(long, value_enum, default_value = "json")]
+    emit: ProgramFormat,
+
+    /// file to write the program to. it's written to stdout if this isn't provided
+    #[clap(short, long, value_parser)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct CompileArgs {
+    /// file to load the program from
+    #[clap(value_parser)]
+    file: PathBuf,
+
+    /// the format the program is written in. detected from the file extension if this isn't provided, defaulting to chicken
+    #[clap(long, value_enum)]
+    from: Option<ProgramFormat>,
+
+    /// the format to convert the program to
+    #[clap(long, value_enum)]
+    emit: ProgramFormat,
+
+    /// file to write the converted program to. it's written to stdout if this isn't provided
+    #[clap(short, long, value_parser)]
+    output: Option<PathBuf>,
+
+    /// put a source map of where each instruction came from in the output, which has to be json.
+    /// only programs assembled from .asm files, or json that already has one, have a source map
+    #[clap(long, value_parser, default_value_t = false)]
+    source_map: bool,
+
+    /// remove the instructions that can never run, printing how many opcodes were removed.
+    /// nothing is removed unless it's provably safe, like when the program never stores anything
+    #[clap(
+        long,
+        value_parser,
+        default_value_t = false,
+        conflicts_with = "source-map"
+    )]
+    remove_dead_code: bool,
+
+    /// promise that the program will be run with --freeze-program, so dead code can be removed from programs that store things
+    #[clap(
+        long,
+        value_parser,
+        default_value_t = false,
+        requires = "remove-dead-code"
+    )]
+    freeze_program: bool,
+
+    /// comment each jump in asm output with where it can land, and each load with the jumps that can land on its operand
+    #[clap(long, value_parser, default_value_t = false)]
+    jump_targets: bool,
+}
+
+/// the ways a program can be written down
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum ProgramFormat {
+    /// chicken code, with one opcode per line. files ending in .chicken
+    Chicken,
+
+    /// raw opcodes separated by whitespace or commas. files ending in .opcodes
+    Opcodes,
+
+    /// one instruction per line, in the same syntax as disassembly listings. files ending in .asm
+    Asm,
+
+    /// a JSON array of opcodes. files ending in .json
+    Json,
+}
+
+impl ProgramFormat {
+    /// parses a program written in this format, along with its source map if it has one
+    fn parse(self, code: String, file: Option<&str>) -> Result<ProgramFile, String> {
+        let program = match self {
+            Self::Chicken => Program::from_chicken(code),
+            Self::Opcodes => {
+                return Ok(ProgramFile {
+                    metadata: Metadata::from_header(&code).map_err(|err| err.to_string())?,
+                    program: Program::from_opcode_text(code).map_err(|err| err.to_string())?,
+                    source_map: None,
+                })
+            }
+            Self::Asm => {
+                let metadata = Metadata::from_header(&code).map_err(|err| err.to_string())?;
+                let (program, source_map) =
+                    Program::from_asm_with_source_map(code, file).map_err(|err| err.to_string())?;
+
+                return Ok(ProgramFile {
+                    program,
+                    source_map: Some(source_map),
+                    metadata,
+                });
+            }
+            Self::Json => return serde_json::from_str(&code).map_err(|err| err.to_string()),
+        };
+
+        Ok(ProgramFile {
+            program,
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Args, Debug)]
+struct FmtArgs {
+    /// the files to format
+    #[clap(value_parser, required = true)]
+    files: Vec<PathBuf>,
+
+    /// don't change any files, just list the ones that aren't formatted and exit with an error if there are any
+    #[clap(long, value_parser, default_value_t = false)]
+    check: bool,
+}
+
+#[derive(Args, Debug)]
+struct LintArgs {
+    /// the files to lint
+    #[clap(value_parser, required = true)]
+    files: Vec<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct TestArgs {
+    /// directory to look for tests in. every `name.chicken` with a `name.expected` next to it is a test,
+    /// and `name.input` is passed to it as input if it exists. a single trailing newline is ignored in both
+    #[clap(value_parser)]
+    dir: PathBuf,
+
+    /// how many tests to run at the same time
+    #[clap(short, long, value_parser, default_value_t = 1)]
+    jobs: usize,
+
+    /// the maximum number of instructions each test can execute, so a broken test can't hang the whole run. accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count, default_value = "100M")]
+    max_steps: u64,
+}
+
+#[derive(Args, Debug)]
+struct RunAllArgs {
+    /// directory or zip archive to run the programs in. every file ending in .chicken is run, including ones in subdirectories
+    #[clap(value_parser)]
+    programs: PathBuf,
+
+    /// directory to look for inputs in, where `name.input` is passed to every `name.chicken` as input.
+    /// a single trailing newline is ignored, and programs without one get an empty input
+    #[clap(long, value_parser)]
+    inputs: Option<PathBuf>,
+
+    /// how many programs to run at the same time
+    #[clap(short, long, value_parser, default_value_t = 1)]
+    jobs: usize,
+
+    /// the maximum number of instructions each program can execute. accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count, default_value = "100M")]
+    max_steps: u64,
+
+    /// the maximum amount of time each program can run for, in seconds like 2.5 or milliseconds like 500ms
+    #[clap(long, value_parser = parse_duration)]
+    max_time: Option<std::time::Duration>,
+}
+
+#[derive(Args, Debug)]
+struct ConformanceArgs {
+    /// directory to load the cases from, where every .toml file is a set of cases. the cases built into chicken are used if this isn't provided
+    #[clap(value_parser)]
+    dir: Option<PathBuf>,
+
+    /// write the cases to this file as JSON instead of running them, with each program as an array of opcodes,
+    /// so other chicken implementations can test themselves against them
+    #[clap(long, value_parser)]
+    export: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum ExamplesCommand {
+    /// lists the example programs
+    List,
+
+    /// prints the source code of an example program
+    Show {
+        /// the name of the example
+        #[clap(value_parser)]
+        name: String,
+    },
+
+    /// runs an example program
+    Run {
+        /// the name of the example
+        #[clap(value_parser)]
+        name: String,
+
+        /// input to be provided to the program. can be given more than once to pass several inputs
+        #[clap(short, long, value_parser, action = clap::ArgAction::Append)]
+        input: Vec<String>,
+
+        /// whether the Char instruction should convert to actual characters instead of HTML entities
+        #[clap(short, long, value_parser, default_value_t = false)]
+        normal_char: bool,
+    },
+}
+
+/// the example programs from the esolangs wiki, along with a description and an example input
+const EXAMPLES: &[(&str, &str, Option<&str>, &str)] = &[
+    (
+        "99chickens",
+        "counts down from the given number of chickens",
+        Some("9"),
+        include_str!("../examples/99chickens.chicken"),
+    ),
+    (
+        "cat",
+        "prints its input",
+        Some("meow"),
+        include_str!("../examples/cat.chicken"),
+    ),
+    (
+        "deadfish",
+        "a Deadfish interpreter, taking Deadfish code as input",
+        Some("iissiso"),
+        include_str!("../examples/deadfish.chicken"),
+    ),
+    (
+        "helloworld",
+        "prints Hello world",
+        None,
+        include_str!("../examples/helloworld.chicken"),
+    ),
+    (
+        "quine",
+        "prints its own source code",
+        None,
+        include_str!("../examples/quine.chicken"),
+    ),
+];
+
+#[cfg(feature = "gui")]
+#[derive(Args, Debug)]
+struct GuiArgs {
+    /// file to load chicken code from
+    #[clap(value_parser)]
+    file: PathBuf,
+
+    /// input to be provided to the program. can be given more than once to pass several inputs
+    #[clap(short, long, value_parser, action = clap::ArgAction::Append)]
+    input: Vec<String>,
+
+    /// whether the Char instruction should convert to actual characters instead of HTML entities
+    #[clap(short, long, value_parser, default_value_t = false)]
+    normal_char: bool,
+}
+
+#[cfg(feature = "serve")]
+#[derive(Args, Debug)]
+struct ServeArgs {
+    /// the port to listen on
+    #[clap(short, long, value_parser, default_value_t = 8080)]
+    port: u16,
+
+    /// the address to listen on
+    #[clap(long, value_parser, default_value = "127.0.0.1")]
+    host: String,
+
+    /// how many requests can be handled at the same time
+    #[clap(short, long, value_parser, default_value_t = 4)]
+    threads: usize,
+
+    /// the maximum number of instructions a program can execute per request. requests can ask for less, but never more.
+    /// accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count, default_value = "10M")]
+    max_steps: u64,
+
+    /// the maximum number of values that can be on the stack per request. requests can ask for less, but never more.
+    /// accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count, default_value = "1M")]
+    max_stack: u64,
+
+    /// the largest request body that will be accepted, in bytes. accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count, default_value = "1M")]
+    max_body: u64,
+}
+
+#[cfg(unix)]
+#[derive(Args, Debug)]
+struct DaemonArgs {
+    /// the path of the Unix socket to listen on
+    #[clap(short, long, value_parser)]
+    socket: PathBuf,
+
+    /// the maximum number of instructions a program can execute per request. requests can ask for less, but never more.
+    /// accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count, default_value = "10M")]
+    max_steps: u64,
+
+    /// the maximum number of values that can be on the stack per request. requests can ask for less, but never more.
+    /// accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count, default_value = "1M")]
+    max_stack: u64,
+
+    /// the longest line that will be accepted as a request, in bytes. accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count, default_value = "1M")]
+    max_request: u64,
+}
+
+#[derive(Args, Debug)]
+struct ReplayArgs {
+    /// file to load the trace from
+    #[clap(value_parser)]
+    file: PathBuf,
+
+    /// whether to step through the replayed run with the debugger
+    #[clap(short, long, value_parser, default_value_t = false)]
+    debug: bool,
+}
+
+#[derive(Args, Debug)]
+struct DiffArgs {
+    /// file to load the first trace from
+    #[clap(value_parser)]
+    first: PathBuf,
+
+    /// file to load the second trace from
+    #[clap(value_parser)]
+    second: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct CompareModesArgs {
+    /// file to load chicken code from
+    #[clap(value_parser)]
+    file: PathBuf,
+
+    /// read the program as raw opcodes separated by whitespace or commas instead of chicken code.
+    /// this is the default for files ending in .opcodes
+    #[clap(long, value_parser, default_value_t = false)]
+    opcodes: bool,
+
+    /// input to be provided to the program. can be given more than once to pass several inputs
+    #[clap(short, long, value_parser, action = clap::ArgAction::Append)]
+    input: Vec<String>,
+
+    /// the modes to run the program in, separated by commas. compat runs it like the original javascript interpreter does,
+    /// and the others are any of strict, normal-char, freeze-program and extensions, joined with + to combine them like strict+normal-char.
+    /// every mode is compared to the first one
+    #[clap(
+        long,
+        value_parser,
+        value_delimiter = ',',
+        default_value = "compat,strict"
+    )]
+    modes: Vec<Mode>,
+
+    /// the maximum number of instructions the program can execute in each mode. accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count, default_value = "10M")]
+    max_steps: u64,
+}
+
+#[derive(Args, Debug)]
+struct MinimizeArgs {
+    /// file to load the failing program from
+    #[clap(value_parser)]
+    file: PathBuf,
+
+    /// the format the program is written in. detected from the file extension if this isn't provided, defaulting to chicken
+    #[clap(long, value_enum)]
+    from: Option<ProgramFormat>,
+
+    /// input to be provided to the program. can be given more than once to pass several inputs
+    #[clap(short, long, value_parser, action = clap::ArgAction::Append)]
+    input: Vec<String>,
+
+    /// the format to write the smaller program in
+    #[clap(long, value_enum, default_value = "asm")]
+    emit: ProgramFormat,
+
+    /// file to write the smaller program to. it's written to stdout if this isn't provided
+    #[clap(short, long, value_parser)]
+    output: Option<PathBuf>,
+
+    /// the maximum number of instructions each attempt can execute, since removing instructions can make a program loop forever.
+    /// accepts suffixes like 10k, 10M and 1G
+    #[clap(long, value_parser = parse_count, default_value = "1M")]
+    max_steps: u64,
+
+    /// run the program in strict mode, for failures that only happen with --strict
+    #[clap(long, value_parser, default_value_t = false)]
+    strict: bool,
+
+    /// enable extension instructions, for programs that use them
+    #[clap(long, value_parser, default_value_t = false)]
+    extensions: bool,
+}
+
+/// a set of options to run a program with for compare-modes, like `strict+normal-char`
+#[derive(Debug, Clone, Default)]
+struct Mode {
+    name: String,
+    strict: bool,
+    normal_char: bool,
+    freeze_program: bool,
+    extensions: bool,
+}
+
+impl std::str::FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mode = Mode {
+            name: s.to_string(),
+            ..Default::default()
+        };
+
+        if s == "compat" {
+            return Ok(mode);
+        }
+
+        for option in s.split('+') {
+            match option {
+                "strict" => mode.strict = true,
+                "normal-char" => mode.normal_char = true,
+                "freeze-program" => mode.freeze_program = true,
+                "extensions" => mode.extensions = true,
+                _ => return Err(format!("unknown mode {:?}", option)),
+            }
         }
+
+        Ok(mode)
+    }
+}
+
+/// parses a count with an optional k/M/G suffix, like 10M
+fn parse_count(s: &str) -> Result<u64, String> {
+    let (number, multiplier) = match s.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&s[..i], 1_000),
+        Some((i, 'm' | 'M')) => (&s[..i], 1_000_000),
+        Some((i, 'g' | 'G')) => (&s[..i], 1_000_000_000),
+        _ => (s, 1),
     };
 
-    match chicken::VMBuilder::from_chicken(&code)
-        .input(args.input)
-        .set_debug(args.debug)
-        .set_normal_char(args.normal_char)
-        .build()
-        .run()
-    {
-        Ok(output) => println!("{}", output),
-        Err(err) => eprintln!("{}", err),
+    number
+        .parse::<u64>()
+        .map_err(|err| err.to_string())?
+        .checked_mul(multiplier)
+        .ok_or_else(|| "number too large".to_string())
+}
+
+/// parses a number of seconds, which can have a fraction like 2.5, or a number of milliseconds ending in ms like 500ms
+fn parse_duration(s: &str) -> Result<std::time::Duration, String> {
+    let duration = match s.strip_suffix("ms") {
+        Some(millis) => millis.parse::<f64>().map(|millis| millis / 1000.0),
+        None => s.strip_suffix('s').unwrap_or(s).parse::<f64>(),
+    };
+
+    std::time::Duration::try_from_secs_f64(duration.map_err(|err| err.to_string())?)
+        .map_err(|err| err.to_string())
+}
+
+/// prints an error that didn't come from the interpreter and exits with the given code
+fn fail(errors: ErrorFormat, kind: &str, message: String, code: i32) -> ! {
+    match errors {
+        ErrorFormat::Text => eprintln!("{}", message),
+        ErrorFormat::Json => eprintln!(
+            "{}",
+            serde_json::json!({ "kind": kind, "message": message })
+        ),
+    }
+
+    std::process::exit(code);
+}
+
+/// a seed for the random extension that's different every run
+fn clock_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64)
+}
+
+/// reads a program from a file in the given format, or the one matching its extension, exiting if it can't be read
+fn read_program(file: &Path, format: Option<ProgramFormat>, errors: ErrorFormat) -> Program {
+    read_program_file(file, format, errors).program
+}
+
+/// reads a program and its source map from a file like [read_program]
+fn read_program_file(
+    file: &Path,
+    format: Option<ProgramFormat>,
+    errors: ErrorFormat,
+) -> ProgramFile {
+    let format = match format {
+        Some(format) => format,
+        None => match Program::from_path_with_source_map(file) {
+            Ok(program) => return program,
+            Err(err) => fail(
+                errors,
+                "load",
+                format!("error loading file {:?}: {}", file, err),
+                EXIT_LOAD_ERROR,
+            ),
+        },
+    };
+
+    let code = match read_source(file) {
+        Ok(code) => code,
+        Err(err) => fail(
+            errors,
+            "load",
+            format!("error reading file {:?}: {}", file, err),
+            EXIT_LOAD_ERROR,
+        ),
+    };
+
+    match format.parse(code, Some(&file.to_string_lossy())) {
+        Ok(program) => program,
+        Err(err) => fail(
+            errors,
+            "load",
+            format!("error parsing file {:?}: {}", file, err),
+            EXIT_LOAD_ERROR,
+        ),
+    }
+}
+
+/// reads the text of a program file, which can be UTF-8 or UTF-16
+fn read_source(file: &Path) -> Result<String, String> {
+    let bytes = std::fs::read(file).map_err(|err| format!("{:?}", err))?;
+    chicken::decode_source(&bytes).map_err(|err| err.to_string())
+}
+
+/// creates a file to write to, exiting if it can't be created
+fn create_file(file: &PathBuf, errors: ErrorFormat) -> BufWriter<File> {
+    match File::create(file) {
+        Ok(file) => BufWriter::new(file),
+        Err(err) => fail(
+            errors,
+            "io",
+            format!("error creating file {:?}: {:?}", file, err),
+            EXIT_IO_ERROR,
+        ),
+    }
+}
+
+/// the result of running a program, as printed by `--format json`
+#[derive(Serialize)]
+struct JsonResult<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event: Option<&'static str>,
+    ok: bool,
+    output: Option<&'a String>,
+    stats: &'a Stats,
+    error: Option<&'a ChickenError>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    warnings: &'a [Warning],
+}
+
+/// prints log messages from the interpreter to stdout, used for its debug information
+struct StdoutLogger;
+
+impl log::Log for StdoutLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        println!("{}", record.args());
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StdoutLogger = StdoutLogger;
+
+/// makes the interpreter's debug information get printed to stdout
+fn enable_debug_logging() {
+    if log::set_logger(&LOGGER).is_ok() {
+        // the debugger prints the whole state of the VM itself, so the stack dumps logged at the trace level aren't needed
+        log::set_max_level(log::LevelFilter::Debug);
+    }
+}
+
+/// exits with the code for the kind of error the interpreter stopped with
+fn exit_with(err: &ChickenError) -> ! {
+    std::process::exit(match err.kind {
+        ErrorKind::Runtime | ErrorKind::ProgramWrite | ErrorKind::StackUnderflow => {
+            EXIT_RUNTIME_ERROR
+        }
+        ErrorKind::Limit | ErrorKind::OutputLimitExceeded => EXIT_LIMIT_EXCEEDED,
+        ErrorKind::Io => EXIT_IO_ERROR,
+        ErrorKind::Interrupted => EXIT_INTERRUPTED,
+    });
+}
+
+/// runs chicken again without --watch every time the program or its input file changes.
+/// each run is a separate process, so programs stuck in infinite loops can just be killed
+fn watch(args: &RunArgs, errors: ErrorFormat) -> ! {
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(err) => fail(
+            errors,
+            "io",
+            format!("couldn't find the chicken executable: {}", err),
+            EXIT_IO_ERROR,
+        ),
+    };
+
+    let files = std::iter::once(args.file())
+        .chain(args.input_file.as_deref())
+        .collect::<Vec<_>>();
+    let modified = || {
+        files
+            .iter()
+            .map(|file| std::fs::metadata(file).and_then(|m| m.modified()).ok())
+            .collect::<Vec<_>>()
+    };
+
+    loop {
+        let last_modified = modified();
+
+        // clear the screen and move the cursor to the top left
+        print!("\x1b[2J\x1b[H");
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+
+        let mut child = match std::process::Command::new(&exe)
+            .args(without_watch(std::env::args_os().skip(1)))
+            .spawn()
+        {
+            Ok(child) => Some(child),
+            Err(err) => {
+                eprintln!("error running program: {}", err);
+                None
+            }
+        };
+
+        while modified() == last_modified {
+            if let Some(status) = child.as_mut().and_then(|c| c.try_wait().ok().flatten()) {
+                eprintln!(
+                    "{}",
+                    paint_stderr(format!("\n[{}, waiting for changes]", status).dimmed())
+                );
+                child = None;
+            }
+
+            std::thread::sleep(std::time::Duration::from_millis(200));
+        }
+
+        if let Some(mut child) = child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// removes the --watch flag from the arguments chicken was run with.
+/// none of the options take values starting with a dash, so a bare `--watch` can only be the flag itself
+/// unless it comes after `--`, where it's the file and has to be left alone
+fn without_watch(args: impl Iterator<Item = OsString>) -> Vec<OsString> {
+    let mut terminated = false;
+
+    args.filter(|arg| {
+        let flag = !terminated && arg == "--watch";
+        terminated |= arg == "--";
+        !flag
+    })
+    .collect()
+}
+
+impl RunArgs {
+    /// the file to run, whether it was passed on its own or with the deprecated --file
+    fn file(&self) -> &Path {
+        (self.file.as_deref())
+            .or(self.file_flag.as_deref())
+            .unwrap_or(Path::new(""))
+    }
+}
+
+fn run(args: RunArgs, errors: ErrorFormat) {
+    let path = args.file().to_path_buf();
+    if args.file_flag.is_some() {
+        eprintln!(
+            "{}--file is deprecated, pass the file on its own like `chicken run {}`",
+            paint_stderr("warning: ".yellow().bold()),
+            path.display()
+        );
+    }
+
+    if args.watch {
+        watch(&args, errors);
+    }
+
+    if args.debug {
+        enable_debug_logging();
+    }
+
+    // the expected output is checked before running so a bad regex doesn't waste a long run
+    let expect = args.expect.map(|expect| {
+        let pattern = match args.expect_regex {
+            true => format!("^(?:{})$", expect),
+            false => format!("^{}$", regex::escape(&expect)),
+        };
+
+        match Regex::new(&pattern) {
+            Ok(regex) => (regex, expect),
+            Err(err) => fail(
+                errors,
+                "usage",
+                format!("invalid --expect regex: {}", err),
+                EXIT_LOAD_ERROR,
+            ),
+        }
+    });
+
+    let mut inputs = match (args.input_file, args.input.is_empty()) {
+        (Some(file), _) => match std::fs::read_to_string(&file) {
+            Ok(input) => vec![input],
+            Err(err) => fail(
+                errors,
+                "load",
+                format!("error reading input file {:?}: {:?}", file, err),
+                EXIT_LOAD_ERROR,
+            ),
+        },
+        (None, true) => vec![String::new()],
+        (None, false) => args.input,
+    };
+
+    if args.input_escapes {
+        for input in inputs.iter_mut() {
+            *input = match unescape(input) {
+                Ok(input) => input,
+                Err(err) => fail(
+                    errors,
+                    "input",
+                    format!("error in input {:?}: {}", input, err),
+                    EXIT_LOAD_ERROR,
+                ),
+            };
+        }
+    }
+
+    let is_chicken = !args.opcodes
+        && !matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("opcodes" | "asm" | "json")
+        );
+    if args.strict_syntax && is_chicken {
+        let code = match read_source(&path) {
+            Ok(code) => code,
+            Err(err) => fail(
+                errors,
+                "load",
+                format!("error reading file {:?}: {}", path, err),
+                EXIT_LOAD_ERROR,
+            ),
+        };
+
+        if let Err(err) = Program::from_chicken_strict(code) {
+            fail(
+                errors,
+                "load",
+                format!("{:?} doesn't follow the spec on {}", path, err),
+                EXIT_LOAD_ERROR,
+            );
+        }
+    }
+
+    let file = read_program_file(
+        &path,
+        args.opcodes.then_some(ProgramFormat::Opcodes),
+        errors,
+    );
+
+    // a program that uses extensions can't do anything sensible without them, but the others only change what it outputs
+    for requirement in &file.metadata.requires {
+        let (enabled, flag) = match requirement {
+            Requirement::Extensions => (args.extensions, "--extensions"),
+            Requirement::Strict => (args.strict || args.sandbox, "--strict"),
+            Requirement::NormalChar => (args.normal_char, "--normal-char"),
+            Requirement::ByteChar => (args.bytes, "--bytes"),
+        };
+
+        match (requirement, enabled) {
+            (_, true) => {}
+            (Requirement::Extensions, false) => fail(
+                errors,
+                "load",
+                format!("{:?} needs to be run with {}", path, flag),
+                EXIT_LOAD_ERROR,
+            ),
+            (_, false) => eprintln!(
+                "{}{:?} expects to be run with {}",
+                paint_stderr("warning: ".yellow().bold()),
+                path,
+                flag
+            ),
+        }
+    }
+
+    // strict mode stops programs from reading what isn't there, and a load without its operand reads the program instead
+    if args.strict || args.sandbox {
+        if let Err(invalid) = file.program.validate() {
+            fail(
+                errors,
+                "load",
+                format!("{:?} can't be run in strict mode: {}", path, invalid[0]),
+                EXIT_LOAD_ERROR,
+            );
+        }
+    }
+
+    let mut limits = if args.sandbox {
+        Limits::untrusted()
+    } else {
+        Limits::none()
+    };
+    if let Some(max_steps) = args.max_steps {
+        limits.max_steps = Some(max_steps);
+    }
+    if let Some(max_stack) = args.max_stack {
+        limits.max_stack = Some(max_stack.try_into().unwrap_or(usize::MAX));
+    }
+    if let Some(max_time) = args.max_time {
+        limits.max_time = Some(max_time);
+    }
+    if let Some(max_output) = args.max_output {
+        limits.max_output_len = Some(max_output.try_into().unwrap_or(usize::MAX));
+    }
+    if let Some(max_memory) = args.max_memory {
+        limits.max_memory = Some(max_memory.try_into().unwrap_or(usize::MAX));
+    }
+    if let Some(max_fuel) = args.max_fuel {
+        limits.max_fuel = Some(max_fuel);
+    }
+
+    let builder = match &args.resume {
+        Some(checkpoint) => match chicken::VMBuilder::from_checkpoint(checkpoint) {
+            Ok(builder) => builder,
+            Err(err) => fail(
+                errors,
+                "load",
+                format!("error reading checkpoint {:?}: {}", checkpoint, err),
+                EXIT_LOAD_ERROR,
+            ),
+        },
+        None => chicken::VMBuilder::from_program(file.program)
+            .inputs(inputs.into_iter().map(Value::from).collect::<Vec<_>>()),
+    };
+
+    let mut builder = builder
+        .set_debug(args.debug)
+        .set_debug_mi(args.debug_mi)
+        .set_debug_pause(!args.no_pause)
+        .set_normal_char(args.normal_char)
+        .set_byte_char(args.bytes)
+        .char_entity(args.char_entity)
+        .entity_decoding(if args.raw {
+            EntityDecoding::None
+        } else {
+            args.decode_entities
+        })
+        .post_process(args.post_process.clone())
+        .set_detect_loops(args.detect_loops)
+        .set_freeze_program(args.freeze_program)
+        .set_strict(args.strict || args.sandbox)
+        .set_warnings(args.warnings)
+        .set_extensions(args.extensions)
+        .exit_policy(args.exit_policy)
+        .stack_capture(args.stack_capture)
+        .set_fuse_instructions(!args.no_fusion)
+        .set_profile(args.profile || args.report_md.is_some())
+        .set_coverage(args.coverage || args.report_md.is_some())
+        .color(color())
+        .limits(limits);
+
+    if let Some(source_map) = file.source_map {
+        builder = builder.source_map(source_map);
+    }
+
+    if let Some(costs) = args.costs.clone() {
+        builder = builder.cost_model(costs);
+    }
+
+    if let Some(window) = args.loop_window {
+        builder = builder.loop_window(window);
+    }
+
+    // a resumed run carries on with the random numbers the checkpoint was up to
+    if args.resume.is_none() || args.seed.is_some() {
+        builder = builder.seed(args.seed.unwrap_or_else(clock_seed));
+    }
+
+    if let Some(checkpoint) = &args.checkpoint {
+        builder = builder.checkpoint_every(args.checkpoint_every, checkpoint);
+    }
+
+    if let Some(every_n_steps) = args.progress {
+        builder = builder.on_progress(every_n_steps, |progress| {
+            eprintln!(
+                "{} steps, stack depth {}",
+                progress.steps, progress.stack_depth
+            )
+        });
+    }
+
+    if args.prompt {
+        let escapes = args.input_escapes;
+
+        builder = builder.lazy_input(move || {
+            eprint!("input: ");
+            std::io::Write::flush(&mut std::io::stderr())?;
+
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line)?;
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            match escapes {
+                true => unescape(line)
+                    .map(Value::from)
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err)),
+                false => Ok(Value::from(line)),
+            }
+        });
+    }
+
+    if let Some(script) = args.debug_script {
+        let script = match std::fs::read_to_string(&script) {
+            Ok(script) => script,
+            Err(err) => fail(
+                errors,
+                "load",
+                format!("error reading debug script {:?}: {:?}", script, err),
+                EXIT_LOAD_ERROR,
+            ),
+        };
+
+        builder = builder.debug_io(std::io::Cursor::new(script), std::io::stdout());
+    }
+
+    if let Some(trace) = args.trace {
+        builder = builder.trace_sampled(create_file(&trace, errors), args.trace_sample);
+    }
+
+    if let Some(trace) = args.chrome_trace {
+        builder = builder.observer(ChromeTracer::new(
+            create_file(&trace, errors),
+            match args.chrome_trace_blocks {
+                true => ChromeTraceGranularity::BasicBlock,
+                false => ChromeTraceGranularity::Instruction,
+            },
+        ));
+    }
+
+    if args.visualize {
+        builder = builder.observer(
+            Visualizer::new(std::io::stderr(), args.visualize_speed, args.visualize_rows)
+                .color(color().enabled(Stream::Stderr)),
+        );
+    }
+
+    if let Some(report) = args.report {
+        builder = builder.observer(HtmlReport::new(
+            create_file(&report, errors),
+            args.report_every,
+            args.report_max_steps.try_into().unwrap_or(usize::MAX),
+        ));
+    }
+
+    // the first Ctrl-C stops the program so where it was up to can be shown,
+    // and a second one gives up on that, in case it's stuck waiting for input
+    let cancellation_token = CancellationToken::new();
+    let handler_token = cancellation_token.clone();
+    let _ = ctrlc::set_handler(move || {
+        if handler_token.is_cancelled() {
+            std::process::exit(EXIT_INTERRUPTED);
+        }
+        handler_token.cancel();
+    });
+    builder = builder.cancellation_token(cancellation_token.clone());
+
+    let mut vm = builder.build();
+
+    let start = Instant::now();
+    let result = if args.bytes {
+        vm.run_bytes()
+    } else {
+        vm.run().map(String::into_bytes)
+    };
+    let elapsed = start.elapsed();
+
+    // frontends reading the debugger's JSON need the result as JSON too
+    let format = match args.debug_mi {
+        true => Format::Json,
+        false => args.format,
+    };
+
+    match format {
+        Format::Text => match &result {
+            Ok(output) => {
+                let mut stdout = std::io::stdout().lock();
+                let written = stdout.write_all(output).and_then(|_| match args.bytes {
+                    true => stdout.flush(),
+                    false => writeln!(stdout),
+                });
+
+                if let Err(err) = written {
+                    fail(
+                        errors,
+                        "io",
+                        format!("error writing output: {:?}", err),
+                        EXIT_IO_ERROR,
+                    );
+                }
+            }
+            Err(err) if errors == ErrorFormat::Json => {
+                eprintln!("{}", serde_json::to_string(err).unwrap())
+            }
+            // the interrupt dump below shows where it was up to without the whole stack
+            Err(_) if cancellation_token.is_cancelled() => {
+                eprintln!("{}", paint_stderr("interrupted".red().bold()))
+            }
+            Err(err) => eprintln!("{}", err),
+        },
+        Format::Json => println!(
+            "{}",
+            serde_json::to_string(&JsonResult {
+                event: args.debug_mi.then_some("exited"),
+                ok: result.is_ok(),
+                output: result
+                    .as_ref()
+                    .ok()
+                    .map(|output| String::from_utf8_lossy(output).into_owned())
+                    .as_ref(),
+                stats: vm.stats(),
+                error: result.as_ref().err(),
+                warnings: vm.warnings.as_deref().unwrap_or_default(),
+            })
+            .unwrap()
+        ),
+    }
+
+    // json output has the warnings in it already
+    if format == Format::Text {
+        for warning in vm.warnings.iter().flatten() {
+            eprintln!("{}{}", paint_stderr("warning: ".yellow().bold()), warning);
+        }
+    }
+
+    if args.stats {
+        let stats = vm.stats();
+
+        eprint!("{}", stats);
+        eprintln!("elapsed time: {:?}", elapsed);
+        eprintln!(
+            "instructions per second: {:.0}",
+            stats.steps as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+        );
+    }
+
+    if let Some(report) = &args.report_md {
+        let output = (result.as_ref()).map(|output| String::from_utf8_lossy(output));
+        let markdown = vm.markdown_report(output.as_deref().map_err(|err| *err));
+
+        let mut file = create_file(report, errors);
+        if let Err(err) = file
+            .write_all(markdown.as_bytes())
+            .and_then(|()| file.flush())
+        {
+            fail(
+                errors,
+                "io",
+                format!("error writing report: {:?}", err),
+                EXIT_IO_ERROR,
+            );
+        }
+    }
+
+    if let Some(profile) = vm.profile.take().filter(|_| args.profile) {
+        eprint!("{}", profile);
+    }
+
+    if let Some(coverage) = vm.coverage.take().filter(|_| args.coverage) {
+        eprint!("{}", coverage);
+    }
+
+    if result.is_err() && cancellation_token.is_cancelled() {
+        if let Some(checkpoint) = &args.checkpoint {
+            if let Err(err) = vm.save_checkpoint(checkpoint) {
+                fail(
+                    errors,
+                    "io",
+                    format!("error writing checkpoint {:?}: {:?}", checkpoint, err),
+                    EXIT_IO_ERROR,
+                );
+            }
+        }
+
+        let dump = format!("{}{}", vm.dump(INTERRUPT_DUMP_CELLS), vm.stats());
+
+        match &args.interrupt_dump {
+            Some(file) => {
+                let mut file = create_file(file, errors);
+                let written = file.write_all(dump.as_bytes()).and_then(|()| file.flush());
+
+                if let Err(err) = written {
+                    fail(
+                        errors,
+                        "io",
+                        format!("error writing interrupt dump: {:?}", err),
+                        EXIT_IO_ERROR,
+                    );
+                }
+            }
+            None => eprint!("{}", dump),
+        }
+    }
+
+    // exiting doesn't drop anything, so the VM has to go first for its observers to flush what they've written,
+    // otherwise traces of runs that failed would be cut off
+    drop(vm);
+
+    match (result, expect) {
+        (Err(err), _) => exit_with(&err),
+        (Ok(output), Some((regex, expect)))
+            if !regex.is_match(&String::from_utf8_lossy(&output)) =>
+        {
+            fail(
+                errors,
+                "unexpected_output",
+                format!(
+                    "output {:?} didn't match {:?}",
+                    String::from_utf8_lossy(&output),
+                    expect
+                ),
+                EXIT_UNEXPECTED_OUTPUT,
+            )
+        }
+        _ => (),
+    }
+}
+
+fn fmt(args: FmtArgs, errors: ErrorFormat) {
+    let mut unformatted = 0;
+
+    for file in args.files.iter() {
+        let code = match read_source(file) {
+            Ok(code) => code,
+            Err(err) => fail(
+                errors,
+                "load",
+                format!("error reading file {:?}: {}", file, err),
+                EXIT_LOAD_ERROR,
+            ),
+        };
+
+        let formatted = chicken::format_chicken(&code);
+        if formatted == code {
+            continue;
+        }
+
+        // formatting should never change what the program does, but it's better to be sure before overwriting anything
+        if Program::from_chicken(&formatted) != Program::from_chicken(&code) {
+            fail(
+                errors,
+                "fmt",
+                format!(
+                    "formatting {:?} would change its opcodes, leaving it alone",
+                    file
+                ),
+                EXIT_RUNTIME_ERROR,
+            );
+        }
+
+        unformatted += 1;
+
+        if args.check {
+            println!("{}", file.display());
+        } else if let Err(err) = std::fs::write(file, formatted) {
+            fail(
+                errors,
+                "io",
+                format!("error writing file {:?}: {:?}", file, err),
+                EXIT_IO_ERROR,
+            );
+        }
+    }
+
+    if args.check && unformatted > 0 {
+        std::process::exit(EXIT_UNFORMATTED);
+    }
+}
+
+fn lint(args: LintArgs, errors: ErrorFormat) {
+    let mut warnings = 0;
+
+    for file in args.files.iter() {
+        let code = match read_source(file) {
+            Ok(code) => code,
+            Err(err) => fail(
+                errors,
+                "load",
+                format!("error reading file {:?}: {}", file, err),
+                EXIT_LOAD_ERROR,
+            ),
+        };
+
+        for lint in chicken::lint(code) {
+            warnings += 1;
+            println!(
+                "{}{}:{}: {}",
+                paint_stdout("warning: ".yellow().bold()),
+                file.display(),
+                lint.line,
+                lint.message
+            );
+        }
+    }
+
+    if warnings > 0 {
+        std::process::exit(EXIT_LINT_WARNINGS);
+    }
+}
+
+/// a single program found by the test subcommand, along with what it should output
+struct GoldenTest {
+    name: String,
+    file: PathBuf,
+    input: Option<String>,
+    expected: String,
+}
+
+/// reads a file used by a test, ignoring a single trailing newline
+fn read_sidecar(file: &Path) -> std::io::Result<String> {
+    let mut text = std::fs::read_to_string(file)?;
+
+    if text.ends_with('\n') {
+        text.pop();
+    }
+
+    Ok(text)
+}
+
+/// finds every test in a directory, sorted by name
+fn find_tests(dir: &Path) -> std::io::Result<Vec<GoldenTest>> {
+    let mut tests = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let file = entry?.path();
+
+        if file.extension().is_none_or(|e| e != "chicken") {
+            continue;
+        }
+
+        let expected = file.with_extension("expected");
+        if !expected.exists() {
+            continue;
+        }
+
+        let input = file.with_extension("input");
+
+        tests.push(GoldenTest {
+            name: file
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            input: match input.exists() {
+                true => Some(read_sidecar(&input)?),
+                false => None,
+            },
+            expected: read_sidecar(&expected)?,
+            file,
+        });
+    }
+
+    tests.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(tests)
+}
+
+/// runs a test, returning a description of what went wrong if it failed
+fn run_test(test: &GoldenTest, max_steps: u64) -> Result<(), String> {
+    let code = read_source(&test.file).map_err(|err| format!("error reading file: {}", err))?;
+
+    let output = chicken::VMBuilder::from_chicken(code)
+        .input(test.input.clone().unwrap_or_default())
+        .limits(Limits {
+            max_steps: Some(max_steps),
+            ..Default::default()
+        })
+        .build()
+        .run()
+        .map_err(|err| err.message)?;
+
+    match output == test.expected {
+        true => Ok(()),
+        false => Err(diff(&test.expected, &output)),
+    }
+}
+
+/// makes a line by line diff of the expected and actual output of a test, with lines only in the expected output marked with `-` and lines only in the actual output marked with `+`
+fn diff(expected: &str, actual: &str) -> String {
+    let expected = expected.split('\n').collect::<Vec<_>>();
+    let actual = actual.split('\n').collect::<Vec<_>>();
+
+    // longest common subsequence of lines, working backwards so the diff can be read off forwards
+    let mut lengths = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lengths[i][j] = match expected[i] == actual[j] {
+                true => lengths[i + 1][j + 1] + 1,
+                false => lengths[i + 1][j].max(lengths[i][j + 1]),
+            };
+        }
+    }
+
+    let mut diff = String::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            diff += &format!("  {}\n", expected[i]);
+            i += 1;
+            j += 1;
+        } else if i < expected.len()
+            && (j == actual.len() || lengths[i + 1][j] >= lengths[i][j + 1])
+        {
+            diff += &format!("{}\n", paint_stdout(format!("- {}", expected[i]).red()));
+            i += 1;
+        } else {
+            diff += &format!("{}\n", paint_stdout(format!("+ {}", actual[j]).green()));
+            j += 1;
+        }
+    }
+
+    diff
+}
+
+fn test(args: TestArgs, errors: ErrorFormat) {
+    let tests = match find_tests(&args.dir) {
+        Ok(tests) => tests,
+        Err(err) => fail(
+            errors,
+            "load",
+            format!("error reading tests from {:?}: {}", args.dir, err),
+            EXIT_LOAD_ERROR,
+        ),
+    };
+
+    let results = run_parallel(&tests, args.jobs, |test| run_test(test, args.max_steps));
+
+    let mut failed = 0;
+
+    for (test, result) in tests.iter().zip(results) {
+        match result {
+            Ok(()) => println!("{} {}", paint_stdout("PASS".green().bold()), test.name),
+            Err(reason) => {
+                failed += 1;
+                println!("{} {}", paint_stdout("FAIL".red().bold()), test.name);
+                println!("{}", reason.trim_end());
+            }
+        }
+    }
+
+    println!("\n{} passed, {} failed", tests.len() - failed, failed);
+
+    if failed > 0 {
+        std::process::exit(EXIT_TESTS_FAILED);
+    }
+}
+
+/// calls a function on every item with the given number of threads, returning the results in the same order as the items
+fn run_parallel<T: Sync, R: Send>(
+    items: &[T],
+    jobs: usize,
+    function: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    // each worker takes the next item that hasn't been started yet
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..items.len()).map(|_| None).collect::<Vec<_>>());
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, items.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+
+                let result = function(item);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    (results.into_inner().unwrap().into_iter())
+        .map(|result| result.unwrap())
+        .collect()
+}
+
+/// a program found by the run-all subcommand, with its source already read so archives don't have to be shared between threads
+struct BatchProgram {
+    name: String,
+    file: String,
+    source: Result<String, String>,
+}
+
+/// what happened when the run-all subcommand ran a program
+#[derive(Serialize)]
+struct BatchResult {
+    name: String,
+    file: String,
+    input: Option<String>,
+    #[serde(flatten)]
+    result: serde_json::Value,
+}
+
+/// the summary printed by the run-all subcommand
+#[derive(Serialize)]
+struct BatchSummary {
+    total: usize,
+    ok: usize,
+    failed: usize,
+    results: Vec<BatchResult>,
+}
+
+/// finds every chicken program in a directory and its subdirectories, with their paths relative to the given directory
+fn find_programs(
+    dir: &Path,
+    prefix: &str,
+    programs: &mut Vec<BatchProgram>,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let file = format!(
+            "{}{}",
+            prefix,
+            path.file_name().unwrap_or_default().to_string_lossy()
+        );
+
+        if path.is_dir() {
+            find_programs(&path, &format!("{}/", file), programs)?;
+        } else if path.extension().is_some_and(|e| e == "chicken") {
+            programs.push(BatchProgram {
+                name: path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                source: read_source(&path),
+                file,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// finds every chicken program in a zip archive
+fn find_archived_programs(archive: &Path) -> Result<Vec<BatchProgram>, String> {
+    use std::io::Read;
+
+    let file = File::open(archive).map_err(|err| format!("{:?}", err))?;
+    let mut archive = zip::ZipArchive::new(BufReader::new(file)).map_err(|err| err.to_string())?;
+    let mut programs = Vec::new();
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(|err| err.to_string())?;
+
+        // entries with paths like ../../file are skipped instead of trusted
+        let Some(path) = entry.enclosed_name().map(Path::to_path_buf) else {
+            continue;
+        };
+        if entry.is_dir() || path.extension().is_none_or(|e| e != "chicken") {
+            continue;
+        }
+
+        let mut bytes = Vec::new();
+        let source = match entry.read_to_end(&mut bytes) {
+            Ok(_) => chicken::decode_source(&bytes).map_err(|err| err.to_string()),
+            Err(err) => Err(format!("{:?}", err)),
+        };
+
+        programs.push(BatchProgram {
+            name: path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            file: entry.name().to_string(),
+            source,
+        });
+    }
+
+    Ok(programs)
+}
+
+/// runs a program found by the run-all subcommand, returning the same JSON object as `run --format json`
+fn run_batch_program(code: String, input: String, args: &RunAllArgs) -> serde_json::Value {
+    let mut vm = chicken::VMBuilder::from_chicken(code)
+        .input(input)
+        .limits(Limits {
+            max_steps: Some(args.max_steps),
+            max_time: args.max_time,
+            ..Default::default()
+        })
+        .build();
+
+    let result = vm.run();
+
+    serde_json::to_value(JsonResult {
+        event: None,
+        ok: result.is_ok(),
+        output: result.as_ref().ok(),
+        stats: vm.stats(),
+        error: result.as_ref().err(),
+        warnings: &[],
+    })
+    .unwrap()
+}
+
+fn run_all(args: RunAllArgs, errors: ErrorFormat) {
+    let programs = match args.programs.is_dir() {
+        true => {
+            let mut programs = Vec::new();
+            find_programs(&args.programs, "", &mut programs)
+                .map(|_| programs)
+                .map_err(|err| format!("{:?}", err))
+        }
+        false => find_archived_programs(&args.programs),
+    };
+
+    let mut programs = match programs {
+        Ok(programs) => programs,
+        Err(err) => fail(
+            errors,
+            "load",
+            format!("error reading programs from {:?}: {}", args.programs, err),
+            EXIT_LOAD_ERROR,
+        ),
+    };
+    programs.sort_by(|a, b| a.file.cmp(&b.file));
+
+    let results = run_parallel(&programs, args.jobs, |program| {
+        let input_file =
+            (args.inputs.as_ref()).map(|dir| dir.join(format!("{}.input", program.name)));
+        let input_file = input_file.filter(|file| file.exists());
+
+        let input = match &input_file {
+            Some(file) => read_sidecar(file)
+                .map_err(|err| format!("error reading input {:?}: {:?}", file, err)),
+            None => Ok(String::new()),
+        };
+
+        let result = match (&program.source, input) {
+            (Ok(code), Ok(input)) => run_batch_program(code.clone(), input, &args),
+            (Err(err), _) => serde_json::json!({
+                "ok": false,
+                "error": { "kind": "load", "message": format!("error reading file: {}", err) },
+            }),
+            (_, Err(err)) => serde_json::json!({
+                "ok": false,
+                "error": { "kind": "io", "message": err },
+            }),
+        };
+
+        BatchResult {
+            name: program.name.clone(),
+            file: program.file.clone(),
+            input: input_file.map(|file| file.display().to_string()),
+            result,
+        }
+    });
+
+    let ok = (results.iter())
+        .filter(|result| result.result["ok"] == true)
+        .count();
+
+    let summary = BatchSummary {
+        total: results.len(),
+        ok,
+        failed: results.len() - ok,
+        results,
+    };
+    println!("{}", serde_json::to_string_pretty(&summary).unwrap());
+}
+
+fn conformance(args: ConformanceArgs, errors: ErrorFormat) {
+    let cases = match &args.dir {
+        Some(dir) => match ConformanceCase::load_dir(dir) {
+            Ok(cases) => cases,
+            Err(err) => fail(
+                errors,
+                "load",
+                format!("error loading cases from {:?}: {}", dir, err),
+                EXIT_LOAD_ERROR,
+            ),
+        },
+        None => ConformanceCase::builtin(),
+    };
+
+    if let Some(export) = args.export {
+        let mut file = create_file(&export, errors);
+        let result = serde_json::to_writer_pretty(&mut file, &ConformanceVectors::new(cases))
+            .map_err(std::io::Error::from)
+            .and_then(|()| file.flush());
+
+        if let Err(err) = result {
+            fail(
+                errors,
+                "io",
+                format!("error writing to {:?}: {:?}", export, err),
+                EXIT_IO_ERROR,
+            )
+        }
+
+        return;
+    }
+
+    let mut failed = 0;
+
+    for case in cases.iter() {
+        match case.run() {
+            Ok(()) => println!("{} {}", paint_stdout("PASS".green().bold()), case),
+            Err(reason) => {
+                failed += 1;
+                println!("{} {}", paint_stdout("FAIL".red().bold()), case);
+                println!("{}", reason);
+            }
+        }
+    }
+
+    println!("\n{} passed, {} failed", cases.len() - failed, failed);
+
+    if failed > 0 {
+        std::process::exit(EXIT_TESTS_FAILED);
+    }
+}
+
+/// finds the source code of an example program, exiting if there isn't one with the given name
+fn find_example(name: &str, errors: ErrorFormat) -> &'static str {
+    match EXAMPLES.iter().find(|(n, ..)| *n == name) {
+        Some((.., code)) => code,
+        None => fail(
+            errors,
+            "load",
+            format!(
+                "there's no example called {:?}, try `chicken examples list`",
+                name
+            ),
+            EXIT_LOAD_ERROR,
+        ),
+    }
+}
+
+fn explain(instruction: Option<String>, errors: ErrorFormat) {
+    let Some(instruction) = instruction else {
+        for explanation in Explanation::all() {
+            println!(
+                "{}: {}",
+                paint_stdout(format!("{:>10}", explanation.mnemonic).bold()),
+                explanation.stack_effect
+            );
+        }
+        return;
+    };
+
+    let Some(explanation) = Explanation::of(&instruction) else {
+        fail(
+            errors,
+            "load",
+            format!(
+                "there's no instruction called {:?}, try `chicken explain` to list them",
+                instruction
+            ),
+            EXIT_LOAD_ERROR,
+        );
+    };
+
+    print!("{}", explanation);
+    match explanation.run_example() {
+        Ok(output) => println!("\noutput: {}", output),
+        Err(err) => println!("\nerror: {}", err.message),
+    }
+}
+
+fn examples(command: ExamplesCommand, errors: ErrorFormat) {
+    match command {
+        ExamplesCommand::List => {
+            for (name, description, input, _) in EXAMPLES {
+                println!(
+                    "{}: {}",
+                    paint_stdout(format!("{:>10}", name).bold()),
+                    description
+                );
+
+                match input {
+                    Some(input) => {
+                        println!("{:>10}  chicken examples run {} -i {}", "", name, input)
+                    }
+                    None => println!("{:>10}  chicken examples run {}", "", name),
+                }
+            }
+        }
+        ExamplesCommand::Show { name } => println!("{}", find_example(&name, errors)),
+        ExamplesCommand::Run {
+            name,
+            input,
+            normal_char,
+        } => {
+            let result = chicken::VMBuilder::from_chicken(find_example(&name, errors))
+                .inputs(match input.is_empty() {
+                    true => vec![Value::from("")],
+                    false => input.into_iter().map(Value::from).collect(),
+                })
+                .set_normal_char(normal_char)
+                .color(color())
+                .build()
+                .run();
+
+            match result {
+                Ok(output) => println!("{}", output),
+                Err(err) => {
+                    match errors {
+                        ErrorFormat::Text => eprintln!("{}", err),
+                        ErrorFormat::Json => eprintln!("{}", serde_json::to_string(&err).unwrap()),
+                    }
+
+                    exit_with(&err);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+fn gui(args: GuiArgs, errors: ErrorFormat) {
+    let program = read_program(&args.file, None, errors);
+    let inputs = match args.input.is_empty() {
+        true => vec![Value::from("")],
+        false => args.input.into_iter().map(Value::from).collect::<Vec<_>>(),
+    };
+
+    let result = chicken::run_gui_debugger(&args.file.display().to_string(), move || {
+        chicken::VMBuilder::from_program(program.clone())
+            .inputs(inputs.clone())
+            .set_normal_char(args.normal_char)
+    });
+
+    if let Err(err) = result {
+        fail(
+            errors,
+            "io",
+            format!("error opening window: {}", err),
+            EXIT_IO_ERROR,
+        );
+    }
+}
+
+/// the body of a request to `POST /run`, or a line sent to `chicken daemon`
+#[cfg(any(feature = "serve", unix))]
+#[derive(Deserialize)]
+struct RunRequest {
+    program: String,
+
+    #[serde(default)]
+    format: Option<ProgramFormat>,
+
+    #[serde(default)]
+    input: RunRequestInput,
+
+    #[serde(default)]
+    normal_char: bool,
+
+    #[serde(default)]
+    max_steps: Option<u64>,
+
+    #[serde(default)]
+    max_stack: Option<u64>,
+}
+
+/// either a single input or a list of them
+#[cfg(any(feature = "serve", unix))]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RunRequestInput {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[cfg(any(feature = "serve", unix))]
+impl Default for RunRequestInput {
+    fn default() -> Self {
+        Self::One(String::new())
+    }
+}
+
+/// an error response, shaped like the errors printed by `--error-format json`
+#[cfg(any(feature = "serve", unix))]
+fn request_error(message: String) -> serde_json::Value {
+    serde_json::json!({ "ok": false, "error": { "kind": "request", "message": message } })
+}
+
+/// the limits a request is run with, which are the ones for untrusted programs with the given step and stack limits.
+/// requests can ask for lower step and stack limits, but never higher
+#[cfg(any(feature = "serve", unix))]
+fn request_limits(run: &RunRequest, max_steps: u64, max_stack: u64) -> Limits {
+    let to_usize = |n: u64| usize::try_from(n).unwrap_or(usize::MAX);
+
+    let asked_for = Limits {
+        max_steps: run.max_steps,
+        max_stack: run.max_stack.map(to_usize),
+        ..Default::default()
+    };
+
+    asked_for.clamp_to(Limits {
+        max_steps: Some(max_steps),
+        max_stack: Some(to_usize(max_stack)),
+        ..Limits::untrusted()
+    })
+}
+
+/// runs the program in a request, returning the same JSON object as `run --format json`.
+/// the request can ask for lower limits than the given ones, but never higher
+#[cfg(any(feature = "serve", unix))]
+fn run_request(
+    run: RunRequest,
+    max_steps: u64,
+    max_stack: u64,
+) -> Result<serde_json::Value, String> {
+    let limits = request_limits(&run, max_steps, max_stack);
+
+    let program = match run
+        .format
+        .unwrap_or(ProgramFormat::Chicken)
+        .parse(run.program, None)
+    {
+        Ok(file) => file.program,
+        Err(err) => return Err(format!("error parsing program: {}", err)),
+    };
+
+    let inputs = match run.input {
+        RunRequestInput::One(input) => vec![Value::from(input)],
+        RunRequestInput::Many(inputs) => inputs.into_iter().map(Value::from).collect(),
+    };
+
+    let mut vm = chicken::VMBuilder::from_program(program)
+        .inputs(inputs)
+        .set_normal_char(run.normal_char)
+        .limits(limits)
+        .build();
+
+    let result = vm.run();
+
+    Ok(serde_json::to_value(JsonResult {
+        event: None,
+        ok: result.is_ok(),
+        output: result.as_ref().ok(),
+        stats: vm.stats(),
+        error: result.as_ref().err(),
+        warnings: &[],
+    })
+    .unwrap())
+}
+
+#[cfg(feature = "serve")]
+fn serve(args: ServeArgs, errors: ErrorFormat) {
+    let server = match tiny_http::Server::http((args.host.as_str(), args.port)) {
+        Ok(server) => server,
+        Err(err) => fail(
+            errors,
+            "io",
+            format!("error listening on {}:{}: {}", args.host, args.port, err),
+            EXIT_IO_ERROR,
+        ),
+    };
+
+    eprintln!("listening on http://{}", server.server_addr());
+
+    std::thread::scope(|scope| {
+        for _ in 0..args.threads.max(1) {
+            scope.spawn(|| {
+                for request in server.incoming_requests() {
+                    handle_request(request, &args);
+                }
+            });
+        }
+    });
+}
+
+#[cfg(feature = "serve")]
+fn handle_request(mut request: tiny_http::Request, args: &ServeArgs) {
+    use tiny_http::Method;
+
+    let (status, body) = match (request.method(), request.url()) {
+        (Method::Get, "/health") => (200, serde_json::json!({ "ok": true })),
+        (Method::Post, "/run") => handle_run_request(&mut request, args),
+        (_, "/health" | "/run") => (405, request_error("method not allowed".to_string())),
+        _ => (404, request_error("not found".to_string())),
+    };
+
+    let response = tiny_http::Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(
+            tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap(),
+        );
+
+    // the client going away isn't the server's problem
+    let _ = request.respond(response);
+}
+
+/// reads and runs a request to `POST /run`
+#[cfg(feature = "serve")]
+fn handle_run_request(
+    request: &mut tiny_http::Request,
+    args: &ServeArgs,
+) -> (u16, serde_json::Value) {
+    use std::io::Read;
+
+    let mut body = String::new();
+    if let Err(err) = request
+        .as_reader()
+        .take(args.max_body + 1)
+        .read_to_string(&mut body)
+    {
+        return (
+            400,
+            request_error(format!("error reading request: {}", err)),
+        );
+    }
+
+    if body.len() as u64 > args.max_body {
+        return (
+            413,
+            request_error(format!("request is larger than {} bytes", args.max_body)),
+        );
+    }
+
+    let run: RunRequest = match serde_json::from_str(&body) {
+        Ok(run) => run,
+        Err(err) => return (400, request_error(format!("invalid request: {}", err))),
+    };
+
+    match run_request(run, args.max_steps, args.max_stack) {
+        Ok(result) => (200, result),
+        Err(err) => (400, request_error(err)),
+    }
+}
+
+#[cfg(unix)]
+fn daemon(args: DaemonArgs, errors: ErrorFormat) {
+    use std::os::unix::{fs::FileTypeExt, net::UnixListener};
+
+    // a socket left behind by a daemon that didn't get to clean up would stop it from binding,
+    // but one that's still being listened on belongs to another daemon and has to be left alone,
+    // and anything that isn't a socket is probably a typo that shouldn't cost someone their file
+    match std::fs::symlink_metadata(&args.socket) {
+        Ok(metadata) if !metadata.file_type().is_socket() => fail(
+            errors,
+            "io",
+            format!(
+                "error listening on {:?}: path exists and is not a socket",
+                args.socket
+            ),
+            EXIT_IO_ERROR,
+        ),
+        Ok(_) if std::os::unix::net::UnixStream::connect(&args.socket).is_err() => {
+            let _ = std::fs::remove_file(&args.socket);
+        }
+        _ => (),
+    }
+
+    let listener = match UnixListener::bind(&args.socket) {
+        Ok(listener) => listener,
+        Err(err) => fail(
+            errors,
+            "io",
+            format!("error listening on {:?}: {}", args.socket, err),
+            EXIT_IO_ERROR,
+        ),
+    };
+
+    let socket = args.socket.clone();
+    let _ = ctrlc::set_handler(move || {
+        let _ = std::fs::remove_file(&socket);
+        std::process::exit(EXIT_INTERRUPTED);
+    });
+
+    eprintln!("listening on {}", args.socket.display());
+
+    std::thread::scope(|scope| {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let args = &args;
+                    scope.spawn(move || handle_connection(stream, args));
+                }
+                Err(err) => eprintln!("error accepting connection: {}", err),
+            }
+        }
+    });
+}
+
+/// runs every request sent over a connection to `chicken daemon`, writing a line of JSON back for each one
+#[cfg(unix)]
+fn handle_connection(stream: std::os::unix::net::UnixStream, args: &DaemonArgs) {
+    use std::io::{BufRead, Read};
+
+    let mut reader = BufReader::new(&stream);
+    let mut writer = BufWriter::new(&stream);
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        let result = match (&mut reader)
+            .take(args.max_request + 1)
+            .read_line(&mut line)
+        {
+            Ok(0) => return,
+            Ok(_) if line.len() as u64 > args.max_request => {
+                // the rest of the line can't be told apart from the next request, so the connection is done for
+                let error =
+                    request_error(format!("request is larger than {} bytes", args.max_request));
+                let _ = writeln!(writer, "{}", error).and_then(|_| writer.flush());
+                return;
+            }
+            Ok(_) if line.trim().is_empty() => continue,
+            Ok(_) => match serde_json::from_str(&line) {
+                Ok(run) => {
+                    run_request(run, args.max_steps, args.max_stack).unwrap_or_else(request_error)
+                }
+                Err(err) => request_error(format!("invalid request: {}", err)),
+            },
+            // the client going away isn't the daemon's problem
+            Err(_) => return,
+        };
+
+        if writeln!(writer, "{}", result)
+            .and_then(|_| writer.flush())
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// reads a trace from a file, exiting if it can't be read
+fn read_trace(file: &Path, errors: ErrorFormat) -> Trace {
+    match File::open(file)
+        .map_err(chicken::TraceError::from)
+        .and_then(|file| Trace::from_reader(BufReader::new(file)))
+    {
+        Ok(trace) => trace,
+        Err(err) => fail(
+            errors,
+            "load",
+            format!("error reading trace {:?}: {}", file, err),
+            EXIT_LOAD_ERROR,
+        ),
+    }
+}
+
+fn replay(args: ReplayArgs, errors: ErrorFormat) {
+    let trace = read_trace(&args.file, errors);
+
+    if args.debug {
+        enable_debug_logging();
+    }
+
+    let (result, divergence) = trace.replay(trace.builder().set_debug(args.debug));
+
+    match &result {
+        Ok(output) => println!("{}", output),
+        Err(err) if errors == ErrorFormat::Json => {
+            eprintln!("{}", serde_json::to_string(err).unwrap())
+        }
+        Err(err) => eprintln!("{}", err),
+    }
+
+    if let Some(divergence) = divergence {
+        fail(
+            errors,
+            "diverged",
+            divergence.to_string(),
+            EXIT_TRACE_DIVERGED,
+        );
+    }
+
+    if let Err(err) = result {
+        exit_with(&err);
+    }
+}
+
+fn diff_traces(args: DiffArgs, errors: ErrorFormat) {
+    let first = read_trace(&args.first, errors);
+    let second = read_trace(&args.second, errors);
+
+    match first.diff(&second) {
+        Some(diff) => fail(errors, "diverged", diff.to_string(), EXIT_TRACE_DIVERGED),
+        None => println!("runs are the same for all {} steps", first.steps.len()),
+    }
+}
+
+fn compare_modes(args: CompareModesArgs, errors: ErrorFormat) {
+    if args.modes.len() < 2 {
+        fail(
+            errors,
+            "usage",
+            "at least two modes are needed to compare".to_string(),
+            EXIT_LOAD_ERROR,
+        );
+    }
+
+    let program = read_program(
+        &args.file,
+        args.opcodes.then_some(ProgramFormat::Opcodes),
+        errors,
+    );
+    let inputs = match args.input.is_empty() {
+        true => vec![Value::from("")],
+        false => args.input.into_iter().map(Value::from).collect(),
+    };
+
+    let mut vms = (args.modes.iter())
+        .map(|mode| {
+            chicken::VMBuilder::from_program(program.clone())
+                .inputs(inputs.clone())
+                .set_strict(mode.strict)
+                .set_normal_char(mode.normal_char)
+                .set_freeze_program(mode.freeze_program)
+                .set_extensions(mode.extensions)
+                .seed(0)
+                .limits(Limits {
+                    max_steps: Some(args.max_steps),
+                    ..Default::default()
+                })
+                .build()
+        })
+        .collect::<Vec<_>>();
+
+    // the runs go in lockstep until one of them does something different, or they all stop the same way
+    let mut step = 0;
+    let (results, diverged) = loop {
+        step += 1;
+        let results = vms.iter_mut().map(|vm| vm.step_info()).collect::<Vec<_>>();
+
+        let diverged = results.iter().any(|result| *result != results[0]);
+        if diverged || !matches!(results[0], Ok(Some(_))) {
+            break (results, diverged);
+        }
+    };
+
+    let mut message = format!("runs diverged at step {}", step);
+    for (mode, result) in args.modes.iter().zip(&results) {
+        let shown = match result {
+            Ok(Some(info)) => serde_json::to_string(info).unwrap(),
+            Ok(None) => "nothing, it had already stopped".to_string(),
+            Err(err) => format!("error {:?}", err.message),
+        };
+        message += &format!("\n{}: {}", mode.name, shown);
+    }
+
+    // then the ones that are still going run to the end on their own
+    let outputs = (vms.iter_mut().zip(results))
+        .map(|(vm, result)| match result {
+            Err(err) => Err(err),
+            Ok(_) => vm.run(),
+        })
+        .collect::<Vec<_>>();
+
+    for ((mode, vm), output) in args.modes.iter().zip(&vms).zip(&outputs) {
+        match output {
+            Ok(output) => println!(
+                "{}: output {:?} after {} steps",
+                mode.name,
+                output,
+                vm.stats().steps
+            ),
+            Err(err) => println!(
+                "{}: error {:?} after {} steps",
+                mode.name,
+                err.message,
+                vm.stats().steps
+            ),
+        }
+    }
+
+    if !diverged {
+        println!(
+            "the runs are the same for all {} steps",
+            vms[0].stats().steps
+        );
+        return;
+    }
+
+    if outputs
+        .iter()
+        .all(|output| output.as_ref().ok() == outputs[0].as_ref().ok())
+    {
+        message += "\nbut they all ended up with the same output";
+    }
+
+    fail(errors, "diverged", message, EXIT_TRACE_DIVERGED);
+}
+
+/// writes some text to a file, or to stdout if there isn't one, exiting if it can't be written
+fn write_output(output: Option<PathBuf>, text: &str, errors: ErrorFormat) {
+    match output {
+        Some(output) => {
+            if let Err(err) = std::fs::write(&output, text) {
+                fail(
+                    errors,
+                    "io",
+                    format!("error writing file {:?}: {:?}", output, err),
+                    EXIT_IO_ERROR,
+                );
+            }
+        }
+        None => print!("{}", text),
+    }
+}
+
+fn cfg(args: CfgArgs, errors: ErrorFormat) {
+    let program = read_program(
+        &args.file,
+        args.opcodes.then_some(ProgramFormat::Opcodes),
+        errors,
+    );
+
+    write_output(args.output, &program.cfg().to_dot(), errors);
+}
+
+fn compile(args: CompileArgs, errors: ErrorFormat) {
+    let file = read_program_file(&args.file, args.from, errors);
+
+    if args.source_map {
+        if args.emit != ProgramFormat::Json {
+            fail(
+                errors,
+                "compile",
+                "source maps can only be written into json output".to_string(),
+                EXIT_LOAD_ERROR,
+            );
+        }
+
+        if file.source_map.is_none() {
+            fail(
+                errors,
+                "compile",
+                format!("{:?} doesn't have a source map", args.file),
+                EXIT_LOAD_ERROR,
+            );
+        }
+
+        write_output(
+            args.output,
+            &(serde_json::to_string(&file).unwrap() + "\n"),
+            errors,
+        );
+        return;
+    }
+
+    if args.jump_targets && args.emit != ProgramFormat::Asm {
+        fail(
+            errors,
+            "compile",
+            "jump targets can only be written into asm output".to_string(),
+            EXIT_LOAD_ERROR,
+        );
+    }
+
+    let mut program = file.program;
+    let metadata = file.metadata;
+
+    if args.remove_dead_code {
+        let removed = program.remove_dead_code(args.freeze_program);
+        eprintln!("removed {} dead opcodes", removed);
+    }
+
+    let text = match args.emit {
+        ProgramFormat::Chicken => match program.to_chicken() {
+            // a trailing newline would add an extra exit to the end of the program
+            Some(chicken) => chicken,
+            None => fail(
+                errors,
+                "compile",
+                "programs with negative opcodes can't be written as chicken code".to_string(),
+                EXIT_LOAD_ERROR,
+            ),
+        },
+        ProgramFormat::Opcodes => metadata.to_header() + &program.to_opcode_text(),
+        ProgramFormat::Asm if args.jump_targets => {
+            metadata.to_header() + &program.to_asm_with_jump_targets()
+        }
+        ProgramFormat::Asm => metadata.to_header() + &program.to_asm(),
+        ProgramFormat::Json => {
+            let file = ProgramFile {
+                program,
+                source_map: None,
+                metadata,
+            };
+            serde_json::to_string(&file).unwrap() + "\n"
+        }
+    };
+
+    write_output(args.output, &text, errors);
+}
+
+// runs a program for minimize, returning the kind of error it failed with and the mnemonic of the instruction it failed at
+fn minimize_failure(
+    program: &Program,
+    inputs: &[Value],
+    args: &MinimizeArgs,
+) -> Option<(ErrorKind, &'static str)> {
+    let mut vm = chicken::VMBuilder::from_program(program.clone())
+        .inputs(inputs.to_vec())
+        .set_strict(args.strict)
+        .set_extensions(args.extensions)
+        .stack_capture(StackCapture::None)
+        .seed(0)
+        .limits(Limits {
+            max_steps: Some(args.max_steps),
+            ..Default::default()
+        })
+        .build();
+
+    loop {
+        let pc = vm.program_counter;
+
+        if let Err(err) = vm.step() {
+            // the limit is hit wherever the program happens to be, so any instruction counts for it
+            let mnemonic = match err.kind {
+                ErrorKind::Limit => "",
+                _ => (pc.checked_sub(vm.program_start))
+                    .and_then(|index| program.decode(index))
+                    .map_or("", |instruction| instruction.mnemonic()),
+            };
+            return Some((err.kind, mnemonic));
+        }
+
+        if vm.exited {
+            return None;
+        }
+    }
+}
+
+fn minimize(args: MinimizeArgs, errors: ErrorFormat) {
+    let program = read_program_file(&args.file, args.from, errors).program;
+    let inputs = match args.input.is_empty() {
+        true => vec![Value::from("")],
+        false => args
+            .input
+            .iter()
+            .map(|input| Value::from(input.as_str()))
+            .collect(),
+    };
+
+    let Some(failure) = minimize_failure(&program, &inputs, &args) else {
+        fail(
+            errors,
+            "usage",
+            "the program doesn't fail, so there's nothing to minimize".to_string(),
+            EXIT_LOAD_ERROR,
+        );
+    };
+
+    let minimized =
+        program.minimize(|candidate| minimize_failure(candidate, &inputs, &args) == Some(failure));
+
+    match failure {
+        (kind, "") => eprintln!(
+            "minimized from {} to {} opcodes, still failing with a {:?} error",
+            program.len(),
+            minimized.len(),
+            kind
+        ),
+        (kind, mnemonic) => eprintln!(
+            "minimized from {} to {} opcodes, still failing with a {:?} error at a {}",
+            program.len(),
+            minimized.len(),
+            kind,
+            mnemonic
+        ),
+    }
+
+    let text = match args.emit {
+        ProgramFormat::Chicken => match minimized.to_chicken() {
+            Some(chicken) => chicken,
+            None => fail(
+                errors,
+                "compile",
+                "programs with negative opcodes can't be written as chicken code".to_string(),
+                EXIT_LOAD_ERROR,
+            ),
+        },
+        ProgramFormat::Opcodes => minimized.to_opcode_text(),
+        ProgramFormat::Asm => minimized.to_asm(),
+        ProgramFormat::Json => serde_json::to_string(&minimized).unwrap() + "\n",
+    };
+
+    write_output(args.output, &text, errors);
+}
+
+const REPL_HELP: &str = "type assembly to run it, with ; between instructions, like push 1; load 0
+commands:
+    :input <text>      run the snippets after this with a different input
+    :help              print this list
+    :quit              leave the repl, which pressing Ctrl-D does too";
+
+// how many instructions run between checks of the budget
+const REPL_CHUNK: u64 = 10_000;
+
+// asks a yes or no question on stderr, taking anything but yes as no
+fn confirm(question: &str) -> bool {
+    eprint!("{} [y/N] ", question);
+    let _ = std::io::stderr().flush();
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).is_ok()
+        && matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+fn repl(args: ReplArgs) {
+    let mut input = args.input;
+    eprintln!(
+        "{}, or :help for a list of commands",
+        REPL_HELP.lines().next().unwrap_or_default()
+    );
+
+    loop {
+        eprint!("{} ", paint_stderr("chicken>".bold()));
+        let _ = std::io::stderr().flush();
+
+        let mut line = String::new();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let line = line.trim();
+
+        if let Some(command) = line.strip_prefix(':') {
+            let (command, rest) = command.split_once(' ').unwrap_or((command, ""));
+
+            match command {
+                "input" => input = rest.to_string(),
+                "h" | "help" => eprintln!("{}", REPL_HELP),
+                "q" | "quit" => break,
+                _ => eprintln!(
+                    "{}",
+                    paint_stderr(
+                        format!(
+                            "unknown command :{}, type :help for a list of commands",
+                            command
+                        )
+                        .red()
+                    )
+                ),
+            }
+            continue;
+        }
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let program = match Program::from_asm(line.replace(';', "\n")) {
+            Ok(program) => program,
+            Err(err) => {
+                eprintln!("{}", paint_stderr(err.to_string().red()));
+                continue;
+            }
+        };
+        let mut vm = chicken::VMBuilder::from_program(program)
+            .input(input.as_str())
+            .set_extensions(args.extensions)
+            .build();
+
+        // each budget is used up a chunk at a time, so an infinite loop can't take over the session
+        let result = loop {
+            let start = Instant::now();
+            let steps_before = vm.stats().steps;
+
+            let progress = loop {
+                let used = vm.stats().steps - steps_before;
+                match vm.run_steps(REPL_CHUNK.min(args.fuel.saturating_sub(used).max(1))) {
+                    Ok(RunProgress::Running)
+                        if vm.stats().steps - steps_before < args.fuel
+                            && start.elapsed() < args.time_budget => {}
+                    progress => break progress,
+                }
+            };
+
+            match progress {
+                Ok(RunProgress::Running) => {
+                    let question = format!(
+                        "still running after {} steps, continue anyway?",
+                        vm.stats().steps
+                    );
+                    if !confirm(&question) {
+                        break None;
+                    }
+                }
+                progress => break Some(progress),
+            }
+        };
+
+        match result {
+            Some(Ok(RunProgress::Exited(output))) => println!("{}", output),
+            Some(Ok(RunProgress::Running)) => unreachable!(),
+            Some(Err(err)) => eprintln!(
+                "{}",
+                paint_stderr(format!("{} at pc {}", err.message, err.program_counter).red())
+            ),
+            None => eprintln!("{}", paint_stderr("stopped".yellow())),
+        }
+    }
+}
+
+fn disasm(args: DisasmArgs, errors: ErrorFormat) {
+    let file = read_program_file(&args.file, args.from, errors);
+    let mut text = String::new();
+
+    for instruction in file.program.disassemble(file.source_map.as_ref()) {
+        match args.format {
+            DisasmFormat::Text => {
+                if let Some(label) = &instruction.label {
+                    text += &format!("{}:\n", label);
+                }
+                text += &format!("{}\n", instruction);
+            }
+            DisasmFormat::Json => {
+                text += &(serde_json::to_string(&instruction).unwrap() + "\n");
+            }
+        }
+    }
+
+    write_output(args.output, &text, errors);
+}
+
+fn link(args: LinkArgs, errors: ErrorFormat) {
+    let mut files = Vec::new();
+
+    for file in args.files.iter() {
+        match read_source(file) {
+            Ok(asm) => files.push((file.display().to_string(), asm)),
+            Err(err) => fail(
+                errors,
+                "load",
+                format!("error reading file {:?}: {}", file, err),
+                EXIT_LOAD_ERROR,
+            ),
+        }
+    }
+
+    // the metadata of the whole program is whatever the first file says
+    let metadata = match Metadata::from_header(&files[0].1) {
+        Ok(metadata) => metadata,
+        Err(err) => fail(
+            errors,
+            "load",
+            format!("invalid program {:?} on {}", args.files[0], err),
+            EXIT_LOAD_ERROR,
+        ),
+    };
+
+    let (program, source_map) = match Program::link(files) {
+        Ok(linked) => linked,
+        Err(err) => fail(
+            errors,
+            "load",
+            format!("error linking {}", err),
+            EXIT_LOAD_ERROR,
+        ),
+    };
+
+    let text = match args.emit {
+        ProgramFormat::Chicken => match program.to_chicken() {
+            Some(chicken) => chicken,
+            None => fail(
+                errors,
+                "compile",
+                "programs with negative opcodes can't be written as chicken code".to_string(),
+                EXIT_LOAD_ERROR,
+            ),
+        },
+        ProgramFormat::Opcodes => metadata.to_header() + &program.to_opcode_text(),
+        ProgramFormat::Asm => metadata.to_header() + &program.to_asm(),
+        ProgramFormat::Json => {
+            let file = ProgramFile {
+                program,
+                source_map: Some(source_map),
+                metadata,
+            };
+            serde_json::to_string(&file).unwrap() + "\n"
+        }
+    };
+
+    write_output(args.output, &text, errors);
+}
+
+/// when to color what's printed, from --color
+static COLOR: OnceLock<ColorMode> = OnceLock::new();
+
+/// the --color mode
+fn color() -> ColorMode {
+    COLOR.get().copied().unwrap_or_default()
+}
+
+/// colors text going to stdout according to --color
+fn paint_stdout(text: ColoredString) -> String {
+    color().paint(text, Stream::Stdout)
+}
+
+/// colors text going to stderr according to --color
+fn paint_stderr(text: ColoredString) -> String {
+    color().paint(text, Stream::Stderr)
+}
+
+/// parses the command line, falling back to running the program for invocations from before there were subcommands,
+/// like `chicken --file program.chicken`
+fn parse_cli() -> Cli {
+    let err = match Cli::try_parse() {
+        Ok(cli) => return cli,
+        Err(err) => err,
+    };
+
+    let mut args = std::env::args_os();
+    let legacy = (args.next().into_iter())
+        .chain(std::iter::once("run".into()))
+        .chain(args);
+
+    match Cli::try_parse_from(legacy) {
+        Ok(
+            cli @ Cli {
+                command:
+                    Command::Run(RunArgs {
+                        file_flag: Some(_), ..
+                    }),
+                ..
+            },
+        ) => cli,
+        _ => err.exit(),
+    }
+}
+
+fn main() {
+    let cli = parse_cli();
+    let _ = COLOR.set(cli.color);
+
+    match cli.command {
+        Command::Run(args) => run(args, cli.error_format),
+        Command::Cfg(args) => cfg(args, cli.error_format),
+        Command::Compile(args) => compile(args, cli.error_format),
+        Command::Link(args) => link(args, cli.error_format),
+        Command::Disasm(args) => disasm(args, cli.error_format),
+        Command::Repl(args) => repl(args),
+        Command::Fmt(args) => fmt(args, cli.error_format),
+        Command::Lint(args) => lint(args, cli.error_format),
+        Command::Test(args) => test(args, cli.error_format),
+        Command::RunAll(args) => run_all(args, cli.error_format),
+        Command::Conformance(args) => conformance(args, cli.error_format),
+        Command::Examples(command) => examples(command, cli.error_format),
+        #[cfg(feature = "gui")]
+        Command::Gui(args) => gui(args, cli.error_format),
+        #[cfg(feature = "serve")]
+        Command::Serve(args) => serve(args, cli.error_format),
+        #[cfg(unix)]
+        Command::Daemon(args) => daemon(args, cli.error_format),
+        Command::Replay(args) => replay(args, cli.error_format),
+        Command::Diff(args) => diff_traces(args, cli.error_format),
+        Command::CompareModes(args) => compare_modes(args, cli.error_format),
+        Command::Minimize(args) => minimize(args, cli.error_format),
+        Command::Explain { instruction } => explain(instruction, cli.error_format),
+        Command::Completions { shell } => clap_complete::generate(
+            shell,
+            &mut Cli::command(),
+            "chicken",
+            &mut std::io::stdout(),
+        ),
     }
 }
@@ -0,0 +1,26 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// stops a running VM from another thread or a signal handler, see [VMBuilder::cancellation_token](crate::VMBuilder::cancellation_token).
+/// clones share the same flag, so cancelling any of them cancels all of them
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// makes a new token that hasn't been cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// cancels every VM using this token, which stop with an [ErrorKind::Interrupted](crate::ErrorKind::Interrupted) error before their next instruction
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// whether this token has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
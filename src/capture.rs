@@ -0,0 +1,62 @@
+use crate::Value;
+use std::{fmt, str::FromStr};
+
+/// how much of the stack a [ChickenError](crate::ChickenError) keeps a copy of, see [VMBuilder::stack_capture](crate::VMBuilder::stack_capture)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StackCapture {
+    /// the whole stack, which is the most useful for debugging but can be megabytes for big programs
+    #[default]
+    Full,
+
+    /// only the first and the last n values, which usually covers the program and whatever it was working on
+    Ends(usize),
+
+    /// none of it
+    None,
+}
+
+impl StackCapture {
+    // copies the parts of the stack this captures, along with how many values were left out of the middle
+    pub(crate) fn capture(self, stack: &[Value]) -> (Vec<Value>, usize) {
+        match self {
+            StackCapture::Full => (stack.to_vec(), 0),
+            StackCapture::Ends(n) if stack.len() <= n.saturating_mul(2) => (stack.to_vec(), 0),
+            StackCapture::Ends(n) => {
+                let captured = stack[..n]
+                    .iter()
+                    .chain(&stack[stack.len() - n..])
+                    .cloned()
+                    .collect();
+                (captured, stack.len() - n * 2)
+            }
+            StackCapture::None => (Vec::new(), stack.len()),
+        }
+    }
+}
+
+impl FromStr for StackCapture {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "full" => Ok(StackCapture::Full),
+            "none" => Ok(StackCapture::None),
+            _ => s.parse().map(StackCapture::Ends).map_err(|_| {
+                format!(
+                    "unknown stack capture {:?}, expected full, none or a number of values to keep from each end",
+                    s
+                )
+            }),
+        }
+    }
+}
+
+impl fmt::Display for StackCapture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StackCapture::Full => f.write_str("full"),
+            StackCapture::Ends(n) => write!(f, "{}", n),
+            StackCapture::None => f.write_str("none"),
+        }
+    }
+}
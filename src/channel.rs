@@ -0,0 +1,73 @@
+use crate::Value;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+/// a queue of values that VMs send to and receive from with [Extension::Send](crate::Extension::Send) and
+/// [Extension::Recv](crate::Extension::Recv), bound to a number with [VMBuilder::channel](crate::VMBuilder::channel).
+/// clones share the same queue, so giving clones to two VMs lets them pass values to each other while they run.
+/// a VM receiving from an empty channel waits on the receive until something is sent, so VMs that talk to each other
+/// should take turns with [VMState::run_steps](crate::VMState::run_steps) or [VMState::poll](crate::VMState::poll),
+/// or run on threads of their own
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::{Channel, Extension, RunProgress, VMBuilder, Value};
+///
+/// let channel = Channel::new();
+/// let (send, recv) = (Extension::Send.opcode(), Extension::Recv.opcode());
+///
+/// // receives from channel 0 and exits with it
+/// let mut receiver = VMBuilder::from_opcodes([10, recv, 0])
+///     .extensions()
+///     .channel(0, channel.clone())
+///     .build();
+///
+/// // sends "chicken" to channel 0, then exits with another one
+/// let mut sender = VMBuilder::from_opcodes([1, 10, send, 1, 0])
+///     .extensions()
+///     .channel(0, channel)
+///     .build();
+///
+/// // nothing's been sent yet, so the receiver waits
+/// assert_eq!(receiver.run_steps(10), Ok(RunProgress::Running));
+/// assert!(receiver.is_waiting());
+///
+/// assert_eq!(sender.run(), Ok("chicken".to_string()));
+/// assert_eq!(receiver.run(), Ok("chicken".to_string()));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Channel(Arc<Mutex<VecDeque<Value>>>);
+
+impl Channel {
+    /// makes an empty channel
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// adds a value to the back of the channel, like a program sending it
+    pub fn send<T: Into<Value>>(&self, value: T) {
+        self.queue().push_back(value.into());
+    }
+
+    /// takes the value at the front of the channel, like a program receiving it, or None if it's empty
+    pub fn recv(&self) -> Option<Value> {
+        self.queue().pop_front()
+    }
+
+    /// how many values have been sent but not received yet
+    pub fn len(&self) -> usize {
+        self.queue().len()
+    }
+
+    /// whether there aren't any values waiting to be received
+    pub fn is_empty(&self) -> bool {
+        self.queue().is_empty()
+    }
+
+    fn queue(&self) -> std::sync::MutexGuard<'_, VecDeque<Value>> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
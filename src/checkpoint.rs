@@ -0,0 +1,95 @@
+use crate::{ChickenError, ErrorKind, VMState};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+// writes a snapshot of the VM to a file every so many steps
+pub(crate) struct Checkpointer {
+    every_n_steps: u64,
+
+    // the step count to write the next checkpoint at
+    next: u64,
+
+    path: PathBuf,
+}
+
+impl Checkpointer {
+    pub(crate) fn new(every_n_steps: u64, path: PathBuf) -> Self {
+        let every_n_steps = every_n_steps.max(1);
+
+        Self {
+            every_n_steps,
+            next: every_n_steps,
+            path,
+        }
+    }
+
+    // starts counting from the first step again
+    pub(crate) fn reset(&mut self) {
+        self.next = self.every_n_steps;
+    }
+}
+
+impl VMState {
+    /// writes a [Snapshot](crate::Snapshot) of the VM to a file as JSON, which can be resumed with [VMBuilder::from_checkpoint](crate::VMBuilder::from_checkpoint).
+    /// the snapshot is written to a temporary file next to it first, so a crash while writing doesn't lose the checkpoint that was already there
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    ///
+    /// let path = std::env::temp_dir().join("chicken-save-checkpoint-example.json");
+    ///
+    /// let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    /// vm.step().unwrap();
+    /// vm.save_checkpoint(&path).unwrap();
+    ///
+    /// let mut resumed = VMBuilder::from_checkpoint(&path).unwrap().build();
+    /// assert_eq!(resumed.program_counter, 3);
+    /// assert_eq!(resumed.run(), Ok("abc".to_string()));
+    /// # std::fs::remove_file(path).unwrap();
+    /// ```
+    pub fn save_checkpoint<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        serde_json::to_writer(&mut writer, &self.snapshot())?;
+        writer.flush()?;
+        drop(writer);
+
+        std::fs::rename(temp_path, path)
+    }
+
+    // whether a checkpoint has to be written before the next step, which the fast path leaves to the slow one
+    pub(crate) fn checkpoint_due(&self) -> bool {
+        (self.checkpointer.as_ref())
+            .is_some_and(|checkpointer| self.stats.steps >= checkpointer.next)
+    }
+
+    // writes a checkpoint if enough steps have been executed since the last one.
+    // fused instructions count as two steps, so the step count can go past the next checkpoint by one
+    pub(crate) fn checkpoint(&mut self) -> Result<(), ChickenError> {
+        if !self.checkpoint_due() {
+            return Ok(());
+        }
+
+        let steps = self.stats.steps;
+        let Some(checkpointer) = self.checkpointer.as_mut() else {
+            return Ok(());
+        };
+        checkpointer.next = (steps / checkpointer.every_n_steps + 1) * checkpointer.every_n_steps;
+
+        let path = checkpointer.path.clone();
+        self.save_checkpoint(&path).map_err(|err| {
+            self.error(
+                ErrorKind::Io,
+                format_args!("error writing checkpoint {:?}: {}", path, err),
+            )
+        })
+    }
+}
@@ -0,0 +1,113 @@
+use colored::{ColoredString, Styles};
+use std::{
+    fmt,
+    io::{stderr, stdout, IsTerminal},
+    str::FromStr,
+};
+
+/// when errors and debugger output should be colored, set with [VMBuilder::color](crate::VMBuilder::color).
+/// each VM follows its own mode, so VMs with different modes can be used side by side
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    /// color output going to a terminal, unless the `NO_COLOR` environment variable is set
+    #[default]
+    Auto,
+
+    /// always color output, even when it isn't going to a terminal
+    Always,
+
+    /// never color output
+    Never,
+}
+
+/// the standard stream some output goes to, which decides whether [ColorMode::Auto] colors it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    /// standard output, where the debugger writes by default
+    Stdout,
+
+    /// standard error, where errors are usually printed
+    Stderr,
+}
+
+impl ColorMode {
+    /// whether output going to the given stream should be colored in this mode
+    pub fn enabled(self, stream: Stream) -> bool {
+        match self {
+            ColorMode::Auto => {
+                let terminal = match stream {
+                    Stream::Stdout => stdout().is_terminal(),
+                    Stream::Stderr => stderr().is_terminal(),
+                };
+                terminal && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+            }
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+
+    /// writes text styled with the [colored] crate with its escape codes if output going to the given stream should be colored, or as plain text if it shouldn't.
+    /// unlike printing the styled text directly, this doesn't depend on the global settings of the colored crate
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{ColorMode, Stream};
+    /// use colored::Colorize;
+    ///
+    /// assert_eq!(ColorMode::Always.paint("hi".red(), Stream::Stderr), "\x1b[31mhi\x1b[0m");
+    /// assert_eq!(ColorMode::Never.paint("hi".red(), Stream::Stderr), "hi");
+    /// ```
+    pub fn paint(self, text: ColoredString, stream: Stream) -> String {
+        paint(text, self.enabled(stream))
+    }
+}
+
+// writes styled text with its escape codes if enabled is set, or as plain text otherwise
+pub(crate) fn paint(text: ColoredString, enabled: bool) -> String {
+    let style = text.style;
+    let mut codes = [
+        (Styles::Bold, "1"),
+        (Styles::Dimmed, "2"),
+        (Styles::Italic, "3"),
+        (Styles::Underline, "4"),
+        (Styles::Reversed, "7"),
+    ]
+    .into_iter()
+    .filter(|(s, _)| style.contains(*s))
+    .map(|(_, code)| code.into())
+    .collect::<Vec<_>>();
+    codes.extend(text.fgcolor.map(|color| color.to_fg_str()));
+    codes.extend(text.bgcolor.map(|color| color.to_bg_str()));
+
+    match enabled && !codes.is_empty() {
+        true => format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text.input),
+        false => text.input,
+    }
+}
+
+impl FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorMode::Auto),
+            "always" => Ok(ColorMode::Always),
+            "never" => Ok(ColorMode::Never),
+            _ => Err(format!(
+                "unknown color mode {:?}, expected auto, always or never",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ColorMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ColorMode::Auto => "auto",
+            ColorMode::Always => "always",
+            ColorMode::Never => "never",
+        })
+    }
+}
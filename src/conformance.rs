@@ -0,0 +1,262 @@
+use crate::{ErrorKind, Limits, LoadError, ParseError, Program, VMBuilder};
+use serde::{Deserialize, Serialize};
+use std::{fmt, path::Path};
+use toml::Spanned;
+
+// enough for any case to finish, while still catching cases that loop forever
+const MAX_STEPS: u64 = 100_000;
+
+/// the version of the format written by [ConformanceVectors], which is bumped whenever it changes
+pub const CONFORMANCE_VERSION: u32 = 1;
+
+// the cases in tests/conformance, built in so they can be used without a copy of the repository
+const BUILTIN: &[(&str, &str)] = &[
+    ("add", include_str!("../tests/conformance/add.toml")),
+    ("char", include_str!("../tests/conformance/char.toml")),
+    ("chicken", include_str!("../tests/conformance/chicken.toml")),
+    ("compare", include_str!("../tests/conformance/compare.toml")),
+    ("exit", include_str!("../tests/conformance/exit.toml")),
+    ("jump", include_str!("../tests/conformance/jump.toml")),
+    ("load", include_str!("../tests/conformance/load.toml")),
+    (
+        "multiply",
+        include_str!("../tests/conformance/multiply.toml"),
+    ),
+    ("push", include_str!("../tests/conformance/push.toml")),
+    ("store", include_str!("../tests/conformance/store.toml")),
+    (
+        "subtract",
+        include_str!("../tests/conformance/subtract.toml"),
+    ),
+];
+
+/// a single case from a conformance file, which runs a small program and checks that it behaves the way the original implementation does.
+/// it's serialized with the program as an array of opcodes, so other implementations don't need to parse assembly
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConformanceCase {
+    /// the name of the file the case is from without its extension, which is usually the instruction it covers
+    pub file: String,
+
+    /// what the case checks
+    pub name: String,
+
+    /// the program to run
+    #[serde(rename = "opcodes")]
+    pub program: Program,
+
+    /// the input to pass to the program, or None to leave it undefined
+    pub input: Option<String>,
+
+    /// whether to run with [VMBuilder::normal_char] set
+    pub normal_char: bool,
+
+    /// what the program should do
+    #[serde(flatten)]
+    pub expected: ConformanceExpectation,
+}
+
+/// what a [ConformanceCase] expects a program to do
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConformanceExpectation {
+    /// exit with this output, after entities have been decoded
+    Output(String),
+
+    /// stop with an error of this kind
+    Error(ErrorKind),
+}
+
+/// every conformance case in a versioned wrapper, for other Chicken implementations to test themselves against.
+/// written as JSON by `chicken conformance --export`
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::ConformanceVectors;
+///
+/// let json = serde_json::to_value(ConformanceVectors::builtin()).unwrap();
+///
+/// assert_eq!(json["chicken_conformance"], 1);
+/// assert_eq!(json["cases"][0]["file"], "add");
+/// assert!(json["cases"][0]["opcodes"].is_array());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConformanceVectors {
+    /// the version of the format, see [CONFORMANCE_VERSION]
+    pub chicken_conformance: u32,
+
+    /// the cases
+    pub cases: Vec<ConformanceCase>,
+}
+
+impl ConformanceVectors {
+    /// wraps the given cases
+    pub fn new(cases: Vec<ConformanceCase>) -> Self {
+        Self {
+            chicken_conformance: CONFORMANCE_VERSION,
+            cases,
+        }
+    }
+
+    /// the cases built into the crate, see [ConformanceCase::builtin]
+    pub fn builtin() -> Self {
+        Self::new(ConformanceCase::builtin())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConformanceFile {
+    #[serde(default, rename = "case")]
+    cases: Vec<RawCase>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawCase {
+    name: Spanned<String>,
+    asm: String,
+    input: Option<String>,
+    #[serde(default)]
+    normal_char: bool,
+    output: Option<String>,
+    error: Option<ErrorKind>,
+}
+
+impl ConformanceCase {
+    /// parses the cases in a conformance file, which is TOML with a `[[case]]` table for each case.
+    /// each case has a `name`, a program written in assembly as `asm`, optionally an `input` and `normal_char`,
+    /// and either the `output` it should exit with or the kind of `error` it should stop with
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{ConformanceCase, ConformanceExpectation};
+    ///
+    /// let cases = ConformanceCase::parse_file("chicken", r#"
+    ///     [[case]]
+    ///     name = "pushes chicken"
+    ///     asm = "chicken"
+    ///     output = "chicken"
+    /// "#).unwrap();
+    ///
+    /// assert_eq!(cases[0].expected, ConformanceExpectation::Output("chicken".to_string()));
+    /// assert_eq!(cases[0].run(), Ok(()));
+    /// ```
+    pub fn parse_file(file: &str, text: &str) -> Result<Vec<Self>, ParseError> {
+        let line_of = |offset: usize| text[..offset.min(text.len())].lines().count().max(1);
+
+        let parsed = toml::from_str::<ConformanceFile>(text).map_err(|err| ParseError {
+            line: err.span().map_or(1, |span| line_of(span.start)),
+            message: err.message().to_string(),
+        })?;
+
+        let mut cases = Vec::new();
+
+        for raw in parsed.cases {
+            let line = line_of(raw.name.span().start);
+            let name = raw.name.into_inner();
+            let error = |message: String| ParseError {
+                line,
+                message: format!("case {:?}: {}", name, message),
+            };
+
+            let program = Program::from_asm(&raw.asm)
+                .map_err(|err| error(format!("line {} of asm: {}", err.line, err.message)))?;
+
+            let expected = match (raw.output, raw.error) {
+                (Some(output), None) => ConformanceExpectation::Output(output),
+                (None, Some(kind)) => ConformanceExpectation::Error(kind),
+                _ => Err(error("expected exactly one of output or error".to_string()))?,
+            };
+
+            cases.push(Self {
+                file: file.to_string(),
+                name,
+                program,
+                input: raw.input,
+                normal_char: raw.normal_char,
+                expected,
+            });
+        }
+
+        Ok(cases)
+    }
+
+    /// the cases from `tests/conformance` in the repository, which are built into the crate
+    pub fn builtin() -> Vec<Self> {
+        BUILTIN
+            .iter()
+            .flat_map(|(file, text)| {
+                Self::parse_file(file, text).expect("built in conformance cases should parse")
+            })
+            .collect()
+    }
+
+    /// loads the cases from every `.toml` file in a directory, sorted by file name
+    pub fn load_dir<P: AsRef<Path>>(dir: P) -> Result<Vec<Self>, LoadError> {
+        let mut files = std::fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        files.retain(|file| file.extension().is_some_and(|e| e == "toml"));
+        files.sort();
+
+        let mut cases = Vec::new();
+
+        for file in files {
+            let name = file.file_stem().unwrap_or_default().to_string_lossy();
+            let text = std::fs::read_to_string(&file)?;
+
+            cases.extend(Self::parse_file(&name, &text).map_err(|err| ParseError {
+                message: format!("{}: {}", file.display(), err.message),
+                ..err
+            })?);
+        }
+
+        Ok(cases)
+    }
+
+    /// runs the case, returning a description of what went wrong if the program didn't do what was expected
+    pub fn run(&self) -> Result<(), String> {
+        let mut builder = VMBuilder::from_program(self.program.clone()).limits(Limits {
+            max_steps: Some(MAX_STEPS),
+            ..Default::default()
+        });
+
+        if let Some(input) = &self.input {
+            builder = builder.input(input.as_str());
+        }
+
+        if self.normal_char {
+            builder = builder.normal_char();
+        }
+
+        match (builder.build().run(), &self.expected) {
+            (Ok(output), ConformanceExpectation::Output(expected)) if output == *expected => Ok(()),
+            (Err(err), ConformanceExpectation::Error(kind)) if err.kind == *kind => Ok(()),
+            (Ok(output), _) => Err(format!(
+                "expected {}, got output {:?}",
+                self.expected, output
+            )),
+            (Err(err), _) => Err(format!(
+                "expected {}, got {:?} error: {}",
+                self.expected, err.kind, err.message
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ConformanceCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.file, self.name)
+    }
+}
+
+impl fmt::Display for ConformanceExpectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConformanceExpectation::Output(output) => write!(f, "output {:?}", output),
+            ConformanceExpectation::Error(kind) => write!(f, "{:?} error", kind),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use crate::stats::MNEMONICS;
+use std::{collections::BTreeMap, str::FromStr};
+
+/// how much fuel each instruction uses up, set with [VMBuilder::cost_model](crate::VMBuilder::cost_model) and limited by [Limits::max_fuel](crate::Limits::max_fuel).
+/// every instruction costs 1 unless it's given a cost of its own, so without any costs fuel is the same as steps.
+/// adding strings together can also cost something for every byte of the string it makes, so the limit reflects the actual work
+/// done by programs that build up long strings, which take a lot longer per step than ones that only push numbers around
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::{CostModel, ErrorKind, Limits, VMBuilder};
+///
+/// // pushes "chicken" and adds another one to it, twice
+/// let program = [1, 1, 2, 1, 2];
+/// let costs = CostModel::new().cost("chicken", 2).string_byte_cost(1);
+///
+/// let mut vm = VMBuilder::from_opcodes(program).cost_model(costs.clone()).build();
+/// vm.run().unwrap();
+///
+/// // 6 instructions including the exit, an extra 1 for each chicken and 14 + 21 bytes of strings made by the adds
+/// assert_eq!(vm.stats().fuel, 6 + 3 + 14 + 21);
+///
+/// let limits = Limits {
+///     max_fuel: Some(40),
+///     ..Default::default()
+/// };
+/// let err = VMBuilder::from_opcodes(program).cost_model(costs).limits(limits).build().run().unwrap_err();
+/// assert_eq!(err.kind, ErrorKind::Limit);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CostModel {
+    costs: BTreeMap<&'static str, u64>,
+    string_byte_cost: u64,
+}
+
+impl CostModel {
+    /// a cost model where every instruction costs 1
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// sets the cost of the instruction with the given mnemonic, like `add` or `push`. this covers every push, whatever it pushes,
+    /// and extension instructions go by their own mnemonics. mnemonics that aren't the name of any instruction are ignored
+    pub fn cost(mut self, mnemonic: &str, cost: u64) -> Self {
+        if let Some(mnemonic) = MNEMONICS.iter().find(|m| **m == mnemonic) {
+            self.costs.insert(mnemonic, cost);
+        }
+        self
+    }
+
+    /// sets how much an Add that makes a string costs for each byte of the string, on top of the cost of the Add itself
+    pub fn string_byte_cost(mut self, cost: u64) -> Self {
+        self.string_byte_cost = cost;
+        self
+    }
+
+    // how much fuel an instruction used, given the length of the string it made if it was an add that made one
+    pub(crate) fn fuel(&self, mnemonic: &str, string_len: Option<usize>) -> u64 {
+        let cost = self.costs.get(mnemonic).copied().unwrap_or(1);
+        let bytes = match mnemonic {
+            "add" => string_len.unwrap_or_default() as u64,
+            _ => 0,
+        };
+
+        cost.saturating_add(bytes.saturating_mul(self.string_byte_cost))
+    }
+}
+
+/// parses a cost model written like `add=2,load=3,byte=1`, with the cost of each instruction by mnemonic
+/// and `byte` for [CostModel::string_byte_cost]. this is what `chicken run --costs` takes
+impl FromStr for CostModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut model = Self::new();
+
+        for entry in s
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let Some((name, cost)) = entry.split_once('=') else {
+                return Err(format!("expected name=cost, not {:?}", entry));
+            };
+            let (name, cost) = (name.trim(), cost.trim());
+            let cost = cost
+                .parse::<u64>()
+                .map_err(|err| format!("invalid cost {:?} for {}: {}", cost, name, err))?;
+
+            model = match name {
+                "byte" => model.string_byte_cost(cost),
+                _ if MNEMONICS.contains(&name) => model.cost(name, cost),
+                _ => return Err(format!("unknown instruction {:?}", name)),
+            };
+        }
+
+        Ok(model)
+    }
+}
@@ -0,0 +1,78 @@
+use crate::Program;
+use std::fmt;
+
+/// tracks which instructions of a program were executed during a run, enabled with [VMBuilder::coverage](crate::VMBuilder::coverage)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    /// the program being covered, as it was before running
+    pub program: Program,
+
+    /// how many times each program index was executed
+    pub hits: Vec<u64>,
+}
+
+impl Coverage {
+    /// creates an empty coverage report for the given program
+    pub fn new(program: Program) -> Self {
+        Self {
+            hits: vec![0; program.len()],
+            program,
+        }
+    }
+
+    /// records that the instruction at the given program index was executed.
+    /// indices outside of the program (i.e. runtime data being executed) are ignored
+    pub fn record(&mut self, index: usize) {
+        if let Some(hits) = self.hits.get_mut(index) {
+            *hits += 1;
+        }
+    }
+
+    /// the number of instructions in the program, decoded linearly from the start
+    pub fn total(&self) -> usize {
+        self.program.instructions().len()
+    }
+
+    /// the number of instructions in the program that were executed at least once
+    pub fn covered(&self) -> usize {
+        self.program
+            .instructions()
+            .iter()
+            .filter(|(index, _)| self.hits[*index] > 0)
+            .count()
+    }
+
+    /// the percentage of instructions that were executed at least once
+    pub fn percentage(&self) -> f64 {
+        match self.total() {
+            0 => 100.0,
+            total => self.covered() as f64 * 100.0 / total as f64,
+        }
+    }
+}
+
+impl fmt::Display for Coverage {
+    /// writes an annotated disassembly of the program, marking instructions that were never executed with `!`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, instruction) in self.program.instructions() {
+            let hits = self.hits[index];
+
+            writeln!(
+                f,
+                "{:>10} {} {:>5}: {}",
+                hits,
+                if hits == 0 { "!" } else { " " },
+                index,
+                instruction
+            )?;
+        }
+
+        writeln!(
+            f,
+            "coverage: {}/{} instructions ({:.1}%)",
+            self.covered(),
+            self.total(),
+            self.percentage()
+        )
+    }
+}
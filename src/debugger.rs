@@ -0,0 +1,837 @@
+use crate::{
+    color::paint, display_cell, is_label, write_window, ChickenError, ErrorKind, Expr, Instruction,
+    Io, SelfModification, SharedIo, StackDiff, StackRegion, StdIo, Stream, TestIo, VMState, Value,
+    ValueKind,
+};
+use colored::{ColoredString, Colorize};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Write as _},
+    ops::Range,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+const HELP: &str = "commands:
+    s [n]              step n instructions, or 1 if n isn't given. an empty line steps once too
+    c                  continue until a breakpoint or the end of the program
+    leave <a>..<b>     run until the program counter leaves the addresses from a up to b, like a loop
+    b <pc>             toggle a breakpoint at an address
+    b <label>:         toggle a breakpoint at a label, like b loop:, for programs assembled with a source map
+    b <instruction>    toggle stopping after every instruction of a kind, like store, or b jump taken for jumps that were taken
+    p <addr>           print the value at an address
+    w <expr>           watch an expression like stack[3] + 1 or len(stack), stopping whenever its value changes
+    unwatch <n>        stop watching the nth expression
+    set <addr> <value> overwrite the value at an address with a JSON value, like 5, \"chicken\", null or {\"ptr\": 3}
+    push <value>       push a JSON value onto the stack
+    truncate <len>     remove values from the top of the stack until it's this long, keeping the program
+    pc <addr>          move the program counter, so the instruction at this address runs next
+    bt                 print the program counters of the last few instructions
+    h                  print this list
+    q                  stop the program
+";
+
+/// a command for the debugger, parsed from what's typed at its prompt
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::{DebugCommand, Value};
+///
+/// assert_eq!("s 5".parse(), Ok(DebugCommand::Step(5)));
+/// assert_eq!("".parse(), Ok(DebugCommand::Step(1)));
+/// assert_eq!("set 1 \"meow\"".parse(), Ok(DebugCommand::Set(1, Value::from("meow"))));
+/// assert!("b".parse::<DebugCommand>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebugCommand {
+    /// executes this many instructions, then stops again
+    Step(usize),
+
+    /// runs until a breakpoint is reached or the program exits
+    Continue,
+
+    /// runs until the program counter is outside of this range, or a breakpoint is reached
+    Leave(Range<usize>),
+
+    /// adds a breakpoint at this address, or removes it if there already is one
+    Break(usize),
+
+    /// adds a breakpoint at the instruction after this label in the program's [SourceMap](crate::SourceMap), or removes it if there already is one
+    BreakAtLabel(String),
+
+    /// stops after every instruction of this kind, or stops doing so if it already was
+    BreakOn(InstructionBreakpoint),
+
+    /// prints the value at this address
+    Print(usize),
+
+    /// shows the value of this expression whenever the debugger stops, and stops whenever it changes
+    Watch(Expr),
+
+    /// removes the watch with this number, counting from 1
+    Unwatch(usize),
+
+    /// overwrites the value at this address
+    Set(usize, Value),
+
+    /// pushes a value onto the stack
+    Push(Value),
+
+    /// removes values from the top of the stack until it's this long. the program and the exit after it are never removed
+    Truncate(usize),
+
+    /// moves the program counter to this address, so the instruction there runs next
+    SetProgramCounter(usize),
+
+    /// prints the program counters of the last few instructions
+    Backtrace,
+
+    /// prints the list of commands
+    Help,
+
+    /// stops the program
+    Quit,
+}
+
+impl FromStr for DebugCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (command, rest) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let rest = rest.trim();
+
+        let address = |rest: &str| match rest.parse::<usize>() {
+            Ok(address) => Ok(address),
+            Err(_) if rest.is_empty() => Err(format!("{} needs an address", command)),
+            Err(_) => Err(format!("invalid address {:?}", rest)),
+        };
+        let no_arguments = |command| match rest.is_empty() {
+            true => Ok(command),
+            false => Err(format!("unexpected {:?} after {}", rest, s)),
+        };
+
+        match command {
+            "" => Ok(DebugCommand::Step(1)),
+            "s" | "step" => match rest {
+                "" => Ok(DebugCommand::Step(1)),
+                _ => rest
+                    .parse()
+                    .map(DebugCommand::Step)
+                    .map_err(|_| format!("invalid number of steps {:?}", rest)),
+            },
+            "c" | "continue" => no_arguments(DebugCommand::Continue),
+            "leave" => {
+                let (start, end) = rest
+                    .split_once("..")
+                    .ok_or_else(|| format!("leave needs a range like 10..20, not {:?}", rest))?;
+                Ok(DebugCommand::Leave(
+                    address(start.trim())?..address(end.trim())?,
+                ))
+            }
+            "b" | "break" => match rest.parse() {
+                Ok(breakpoint) => Ok(DebugCommand::BreakOn(breakpoint)),
+                // labels are written like they are in the assembly, so a misspelled instruction isn't taken for one
+                Err(_) => match rest.strip_suffix(':').filter(|label| is_label(label)) {
+                    Some(label) => Ok(DebugCommand::BreakAtLabel(label.to_string())),
+                    None => address(rest).map(DebugCommand::Break),
+                },
+            },
+            "p" | "print" => address(rest).map(DebugCommand::Print),
+            "w" | "watch" => match rest {
+                "" => Err(format!("{} needs an expression", command)),
+                _ => rest.parse().map(DebugCommand::Watch),
+            },
+            "unwatch" => rest
+                .parse()
+                .map(DebugCommand::Unwatch)
+                .map_err(|_| format!("invalid watch number {:?}", rest)),
+            "set" => {
+                let (addr, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                Ok(DebugCommand::Set(address(addr)?, json_value(value)?))
+            }
+            "push" => json_value(rest).map(DebugCommand::Push),
+            "truncate" => match rest {
+                "" => Err(format!("{} needs a length", command)),
+                _ => rest
+                    .parse()
+                    .map(DebugCommand::Truncate)
+                    .map_err(|_| format!("invalid length {:?}", rest)),
+            },
+            "pc" => address(rest).map(DebugCommand::SetProgramCounter),
+            "bt" | "backtrace" => no_arguments(DebugCommand::Backtrace),
+            "h" | "help" | "?" => no_arguments(DebugCommand::Help),
+            "q" | "quit" => no_arguments(DebugCommand::Quit),
+            _ => Err(format!(
+                "unknown command {:?}, type h for a list of commands",
+                command
+            )),
+        }
+    }
+}
+
+fn json_value(value: &str) -> Result<Value, String> {
+    serde_json::from_str(value.trim())
+        .map_err(|err| format!("invalid value {:?}: {}", value.trim(), err))
+}
+
+/// a kind of instruction the debugger can stop after, wherever it is in the program.
+/// in self modifying programs the address of the interesting instruction keeps changing, but what it does doesn't
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::InstructionBreakpoint;
+///
+/// assert_eq!("store".parse(), Ok(InstructionBreakpoint::Mnemonic("store")));
+/// assert_eq!("jump taken".parse(), Ok(InstructionBreakpoint::TakenJump));
+/// assert!("peck".parse::<InstructionBreakpoint>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum InstructionBreakpoint {
+    /// every instruction with this mnemonic, as used in disassembly listings
+    Mnemonic(&'static str),
+
+    /// jumps that were taken, rather than falling through to the next instruction
+    TakenJump,
+}
+
+impl InstructionBreakpoint {
+    fn matches(self, mnemonic: &str, jumped: bool) -> bool {
+        match self {
+            InstructionBreakpoint::Mnemonic(m) => m == mnemonic,
+            InstructionBreakpoint::TakenJump => jumped,
+        }
+    }
+}
+
+impl FromStr for InstructionBreakpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = s.split_whitespace().collect::<Vec<_>>();
+
+        match words[..] {
+            ["jump", "taken"] => Ok(InstructionBreakpoint::TakenJump),
+            [mnemonic] => (0..=10)
+                .map(Instruction::mnemonic_of)
+                .find(|m| *m == mnemonic)
+                .map(InstructionBreakpoint::Mnemonic)
+                .ok_or_else(|| format!("unknown instruction {:?}", mnemonic)),
+            _ => Err(format!("unknown instruction {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for InstructionBreakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstructionBreakpoint::Mnemonic(mnemonic) => write!(f, "{}", mnemonic),
+            InstructionBreakpoint::TakenJump => write!(f, "jump taken"),
+        }
+    }
+}
+
+/// what happened after a [DebugCommand] was given to [VMState::debug_command]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugResponse {
+    /// the debugger is still stopped, and this should be shown to the user
+    Output(String),
+
+    /// the program should keep running
+    Resume,
+
+    /// the program should be stopped
+    Quit,
+}
+
+// a command for the debugger in machine interface mode, like {"command": "step", "count": 5}
+#[derive(Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case", deny_unknown_fields)]
+enum MiCommand {
+    Step {
+        #[serde(default = "one")]
+        count: usize,
+    },
+    Continue,
+    Leave {
+        start: usize,
+        end: usize,
+    },
+    Break {
+        address: usize,
+    },
+    BreakAtLabel {
+        label: String,
+    },
+    BreakOn {
+        instruction: String,
+    },
+    Print {
+        address: usize,
+    },
+    Watch {
+        expr: String,
+    },
+    Unwatch {
+        number: usize,
+    },
+    Set {
+        address: usize,
+        value: Value,
+    },
+    Push {
+        value: Value,
+    },
+    Truncate {
+        len: usize,
+    },
+    SetPc {
+        address: usize,
+    },
+    Backtrace,
+    Help,
+    Quit,
+}
+
+fn one() -> usize {
+    1
+}
+
+impl TryFrom<MiCommand> for DebugCommand {
+    type Error = String;
+
+    fn try_from(command: MiCommand) -> Result<Self, Self::Error> {
+        Ok(match command {
+            MiCommand::Step { count } => DebugCommand::Step(count),
+            MiCommand::Continue => DebugCommand::Continue,
+            MiCommand::Leave { start, end } => DebugCommand::Leave(start..end),
+            MiCommand::Break { address } => DebugCommand::Break(address),
+            MiCommand::BreakAtLabel { label } => DebugCommand::BreakAtLabel(label),
+            MiCommand::BreakOn { instruction } => DebugCommand::BreakOn(instruction.parse()?),
+            MiCommand::Print { address } => DebugCommand::Print(address),
+            MiCommand::Watch { expr } => DebugCommand::Watch(expr.parse()?),
+            MiCommand::Unwatch { number } => DebugCommand::Unwatch(number),
+            MiCommand::Set { address, value } => DebugCommand::Set(address, value),
+            MiCommand::Push { value } => DebugCommand::Push(value),
+            MiCommand::Truncate { len } => DebugCommand::Truncate(len),
+            MiCommand::SetPc { address } => DebugCommand::SetProgramCounter(address),
+            MiCommand::Backtrace => DebugCommand::Backtrace,
+            MiCommand::Help => DebugCommand::Help,
+            MiCommand::Quit => DebugCommand::Quit,
+        })
+    }
+}
+
+// a line printed by the debugger in machine interface mode
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum MiEvent<'a> {
+    Stopped {
+        reason: &'static str,
+        pc: usize,
+        stack: &'a [Value],
+        #[serde(skip_serializing_if = "Option::is_none")]
+        self_modification: Option<&'a SelfModification>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        watches: Vec<MiWatch<'a>>,
+    },
+    Output {
+        text: String,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Serialize)]
+struct MiWatch<'a> {
+    expr: String,
+    value: &'a Value,
+    changed: bool,
+}
+
+// an expression the debugger shows the value of whenever it stops
+#[derive(Clone)]
+struct Watch {
+    expr: Expr,
+
+    // the value after the last instruction
+    value: Value,
+
+    // whether the last instruction changed the value
+    changed: bool,
+}
+
+// whether two values are the same, unlike == which also counts 1 and "1" as equal, and NaN as different from itself
+fn same_value(a: &Value, b: &Value) -> bool {
+    a.kind() == b.kind() && (a.kind() == ValueKind::NaN || a == b)
+}
+
+// where the debugger is up to, kept in the VM so it survives between steps
+#[derive(Clone, Default)]
+pub(crate) struct Debugger {
+    // where the debugger reads commands from and writes to, with None meaning the terminal
+    io: Option<SharedIo>,
+
+    // whether to speak JSON instead of text, see [VMBuilder::debug_mi](crate::VMBuilder::debug_mi)
+    mi: bool,
+
+    // whether to wait for commands after each instruction, see [VMBuilder::set_debug_pause](crate::VMBuilder::set_debug_pause)
+    pause: bool,
+
+    breakpoints: BTreeSet<usize>,
+    instruction_breakpoints: BTreeSet<InstructionBreakpoint>,
+    watches: Vec<Watch>,
+
+    // how many more instructions to execute before stopping
+    steps_left: usize,
+    continuing: bool,
+    leaving: Option<Range<usize>>,
+
+    // the stack when the debugger last stopped, so it can show what changed since then
+    stack: Vec<Value>,
+}
+
+impl Debugger {
+    pub(crate) fn new(io: Option<SharedIo>, mi: bool, pause: bool) -> Self {
+        Self {
+            io,
+            mi,
+            pause,
+            ..Default::default()
+        }
+    }
+}
+
+impl VMState {
+    /// carries out a debugger command. this is what the debugger's prompt does with each command typed into it,
+    /// so other frontends can share the same commands.
+    /// step and continue only set up when the debugger stops next, the program itself is run as usual
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{DebugCommand, DebugResponse, VMBuilder};
+    ///
+    /// let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    ///
+    /// assert_eq!(vm.debug_command(DebugCommand::Print(1)), DebugResponse::Output("\"abc\"".to_string()));
+    /// assert_eq!(vm.debug_command(DebugCommand::Step(1)), DebugResponse::Resume);
+    /// ```
+    pub fn debug_command(&mut self, command: DebugCommand) -> DebugResponse {
+        match command {
+            DebugCommand::Step(steps) => {
+                self.debugger.steps_left = steps;
+                DebugResponse::Resume
+            }
+            DebugCommand::Continue => {
+                self.debugger.continuing = true;
+                DebugResponse::Resume
+            }
+            DebugCommand::Leave(range) => {
+                self.debugger.leaving = Some(range);
+                DebugResponse::Resume
+            }
+            DebugCommand::Break(address) => {
+                let output = match self.debugger.breakpoints.remove(&address) {
+                    true => format!("removed breakpoint at {}", address),
+                    false => {
+                        self.debugger.breakpoints.insert(address);
+                        format!("added breakpoint at {}", address)
+                    }
+                };
+                DebugResponse::Output(output)
+            }
+            DebugCommand::BreakAtLabel(label) => {
+                match (self.source_map()).and_then(|map| map.label_address(&label)) {
+                    Some(address) => match self.debug_command(DebugCommand::Break(address)) {
+                        DebugResponse::Output(output) => {
+                            DebugResponse::Output(format!("{} ({})", output, label))
+                        }
+                        response => response,
+                    },
+                    None => DebugResponse::Output(format!("no label named {:?}", label)),
+                }
+            }
+            DebugCommand::BreakOn(breakpoint) => {
+                let output = match self.debugger.instruction_breakpoints.remove(&breakpoint) {
+                    true => format!("no longer stopping after every {}", breakpoint),
+                    false => {
+                        self.debugger.instruction_breakpoints.insert(breakpoint);
+                        format!("stopping after every {}", breakpoint)
+                    }
+                };
+                DebugResponse::Output(output)
+            }
+            DebugCommand::Print(address) => DebugResponse::Output(
+                match (
+                    self.stack.get(address),
+                    (self.source_map()).and_then(|map| map.symbol(address)),
+                ) {
+                    (Some(value), Some(symbol)) => format!("{} ({})", display_cell(value), symbol),
+                    (Some(value), None) => display_cell(value),
+                    (None, _) => format!("nothing at address {}", address),
+                },
+            ),
+            DebugCommand::Watch(expr) => {
+                let value = expr.eval(self);
+                let output = format!(
+                    "watch {}: {} = {}",
+                    self.debugger.watches.len() + 1,
+                    expr,
+                    display_cell(&value)
+                );
+
+                self.debugger.watches.push(Watch {
+                    expr,
+                    value,
+                    changed: false,
+                });
+                DebugResponse::Output(output)
+            }
+            DebugCommand::Unwatch(number) => DebugResponse::Output(
+                match number
+                    .checked_sub(1)
+                    .filter(|&i| i < self.debugger.watches.len())
+                {
+                    Some(i) => {
+                        let watch = self.debugger.watches.remove(i);
+                        format!("removed watch {}: {}", number, watch.expr)
+                    }
+                    None => format!("no watch {}", number),
+                },
+            ),
+            DebugCommand::Set(address, value) => {
+                DebugResponse::Output(match self.stack.get_mut(address) {
+                    Some(cell) => {
+                        let output = format!("{} = {}", address, display_cell(&value));
+                        *cell = value;
+                        output
+                    }
+                    None => format!("nothing at address {}", address),
+                })
+            }
+            DebugCommand::Push(value) => {
+                let output = format!("{} = {}", self.stack.len(), display_cell(&value));
+                self.stack.push(value);
+                DebugResponse::Output(output)
+            }
+            DebugCommand::Truncate(len) => DebugResponse::Output(if len <= self.program_end {
+                format!(
+                    "the stack can't be shorter than {}, since the program ends at {}",
+                    self.program_end + 1,
+                    self.program_end
+                )
+            } else if len >= self.stack.len() {
+                format!("the stack is already {} values long", self.stack.len())
+            } else {
+                self.stack.truncate(len);
+                format!("the stack is now {} values long", len)
+            }),
+            DebugCommand::SetProgramCounter(address) => {
+                DebugResponse::Output(match address < self.stack.len() {
+                    true => {
+                        self.program_counter = address;
+                        format!("pc = {}", address)
+                    }
+                    false => format!("nothing at address {}", address),
+                })
+            }
+            DebugCommand::Backtrace => DebugResponse::Output(
+                self.recent_program_counters()
+                    .iter()
+                    .rev()
+                    .map(|pc| format!("    {}", pc))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            DebugCommand::Help => DebugResponse::Output(HELP.trim_end().to_string()),
+            DebugCommand::Quit => DebugResponse::Quit,
+        }
+    }
+
+    /// runs the program with the debugger reading its commands from `script` instead of the terminal,
+    /// returning the result along with a transcript of everything the debugger printed, including the commands themselves.
+    /// if the script runs out before the program exits, the program runs to the end without stopping again
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    ///
+    /// let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    /// let (result, transcript) = vm.run_debug_script("s 2\np 6\nq\n");
+    ///
+    /// assert!(result.is_err());
+    /// assert!(transcript.contains("(chicken) p 6\n\"abc\"\n"));
+    /// ```
+    pub fn run_debug_script(&mut self, script: &str) -> (Result<String, ChickenError>, String) {
+        let io = TestIo::new().commands(script);
+
+        let old_io = self.debugger.io.replace(Arc::new(Mutex::new(io.clone())));
+        let old_debug = std::mem::replace(&mut self.debug, true);
+        let old_pause = std::mem::replace(&mut self.debugger.pause, true);
+
+        let result = self.run();
+
+        self.debugger.io = old_io;
+        self.debug = old_debug;
+        self.debugger.pause = old_pause;
+
+        (result, io.debug_output())
+    }
+
+    // shows the whole stack before the first instruction, then waits for a command
+    pub(crate) fn debug_start(&mut self) -> Result<(), ChickenError> {
+        if self.debugger.mi {
+            self.debug_stopped("start")?;
+            return self.debug_prompt();
+        }
+
+        match self.debugger.pause {
+            true => self.debug_write(&format!(
+                "{}press enter to step, or type h for a list of commands\n",
+                self
+            ))?,
+            false => self.debug_write(&self.to_string())?,
+        }
+        self.debug_prompt()
+    }
+
+    // called after every instruction while debugging, stopping at the prompt if it's time to
+    pub(crate) fn debug_step(
+        &mut self,
+        program_counter: usize,
+        mnemonic: &str,
+        jumped: bool,
+    ) -> Result<(), ChickenError> {
+        self.debugger.steps_left = self.debugger.steps_left.saturating_sub(1);
+
+        let watch_changed = self.update_watches();
+        if self.exited {
+            return Ok(());
+        }
+
+        let at_breakpoint = self.debugger.breakpoints.contains(&self.program_counter);
+        let after_instruction = (self.debugger.instruction_breakpoints.iter())
+            .find(|breakpoint| breakpoint.matches(mnemonic, jumped));
+        let in_range = (self.debugger.leaving.as_ref())
+            .is_some_and(|range| range.contains(&self.program_counter));
+
+        let reason = match () {
+            _ if at_breakpoint => "breakpoint",
+            _ if after_instruction.is_some() => "instruction",
+            _ if watch_changed => "watch",
+            _ if self.debugger.continuing || self.debugger.steps_left > 0 || in_range => {
+                return Ok(());
+            }
+            _ => "step",
+        };
+
+        if self.debugger.mi {
+            self.debug_stopped(reason)?;
+            return self.debug_prompt();
+        }
+
+        let mut text = String::new();
+
+        if at_breakpoint {
+            let breakpoint = format!("breakpoint at {}", self.program_counter);
+            let _ = writeln!(text, "{}", self.paint(breakpoint.yellow()));
+        }
+
+        if let Some(breakpoint) = after_instruction {
+            let stopped = format!("stopped after {} at {}", breakpoint, program_counter);
+            let _ = writeln!(text, "{}", self.paint(stopped.yellow()));
+        }
+
+        for (i, watch) in self.debugger.watches.iter().enumerate() {
+            let line = format!(
+                "watch {}: {} = {}",
+                i + 1,
+                watch.expr,
+                display_cell(&watch.value)
+            );
+            let _ = match watch.changed {
+                true => writeln!(text, "{}", self.paint(line.yellow())),
+                false => writeln!(text, "{}", line),
+            };
+        }
+
+        if let Some(modification) = &self.self_modification {
+            let _ = writeln!(
+                text,
+                "{}",
+                StackRegion::SelfModified.paint(
+                    &modification.to_string(),
+                    self.color.enabled(Stream::Stdout)
+                )
+            );
+        }
+
+        let diff = StackDiff::between(&self.debugger.stack, &self.stack);
+        match diff.is_empty() {
+            true => text.push_str("stack unchanged\n"),
+            false => {
+                let _ = write!(text, "{}", diff);
+            }
+        }
+
+        if let Some(source) = (self.source_map()).and_then(|map| map.describe(self.program_counter))
+        {
+            let _ = writeln!(text, "at {}", source);
+        }
+
+        let _ = write_window(
+            &mut text,
+            &self.stack,
+            self.program_counter,
+            self.source_map(),
+        );
+        self.debug_write(&text)?;
+
+        self.debug_prompt()
+    }
+
+    // reads commands until one of them resumes the program
+    fn debug_prompt(&mut self) -> Result<(), ChickenError> {
+        self.debugger.steps_left = 0;
+        self.debugger.continuing = false;
+        self.debugger.leaving = None;
+        self.debugger.stack.clone_from(&self.stack);
+
+        // without pausing, the debugger only shows what each instruction did
+        if !self.debugger.pause {
+            return Ok(());
+        }
+
+        loop {
+            if !self.debugger.mi {
+                self.debug_write(&format!("{} ", self.paint("(chicken)".bold())))?;
+            }
+
+            let Some(line) = self.debug_read_line() else {
+                // nothing more can be typed, so just let the program finish
+                if !self.debugger.mi {
+                    self.debug_write("\n")?;
+                }
+                self.debug = false;
+                return Ok(());
+            };
+
+            let command = match self.debugger.mi {
+                true if line.trim().is_empty() => continue,
+                true => serde_json::from_str::<MiCommand>(&line)
+                    .map_err(|err| format!("invalid command: {}", err))
+                    .and_then(DebugCommand::try_from),
+                false => line.parse::<DebugCommand>(),
+            };
+
+            let command = match command {
+                Ok(command) => command,
+                Err(err) if self.debugger.mi => {
+                    self.debug_event(&MiEvent::Error { message: err })?;
+                    continue;
+                }
+                Err(err) => {
+                    self.debug_write(&format!("{}\n", self.paint(err.red())))?;
+                    continue;
+                }
+            };
+
+            match self.debug_command(command) {
+                DebugResponse::Output(text) if self.debugger.mi => {
+                    self.debug_event(&MiEvent::Output { text })?
+                }
+                DebugResponse::Output(output) => self.debug_write(&format!("{}\n", output))?,
+                DebugResponse::Resume => return Ok(()),
+                DebugResponse::Quit => Err(self.error(
+                    ErrorKind::Interrupted,
+                    format_args!("quit from the debugger"),
+                ))?,
+            }
+        }
+    }
+
+    fn debug_stopped(&self, reason: &'static str) -> Result<(), ChickenError> {
+        let watches = (self.debugger.watches.iter())
+            .map(|watch| MiWatch {
+                expr: watch.expr.to_string(),
+                value: &watch.value,
+                changed: watch.changed,
+            })
+            .collect();
+
+        self.debug_event(&MiEvent::Stopped {
+            reason,
+            pc: self.program_counter,
+            stack: &self.stack,
+            self_modification: self.self_modification.as_ref(),
+            watches,
+        })
+    }
+
+    // works out the value of every watch after an instruction, returning whether any of them changed
+    fn update_watches(&mut self) -> bool {
+        let mut watches = std::mem::take(&mut self.debugger.watches);
+        let mut changed = false;
+
+        for watch in watches.iter_mut() {
+            let value = watch.expr.eval(self);
+            watch.changed = !same_value(&value, &watch.value);
+            watch.value = value;
+            changed |= watch.changed;
+        }
+
+        self.debugger.watches = watches;
+        changed
+    }
+
+    fn debug_event(&self, event: &MiEvent) -> Result<(), ChickenError> {
+        let json = serde_json::to_string(event).expect("debugger events should serialize");
+        self.debug_write(&format!("{}\n", json))
+    }
+
+    // colors debugger output according to [VMState::color], as if it's going to stdout
+    fn paint(&self, text: ColoredString) -> String {
+        paint(text, self.color.enabled(Stream::Stdout))
+    }
+
+    fn debug_write(&self, text: &str) -> Result<(), ChickenError> {
+        let result = match self.debugger.io.as_ref() {
+            Some(io) => io.lock().unwrap_or_else(|err| err.into_inner()).write(text),
+            None => StdIo.write(text),
+        };
+
+        result.map_err(|err| {
+            self.error(
+                ErrorKind::Io,
+                format_args!("error writing debugger output: {}", err),
+            )
+        })
+    }
+
+    // reads a line of input, or None if there's nothing more to read.
+    // commands that aren't typed at a terminal are echoed, so transcripts show what was typed,
+    // unless the output is JSON
+    fn debug_read_line(&self) -> Option<String> {
+        let (line, interactive) = match self.debugger.io.as_ref() {
+            Some(io) => {
+                let mut io = io.lock().unwrap_or_else(|err| err.into_inner());
+                (io.read_line(), io.is_interactive())
+            }
+            None => (StdIo.read_line(), true),
+        };
+        let line = line.ok()??;
+
+        if !interactive && !self.debugger.mi {
+            let echo = format!("{}\n", line.trim_end_matches(['\r', '\n']));
+            let _ = self.debug_write(&echo);
+        }
+
+        Some(line)
+    }
+}
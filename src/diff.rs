@@ -0,0 +1,107 @@
+use crate::{display_cell, Value};
+use std::{fmt, ops::Range};
+
+/// a cell that's on both stacks compared by a [StackDiff], but with a different value
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangedCell {
+    /// the address of the cell
+    pub address: usize,
+
+    /// the value in the older stack
+    pub old: Value,
+
+    /// the value in the newer stack
+    pub new: Value,
+}
+
+/// the differences between two stacks, usually from before and after an instruction was executed
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StackDiff {
+    /// cells that are on both stacks but have different values, in order of address
+    pub changed: Vec<ChangedCell>,
+
+    /// the addresses of the cells that are only on the newer stack
+    pub pushed: Range<usize>,
+
+    /// the addresses of the cells that are only on the older stack
+    pub popped: Range<usize>,
+
+    new: Vec<Value>,
+    old: Vec<Value>,
+}
+
+impl StackDiff {
+    /// compares an older stack to a newer one
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{StackDiff, Value};
+    ///
+    /// let diff = StackDiff::between(&[Value::Num(1), Value::Num(2)], &[Value::Num(3)]);
+    /// assert_eq!(diff.changed.len(), 1);
+    /// assert_eq!(diff.changed[0].new, Value::Num(3));
+    /// assert_eq!((diff.pushed, diff.popped), (1..1, 1..2));
+    /// ```
+    pub fn between(old: &[Value], new: &[Value]) -> Self {
+        let changed = old
+            .iter()
+            .zip(new.iter())
+            .enumerate()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(address, (old, new))| ChangedCell {
+                address,
+                old: old.clone(),
+                new: new.clone(),
+            })
+            .collect();
+
+        Self {
+            changed,
+            pushed: old.len().min(new.len())..new.len(),
+            popped: new.len().min(old.len())..old.len(),
+            new: new.get(old.len()..).unwrap_or_default().to_vec(),
+            old: old.get(new.len()..).unwrap_or_default().to_vec(),
+        }
+    }
+
+    /// whether the stacks were exactly the same
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.pushed.is_empty() && self.popped.is_empty()
+    }
+
+    /// the values of the cells that are only on the newer stack
+    pub fn pushed_values(&self) -> &[Value] {
+        &self.new
+    }
+
+    /// the values of the cells that are only on the older stack
+    pub fn popped_values(&self) -> &[Value] {
+        &self.old
+    }
+}
+
+/// lists the changes one per line, with `~` for changed cells, `+` for pushed ones and `-` for popped ones
+impl fmt::Display for StackDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for cell in self.changed.iter() {
+            writeln!(
+                f,
+                "~ {:>7}: {} -> {}",
+                cell.address,
+                display_cell(&cell.old),
+                display_cell(&cell.new)
+            )?;
+        }
+
+        for (address, value) in self.popped.clone().zip(self.old.iter()).rev() {
+            writeln!(f, "- {:>7}: {}", address, display_cell(value))?;
+        }
+
+        for (address, value) in self.pushed.clone().zip(self.new.iter()) {
+            writeln!(f, "+ {:>7}: {}", address, display_cell(value))?;
+        }
+
+        Ok(())
+    }
+}
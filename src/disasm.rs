@@ -0,0 +1,76 @@
+use crate::{Instruction, Program, SourceMap};
+use serde::Serialize;
+use std::fmt;
+
+/// one instruction of a disassembled program, from [Program::disassemble].
+/// these serialize to flat JSON objects, so tools can compare two versions of a program instruction by instruction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DisassembledInstruction {
+    /// the program index of the instruction
+    pub address: usize,
+
+    /// the opcode of the instruction, without its operand
+    pub opcode: isize,
+
+    /// the mnemonic of the instruction, as used in disassembly listings
+    pub mnemonic: &'static str,
+
+    /// the operand of a Load, or the number a push pushes. a Load at the very end of the program doesn't have one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operand: Option<isize>,
+
+    /// the label right before the instruction, if the program has a source map with one there
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// writes the instruction like a line of a disassembly listing, with its address in front
+impl fmt::Display for DisassembledInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>6}  {}", self.address, self.mnemonic)?;
+
+        match self.operand {
+            Some(operand) => write!(f, " {}", operand),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Program {
+    /// linearly decodes this program like [Program::instructions], with the address, opcode, mnemonic and operand of each instruction
+    /// split out, and its label if there's a source map with one there
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::Program;
+    ///
+    /// let (program, source_map) = Program::from_asm_with_source_map("push 1\nread: load 0\nexit", None).unwrap();
+    /// let listing = program.disassemble(Some(&source_map));
+    ///
+    /// assert_eq!(listing[1].to_string(), "     1  load 0");
+    /// assert_eq!(listing[1].label.as_deref(), Some("read"));
+    /// assert_eq!(
+    ///     serde_json::to_string(&listing[0]).unwrap(),
+    ///     r#"{"address":0,"opcode":11,"mnemonic":"push","operand":1}"#
+    /// );
+    /// ```
+    pub fn disassemble(&self, source_map: Option<&SourceMap>) -> Vec<DisassembledInstruction> {
+        (self.instructions().into_iter())
+            .map(|(address, instruction)| DisassembledInstruction {
+                address,
+                opcode: instruction.opcode(),
+                mnemonic: instruction.mnemonic(),
+                operand: match instruction {
+                    Instruction::Load(operand) if address + 1 < self.len() => Some(operand),
+                    Instruction::Push(n) => Some(n),
+                    _ => None,
+                },
+                label: (source_map.into_iter())
+                    .flat_map(|source_map| source_map.labels.iter())
+                    .find(|(_, index)| **index == address)
+                    .map(|(label, _)| label.clone()),
+            })
+            .collect()
+    }
+}
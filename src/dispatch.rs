@@ -0,0 +1,485 @@
+use crate::{
+    ChickenError, ErrorKind, Extension, Instruction, SelfModification, VMState, Value, WarningKind,
+    ADD, CHAR, CHICKEN, COMPARE, EXIT, JUMP, LAZY_INPUT_ADDRESS, LOAD, MULTIPLY, STORE, SUBTRACT,
+};
+use Value::*;
+
+/// executes one instruction, with the program counter already pointing past it
+type Handler = fn(&mut VMState) -> Result<(), ChickenError>;
+
+/// the handler for every instruction other than a literal, indexed by its opcode
+const HANDLERS: [Handler; 10] = [
+    VMState::exit,
+    VMState::chicken,
+    VMState::add,
+    VMState::subtract,
+    VMState::multiply,
+    VMState::compare,
+    VMState::load,
+    VMState::store,
+    VMState::jump,
+    VMState::char,
+];
+
+/// an opcode decoded from the stack, so it only has to be looked at once per step.
+/// programs can change themselves and the stack is public, so this is decoded from the stack every step instead of ahead of time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Op {
+    /// pushes a number, which is by far the most common thing a program does
+    Push(isize),
+
+    /// any other instruction, as an index into [HANDLERS]
+    Instruction(usize),
+
+    /// a call to the host function bound to this opcode with [VMBuilder::host_fn](crate::VMBuilder::host_fn).
+    /// these are only decoded by [VMState::decode], since they depend on the VM
+    Host(isize),
+
+    /// an extension instruction, only decoded by [VMState::decode] when extensions are on
+    Extension(Extension),
+
+    /// something that isn't a number, or nothing at all past the end of the stack
+    Invalid,
+}
+
+impl Op {
+    pub(crate) fn decode(value: Option<&Value>) -> Self {
+        match value {
+            Some(Num(n @ EXIT..=CHAR)) => Op::Instruction(*n as usize),
+            Some(Num(n)) => Op::Push(n.wrapping_sub(10)),
+            _ => Op::Invalid,
+        }
+    }
+
+    /// the opcode this was decoded from, if it was a number
+    pub(crate) fn opcode(self) -> Option<isize> {
+        match self {
+            Op::Push(n) => Some(n.wrapping_add(10)),
+            Op::Instruction(opcode) => Some(opcode as isize),
+            Op::Host(opcode) => Some(opcode),
+            Op::Extension(extension) => Some(extension.opcode()),
+            Op::Invalid => None,
+        }
+    }
+
+    pub(crate) fn mnemonic(self) -> &'static str {
+        match self {
+            Op::Host(_) => "host",
+            Op::Extension(extension) => extension.mnemonic(),
+            _ => match self.opcode() {
+                Some(opcode) => Instruction::mnemonic_of(opcode),
+                None => "invalid",
+            },
+        }
+    }
+}
+
+impl VMState {
+    /// executes a single instruction, with the program counter already pointing past it
+    pub(crate) fn execute(&mut self, op: Op) -> Result<(), ChickenError> {
+        if self.strict {
+            if let Some(message) = self.underflow(op) {
+                Err(self.error(ErrorKind::StackUnderflow, format_args!("{}", message)))?
+            }
+        } else if self.tracking_warnings() {
+            if let Some(message) = self.underflow(op) {
+                self.warn(
+                    WarningKind::Underflow,
+                    self.program_counter - 1,
+                    format_args!("{}", message),
+                );
+            }
+        }
+
+        match op {
+            // pushes n - 10 to the stack
+            Op::Push(n) => {
+                self.push(Num(n));
+                Ok(())
+            }
+            Op::Instruction(opcode) => HANDLERS[opcode](self),
+            Op::Host(opcode) => self.call_host_fn(opcode),
+            Op::Extension(extension) => self.execute_extension(extension),
+
+            // running past the exit at the end of the program, or before its start, usually means a jump went to the wrong place
+            Op::Invalid
+                if !(self.program_start..=self.program_end)
+                    .contains(&(self.program_counter - 1)) =>
+            {
+                Err(self.left_program_error(self.program_counter - 1))
+            }
+            Op::Invalid => Err(self.error(
+                ErrorKind::Runtime,
+                format_args!(
+                    "invalid opcode {:?}",
+                    self.stack.get(self.program_counter - 1)
+                ),
+            )),
+        }
+    }
+
+    /// describes an opcode for the debug log
+    pub(crate) fn describe_op(&self, op: Op) -> std::string::String {
+        match op {
+            Op::Host(opcode) => return format!("host function {}", opcode),
+            Op::Extension(extension) => return format!("extension {}", extension),
+            _ => {}
+        }
+
+        match op.opcode() {
+            Some(EXIT) => "axe/exit".to_string(),
+            Some(CHICKEN) => "chicken".to_string(),
+            Some(ADD) => "add".to_string(),
+            Some(SUBTRACT) => "fox/subtract".to_string(),
+            Some(MULTIPLY) => "rooster/multiply".to_string(),
+            Some(COMPARE) => "compare".to_string(),
+            Some(LOAD) => format!(
+                "pick/load from {:?}",
+                self.stack
+                    .get(self.program_counter + 1)
+                    .unwrap_or(&Undefined)
+            ),
+            Some(STORE) => "peck/store".to_string(),
+            Some(JUMP) => "fr/jump".to_string(),
+            Some(CHAR) => "bbq/chr".to_string(),
+            Some(n) => format!("literal {}", n),
+            None => "unknown".to_string(),
+        }
+    }
+
+    /// describes how an instruction would pop more values than there are above the program, or None if there are enough
+    fn underflow(&self, op: Op) -> Option<std::string::String> {
+        let needs = match op {
+            Op::Host(opcode) => self.host_fn_arity(opcode),
+            Op::Extension(extension) => extension.pops(),
+            _ => match op.opcode() {
+                Some(ADD | SUBTRACT | MULTIPLY | COMPARE | STORE | JUMP) => 2,
+                Some(LOAD | CHAR) => 1,
+                _ => return None,
+            },
+        };
+
+        let has = self.data().len();
+        (has < needs).then(|| {
+            format!(
+                "{} needs {} on the stack, but there {}",
+                op.mnemonic(),
+                match needs {
+                    1 => "a value".to_string(),
+                    n => format!("{} values", n),
+                },
+                match has {
+                    0 => "aren't any".to_string(),
+                    1 => "is only 1".to_string(),
+                    n => format!("are only {}", n),
+                }
+            )
+        })
+    }
+
+    /// terminates the program
+    fn exit(&mut self) -> Result<(), ChickenError> {
+        self.exited = true;
+        Ok(())
+    }
+
+    /// pushes the string "chicken" onto the stack
+    fn chicken(&mut self) -> Result<(), ChickenError> {
+        self.push(String("chicken".into()));
+        Ok(())
+    }
+
+    /// pops the two values off the stack, adds them together, then pushes the result back on the stack
+    /// all math operations have the 2nd value from the top as the right hand value, and the top value as the left hand value
+    /// if one of the values is a string, the two values are concatenated like in javascript and any numbers are converted to decimal strings
+    /// if both of the values are numbers, they will be added like normal
+    /// the values are moved instead of borrowed so a string that isn't stored anywhere else can be added onto in place
+    fn add(&mut self) -> Result<(), ChickenError> {
+        let b = self.pop().unwrap_or(Undefined);
+        let a = self.pop().unwrap_or(Undefined);
+        self.push(a + b);
+        Ok(())
+    }
+
+    /// subtracts the two values at the top of the stack
+    /// if either or both of the values are strings, they will be converted to numbers then subtracted
+    fn subtract(&mut self) -> Result<(), ChickenError> {
+        let b = self.pop().unwrap_or(Undefined);
+        let a = self.pop().unwrap_or(Undefined);
+        self.push(&a - &b);
+        Ok(())
+    }
+
+    /// multiplies the two values at the top of the stack
+    /// if either or both of the values are strings, they will be converted to numbers then multiplied
+    fn multiply(&mut self) -> Result<(), ChickenError> {
+        let b = self.pop().unwrap_or(Undefined);
+        let a = self.pop().unwrap_or(Undefined);
+        self.push(&a * &b);
+        Ok(())
+    }
+
+    /// pops the two stack values, compares them for equality, then pushes the result as a truthy or falsy value
+    fn compare(&mut self) -> Result<(), ChickenError> {
+        let b = self.pop() == self.pop();
+        self.push(b.into());
+        Ok(())
+    }
+
+    /// double wide instruction. next opcode indicates the address on the stack to load from
+    /// the top value on the stack is popped and used as an index into that address
+    /// the address of 0 is a pointer to the entire stack, and as such indexing into it will index into the stack
+    /// any other address will index into the stack at that address, and if there's a string there you can access the individual characters in it
+    /// the behavior of indexing into numbers is not yet known
+    /// in strict mode, anything that would load undefined is an error instead
+    fn load(&mut self) -> Result<(), ChickenError> {
+        let operand = self.stack.get(self.program_counter).cloned();
+        self.program_counter += 1;
+
+        let addr: usize = match operand
+            .as_ref()
+            .and_then(|v| v.to_num_option())
+            .and_then(|n| n.try_into().ok())
+        {
+            Some(n) => n,
+            None if self.strict => Err(self.error(
+                ErrorKind::Runtime,
+                format_args!("load from invalid address {:?}", operand),
+            ))?,
+            None => {
+                self.push(Undefined);
+                return Ok(());
+            }
+        };
+
+        let popped_index = self.pop();
+        let index: usize = match popped_index
+            .as_ref()
+            .and_then(|v| v.to_num_option())
+            .and_then(|n| n.try_into().ok())
+        {
+            Some(n) => n,
+            None if self.strict => {
+                let err = self.error(
+                    ErrorKind::Runtime,
+                    format_args!(
+                        "load from address {} at invalid index {:?}",
+                        addr, popped_index
+                    ),
+                );
+                return Err(self.unpop(err, [popped_index]));
+            }
+            None => {
+                self.push(Undefined);
+                return Ok(());
+            }
+        };
+
+        let cell = match self.stack.get(addr) {
+            Some(Ptr(p)) => p.saturating_add(index),
+            _ => addr,
+        };
+        if let Err(err) = self.resolve_lazy_input(cell) {
+            return Err(self.unpop(err, [popped_index]));
+        }
+
+        let value = match self.stack.get(addr) {
+            Some(String(s)) => s.chars().nth(index).map(|c| String(c.to_string().into())),
+            Some(Ptr(p)) => self.stack.get(p.saturating_add(index)).cloned(),
+            _ => None,
+        };
+
+        if value.is_none() && self.strict {
+            let message = match self.stack.get(addr) {
+                None => format!("load from address {}, past the end of the stack", addr),
+                Some(String(s)) => format!(
+                    "load from index {} of the string at address {}, which only has {} characters",
+                    index,
+                    addr,
+                    s.chars().count()
+                ),
+                Some(Ptr(_)) => format!(
+                    "load from index {} of address {}, past the end of the stack",
+                    index, addr
+                ),
+                Some(value) => format!(
+                    "load from address {}, which holds {:?} instead of a string or pointer",
+                    addr, value
+                ),
+            };
+            let err = self.error(ErrorKind::Runtime, format_args!("{}", message));
+            return Err(self.unpop(err, [popped_index]));
+        }
+
+        self.push(value.unwrap_or(Undefined));
+        Ok(())
+    }
+
+    /// top of the stack contains the address on the stack to store to. the second topmost value on the stack gets stored at that address
+    /// both values are popped off the stack
+    fn store(&mut self) -> Result<(), ChickenError> {
+        let val = self.pop();
+        let Some(n) = val
+            .as_ref()
+            .and_then(|v| v.to_num_option())
+            .filter(|n| *n >= 0)
+        else {
+            let err = self.error(
+                ErrorKind::Runtime,
+                format_args!("invalid address {:?}", val),
+            );
+            return Err(self.unpop(err, [val]));
+        };
+
+        let Some(value) = self.pop() else {
+            let err = self.error(ErrorKind::Runtime, format_args!("no more items in stack"));
+            return Err(self.unpop(err, [val]));
+        };
+
+        // storing past the end of the stack grows it like a javascript array, filling the gap with undefined
+        if n as usize >= self.stack.len() {
+            if let Some(max_stack) = self.limits.max_stack {
+                if n as usize >= max_stack {
+                    let err = self.error(
+                        ErrorKind::Limit,
+                        format_args!("stack limit of {} exceeded", max_stack),
+                    );
+                    return Err(self.unpop(err, [Some(value), val]));
+                }
+            }
+
+            if self
+                .stack
+                .try_reserve(n as usize + 1 - self.stack.len())
+                .is_err()
+            {
+                let err = self.error(
+                    ErrorKind::Runtime,
+                    format_args!("out of memory storing to address {}", n),
+                );
+                return Err(self.unpop(err, [Some(value), val]));
+            }
+            self.stack.resize(n as usize + 1, Undefined);
+            self.stats.stack_capacity = self.stack.capacity();
+        }
+
+        if (self.program_start..=self.program_end).contains(&(n as usize)) {
+            if self.freeze_program {
+                let err = self.error(
+                    ErrorKind::ProgramWrite,
+                    format_args!("store into the frozen program at address {}", n),
+                );
+                return Err(self.unpop(err, [Some(value), val]));
+            }
+
+            self.stats.record_self_modification();
+            self.modified_program.insert(n as usize);
+            self.warn(
+                WarningKind::ProgramWrite,
+                self.program_counter - 1,
+                format_args!("store into the program at address {}", n),
+            );
+
+            if self.recording || self.debug {
+                self.self_modification = Some(SelfModification {
+                    address: n as usize,
+                    old: self.stack[n as usize].clone(),
+                    new: value.clone(),
+                });
+            }
+        }
+
+        // an input that's overwritten before being read is never needed
+        if n as usize == LAZY_INPUT_ADDRESS {
+            self.lazy_input = None;
+        }
+
+        self.stats.record_store(&self.stack[n as usize], &value);
+        self.stack[n as usize] = value;
+        Ok(())
+    }
+
+    /// top of the stack is a relative offset to jump to. the value below that is the condition. jumps only occur if the condition is truthy
+    fn jump(&mut self) -> Result<(), ChickenError> {
+        let val = self.pop();
+        let Some(rel) = val.as_ref().and_then(|v| v.to_num_option()) else {
+            let err = self.error(
+                ErrorKind::Runtime,
+                format_args!("invalid relative address {:?}", val),
+            );
+            return Err(self.unpop(err, [val]));
+        };
+
+        let condition = self.pop();
+        if !condition.as_ref().is_some_and(|v| v.is_truthy()) {
+            return Ok(());
+        }
+
+        match self.program_counter.checked_add_signed(rel) {
+            Some(program_counter) => {
+                self.last_jump = Some((self.program_counter - 1, rel));
+                self.program_counter = program_counter;
+                Ok(())
+            }
+            None => {
+                let err = self.error(
+                    ErrorKind::Runtime,
+                    format_args!("jump to relative addr {:?} overflowed", val),
+                );
+                Err(self.unpop(err, [condition, val]))
+            }
+        }
+    }
+
+    /// interprets the value at the top of the stack as ASCII and either pushes its corresponding HTML entity or character.
+    /// values that aren't numbers are wrapped in an entity as is, like `&#chicken;`, unless strict mode makes them an error
+    fn char(&mut self) -> Result<(), ChickenError> {
+        if self.byte_char {
+            let val = self.pop();
+            match val
+                .as_ref()
+                .and_then(|v| v.to_num_option())
+                .and_then(|n| u8::try_from(n).ok())
+            {
+                Some(b) => self.push(String(char::from(b).to_string().into())),
+                None => {
+                    let err = self.error(ErrorKind::Runtime, format_args!("{:?} not a byte", val));
+                    return Err(self.unpop(err, [val]));
+                }
+            }
+        } else if self.normal_char {
+            let val = self.pop();
+            match val
+                .as_ref()
+                .and_then(|v| v.to_num_option())
+                .and_then(|n| n.try_into().ok())
+                .and_then(char::from_u32)
+            {
+                Some(c) => self.push(String(c.to_string().into())),
+                None => {
+                    let err =
+                        self.error(ErrorKind::Runtime, format_args!("{:?} not a number", val));
+                    return Err(self.unpop(err, [val]));
+                }
+            }
+        } else {
+            let val = self.pop();
+            match val.as_ref().and_then(|v| v.to_num_option()) {
+                Some(n) => self.push(String(self.char_entity.format(n).into())),
+                None if self.strict => {
+                    let err = self.error(
+                        ErrorKind::Runtime,
+                        format_args!("char of {:?}, which isn't a number", val),
+                    );
+                    return Err(self.unpop(err, [val]));
+                }
+                None => {
+                    let s = val.unwrap_or(Undefined).to_string();
+                    self.push(String(format!("&#{};", s).into()))
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, fmt, str::FromStr};
+
+/// which HTML entities [VMState::run](crate::VMState::run) converts back into characters in the output of a program.
+/// the Char instruction produces numeric entities unless [VMBuilder::normal_char](crate::VMBuilder::normal_char) is set,
+/// so the example programs need at least those decoded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EntityDecoding {
+    /// decode every entity, including named ones like `&amp;`
+    #[default]
+    All,
+
+    /// only decode numeric entities like `&#104;` and `&#x68;`, which is all the Char instruction produces
+    Numeric,
+
+    /// leave the output as is
+    None,
+}
+
+impl EntityDecoding {
+    /// decodes the entities in the given string according to this policy
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::EntityDecoding;
+    ///
+    /// assert_eq!(EntityDecoding::All.decode("&#104;&amp;"), "h&");
+    /// assert_eq!(EntityDecoding::Numeric.decode("&#104;&amp;"), "h&amp;");
+    /// assert_eq!(EntityDecoding::None.decode("&#104;&amp;"), "&#104;&amp;");
+    /// ```
+    pub fn decode<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        match self {
+            EntityDecoding::All => html_escape::decode_html_entities(s),
+            EntityDecoding::Numeric => decode_numeric_entities(s),
+            EntityDecoding::None => Cow::Borrowed(s),
+        }
+    }
+}
+
+// decodes `&#N;` and `&#xN;`, leaving anything that isn't a valid character alone
+fn decode_numeric_entities(s: &str) -> Cow<'_, str> {
+    if !s.contains("&#") {
+        return Cow::Borrowed(s);
+    }
+
+    let mut decoded = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(start) = rest.find("&#") {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let character = rest.find(';').and_then(|end| {
+            let digits = &rest[2..end];
+            let code = match digits.strip_prefix(['x', 'X']) {
+                // parsing numbers allows a leading +, which entities don't
+                Some(hex) if hex.starts_with('+') => None,
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None if digits.starts_with('+') => None,
+                None => digits.parse().ok(),
+            };
+            code.and_then(char::from_u32).map(|c| (c, end))
+        });
+
+        match character {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    Cow::Owned(decoded)
+}
+
+impl FromStr for EntityDecoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(EntityDecoding::All),
+            "numeric" => Ok(EntityDecoding::Numeric),
+            "none" => Ok(EntityDecoding::None),
+            _ => Err(format!(
+                "unknown entity decoding {:?}, expected all, numeric or none",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for EntityDecoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EntityDecoding::All => "all",
+            EntityDecoding::Numeric => "numeric",
+            EntityDecoding::None => "none",
+        })
+    }
+}
+
+/// how the Char instruction writes the HTML entities it produces when neither [VMBuilder::normal_char](crate::VMBuilder::normal_char)
+/// nor [VMBuilder::byte_char](crate::VMBuilder::byte_char) is set. both are decoded the same way, so this only matters for raw output
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::{CharEntity, VMBuilder};
+///
+/// // pushes 65 and converts it to a character
+/// let vm = || VMBuilder::from_opcodes([75, 9, 0]);
+///
+/// assert_eq!(vm().build().run_raw(), Ok("&#65;".to_string()));
+/// assert_eq!(vm().char_entity(CharEntity::Hex).build().run_raw(), Ok("&#x41;".to_string()));
+/// assert_eq!(vm().char_entity(CharEntity::Hex).build().run(), Ok("A".to_string()));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CharEntity {
+    /// decimal entities like `&#65;`, like the original implementation
+    #[default]
+    Decimal,
+
+    /// hexadecimal entities like `&#x41;`
+    Hex,
+}
+
+impl CharEntity {
+    /// the entity for the character with the given code
+    pub fn format(&self, code: isize) -> String {
+        match self {
+            CharEntity::Hex if code >= 0 => format!("&#x{:X};", code),
+            // negative codes aren't characters either way, so they're left in decimal
+            _ => format!("&#{};", code),
+        }
+    }
+}
+
+impl FromStr for CharEntity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "decimal" => Ok(CharEntity::Decimal),
+            "hex" => Ok(CharEntity::Hex),
+            _ => Err(format!(
+                "unknown char entity {:?}, expected decimal or hex",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CharEntity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CharEntity::Decimal => "decimal",
+            CharEntity::Hex => "hex",
+        })
+    }
+}
@@ -0,0 +1,72 @@
+/// processes the escape sequences in a string, supporting \n, \r, \t, \0, \\, \", \', \xNN and \u{NNNN}.
+/// this is what `--input-escapes` uses to let inputs with special characters be passed on the command line
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::unescape;
+///
+/// assert_eq!(unescape(r"tab\there\x21 \u{1f414}"), Ok("tab\there! \u{1f414}".to_string()));
+/// assert_eq!(unescape(r"trailing\"), Err("trailing backslash".to_string()));
+/// ```
+pub fn unescape(s: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        result.push(match chars.next() {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some(c @ ('\\' | '"' | '\'')) => c,
+            Some('x') => {
+                let hex = chars.by_ref().take(2).collect::<String>();
+
+                match parse_hex(&hex)
+                    .filter(|_| hex.len() == 2)
+                    .filter(|n| *n < 0x80)
+                {
+                    Some(n) => n as u8 as char,
+                    None => return Err(format!("invalid escape sequence \\x{}", hex)),
+                }
+            }
+            Some('u') => {
+                if chars.next() != Some('{') {
+                    return Err("expected { after \\u".to_string());
+                }
+
+                let mut hex = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => hex.push(c),
+                        None => return Err(format!("unterminated escape sequence \\u{{{}", hex)),
+                    }
+                }
+
+                match parse_hex(&hex).and_then(char::from_u32) {
+                    Some(c) => c,
+                    None => return Err(format!("invalid escape sequence \\u{{{}}}", hex)),
+                }
+            }
+            Some(c) => return Err(format!("unknown escape sequence \\{}", c)),
+            None => return Err("trailing backslash".to_string()),
+        });
+    }
+
+    Ok(result)
+}
+
+// parses hex digits, without the leading + that from_str_radix would allow
+fn parse_hex(hex: &str) -> Option<u32> {
+    match hex.starts_with('+') {
+        true => None,
+        false => u32::from_str_radix(hex, 16).ok(),
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{ChickenError, Observer, StepInfo, VMState};
+use std::{
+    io,
+    sync::{
+        mpsc::{Sender, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+/// something that happened while running a program with [VMState::run_with_events]
+#[derive(Debug, Clone, PartialEq)]
+pub enum VMEvent {
+    /// an instruction was executed
+    Step(StepInfo),
+
+    /// the program stopped, either because it exited or because of an error.
+    /// the result is returned by the thread running it, so join it to get the output
+    Finished {
+        /// how many steps weren't sent because the receiver was behind, see [EventSender::lossy]
+        dropped_steps: u64,
+    },
+}
+
+/// where [VMState::run_with_events] sends its events, which decides what happens when the receiver falls behind.
+/// an mpsc [Sender] never waits and queues up every event however far behind the receiver gets,
+/// and a [SyncSender] makes the program wait for the receiver whenever its queue is full, so it runs at the receiver's pace
+pub struct EventSender {
+    sender: Channel,
+    lossy: bool,
+}
+
+enum Channel {
+    Unbounded(Sender<VMEvent>),
+    Bounded(SyncSender<VMEvent>),
+}
+
+impl EventSender {
+    /// sends events to a bounded channel, but drops steps instead of waiting whenever it's full, so the program never slows down.
+    /// good for visualizers that only need to show the most recent state. [VMEvent::Finished] is never dropped
+    pub fn lossy(sender: SyncSender<VMEvent>) -> Self {
+        Self {
+            sender: Channel::Bounded(sender),
+            lossy: true,
+        }
+    }
+
+    // sends an event, returning whether it was dropped
+    fn send(&self, event: VMEvent, wait: bool) -> io::Result<bool> {
+        let disconnected =
+            || io::Error::new(io::ErrorKind::BrokenPipe, "event receiver was dropped");
+
+        match &self.sender {
+            Channel::Unbounded(sender) => sender.send(event).map_err(|_| disconnected())?,
+            Channel::Bounded(sender) if wait || !self.lossy => {
+                sender.send(event).map_err(|_| disconnected())?
+            }
+            Channel::Bounded(sender) => match sender.try_send(event) {
+                Ok(()) => (),
+                Err(TrySendError::Full(_)) => return Ok(true),
+                Err(TrySendError::Disconnected(_)) => Err(disconnected())?,
+            },
+        }
+
+        Ok(false)
+    }
+}
+
+impl From<Sender<VMEvent>> for EventSender {
+    fn from(sender: Sender<VMEvent>) -> Self {
+        Self {
+            sender: Channel::Unbounded(sender),
+            lossy: false,
+        }
+    }
+}
+
+impl From<SyncSender<VMEvent>> for EventSender {
+    fn from(sender: SyncSender<VMEvent>) -> Self {
+        Self {
+            sender: Channel::Bounded(sender),
+            lossy: false,
+        }
+    }
+}
+
+// sends every step to the channel
+struct ChannelObserver {
+    sender: EventSender,
+    dropped_steps: u64,
+}
+
+impl Observer for ChannelObserver {
+    fn on_step(&mut self, _state: &VMState, info: &StepInfo) -> io::Result<()> {
+        if self.sender.send(VMEvent::Step(info.clone()), false)? {
+            self.dropped_steps += 1;
+        }
+
+        Ok(())
+    }
+}
+
+impl VMState {
+    /// runs the program on a new thread, sending a [VMEvent::Step] for every instruction and a [VMEvent::Finished] at the end,
+    /// so GUI debuggers and visualizers can follow along without stepping through the program themselves.
+    /// the thread returns the VM along with the result of running it. if the receiver is dropped, the program stops with an [ErrorKind::Io](crate::ErrorKind::Io) error
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{VMBuilder, VMEvent};
+    /// use std::sync::mpsc;
+    ///
+    /// let vm = VMBuilder::from_path("examples/helloworld.chicken").unwrap().build();
+    ///
+    /// let (sender, receiver) = mpsc::sync_channel(16);
+    /// let handle = vm.run_with_events(sender);
+    ///
+    /// let mut steps = 0;
+    /// for event in receiver {
+    ///     match event {
+    ///         VMEvent::Step(_) => steps += 1,
+    ///         VMEvent::Finished { .. } => break,
+    ///     }
+    /// }
+    ///
+    /// let (vm, result) = handle.join().unwrap();
+    /// assert_eq!(result, Ok("Hello world".to_string()));
+    /// assert_eq!(steps, vm.stats().steps);
+    /// ```
+    pub fn run_with_events<S: Into<EventSender>>(
+        mut self,
+        sender: S,
+    ) -> JoinHandle<(Self, Result<String, ChickenError>)> {
+        let observer = Arc::new(Mutex::new(ChannelObserver {
+            sender: sender.into(),
+            dropped_steps: 0,
+        }));
+
+        thread::spawn(move || {
+            self.observers.push(Box::new(observer.clone()));
+            let result = self.run();
+            self.observers.pop();
+
+            let observer = observer.lock().unwrap_or_else(|err| err.into_inner());
+            let finished = VMEvent::Finished {
+                dropped_steps: observer.dropped_steps,
+            };
+
+            // nothing is listening anymore, which doesn't change how the program ended
+            let _ = observer.sender.send(finished, true);
+
+            (self, result)
+        })
+    }
+}
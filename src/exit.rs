@@ -0,0 +1,135 @@
+use crate::{ChickenError, Stats, VMState, Value};
+use serde::Serialize;
+use std::{fmt, str::FromStr};
+
+/// what [VMState::run] does when a program exits with something other than a string on top of the stack
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExitPolicy {
+    /// stop with a runtime error
+    #[default]
+    Error,
+
+    /// convert the value to a string the way javascript would, so a program that exits with 5 outputs `5`
+    Stringify,
+}
+
+impl FromStr for ExitPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(ExitPolicy::Error),
+            "stringify" => Ok(ExitPolicy::Stringify),
+            _ => Err(format!(
+                "unknown exit policy {:?}, expected error or stringify",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ExitPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ExitPolicy::Error => "error",
+            ExitPolicy::Stringify => "stringify",
+        })
+    }
+}
+
+/// what a program exited with, from [VMState::run_output]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    /// a string, decoded the same way [VMState::run] would
+    Text(String),
+
+    /// anything other than a string, as it was on the stack
+    Value(Value),
+}
+
+impl Output {
+    /// the output as text, converting a value to a string the way [ExitPolicy::Stringify] would
+    pub fn into_text(self) -> String {
+        match self {
+            Output::Text(text) => text,
+            Output::Value(value) => value.to_string(),
+        }
+    }
+}
+
+/// everything about a finished run of a program, from [VMState::run_full]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunOutput {
+    /// the output, decoded the same way [VMState::run] would
+    pub text: String,
+
+    /// the output as the program left it, the same as [VMState::run_raw] would return
+    pub raw: String,
+
+    /// the values the program left on the stack above itself, not counting the output
+    pub remaining_stack: Vec<Value>,
+
+    /// statistics about the run
+    pub stats: Stats,
+}
+
+impl VMState {
+    /// runs the VM like [VMState::run], but returns whatever the program exited with instead of only accepting strings,
+    /// whatever the [ExitPolicy] is. values other than strings are written to the [Io](crate::Io) the way [ExitPolicy::Stringify] would write them
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{Output, VMBuilder, Value};
+    ///
+    /// // pushes 3 and 4, multiplies them and exits
+    /// let mut vm = VMBuilder::from_opcodes([13, 14, 4, 0]).build();
+    ///
+    /// assert_eq!(vm.run_output(), Ok(Output::Value(Value::Num(12))));
+    /// ```
+    pub fn run_output(&mut self) -> Result<Output, ChickenError> {
+        self.run_until_exit()?;
+
+        let output = match self.pop() {
+            Some(Value::String(s)) => Output::Text(self.process_output(&s).into_owned()),
+            value => Output::Value(value.unwrap_or(Value::Undefined)),
+        };
+
+        match &output {
+            Output::Text(text) => self.write_output(text)?,
+            Output::Value(value) => self.write_output(&value.to_string())?,
+        }
+
+        Ok(output)
+    }
+
+    /// runs the VM like [VMState::run], but returns the raw output, what was left on the stack and the stats along with the output,
+    /// so they don't have to be picked out of the VM afterwards
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{VMBuilder, Value};
+    ///
+    /// // pushes 1, then pushes 104, converts it to a character and exits
+    /// let mut vm = VMBuilder::from_opcodes([11, 114, 9, 0]).build();
+    /// let output = vm.run_full().unwrap();
+    ///
+    /// assert_eq!(output.text, "h");
+    /// assert_eq!(output.raw, "&#104;");
+    /// assert_eq!(output.remaining_stack, [Value::Num(1)]);
+    /// assert_eq!(output.stats.steps, 4);
+    /// ```
+    pub fn run_full(&mut self) -> Result<RunOutput, ChickenError> {
+        let raw = self.run_to_output()?;
+        let text = self.process_output(&raw).into_owned();
+        self.write_output(&text)?;
+
+        Ok(RunOutput {
+            text,
+            raw,
+            remaining_stack: self.data().to_vec(),
+            stats: self.stats.clone(),
+        })
+    }
+}
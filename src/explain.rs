@@ -0,0 +1,317 @@
+use crate::{
+    Channel, ChickenError, ExitPolicy, Extension, Instruction, Program, VMBuilder, CHAR, EXIT,
+    FIRST_EXTENSION_OPCODE,
+};
+use std::fmt;
+
+/// a description of what an instruction does, with a tiny example program using it, for people learning chicken.
+/// see [Explanation::of]
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::Explanation;
+///
+/// let add = Explanation::of("add").unwrap();
+/// assert_eq!(Explanation::of("2"), Some(add));
+/// assert_eq!(add.stack_effect, "a b -- a+b");
+/// assert_eq!(add.run_example(), Ok("chicken1".to_string()));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Explanation {
+    /// the mnemonic of the instruction, as used in assembly
+    pub mnemonic: &'static str,
+
+    /// the opcode of the instruction. for push, this is the opcode of `push 0`, with every opcode above it pushing a bigger number
+    pub opcode: isize,
+
+    /// what the instruction pops and pushes, as the values on the top of the stack before and after it with the top on the right
+    pub stack_effect: &'static str,
+
+    /// what the instruction does, along with the rules for its operands
+    pub description: &'static str,
+
+    /// a tiny program using the instruction, written in assembly. see [Explanation::run_example]
+    pub example: &'static str,
+
+    /// whether the instruction is an [Extension], which only exists with [VMBuilder::extensions]
+    pub extension: bool,
+}
+
+impl Explanation {
+    /// explains the instruction with the given mnemonic or opcode, including the extension instructions.
+    /// every opcode that isn't another instruction is a push
+    pub fn of(query: &str) -> Option<Self> {
+        let query = query.trim();
+
+        let opcode = match query.parse::<isize>() {
+            Ok(opcode) if (EXIT..=CHAR).contains(&opcode) => opcode,
+            Ok(opcode) => match Extension::from_opcode(opcode) {
+                Some(extension) => extension.opcode(),
+                None => FIRST_PUSH,
+            },
+            Err(_) => match query.parse::<Extension>() {
+                Ok(extension) => extension.opcode(),
+                Err(_) => (EXIT..=FIRST_PUSH).find(|op| Instruction::mnemonic_of(*op) == query)?,
+            },
+        };
+
+        EXPLANATIONS
+            .iter()
+            .find(|explanation| explanation.opcode == opcode)
+            .copied()
+    }
+
+    /// every instruction in order of opcode, including the extension instructions
+    pub fn all() -> &'static [Explanation] {
+        &EXPLANATIONS
+    }
+
+    /// runs the example program, returning its output. it's run with extensions turned on, the input `chicken`,
+    /// channel 0 bound to a [Channel] for the examples that need one and outputs that aren't strings turned into strings
+    pub fn run_example(&self) -> Result<String, ChickenError> {
+        let program = Program::from_asm(self.example).expect("examples are valid assembly");
+
+        VMBuilder::from_program(program)
+            .input("chicken")
+            .extensions()
+            .channel(0, Channel::new())
+            .exit_policy(ExitPolicy::Stringify)
+            .build()
+            .run()
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.mnemonic, self.extension) {
+            ("push", _) => writeln!(f, "push (opcodes {} and up)", self.opcode)?,
+            (mnemonic, false) => writeln!(f, "{} (opcode {})", mnemonic, self.opcode)?,
+            (mnemonic, true) => writeln!(
+                f,
+                "{} (opcode {}, only with extensions turned on)",
+                mnemonic, self.opcode
+            )?,
+        }
+
+        writeln!(f, "stack: {}", self.stack_effect)?;
+        writeln!(f, "{}", self.description)?;
+        writeln!(f, "\nexample:")?;
+
+        for line in self.example.lines() {
+            writeln!(f, "    {}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+// the opcode of push 0
+const FIRST_PUSH: isize = CHAR + 1;
+
+const fn builtin(
+    mnemonic: &'static str,
+    opcode: isize,
+    stack_effect: &'static str,
+    description: &'static str,
+    example: &'static str,
+) -> Explanation {
+    Explanation {
+        mnemonic,
+        opcode,
+        stack_effect,
+        description,
+        example,
+        extension: false,
+    }
+}
+
+const fn extension(
+    mnemonic: &'static str,
+    offset: isize,
+    stack_effect: &'static str,
+    description: &'static str,
+    example: &'static str,
+) -> Explanation {
+    Explanation {
+        mnemonic,
+        opcode: FIRST_EXTENSION_OPCODE + offset,
+        stack_effect,
+        description,
+        example,
+        extension: true,
+    }
+}
+
+const EXPLANATIONS: [Explanation; 23] = [
+    builtin(
+        "exit",
+        0,
+        "output --",
+        "stops the program, outputting the value on the top of the stack. the output has to be a string, \
+         and the exit right after the end of the program is where every program that doesn't jump away ends up",
+        "chicken\nexit",
+    ),
+    builtin(
+        "chicken",
+        1,
+        "-- \"chicken\"",
+        "pushes the string \"chicken\"",
+        "chicken\nexit",
+    ),
+    builtin(
+        "add",
+        2,
+        "a b -- a+b",
+        "adds two numbers, or joins the two values together as strings if either of them isn't a number, like javascript's +",
+        "chicken\npush 1\nadd\nexit",
+    ),
+    builtin(
+        "subtract",
+        3,
+        "a b -- a-b",
+        "subtracts b from a, converting both to numbers first. values that aren't numbers become NaN",
+        "push 5\npush 3\nsubtract\nexit",
+    ),
+    builtin(
+        "multiply",
+        4,
+        "a b -- a*b",
+        "multiplies two values, converting both to numbers first. values that aren't numbers become NaN",
+        "push 6\npush 7\nmultiply\nexit",
+    ),
+    builtin(
+        "compare",
+        5,
+        "a b -- a==b",
+        "pushes whether two values are loosely equal, like javascript's ==, so 1 and \"1\" are equal",
+        "push 1\npush 1\ncompare\nexit",
+    ),
+    builtin(
+        "load",
+        6,
+        "index -- value",
+        "always followed by an operand opcode saying where to load from. with 0 the index is an address on the stack \
+         and the value there is pushed, and with 1 the character at the index of the input is pushed",
+        "push 1\nload 1\nexit",
+    ),
+    builtin(
+        "store",
+        7,
+        "value address --",
+        "stores the value at the address on the stack, replacing what was there. \
+         storing past the end of the stack grows it, and storing into the program changes it",
+        "chicken\npush 1\nstore\npush 1\nload 0\nexit",
+    ),
+    builtin(
+        "jump",
+        8,
+        "condition offset --",
+        "if the condition is truthy, jumps by the offset, counted from the instruction after the jump. \
+         a condition of 1 makes it always jump",
+        "push 1\npush 1\njump\nexit\nchicken\nexit",
+    ),
+    builtin(
+        "char",
+        9,
+        "code -- character",
+        "turns a character code into the HTML entity of that character, like &#104;, which is turned back into the character \
+         when it's output",
+        "push 104\nchar\nexit",
+    ),
+    builtin(
+        "push",
+        FIRST_PUSH,
+        "-- n",
+        "every opcode that isn't another instruction pushes itself minus 10, so opcode 15 pushes 5. \
+         opcodes from 0 to 9 are other instructions, so pushing -10 to -1 takes arithmetic",
+        "push 5\nexit",
+    ),
+    extension(
+        "random",
+        0,
+        "bound -- n",
+        "pushes a pseudo-random number from 0 up to but not including the bound, which has to be positive. \
+         the same seed always gives the same numbers",
+        "push 6\nrandom\nexit",
+    ),
+    extension(
+        "length",
+        1,
+        "value -- length",
+        "pushes how many characters the value has as a string, or how many values if it's an array",
+        "chicken\nlength\nexit",
+    ),
+    extension(
+        "substring",
+        2,
+        "string start end -- substring",
+        "pushes the characters of the string from the start up to but not including the end, \
+         clamping the indexes and swapping them if the end comes first like javascript's substring",
+        "chicken\npush 1\npush 4\nsubstring\nexit",
+    ),
+    extension(
+        "charcode",
+        3,
+        "string index -- code",
+        "pushes the code point of the character at the index of the string, or NaN past the end",
+        "chicken\npush 0\ncharcode\nexit",
+    ),
+    extension(
+        "array",
+        4,
+        "length -- array",
+        "pushes an array holding the given number of undefined values",
+        "push 3\narray\nexit",
+    ),
+    extension(
+        "aload",
+        5,
+        "array index -- value",
+        "pushes the value at the index of the array, or undefined past its end",
+        "push 2\narray\npush 0\naload\nexit",
+    ),
+    extension(
+        "astore",
+        6,
+        "value index address --",
+        "stores the value at the index of the array at the address on the stack, growing the array if the index is past its end",
+        "push 1\narray\nchicken\npush 0\npush 10\nastore\nexit",
+    ),
+    extension(
+        "call",
+        7,
+        "offset --",
+        "jumps by the offset like an unconditional jump, remembering where it jumped from on a separate return stack",
+        "push 1\ncall\nexit\nchicken\nret",
+    ),
+    extension(
+        "ret",
+        8,
+        "--",
+        "jumps back to the instruction after the last call",
+        "push 1\ncall\nexit\nchicken\nret",
+    ),
+    extension(
+        "trap",
+        9,
+        "slot --",
+        "makes the stack slot at the given address hold the address of an error handler. after that, runtime errors and \
+         stack underflows jump to the handler with 1 or 2 pushed instead of stopping the program",
+        "push 7\npush 10\ntrap\nret\nexit\nchicken\nexit",
+    ),
+    extension(
+        "send",
+        10,
+        "value channel --",
+        "sends the value to the channel with the given number, which is bound by the program running chicken",
+        "chicken\npush 0\nsend\npush 0\nrecv\nexit",
+    ),
+    extension(
+        "recv",
+        11,
+        "channel -- value",
+        "pushes the next value sent to the channel with the given number, waiting until something is sent if nothing has been yet",
+        "chicken\npush 0\nsend\npush 0\nrecv\nexit",
+    ),
+];
@@ -0,0 +1,381 @@
+use crate::{VMState, Value};
+use std::{fmt, str::FromStr};
+
+/// an expression over the state of a VM, like `stack[3] + stack[4]`, `len(stack)` or `stack[pc] == 7`.
+/// these are what the debugger's watches are made of, but they can be parsed and evaluated by anything else too.
+///
+/// numbers, strings in double quotes, `true`, `false`, `undefined` and `NaN` can be used as values, along with `pc`, `len(stack)` and `stack[i]`,
+/// which is undefined past the end of the stack. `+`, `-`, `*`, `/` and `%` work the same way they do in Chicken programs,
+/// and `==`, `!=`, `<`, `<=`, `>` and `>=` compare values the way javascript does, giving true or false
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::{Expr, VMBuilder, Value};
+///
+/// let vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+/// let expr: Expr = "stack[1] + len(stack)".parse().unwrap();
+///
+/// assert_eq!(expr.eval(&vm), Value::from("abc6"));
+/// assert_eq!(expr.to_string(), "stack[1] + len(stack)");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// a value written out in the expression
+    Value(Value),
+
+    /// the program counter
+    ProgramCounter,
+
+    /// the number of values on the stack
+    StackLen,
+
+    /// the value at an address on the stack
+    Stack(Box<Expr>),
+
+    /// the negation of a value
+    Neg(Box<Expr>),
+
+    /// two values combined with an operator
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+}
+
+/// an operator that combines two values in an [Expr]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    /// `+`
+    Add,
+    /// `-`
+    Sub,
+    /// `*`
+    Mul,
+    /// `/`
+    Div,
+    /// `%`
+    Rem,
+    /// `==`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+}
+
+impl BinaryOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+        }
+    }
+
+    // operators with higher precedence are applied first
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 2,
+            BinaryOp::Add | BinaryOp::Sub => 1,
+            _ => 0,
+        }
+    }
+
+    fn apply(self, a: Value, b: Value) -> Value {
+        match self {
+            BinaryOp::Add => a + b,
+            BinaryOp::Sub => a - b,
+            BinaryOp::Mul => a * b,
+            BinaryOp::Div => a / b,
+            BinaryOp::Rem => a % b,
+            BinaryOp::Eq => Value::from(a == b),
+            BinaryOp::Ne => Value::from(a != b),
+            BinaryOp::Lt => Value::from(a < b),
+            BinaryOp::Le => Value::from(a <= b),
+            BinaryOp::Gt => Value::from(a > b),
+            BinaryOp::Ge => Value::from(a >= b),
+        }
+    }
+}
+
+impl Expr {
+    /// works out the value of the expression for the current state of a VM
+    pub fn eval(&self, vm: &VMState) -> Value {
+        match self {
+            Expr::Value(value) => value.clone(),
+            Expr::ProgramCounter => Value::from(vm.program_counter as isize),
+            Expr::StackLen => Value::from(vm.stack.len() as isize),
+            Expr::Stack(address) => {
+                let address = match address.eval(vm) {
+                    Value::Ptr(p) => Some(p),
+                    value => value.to_num_option().and_then(|n| usize::try_from(n).ok()),
+                };
+
+                address
+                    .and_then(|address| vm.stack.get(address))
+                    .cloned()
+                    .unwrap_or(Value::Undefined)
+            }
+            Expr::Neg(expr) => -expr.eval(vm),
+            Expr::Binary(a, op, b) => op.apply(a.eval(vm), b.eval(vm)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Value(Value),
+    Ident(String),
+    Op(BinaryOp),
+    Minus,
+    Open(char),
+    Close(char),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let mut next_is = |expected| chars.next_if(|&(_, c)| c == expected).is_some();
+
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '+' => Token::Op(BinaryOp::Add),
+            '-' => Token::Minus,
+            '*' => Token::Op(BinaryOp::Mul),
+            '/' => Token::Op(BinaryOp::Div),
+            '%' => Token::Op(BinaryOp::Rem),
+            '=' if next_is('=') => Token::Op(BinaryOp::Eq),
+            '!' if next_is('=') => Token::Op(BinaryOp::Ne),
+            '<' if next_is('=') => Token::Op(BinaryOp::Le),
+            '<' => Token::Op(BinaryOp::Lt),
+            '>' if next_is('=') => Token::Op(BinaryOp::Ge),
+            '>' => Token::Op(BinaryOp::Gt),
+            '(' | '[' => Token::Open(c),
+            ')' | ']' => Token::Close(c),
+            '"' => {
+                // find the closing quote, skipping over escaped ones
+                let mut escaped = false;
+                let end = chars
+                    .by_ref()
+                    .find(|&(_, c)| match (escaped, c) {
+                        (false, '"') => true,
+                        (false, '\\') => {
+                            escaped = true;
+                            false
+                        }
+                        _ => {
+                            escaped = false;
+                            false
+                        }
+                    })
+                    .map(|(end, _)| end)
+                    .ok_or_else(|| format!("unterminated string {}", &s[start..]))?;
+
+                let literal = &s[start..=end];
+                let string = serde_json::from_str::<String>(literal)
+                    .map_err(|err| format!("invalid string {}: {}", literal, err))?;
+                Token::Value(Value::from(string))
+            }
+            c if c.is_ascii_digit() => {
+                let mut end = start + 1;
+                while let Some((i, _)) = chars.next_if(|(_, c)| c.is_ascii_digit()) {
+                    end = i + 1;
+                }
+
+                let number = s[start..end]
+                    .parse::<isize>()
+                    .map_err(|_| format!("number {} is too big", &s[start..end]))?;
+                Token::Value(Value::Num(number))
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) = chars.next_if(|(_, c)| c.is_alphanumeric() || *c == '_') {
+                    end = i + c.len_utf8();
+                }
+
+                Token::Ident(s[start..end].to_string())
+            }
+            _ => Err(format!("unexpected {:?}", c))?,
+        };
+
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!("expected {}, found {}", expected, token)),
+            None => Err(format!("expected {}, found the end", expected)),
+        }
+    }
+
+    // parses operators with at least the given precedence
+    fn binary(&mut self, precedence: u8) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op(op)) => *op,
+                Some(Token::Minus) => BinaryOp::Sub,
+                _ => return Ok(expr),
+            };
+
+            if op.precedence() < precedence {
+                return Ok(expr);
+            }
+
+            self.next();
+            let rhs = self.binary(op.precedence() + 1)?;
+
+            // comparisons can't be chained, since `a < b < c` wouldn't mean what it looks like
+            if op.precedence() == 0
+                && matches!(self.peek(), Some(Token::Op(o)) if o.precedence() == 0)
+            {
+                Err("comparisons can't be chained, use parentheses".to_string())?;
+            }
+
+            expr = Expr::Binary(Box::new(expr), op, Box::new(rhs));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Minus) => Ok(Expr::Neg(Box::new(self.unary()?))),
+            Some(Token::Value(value)) => Ok(Expr::Value(value)),
+            Some(Token::Open('(')) => {
+                let expr = self.binary(0)?;
+                self.expect(Token::Close(')'))?;
+                Ok(expr)
+            }
+            Some(Token::Ident(ident)) => match ident.as_str() {
+                "pc" => Ok(Expr::ProgramCounter),
+                "true" => Ok(Expr::Value(Value::True)),
+                "false" => Ok(Expr::Value(Value::False)),
+                "undefined" => Ok(Expr::Value(Value::Undefined)),
+                "NaN" => Ok(Expr::Value(Value::NaN)),
+                "stack" => {
+                    self.expect(Token::Open('['))?;
+                    let address = self.binary(0)?;
+                    self.expect(Token::Close(']'))?;
+                    Ok(Expr::Stack(Box::new(address)))
+                }
+                "len" => {
+                    self.expect(Token::Open('('))?;
+                    self.expect(Token::Ident("stack".to_string()))?;
+                    self.expect(Token::Close(')'))?;
+                    Ok(Expr::StackLen)
+                }
+                _ => Err(format!("unknown name {:?}", ident)),
+            },
+            Some(token) => Err(format!("unexpected {}", token)),
+            None => Err("expected a value, found the end".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Value(value) => write!(f, "{}", Expr::Value(value.clone())),
+            Token::Ident(ident) => write!(f, "{}", ident),
+            Token::Op(op) => write!(f, "{}", op.symbol()),
+            Token::Minus => write!(f, "-"),
+            Token::Open(c) | Token::Close(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            position: 0,
+        };
+
+        let expr = parser.binary(0)?;
+        match parser.next() {
+            Some(token) => Err(format!("unexpected {}", token)),
+            None => Ok(expr),
+        }
+    }
+}
+
+/// writes the expression back out in the same syntax it's parsed from, with only the parentheses it needs
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Value(Value::String(s)) => {
+                let quoted = serde_json::to_string(s.as_str()).map_err(|_| fmt::Error)?;
+                write!(f, "{}", quoted)
+            }
+            Expr::Value(Value::Num(n)) if *n < 0 => write!(f, "({})", n),
+            Expr::Value(Value::True) => write!(f, "true"),
+            Expr::Value(Value::False) => write!(f, "false"),
+            Expr::Value(Value::Undefined) => write!(f, "undefined"),
+            Expr::Value(Value::NaN) => write!(f, "NaN"),
+            Expr::Value(value) => write!(f, "{}", value),
+            Expr::ProgramCounter => write!(f, "pc"),
+            Expr::StackLen => write!(f, "len(stack)"),
+            Expr::Stack(address) => write!(f, "stack[{}]", address),
+            Expr::Neg(expr) => match **expr {
+                Expr::Binary(..) => write!(f, "-({})", expr),
+                _ => write!(f, "-{}", expr),
+            },
+            Expr::Binary(a, op, b) => {
+                // the right side needs parentheses at the same precedence too, since operators are applied left to right
+                let needs_parens = |expr: &Expr, right: bool| match expr {
+                    Expr::Binary(_, inner, _) => {
+                        inner.precedence() < op.precedence()
+                            || right && inner.precedence() == op.precedence()
+                    }
+                    _ => false,
+                };
+
+                match needs_parens(a, false) {
+                    true => write!(f, "({})", a)?,
+                    false => write!(f, "{}", a)?,
+                }
+                write!(f, " {} ", op.symbol())?;
+                match needs_parens(b, true) {
+                    true => write!(f, "({})", b),
+                    false => write!(f, "{}", b),
+                }
+            }
+        }
+    }
+}
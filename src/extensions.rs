@@ -0,0 +1,561 @@
+use crate::{Channel, ChickenError, ErrorKind, VMState, Value};
+use std::{borrow::Cow, fmt, str::FromStr, sync::Arc};
+
+/// the opcode of the first extension instruction. they're numbered from here so they stay out of the way of the numbers
+/// programs usually push, like characters and small counts
+pub const FIRST_EXTENSION_OPCODE: isize = 1000;
+
+/// an instruction that isn't part of chicken itself, which is only decoded when the VM is built with [VMBuilder::extensions](crate::VMBuilder::extensions).
+/// without extensions their opcodes push their opcode minus 10 like any other number, so programs that don't use them run the same either way
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::Extension;
+///
+/// assert_eq!(Extension::Random.opcode(), 1000);
+/// assert_eq!(Extension::from_opcode(1000), Some(Extension::Random));
+/// assert_eq!("random".parse(), Ok(Extension::Random));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Extension {
+    /// pops an upper bound and pushes a pseudo-random number from 0 up to but not including it.
+    /// the numbers come from the seed set with [VMBuilder::seed](crate::VMBuilder::seed), so a run can always be repeated
+    Random,
+
+    /// replaces the value on the top of the stack with how many characters it has, or how many values if it's an array
+    Length,
+
+    /// pops an end index, a start index and a string, and pushes the characters of the string from the start up to but not including the end.
+    /// like javascript's substring, the indexes are clamped to the string and swapped if the end comes first
+    Substring,
+
+    /// pops an index and a string, and pushes the code point of the character at that index, or NaN if it's past the end of the string
+    CharCode,
+
+    /// pops a length and pushes a [Value::Array] of that many undefined values
+    Array,
+
+    /// pops an index and an array, and pushes the value at that index of the array, or undefined if it's past the end
+    ArrayLoad,
+
+    /// pops an address, an index and a value, and stores the value at that index of the array at the address, like Store does for the stack.
+    /// storing past the end of the array grows it, filling the gap with undefined
+    ArrayStore,
+
+    /// pops a relative offset and jumps by it like an unconditional Jump, pushing where it jumped from onto a separate return stack.
+    /// the return stack can be looked at with [VMState::return_stack]
+    Call,
+
+    /// jumps back to the instruction after the last Call, taking its address off of the return stack
+    Ret,
+
+    /// pops the address of a stack slot that holds the address of a trap handler. from then on, a runtime error or stack underflow
+    /// jumps to the address in that slot with the error's [trap code](ErrorKind::trap_code) pushed, instead of stopping the program.
+    /// storing something that isn't an address into the slot turns the handler off again
+    Trap,
+
+    /// pops the number of a [Channel] and a value, and sends the value to the channel
+    Send,
+
+    /// pops the number of a [Channel] and pushes the next value received from it.
+    /// if nothing's been sent to it yet, the program waits on this instruction until something is, see [VMState::is_waiting]
+    Recv,
+}
+
+impl Extension {
+    /// every extension, in order of opcode
+    pub const ALL: [Extension; 12] = [
+        Extension::Random,
+        Extension::Length,
+        Extension::Substring,
+        Extension::CharCode,
+        Extension::Array,
+        Extension::ArrayLoad,
+        Extension::ArrayStore,
+        Extension::Call,
+        Extension::Ret,
+        Extension::Trap,
+        Extension::Send,
+        Extension::Recv,
+    ];
+
+    /// the extension with the given opcode, if there is one
+    pub fn from_opcode(opcode: isize) -> Option<Self> {
+        let index = usize::try_from(opcode.checked_sub(FIRST_EXTENSION_OPCODE)?).ok()?;
+        Self::ALL.get(index).copied()
+    }
+
+    /// the opcode of this extension
+    pub fn opcode(self) -> isize {
+        FIRST_EXTENSION_OPCODE + self as isize
+    }
+
+    /// the mnemonic of this extension, as used in disassembly listings and stats
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Extension::Random => "random",
+            Extension::Length => "length",
+            Extension::Substring => "substring",
+            Extension::CharCode => "charcode",
+            Extension::Array => "array",
+            Extension::ArrayLoad => "aload",
+            Extension::ArrayStore => "astore",
+            Extension::Call => "call",
+            Extension::Ret => "ret",
+            Extension::Trap => "trap",
+            Extension::Send => "send",
+            Extension::Recv => "recv",
+        }
+    }
+
+    /// how many values this extension pops
+    pub fn pops(self) -> usize {
+        match self {
+            Extension::Ret => 0,
+            Extension::Random
+            | Extension::Length
+            | Extension::Array
+            | Extension::Call
+            | Extension::Trap
+            | Extension::Recv => 1,
+            Extension::CharCode | Extension::ArrayLoad | Extension::Send => 2,
+            Extension::Substring | Extension::ArrayStore => 3,
+        }
+    }
+}
+
+impl fmt::Display for Extension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.mnemonic())
+    }
+}
+
+impl FromStr for Extension {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        (Self::ALL.into_iter())
+            .find(|extension| extension.mnemonic() == s)
+            .ok_or_else(|| format!("unknown extension {:?}", s))
+    }
+}
+
+// the pseudo-random number generator behind Random, which is splitmix64.
+// it doesn't need to be good enough for anything but games, just small, fast and the same everywhere
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Rng {
+    pub(crate) state: u64,
+}
+
+impl Rng {
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // a number from 0 up to but not including bound, without favoring the low numbers like a plain modulo would
+    pub(crate) fn below(&mut self, bound: u64) -> u64 {
+        let zone = u64::MAX - u64::MAX % bound;
+
+        loop {
+            let n = self.next_u64();
+            if n < zone {
+                return n % bound;
+            }
+        }
+    }
+}
+
+impl VMState {
+    /// executes an extension instruction, with the program counter already pointing past it
+    pub(crate) fn execute_extension(&mut self, extension: Extension) -> Result<(), ChickenError> {
+        match extension {
+            Extension::Random => self.random(),
+            Extension::Length => self.length(),
+            Extension::Substring => self.substring(),
+            Extension::CharCode => self.char_code(),
+            Extension::Array => self.array(),
+            Extension::ArrayLoad => self.array_load(),
+            Extension::ArrayStore => self.array_store(),
+            Extension::Call => self.call(),
+            Extension::Ret => self.ret(),
+            Extension::Trap => self.trap(),
+            Extension::Send => self.send(),
+            Extension::Recv => self.recv(),
+        }
+    }
+
+    /// top of the stack contains the upper bound, which is replaced with a random number below it
+    fn random(&mut self) -> Result<(), ChickenError> {
+        let val = self.pop();
+        let Some(bound) = val
+            .as_ref()
+            .and_then(|v| v.to_num_option())
+            .filter(|n| *n > 0)
+        else {
+            let err = self.error(
+                ErrorKind::Runtime,
+                format_args!("random needs a positive upper bound, found {:?}", val),
+            );
+            return Err(self.unpop(err, [val]));
+        };
+
+        let n = self.rng.below(bound as u64);
+        self.push(Value::Num(n as isize));
+        Ok(())
+    }
+
+    /// top of the stack contains the value to measure, which is replaced with its length
+    fn length(&mut self) -> Result<(), ChickenError> {
+        let val = self.pop().unwrap_or(Value::Undefined);
+        let length = match &val {
+            Value::Array(values) => values.len(),
+            val => string_of(val).chars().count(),
+        };
+        self.push(Value::Num(length as isize));
+        Ok(())
+    }
+
+    /// top of the stack contains the end index, then the start index, then the string to take part of
+    fn substring(&mut self) -> Result<(), ChickenError> {
+        let end = self.pop().unwrap_or(Value::Undefined);
+        let start = self.pop().unwrap_or(Value::Undefined);
+        let val = self.pop().unwrap_or(Value::Undefined);
+
+        let s = string_of(&val);
+        let length = s.chars().count();
+        let clamp =
+            |index: &Value| (index.to_num_option().unwrap_or(0).max(0) as usize).min(length);
+        let (start, end) = (clamp(&start), clamp(&end));
+
+        let substring: std::string::String = s
+            .chars()
+            .skip(start.min(end))
+            .take(start.abs_diff(end))
+            .collect();
+        self.push(Value::String(substring.into()));
+        Ok(())
+    }
+
+    /// top of the stack contains the index, then the string to look in
+    fn char_code(&mut self) -> Result<(), ChickenError> {
+        let index = self.pop().unwrap_or(Value::Undefined);
+        let val = self.pop().unwrap_or(Value::Undefined);
+
+        let code = index
+            .to_num_option()
+            .and_then(|n| usize::try_from(n).ok())
+            .and_then(|n| string_of(&val).chars().nth(n));
+
+        self.push(code.map_or(Value::NaN, |c| Value::Num(c as isize)));
+        Ok(())
+    }
+
+    /// top of the stack contains the length of the array to make
+    fn array(&mut self) -> Result<(), ChickenError> {
+        let val = self.pop();
+        let Some(length) = val
+            .as_ref()
+            .and_then(|v| v.to_num_option())
+            .and_then(|n| usize::try_from(n).ok())
+        else {
+            let err = self.error(
+                ErrorKind::Runtime,
+                format_args!("array needs a length, found {:?}", val),
+            );
+            return Err(self.unpop(err, [val]));
+        };
+
+        if let Err(err) = self.check_array_len(length) {
+            return Err(self.unpop(err, [val]));
+        }
+
+        let mut values = Vec::new();
+        if values.try_reserve_exact(length).is_err() {
+            let err = self.error(
+                ErrorKind::Runtime,
+                format_args!("out of memory making an array of {} values", length),
+            );
+            return Err(self.unpop(err, [val]));
+        }
+        values.resize(length, Value::Undefined);
+
+        self.push(Value::Array(Arc::new(values)));
+        Ok(())
+    }
+
+    /// top of the stack contains the index, then the array to load from
+    fn array_load(&mut self) -> Result<(), ChickenError> {
+        let index = self.pop();
+        let array = self.pop();
+
+        let value = match (&array, index.as_ref().and_then(|v| v.to_num_option())) {
+            (Some(Value::Array(values)), Some(n)) => {
+                usize::try_from(n).ok().and_then(|n| values.get(n)).cloned()
+            }
+            _ => None,
+        };
+
+        if value.is_none() && self.strict {
+            let err = self.error(
+                ErrorKind::Runtime,
+                format_args!("aload from index {:?} of {:?}", index, array),
+            );
+            return Err(self.unpop(err, [array, index]));
+        }
+
+        self.push(value.unwrap_or(Value::Undefined));
+        Ok(())
+    }
+
+    /// top of the stack contains the address of the array, then the index to store to, then the value to store
+    fn array_store(&mut self) -> Result<(), ChickenError> {
+        let address = self.pop();
+        let index = self.pop();
+        let value = self.pop();
+
+        let Some(addr) = address
+            .as_ref()
+            .and_then(|v| v.to_num_option())
+            .and_then(|n| usize::try_from(n).ok())
+        else {
+            let err = self.error(
+                ErrorKind::Runtime,
+                format_args!("invalid address {:?}", address),
+            );
+            return Err(self.unpop(err, [value, index, address]));
+        };
+
+        if let Err(err) = self.resolve_lazy_input(addr) {
+            return Err(self.unpop(err, [value, index, address]));
+        }
+
+        let Some(n) = index
+            .as_ref()
+            .and_then(|v| v.to_num_option())
+            .and_then(|n| usize::try_from(n).ok())
+        else {
+            let err = self.error(
+                ErrorKind::Runtime,
+                format_args!("astore at invalid index {:?}", index),
+            );
+            return Err(self.unpop(err, [value, index, address]));
+        };
+
+        if let Err(err) = self.check_array_len(n.saturating_add(1)) {
+            return Err(self.unpop(err, [value, index, address]));
+        }
+
+        let Some(Value::Array(values)) = self.stack.get_mut(addr) else {
+            let err = self.error(
+                ErrorKind::Runtime,
+                format_args!(
+                    "astore into address {}, which holds {:?} instead of an array",
+                    addr,
+                    self.stack.get(addr).unwrap_or(&Value::Undefined)
+                ),
+            );
+            return Err(self.unpop(err, [value, index, address]));
+        };
+
+        // this only copies the array if something else still has it, like a fork of the VM
+        let values = Arc::make_mut(values);
+
+        // storing past the end grows the array like a javascript array, filling the gap with undefined
+        if n < values.len() || values.try_reserve(n + 1 - values.len()).is_ok() {
+            if n >= values.len() {
+                values.resize(n + 1, Value::Undefined);
+            }
+            values[n] = value.unwrap_or(Value::Undefined);
+            return Ok(());
+        }
+
+        let err = self.error(
+            ErrorKind::Runtime,
+            format_args!("out of memory storing to index {} of an array", n),
+        );
+        Err(self.unpop(err, [value, index, address]))
+    }
+
+    /// top of the stack is the relative offset to call
+    fn call(&mut self) -> Result<(), ChickenError> {
+        let val = self.pop();
+        let Some(rel) = val.as_ref().and_then(|v| v.to_num_option()) else {
+            let err = self.error(
+                ErrorKind::Runtime,
+                format_args!("invalid relative address {:?}", val),
+            );
+            return Err(self.unpop(err, [val]));
+        };
+
+        // the return stack is held to the stack limit too, so runaway recursion stops instead of using up all the memory
+        if let Some(max_stack) = self.limits.max_stack {
+            if self.return_stack.len() >= max_stack {
+                let err = self.error(
+                    ErrorKind::Limit,
+                    format_args!("return stack limit of {} exceeded", max_stack),
+                );
+                return Err(self.unpop(err, [val]));
+            }
+        }
+
+        let Some(program_counter) = self.program_counter.checked_add_signed(rel) else {
+            let err = self.error(
+                ErrorKind::Runtime,
+                format_args!("call to relative addr {:?} overflowed", val),
+            );
+            return Err(self.unpop(err, [val]));
+        };
+
+        self.return_stack.push(self.program_counter);
+        self.last_jump = Some((self.program_counter - 1, rel));
+        self.program_counter = program_counter;
+        Ok(())
+    }
+
+    /// takes no arguments, returning to where the return stack says to
+    fn ret(&mut self) -> Result<(), ChickenError> {
+        let Some(program_counter) = self.return_stack.pop() else {
+            return Err(self.error(
+                ErrorKind::Runtime,
+                format_args!("ret without a call to return from"),
+            ));
+        };
+
+        let rel = program_counter as isize - self.program_counter as isize;
+        self.last_jump = Some((self.program_counter - 1, rel));
+        self.program_counter = program_counter;
+        Ok(())
+    }
+
+    /// top of the stack is the address of the slot holding the trap handler
+    fn trap(&mut self) -> Result<(), ChickenError> {
+        let val = self.pop();
+        let Some(slot) = val
+            .as_ref()
+            .and_then(|v| v.to_num_option())
+            .and_then(|n| usize::try_from(n).ok())
+        else {
+            let err = self.error(
+                ErrorKind::Runtime,
+                format_args!("invalid trap slot {:?}", val),
+            );
+            return Err(self.unpop(err, [val]));
+        };
+
+        self.trap_slot = Some(slot);
+        Ok(())
+    }
+
+    /// the address of the stack slot holding the trap handler, if [Extension::Trap] has set one
+    pub fn trap_slot(&self) -> Option<usize> {
+        self.trap_slot
+    }
+
+    // jumps to the trap handler with the error's code pushed if the error can be trapped and there's a handler to jump to,
+    // otherwise hands the error back. the failed instruction has already put back what it popped
+    pub(crate) fn handle_trap(&mut self, err: ChickenError) -> Result<(), ChickenError> {
+        let Some(code) = err.kind.trap_code() else {
+            return Err(err);
+        };
+
+        let handler = (self.trap_slot)
+            .and_then(|slot| self.stack.get(slot))
+            .and_then(|v| v.to_num_option())
+            .and_then(|n| usize::try_from(n).ok());
+
+        match handler {
+            Some(handler) => {
+                log::debug!("trapped {:?}, jumping to {}", err.message, handler);
+                self.push(Value::Num(code));
+                self.program_counter = handler;
+                Ok(())
+            }
+            None => Err(err),
+        }
+    }
+
+    /// top of the stack is the number of the channel, then the value to send to it
+    fn send(&mut self) -> Result<(), ChickenError> {
+        let number = self.pop();
+        let value = self.pop();
+
+        match self.channel(number.as_ref()) {
+            Ok(channel) => {
+                channel.send(value.unwrap_or(Value::Undefined));
+                Ok(())
+            }
+            Err(err) => Err(self.unpop(err, [value, number])),
+        }
+    }
+
+    /// top of the stack is the number of the channel, which is replaced with what's received from it
+    fn recv(&mut self) -> Result<(), ChickenError> {
+        let number = self.pop();
+
+        let channel = match self.channel(number.as_ref()) {
+            Ok(channel) => channel,
+            Err(err) => return Err(self.unpop(err, [number])),
+        };
+
+        match channel.recv() {
+            Some(value) => self.push(value),
+            // nothing to receive yet, so the number goes back and the receive runs again next step
+            None => {
+                self.push(number.unwrap_or(Value::Undefined));
+                self.program_counter -= 1;
+                self.waiting = true;
+                std::thread::yield_now();
+            }
+        }
+
+        Ok(())
+    }
+
+    // the channel bound to a number with VMBuilder::channel
+    fn channel(&self, number: Option<&Value>) -> Result<Channel, ChickenError> {
+        number
+            .and_then(|v| v.to_num_option())
+            .and_then(|n| self.channels.get(&n))
+            .cloned()
+            .ok_or_else(|| self.error(ErrorKind::Runtime, format_args!("no channel {:?}", number)))
+    }
+
+    /// whether the program is waiting on an [Extension::Recv] from a channel that nothing's been sent to yet.
+    /// running it more just retries the receive, so it's worth running other VMs until one of them sends something
+    pub fn is_waiting(&self) -> bool {
+        self.waiting
+    }
+
+    /// the return stack of the [Extension::Call] instruction, with the address the last Call returns to on top
+    pub fn return_stack(&self) -> &[usize] {
+        &self.return_stack
+    }
+
+    // arrays are held to the stack limit too, so they can't be used to get around it
+    fn check_array_len(&self, length: usize) -> Result<(), ChickenError> {
+        match self.limits.max_stack {
+            Some(max_stack) if length > max_stack => Err(self.error(
+                ErrorKind::Limit,
+                format_args!(
+                    "array of {} values is bigger than the stack limit of {}",
+                    length, max_stack
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+// a value as a string, converting anything that isn't one like javascript would
+fn string_of(value: &Value) -> Cow<'_, str> {
+    match value {
+        Value::String(s) => Cow::Borrowed(s),
+        value => Cow::Owned(value.to_string()),
+    }
+}
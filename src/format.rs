@@ -0,0 +1,20 @@
+use crate::chicken_lines;
+
+/// rewrites Chicken code into its canonical form, with words separated by single spaces and no whitespace at the start or end of lines.
+/// anything that isn't whitespace is kept as is, so the program always stays the same.
+/// lines are never added or removed, since every line is an opcode, so whether the file ends with a newline is left alone too,
+/// but they always end up ending with `\n`
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::format_chicken;
+///
+/// assert_eq!(format_chicken("  chicken\tchicken  \r\n\nchicken"), "chicken chicken\n\nchicken");
+/// ```
+pub fn format_chicken<T: AsRef<str>>(chicken: T) -> String {
+    chicken_lines(chicken.as_ref())
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
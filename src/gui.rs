@@ -0,0 +1,243 @@
+use crate::{ChickenError, Instruction, Program, StepInfo, VMBuilder, VMState};
+use eframe::egui;
+use std::collections::BTreeSet;
+
+/// how many instructions are run per frame while continuing, so the window stays responsive while a program runs
+const STEPS_PER_FRAME: usize = 10_000;
+
+/// a graphical debugger for Chicken programs, with a stack table, disassembly, breakpoints and step/continue buttons
+struct Debugger {
+    make_builder: Box<dyn Fn() -> VMBuilder>,
+    vm: VMState,
+    last_step: Option<StepInfo>,
+    disassembly: Vec<(usize, Instruction)>,
+    breakpoints: BTreeSet<usize>,
+    running: bool,
+    result: Option<Result<String, ChickenError>>,
+}
+
+impl Debugger {
+    fn new(make_builder: Box<dyn Fn() -> VMBuilder>) -> Self {
+        let vm = make_builder().build();
+        let disassembly = disassemble(&vm);
+
+        Self {
+            make_builder,
+            vm,
+            last_step: None,
+            disassembly,
+            breakpoints: BTreeSet::new(),
+            running: false,
+            result: None,
+        }
+    }
+
+    fn restart(&mut self) {
+        let breakpoints = std::mem::take(&mut self.breakpoints);
+        *self = Self::new(std::mem::replace(
+            &mut self.make_builder,
+            Box::new(|| VMBuilder::from_opcodes([])),
+        ));
+        self.breakpoints = breakpoints;
+    }
+
+    /// runs a single instruction, finishing the run once the program exits
+    fn step(&mut self) {
+        if self.result.is_some() {
+            return;
+        }
+
+        match self.vm.step_info() {
+            Ok(info) => {
+                // the program changed, so the old disassembly is wrong now
+                if info.as_ref().is_some_and(|i| i.self_modification.is_some()) {
+                    self.disassembly = disassemble(&self.vm);
+                }
+
+                self.last_step = info;
+            }
+            Err(err) => self.result = Some(Err(err)),
+        }
+
+        if self.result.is_none() && self.vm.exited {
+            self.result = Some(self.vm.run());
+        }
+
+        if self.result.is_some() {
+            self.running = false;
+        }
+    }
+
+    fn run_until_breakpoint(&mut self) {
+        for _ in 0..STEPS_PER_FRAME {
+            self.step();
+
+            if !self.running || self.breakpoints.contains(&self.vm.program_counter) {
+                self.running = false;
+                return;
+            }
+        }
+    }
+}
+
+/// disassembles the program as it currently is on the stack, with the addresses of the instructions
+fn disassemble(vm: &VMState) -> Vec<(usize, Instruction)> {
+    let program = Program::from_opcodes(
+        vm.program()
+            .iter()
+            .map(|v| v.to_num_option().unwrap_or_default())
+            .collect::<Vec<_>>(),
+    );
+
+    program
+        .instructions()
+        .into_iter()
+        .map(|(index, instruction)| (index + vm.program_start, instruction))
+        .collect()
+}
+
+impl eframe::App for Debugger {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if self.running {
+            self.run_until_breakpoint();
+            ctx.request_repaint();
+        }
+
+        egui::TopBottomPanel::top("controls").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let finished = self.result.is_some();
+
+                if ui
+                    .add_enabled(!finished, egui::Button::new("step"))
+                    .clicked()
+                {
+                    self.step();
+                }
+
+                match self.running {
+                    true if ui.button("pause").clicked() => self.running = false,
+                    false
+                        if ui
+                            .add_enabled(!finished, egui::Button::new("continue"))
+                            .clicked() =>
+                    {
+                        // step once first so continuing from a breakpoint doesn't stop on it straight away
+                        self.running = true;
+                        self.step();
+                    }
+                    _ => (),
+                }
+
+                if ui.button("restart").clicked() {
+                    self.restart();
+                }
+
+                // the instruction that failed can be skipped to see what the rest of the program does
+                let failed = matches!(self.result, Some(Err(_)));
+                if ui
+                    .add_enabled(failed, egui::Button::new("skip instruction"))
+                    .clicked()
+                    && self.vm.resume_after_error()
+                {
+                    self.result = None;
+                }
+
+                ui.separator();
+                ui.label(format!(
+                    "pc {}, {} steps",
+                    self.vm.program_counter,
+                    self.vm.stats().steps
+                ));
+            });
+
+            match &self.result {
+                Some(Ok(output)) => {
+                    ui.label(format!("output: {}", output));
+                }
+                Some(Err(err)) => {
+                    ui.colored_label(egui::Color32::RED, format!("error: {}", err.message));
+                }
+                None => (),
+            }
+
+            if let Some(info) = &self.last_step {
+                ui.label(format!(
+                    "last step: {} at pc {}, popped {:?}, pushed {:?}",
+                    info.instruction
+                        .map_or_else(|| "invalid".to_string(), |i| i.to_string()),
+                    info.program_counter,
+                    info.popped,
+                    info.pushed
+                ));
+
+                if let Some(modification) = &info.self_modification {
+                    ui.colored_label(egui::Color32::YELLOW, modification.to_string());
+                }
+            }
+        });
+
+        egui::SidePanel::left("disassembly").show(ctx, |ui| {
+            ui.heading("disassembly");
+            ui.label("click an instruction to toggle a breakpoint on it");
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (address, instruction) in self.disassembly.iter() {
+                    let breakpoint = self.breakpoints.contains(address);
+                    let current = *address == self.vm.program_counter;
+
+                    let text = format!(
+                        "{} {:>5}: {}",
+                        if breakpoint { "●" } else { " " },
+                        address,
+                        instruction
+                    );
+                    let text = match current {
+                        true => egui::RichText::new(text)
+                            .monospace()
+                            .strong()
+                            .background_color(egui::Color32::from_rgb(255, 224, 138))
+                            .color(egui::Color32::BLACK),
+                        false => egui::RichText::new(text).monospace(),
+                    };
+
+                    if ui.selectable_label(false, text).clicked()
+                        && !self.breakpoints.remove(address)
+                    {
+                        self.breakpoints.insert(*address);
+                    }
+                }
+            });
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("stack");
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                egui::Grid::new("stack").striped(true).show(ui, |ui| {
+                    ui.strong("address");
+                    ui.strong("value");
+                    ui.end_row();
+
+                    for (address, value) in self.vm.stack.iter().enumerate().rev() {
+                        ui.monospace(address.to_string());
+                        ui.monospace(format!("{:?}", value));
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+    }
+}
+
+/// opens a window with a graphical debugger for the VM made by the given builder.
+/// the builder is called again whenever the program is restarted
+pub fn run_gui_debugger<F: Fn() -> VMBuilder + 'static>(
+    title: &str,
+    make_builder: F,
+) -> eframe::Result<()> {
+    eframe::run_native(
+        title,
+        eframe::NativeOptions::default(),
+        Box::new(|_cc| Ok(Box::new(Debugger::new(Box::new(make_builder))))),
+    )
+}
@@ -0,0 +1,71 @@
+use crate::{dispatch::Op, ChickenError, ErrorKind, Extension, VMState, Value};
+use std::{collections::BTreeMap, sync::Arc};
+
+/// a Rust function that chicken programs can call, bound to an opcode with [VMBuilder::host_fn](crate::VMBuilder::host_fn).
+/// it's called with the values it pops, bottom first, and returns the values to push, or an error message to stop the program with
+pub type HostFunction = dyn Fn(Vec<Value>) -> Result<Vec<Value>, std::string::String> + Send + Sync;
+
+// a host function along with how many values it pops
+#[derive(Clone)]
+pub(crate) struct HostFn {
+    pub(crate) arity: usize,
+    pub(crate) function: Arc<HostFunction>,
+}
+
+// every host function of a VM, keyed by opcode. forks share them
+pub(crate) type HostFns = Arc<BTreeMap<isize, HostFn>>;
+
+impl VMState {
+    // decodes an opcode, taking host functions and extensions into account.
+    // a host function bound to the opcode of an extension replaces it
+    pub(crate) fn decode(&self, value: Option<&Value>) -> Op {
+        match Op::decode(value) {
+            Op::Push(n)
+                if !self.host_fns.is_empty() && self.host_fns.contains_key(&n.wrapping_add(10)) =>
+            {
+                Op::Host(n.wrapping_add(10))
+            }
+            Op::Push(n) if self.extensions => match Extension::from_opcode(n.wrapping_add(10)) {
+                Some(extension) => Op::Extension(extension),
+                None => Op::Push(n),
+            },
+            op => op,
+        }
+    }
+
+    // how many values the host function bound to an opcode pops
+    pub(crate) fn host_fn_arity(&self, opcode: isize) -> usize {
+        self.host_fns
+            .get(&opcode)
+            .map_or(0, |host_fn| host_fn.arity)
+    }
+
+    // pops the arguments of a host function, calls it and pushes what it returns.
+    // if it fails, its arguments are put back so the VM is left as it was before the call
+    pub(crate) fn call_host_fn(&mut self, opcode: isize) -> Result<(), ChickenError> {
+        let host_fn = self.host_fns[&opcode].clone();
+
+        let mut popped: Vec<Option<Value>> = (0..host_fn.arity).map(|_| self.pop()).collect();
+        popped.reverse();
+
+        let args = (popped.iter())
+            .map(|value| value.clone().unwrap_or(Value::Undefined))
+            .collect();
+
+        match (host_fn.function)(args) {
+            Ok(results) => {
+                for value in results {
+                    self.push(value);
+                }
+                Ok(())
+            }
+            Err(message) => {
+                let err = self.error(
+                    ErrorKind::Runtime,
+                    format_args!("host function {} failed: {}", opcode, message),
+                );
+                Err(self.unpop(err, popped))
+            }
+        }
+    }
+}
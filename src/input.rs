@@ -0,0 +1,106 @@
+use crate::Value;
+use std::{
+    io::{self, Read},
+    path::PathBuf,
+};
+
+/// somewhere the input to a program can come from, given to [crate::VMBuilder::input_source].
+/// it's only read the first time the program reads its input, so sources like [StdinInput] don't block programs that never need it
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::{EnvInput, InputSource, StaticInput, Value, VMBuilder};
+///
+/// let result = VMBuilder::from_opcodes([11, 6, 0])
+///     .input_source(StaticInput::from("meow"))
+///     .build()
+///     .run();
+/// assert_eq!(result, Ok("meow".to_string()));
+///
+/// // closures work too
+/// let result = VMBuilder::from_opcodes([11, 6, 0])
+///     .input_source(|| Ok(Value::from("woof")))
+///     .build()
+///     .run();
+/// assert_eq!(result, Ok("woof".to_string()));
+///
+/// assert!(EnvInput::new("CHICKEN_SURELY_UNSET_VARIABLE").read().is_err());
+/// ```
+pub trait InputSource: Send {
+    /// produces the input
+    fn read(&mut self) -> io::Result<Value>;
+}
+
+impl<F: FnMut() -> io::Result<Value> + Send> InputSource for F {
+    fn read(&mut self) -> io::Result<Value> {
+        self()
+    }
+}
+
+/// an input that's known ahead of time
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticInput(pub Value);
+
+impl<T: Into<Value>> From<T> for StaticInput {
+    fn from(value: T) -> Self {
+        Self(value.into())
+    }
+}
+
+impl InputSource for StaticInput {
+    fn read(&mut self) -> io::Result<Value> {
+        Ok(self.0.clone())
+    }
+}
+
+/// reads everything from stdin as the input
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdinInput;
+
+impl InputSource for StdinInput {
+    fn read(&mut self) -> io::Result<Value> {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input)?;
+        Ok(input.into())
+    }
+}
+
+/// reads the contents of a file as the input
+#[derive(Debug, Clone)]
+pub struct FileInput(pub PathBuf);
+
+impl FileInput {
+    /// creates a source that reads the given file
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self(path.into())
+    }
+}
+
+impl InputSource for FileInput {
+    fn read(&mut self) -> io::Result<Value> {
+        std::fs::read_to_string(&self.0).map(Value::from)
+    }
+}
+
+/// uses the value of an environment variable as the input, failing if it isn't set
+#[derive(Debug, Clone)]
+pub struct EnvInput(pub String);
+
+impl EnvInput {
+    /// creates a source that reads the given environment variable
+    pub fn new<T: Into<String>>(name: T) -> Self {
+        Self(name.into())
+    }
+}
+
+impl InputSource for EnvInput {
+    fn read(&mut self) -> io::Result<Value> {
+        std::env::var(&self.0).map(Value::from).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("environment variable {}: {}", self.0, err),
+            )
+        })
+    }
+}
@@ -0,0 +1,196 @@
+use crate::Value;
+use std::{
+    collections::VecDeque,
+    io::{self, stdin, stdout, BufRead, Read, Write},
+    sync::{Arc, Mutex},
+};
+
+/// everything a VM reads and writes outside of its own stack, given to [VMBuilder::io](crate::VMBuilder::io).
+/// this covers the commands read by the debugger and everything it writes, the input of a program that's read the first time
+/// the program reads it, and the output of a program once it exits, so a VM with a [TestIo] never touches the terminal
+pub trait Io: Send {
+    /// reads a line of debugger commands, including its line ending, or None once there's nothing more to read
+    fn read_line(&mut self) -> io::Result<Option<String>>;
+
+    /// writes what the debugger shows
+    fn write(&mut self, text: &str) -> io::Result<()>;
+
+    /// reads the first input of a program that wasn't given one, the first time the program reads it
+    fn read_input(&mut self) -> io::Result<Value>;
+
+    /// writes the output of a program, once it's exited
+    fn write_output(&mut self, output: &str) -> io::Result<()>;
+
+    /// whether the commands are typed by someone who can already see them. the debugger echoes commands that aren't,
+    /// so what it writes ends up being a transcript of the whole session
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+/// the terminal, which is what a VM uses without [VMBuilder::io](crate::VMBuilder::io).
+/// the input is everything on stdin, and the output is printed to stdout
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdIo;
+
+impl Io for StdIo {
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        match stdin().lock().read_line(&mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some(line)),
+        }
+    }
+
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        let mut stdout = stdout().lock();
+        stdout.write_all(text.as_bytes())?;
+        stdout.flush()
+    }
+
+    fn read_input(&mut self) -> io::Result<Value> {
+        let mut input = String::new();
+        stdin().read_to_string(&mut input)?;
+        Ok(input.into())
+    }
+
+    fn write_output(&mut self, output: &str) -> io::Result<()> {
+        self.write(output)
+    }
+
+    fn is_interactive(&self) -> bool {
+        true
+    }
+}
+
+/// an [Io] that reads from and writes to memory, for testing things built on chicken without a terminal.
+/// clones share everything, so one can be given to a VM while another is kept to look at what it wrote
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::{TestIo, VMBuilder};
+///
+/// let io = TestIo::new().input("meow").commands("s\nq\n");
+/// let mut vm = VMBuilder::from_opcodes([11, 6, 0]).io(io.clone()).build();
+///
+/// assert_eq!(vm.run(), Ok("meow".to_string()));
+/// assert_eq!(io.output(), "meow");
+///
+/// // the debugger isn't on, so it didn't read any of the commands
+/// assert_eq!(io.debug_output(), "");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TestIo(Arc<Mutex<TestIoState>>);
+
+#[derive(Debug, Default)]
+struct TestIoState {
+    commands: VecDeque<String>,
+    input: Option<Value>,
+    debug_output: String,
+    output: String,
+}
+
+impl TestIo {
+    /// makes a TestIo without any commands or input
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// adds lines of commands for the debugger to read
+    pub fn commands(self, script: &str) -> Self {
+        (self.state().commands).extend(script.split_inclusive('\n').map(String::from));
+        self
+    }
+
+    /// sets the input read by [Io::read_input]. without one, reading the input fails
+    pub fn input<T: Into<Value>>(self, input: T) -> Self {
+        self.state().input = Some(input.into());
+        self
+    }
+
+    /// everything the debugger has written so far, including the commands it read
+    pub fn debug_output(&self) -> String {
+        self.state().debug_output.clone()
+    }
+
+    /// everything the program has output so far
+    pub fn output(&self) -> String {
+        self.state().output.clone()
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, TestIoState> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl Io for TestIo {
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        Ok(self.state().commands.pop_front())
+    }
+
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        self.state().debug_output.push_str(text);
+        Ok(())
+    }
+
+    fn read_input(&mut self) -> io::Result<Value> {
+        self.state().input.clone().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the TestIo doesn't have an input",
+            )
+        })
+    }
+
+    fn write_output(&mut self, output: &str) -> io::Result<()> {
+        self.state().output.push_str(output);
+        Ok(())
+    }
+}
+
+// the debugger reading from and writing to the given reader and writer, with None meaning the terminal,
+// see [VMBuilder::debug_io](crate::VMBuilder::debug_io)
+#[derive(Default)]
+pub(crate) struct DebugIo {
+    pub(crate) input: Option<Box<dyn BufRead + Send>>,
+    pub(crate) output: Option<Box<dyn Write + Send>>,
+}
+
+impl Io for DebugIo {
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let Some(input) = self.input.as_mut() else {
+            return StdIo.read_line();
+        };
+
+        let mut line = String::new();
+        match input.read_line(&mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some(line)),
+        }
+    }
+
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        let Some(output) = self.output.as_mut() else {
+            return StdIo.write(text);
+        };
+
+        output.write_all(text.as_bytes())?;
+        output.flush()
+    }
+
+    fn read_input(&mut self) -> io::Result<Value> {
+        StdIo.read_input()
+    }
+
+    fn write_output(&mut self, output: &str) -> io::Result<()> {
+        StdIo.write_output(output)
+    }
+
+    fn is_interactive(&self) -> bool {
+        self.input.is_none()
+    }
+}
+
+// an Io shared between a VM and its debugger
+pub(crate) type SharedIo = Arc<Mutex<dyn Io>>;
@@ -0,0 +1,185 @@
+use crate::Value;
+use std::fmt;
+
+/// what kind of [Value] a value is, without what's in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueKind {
+    /// a [Value::Num]
+    Num,
+
+    /// a [Value::String]
+    String,
+
+    /// a [Value::Ptr]
+    Ptr,
+
+    /// [Value::True] or [Value::False]
+    Bool,
+
+    /// [Value::Undefined]
+    Undefined,
+
+    /// [Value::NaN]
+    NaN,
+
+    /// a [Value::Array]
+    Array,
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ValueKind::Num => "number",
+            ValueKind::String => "string",
+            ValueKind::Ptr => "pointer",
+            ValueKind::Bool => "boolean",
+            ValueKind::Undefined => "undefined",
+            ValueKind::NaN => "NaN",
+            ValueKind::Array => "array",
+        })
+    }
+}
+
+/// the error from converting a [Value] into a Rust type when it's the wrong kind of value.
+/// unlike [Value::to_num], these conversions never coerce one kind of value into another
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValueKindError {
+    /// the kind of value that was needed
+    pub expected: ValueKind,
+
+    /// the kind of value that was found instead
+    pub found: ValueKind,
+}
+
+impl fmt::Display for ValueKindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected a {}, found {}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for ValueKindError {}
+
+impl Value {
+    /// what kind of value this is
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{Value, ValueKind};
+    ///
+    /// assert_eq!(Value::Num(1).kind(), ValueKind::Num);
+    /// assert_eq!(Value::False.kind(), ValueKind::Bool);
+    /// ```
+    pub fn kind(&self) -> ValueKind {
+        match self {
+            Value::Num(_) => ValueKind::Num,
+            Value::String(_) => ValueKind::String,
+            Value::Ptr(_) => ValueKind::Ptr,
+            Value::True | Value::False => ValueKind::Bool,
+            Value::Undefined => ValueKind::Undefined,
+            Value::NaN => ValueKind::NaN,
+            Value::Array(_) => ValueKind::Array,
+        }
+    }
+
+    /// the number in this value if it's a [Value::Num], without converting anything else to a number like [Value::to_num_option] does
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::Value;
+    ///
+    /// assert_eq!(Value::Num(3).as_num(), Some(3));
+    /// assert_eq!(Value::from("3").as_num(), None);
+    /// assert_eq!(Value::from("3").to_num_option(), Some(3));
+    /// ```
+    pub fn as_num(&self) -> Option<isize> {
+        match self {
+            Value::Num(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// the string in this value if it's a [Value::String]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    /// the address in this value if it's a [Value::Ptr]
+    pub fn as_ptr(&self) -> Option<usize> {
+        match self {
+            Value::Ptr(p) => Some(*p),
+            _ => None,
+        }
+    }
+
+    /// the values in this value if it's a [Value::Array]
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// whether this value is [Value::True] or [Value::False], or None for anything else. see [Value::is_truthy] for truthiness
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::True => Some(true),
+            Value::False => Some(false),
+            _ => None,
+        }
+    }
+
+    fn kind_error(&self, expected: ValueKind) -> ValueKindError {
+        ValueKindError {
+            expected,
+            found: self.kind(),
+        }
+    }
+}
+
+/// gets the number out of a [Value::Num]
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::{Value, ValueKind};
+///
+/// assert_eq!(isize::try_from(Value::Num(3)), Ok(3));
+/// assert_eq!(isize::try_from(Value::NaN).unwrap_err().found, ValueKind::NaN);
+/// ```
+impl TryFrom<Value> for isize {
+    type Error = ValueKindError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value
+            .as_num()
+            .ok_or_else(|| value.kind_error(ValueKind::Num))
+    }
+}
+
+/// gets the string out of a [Value::String], without copying it if nothing else is using it
+impl TryFrom<Value> for String {
+    type Error = ValueKindError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::String(s) => Ok(s.into()),
+            other => Err(other.kind_error(ValueKind::String)),
+        }
+    }
+}
+
+/// gets the boolean out of [Value::True] or [Value::False]
+impl TryFrom<Value> for bool {
+    type Error = ValueKindError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value
+            .as_bool()
+            .ok_or_else(|| value.kind_error(ValueKind::Bool))
+    }
+}
@@ -1,18 +1,185 @@
-#![feature(mixed_integer_ops)]
-
+mod alignment;
+mod cancel;
+mod capture;
+mod channel;
+mod checkpoint;
+mod color;
+mod conformance;
+mod cost;
+mod coverage;
+mod debugger;
+mod diff;
+mod disasm;
+mod dispatch;
+mod entities;
+mod escape;
+mod events;
+mod exit;
+mod explain;
+mod expr;
+mod extensions;
+mod format;
+#[cfg(feature = "gui")]
+mod gui;
+mod host;
+mod input;
+mod io;
+mod kind;
+mod limits;
+mod link;
+mod lint;
+mod loops;
+mod metadata;
+mod minimize;
+mod number;
+mod observer;
+mod optimize;
+mod postprocess;
+mod profile;
+mod program;
+mod progress;
+mod region;
+mod replay;
+mod report;
+mod rope;
+mod snapshot;
+mod sourcemap;
+mod stats;
 #[cfg(test)]
 mod test;
+pub mod testing;
+mod visualize;
+mod warning;
+
+pub use alignment::*;
+pub use cancel::*;
+pub use capture::*;
+pub use channel::*;
+pub use color::*;
+pub use conformance::*;
+pub use cost::*;
+pub use coverage::*;
+pub use debugger::*;
+pub use diff::*;
+pub use disasm::*;
+pub use entities::*;
+pub use escape::*;
+pub use events::*;
+pub use exit::*;
+pub use explain::*;
+pub use expr::*;
+pub use extensions::*;
+pub use format::*;
+#[cfg(feature = "gui")]
+pub use gui::*;
+pub use host::*;
+pub use input::*;
+pub use io::*;
+pub use kind::*;
+pub use limits::*;
+pub use link::*;
+pub use lint::*;
+pub use loops::*;
+pub use metadata::*;
+pub use number::*;
+pub use observer::*;
+pub use postprocess::*;
+pub use profile::*;
+pub use program::*;
+pub use progress::*;
+pub use region::*;
+pub use replay::*;
+pub use report::*;
+pub use rope::*;
+pub use snapshot::*;
+pub use sourcemap::*;
+pub use stats::*;
+pub use visualize::*;
+pub use warning::*;
+
+/// reads a Chicken program at compile time, expanding to an array of its opcodes.
+/// the path is relative to the root of the crate using the macro
+#[cfg(feature = "macros")]
+pub use chicken_macros::chicken;
+
+/// embeds a Chicken program file into the binary as a [Program], parsing it at compile time.
+/// the path is relative to the root of the crate using the macro
+///
+/// # Example
+///
+/// ```rust
+/// # #[cfg(feature = "macros")] {
+/// use chicken::{include_chicken, VMBuilder};
+///
+/// let program = include_chicken!("examples/helloworld.chicken");
+/// assert_eq!(VMBuilder::from_program(program).build().run(), Ok("Hello world".to_string()));
+/// # }
+/// ```
+#[cfg(feature = "macros")]
+#[macro_export]
+macro_rules! include_chicken {
+    ($path:literal) => {
+        $crate::Program::from_opcodes(&$crate::chicken!($path)[..])
+    };
+}
 
 use colored::*;
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 use std::{
-    cmp::PartialEq,
+    cmp::{Ordering, PartialEq},
+    collections::{BTreeMap, BTreeSet},
     fmt,
-    io::{stdin, stdout, Read, Write},
-    ops::{Add, Mul, Sub},
+    io::{BufRead, Read, Write},
+    ops::{Add, Div, Mul, Neg, Range, Rem, Sub},
+    sync::{Arc, Mutex, OnceLock},
+    task::Poll,
+    time::Instant,
 };
 
+use checkpoint::Checkpointer;
+use dispatch::Op;
 use Value::*;
 
+/// how many of the most recent program counters are kept for [ChickenError::recent_program_counters]
+const PC_HISTORY_LEN: usize = 32;
+
+/// the address of the input that [VMBuilder::lazy_input] fills in
+const LAZY_INPUT_ADDRESS: usize = 1;
+
+/// how many instructions [VMState::poll] runs between checks of the clock
+const POLL_INTERVAL: u64 = 1024;
+
+/// produces the input for [VMBuilder::lazy_input]
+type LazyInput = Box<dyn FnOnce() -> std::io::Result<Value> + Send>;
+
+/// a lazy input shared between a VM and its forks, so it's only ever read once
+struct SharedInput {
+    source: Mutex<Option<LazyInput>>,
+    value: OnceLock<Result<Value, std::string::String>>,
+}
+
+impl SharedInput {
+    fn new(source: LazyInput) -> Self {
+        Self {
+            source: Mutex::new(Some(source)),
+            value: OnceLock::new(),
+        }
+    }
+
+    /// reads the input the first time this is called, returning the same value every time after that
+    fn read(&self) -> &Result<Value, std::string::String> {
+        self.value
+            .get_or_init(|| match self.source.lock().unwrap().take() {
+                Some(source) => source().map_err(|err| err.to_string()),
+                None => Ok(Undefined),
+            })
+    }
+}
+
 // instructions
 const EXIT: isize = 0;
 const CHICKEN: isize = 1;
@@ -32,7 +199,7 @@ pub enum Value {
     Num(isize),
 
     /// a string
-    String(std::string::String),
+    String(Rope),
 
     /// a pointer to some area of the stack
     Ptr(usize),
@@ -48,17 +215,40 @@ pub enum Value {
 
     /// not a number
     NaN,
+
+    /// an array of values, which is only made by the [Extension::Array] instruction.
+    /// arrays are copied when they're changed while something else still has them, so they act like values instead of references
+    Array(Arc<Vec<Value>>),
 }
 
+// values are kept small so the stack stays dense: numbers and pointers are stored inline, and strings are
+// reference counted ropes stored outside of the stack. this keeps a value at two words instead of the four a String would need
+const _: () = assert!(std::mem::size_of::<Value>() <= 2 * std::mem::size_of::<usize>());
+
 impl Value {
+    /// converts this Value into a string, without copying it if it already is one
+    fn into_rope(self) -> Rope {
+        match self {
+            String(s) => s,
+            other => other.to_string().into(),
+        }
+    }
+
+    /// converts this Value into a string, sharing it if it already is one
+    fn to_rope(&self) -> Rope {
+        match self {
+            String(s) => s.clone(),
+            other => other.to_string().into(),
+        }
+    }
+
     /// tries to convert this Value into a [number](Value::Num) or [NaN](Value::NaN) if we can't
     pub fn to_num(&self) -> Self {
         match self {
             Num(n) => Num(*n),
-            String(s) => match s.parse::<isize>() {
-                Ok(n) => Num(n),
-                Err(_) => NaN,
-            },
+            String(s) => s.to_num().map_or(NaN, Num),
+            // like in javascript, an array is converted to a string first, so [5] is 5
+            Array(_) => self.to_rope().to_num().map_or(NaN, Num),
             True => Num(1),
             False => Num(0),
             _ => NaN,
@@ -83,6 +273,7 @@ impl Value {
             False => false,
             Undefined => false,
             NaN => false,
+            Array(_) => true,
         }
     }
 }
@@ -103,13 +294,13 @@ impl TryFrom<usize> for Value {
 
 impl From<std::string::String> for Value {
     fn from(s: std::string::String) -> Self {
-        String(s)
+        String(s.into())
     }
 }
 
 impl From<&str> for Value {
     fn from(s: &str) -> Self {
-        String(s.to_string())
+        String(s.into())
     }
 }
 
@@ -131,25 +322,170 @@ impl fmt::Display for Value {
             False => write!(f, "false"),
             Undefined => write!(f, "undefined"),
             NaN => write!(f, "NaN"),
+            // the values are separated by commas like in javascript, which leaves undefined out
+            Array(values) => {
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    if !matches!(value, Undefined) {
+                        write!(f, "{}", value)?;
+                    }
+                }
+                Ok(())
+            }
             _ => write!(f, "{:?}", self),
         }
     }
 }
 
+/// values are serialized like their JavaScript counterparts would be in JSON where possible,
+/// with pointers as `{"ptr": n}`, NaN as `{"nan": true}` and undefined as `null`
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Num(n) => serializer.serialize_i64(*n as i64),
+            String(s) => serializer.serialize_str(s),
+            Ptr(p) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("ptr", p)?;
+                map.end()
+            }
+            True => serializer.serialize_bool(true),
+            False => serializer.serialize_bool(false),
+            Undefined => serializer.serialize_unit(),
+            NaN => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("nan", &true)?;
+                map.end()
+            }
+            Array(values) => values.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ValueVisitor;
+
+        impl<'de> Visitor<'de> for ValueVisitor {
+            type Value = Value;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(
+                    f,
+                    "a number, string, boolean, null, array, {{\"ptr\": n}} or {{\"nan\": true}}"
+                )
+            }
+
+            fn visit_i64<E: de::Error>(self, n: i64) -> Result<Value, E> {
+                n.try_into().map(Num).map_err(E::custom)
+            }
+
+            fn visit_u64<E: de::Error>(self, n: u64) -> Result<Value, E> {
+                n.try_into().map(Num).map_err(E::custom)
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<Value, E> {
+                Ok(s.into())
+            }
+
+            fn visit_bool<E: de::Error>(self, b: bool) -> Result<Value, E> {
+                Ok(b.into())
+            }
+
+            fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+                Ok(Undefined)
+            }
+
+            fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+                Ok(Undefined)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+                let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(value) = seq.next_element()? {
+                    values.push(value);
+                }
+
+                Ok(Array(Arc::new(values)))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+                let value = match map.next_key::<std::string::String>()?.as_deref() {
+                    Some("ptr") => Ptr(map.next_value()?),
+                    Some("nan") => {
+                        map.next_value::<bool>()?;
+                        NaN
+                    }
+                    Some(key) => return Err(de::Error::unknown_field(key, &["ptr", "nan"])),
+                    None => return Err(de::Error::invalid_length(0, &self)),
+                };
+
+                Ok(value)
+            }
+        }
+
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+/// takes ownership of strings, so a string nothing else is using can be added onto in place
 impl Add for Value {
     type Output = Self;
 
     fn add(self, other: Self) -> Self {
+        // arrays are added like strings, since that's what javascript turns them into
+        if matches!(self, Array(_)) {
+            return String(self.into_rope()) + other;
+        } else if matches!(other, Array(_)) {
+            return self + String(other.into_rope());
+        }
+
+        // handle string conversion/concatenation if applicable
+        if let String(a) = self {
+            String(a.concat(other.into_rope()))
+        } else if let String(b) = other {
+            String(self.into_rope().concat(b))
+        } else {
+            &self + &other
+        }
+    }
+}
+
+/// the same as adding owned values, but strings are always shared instead of added onto
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::Value;
+///
+/// let a = Value::from("chicken");
+/// assert_eq!(&a + &Value::Num(1), Value::from("chicken1"));
+/// assert_eq!(&Value::Num(2) + &Value::True, Value::Num(3));
+/// assert_eq!(a, Value::from("chicken"));
+/// ```
+impl Add for &Value {
+    type Output = Value;
+
+    fn add(self, other: Self) -> Value {
+        // arrays are added like strings, since that's what javascript turns them into
+        if matches!(self, Array(_)) {
+            return &String(self.to_rope()) + other;
+        } else if matches!(other, Array(_)) {
+            return self + &String(other.to_rope());
+        }
+
         // handle string conversion/concatenation if applicable
         if let String(a) = self {
-            String(format!("{}{}", a, other))
+            String(a.clone().concat(other.to_rope()))
         } else if let String(b) = other {
-            String(format!("{}{}", self, b))
+            String(self.to_rope().concat(b.clone()))
         } else {
-            // no strings, just add
+            // no strings, just add. numbers wrap around instead of overflowing
             match self.to_num() {
                 Num(a) => match other.to_num() {
-                    Num(b) => Num(a + b),
+                    Num(b) => Num(a.wrapping_add(b)),
                     _ => NaN,
                 },
                 _ => NaN,
@@ -162,9 +498,17 @@ impl Sub for Value {
     type Output = Self;
 
     fn sub(self, other: Self) -> Self {
+        &self - &other
+    }
+}
+
+impl Sub for &Value {
+    type Output = Value;
+
+    fn sub(self, other: Self) -> Value {
         match self.to_num() {
             Num(a) => match other.to_num() {
-                Num(b) => Num(a - b),
+                Num(b) => Num(a.wrapping_sub(b)),
                 _ => NaN,
             },
             _ => NaN,
@@ -176,9 +520,17 @@ impl Mul for Value {
     type Output = Self;
 
     fn mul(self, other: Self) -> Self {
+        &self * &other
+    }
+}
+
+impl Mul for &Value {
+    type Output = Value;
+
+    fn mul(self, other: Self) -> Value {
         match self.to_num() {
             Num(a) => match other.to_num() {
-                Num(b) => Num(a * b),
+                Num(b) => Num(a.wrapping_mul(b)),
                 _ => NaN,
             },
             _ => NaN,
@@ -186,18 +538,113 @@ impl Mul for Value {
     }
 }
 
+impl Div for Value {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        &self / &other
+    }
+}
+
+/// divides the values as numbers. there are only whole numbers, so the result is rounded towards zero,
+/// and since there's no infinity, dividing by zero is NaN
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::Value;
+///
+/// assert_eq!(&Value::Num(7) / &Value::from("2"), Value::Num(3));
+/// assert_eq!(&Value::Num(-7) / &Value::Num(2), Value::Num(-3));
+/// assert_eq!(&Value::Num(7) / &Value::Num(0), Value::NaN);
+/// ```
+impl Div for &Value {
+    type Output = Value;
+
+    fn div(self, other: Self) -> Value {
+        match (self.to_num(), other.to_num()) {
+            (Num(_), Num(0)) => NaN,
+            (Num(a), Num(b)) => Num(a.wrapping_div(b)),
+            _ => NaN,
+        }
+    }
+}
+
+impl Rem for Value {
+    type Output = Self;
+
+    fn rem(self, other: Self) -> Self {
+        &self % &other
+    }
+}
+
+/// the remainder of dividing the values as numbers, which has the same sign as the left hand value like in javascript.
+/// the remainder of dividing by zero is NaN
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::Value;
+///
+/// assert_eq!(&Value::Num(-7) % &Value::Num(2), Value::Num(-1));
+/// assert_eq!(&Value::Num(7) % &Value::Num(0), Value::NaN);
+/// ```
+impl Rem for &Value {
+    type Output = Value;
+
+    fn rem(self, other: Self) -> Value {
+        match (self.to_num(), other.to_num()) {
+            (Num(_), Num(0)) => NaN,
+            (Num(a), Num(b)) => Num(a.wrapping_rem(b)),
+            _ => NaN,
+        }
+    }
+}
+
+impl Neg for Value {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        -&self
+    }
+}
+
+/// negates the value as a number
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::Value;
+///
+/// assert_eq!(-&Value::from("5"), Value::Num(-5));
+/// assert_eq!(-&Value::True, Value::Num(-1));
+/// assert_eq!(-&Value::from("chicken"), Value::NaN);
+/// ```
+impl Neg for &Value {
+    type Output = Value;
+
+    fn neg(self) -> Value {
+        match self.to_num() {
+            Num(a) => Num(a.wrapping_neg()),
+            _ => NaN,
+        }
+    }
+}
+
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match self {
+            // arrays are compared with everything else on their own, so they're converted to strings like in javascript
+            _ if matches!(other, Array(_)) && !matches!(self, Array(_)) => other == self,
             Num(a) => match other {
                 Num(b) => a == b,
-                String(b) => &a.to_string() == b,
+                String(b) => a.to_string() == b.as_str(),
                 True => *a == 1,
                 False => *a == 0,
                 _ => false,
             },
             String(a) => match other {
-                Num(b) => a == &b.to_string(),
+                Num(b) => a.as_str() == b.to_string(),
                 String(b) => a == b,
                 True => a == "1",
                 False => a == "0",
@@ -221,41 +668,324 @@ impl PartialEq for Value {
             },
             Undefined => matches!(other, Undefined),
             NaN => matches!(other, NaN),
+            Array(a) => match other {
+                Array(b) => a == b,
+                Ptr(_) | Undefined | NaN => false,
+                other => String(self.to_rope()) == *other,
+            },
+        }
+    }
+}
+
+/// compares values like javascript's relational operators.
+/// two strings are compared character by character, and anything else is converted to a number first.
+/// values that don't convert to numbers, like undefined and pointers, can't be compared with anything except themselves,
+/// NaN can't be compared with anything, not even itself, and values that convert to the same number without being [equal](PartialEq), like `"01"` and `1`, can't be compared either.
+/// like in javascript, this isn't transitive when strings and numbers are mixed
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::Value;
+///
+/// assert!(Value::Num(2) < Value::Num(10));
+/// assert!(Value::from("10") < Value::from("2"));
+/// assert!(Value::from("2") < Value::Num(10));
+/// assert!(Value::False < Value::True);
+/// assert_eq!(Value::NaN.partial_cmp(&Value::Num(0)), None);
+/// assert_eq!(Value::NaN.partial_cmp(&Value::NaN), None);
+/// ```
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        // NaN is equal to itself here, but like in javascript it isn't ordered against anything, itself included
+        if matches!(self, NaN) || matches!(other, NaN) {
+            return None;
+        }
+
+        if self == other {
+            return Some(Ordering::Equal);
+        }
+
+        let ordering = match (self, other) {
+            // javascript strings are UTF-16, which sorts some characters differently than UTF-8 does
+            (String(a), String(b)) => Some(a.encode_utf16().cmp(b.encode_utf16())),
+            _ => self.to_num_option()?.partial_cmp(&other.to_num_option()?),
+        };
+
+        ordering.filter(|ordering| *ordering != Ordering::Equal)
+    }
+}
+
+/// the broad category of a [ChickenError]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// the program did something invalid while running
+    Runtime,
+
+    /// the program went past a limit placed on it, like getting stuck in an infinite loop
+    Limit,
+
+    /// something outside of the program failed, like an [Observer] writing to a file
+    Io,
+
+    /// the program tried to write over itself while it was frozen with [VMBuilder::freeze_program]
+    ProgramWrite,
+
+    /// the program was stopped before it finished, like by quitting the debugger
+    Interrupted,
+
+    /// an instruction tried to pop more values than there were above the program, with [VMBuilder::strict] set
+    StackUnderflow,
+
+    /// the program built up more output than [Limits::max_output_len] allows
+    OutputLimitExceeded,
+}
+
+impl ErrorKind {
+    /// the code pushed for errors of this kind when they're caught by a trap handler set with [Extension::Trap],
+    /// or None for kinds that can't be caught. runtime errors are 1 and stack underflows are 2
+    pub fn trap_code(self) -> Option<isize> {
+        match self {
+            ErrorKind::Runtime => Some(1),
+            ErrorKind::StackUnderflow => Some(2),
+            _ => None,
         }
     }
 }
 
 /// an error that can be thrown by the chicken interpreter
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct ChickenError {
-    /// the error message
+    /// what kind of error this is
+    pub kind: ErrorKind,
+
+    /// the error message, which is empty for errors from a VM with [VMBuilder::lean_errors] set
     pub message: std::string::String,
 
     /// the value of the program counter when the error was thrown
     pub program_counter: usize,
 
-    /// a copy of the stack for debugging purposes
+    /// a copy of the stack for debugging purposes, which only has its first and last values if [ChickenError::stack_omitted] isn't 0
     pub stack: Vec<Value>,
+
+    /// how many values from the middle of the stack weren't copied into [ChickenError::stack] because of the [StackCapture] policy.
+    /// they were left out right after the first half of the copy
+    #[serde(skip_serializing_if = "is_zero")]
+    pub stack_omitted: usize,
+
+    /// the program counters of the last few instructions executed before the error, oldest first
+    pub recent_program_counters: Vec<usize>,
+
+    /// the string at the top of the stack when the error was thrown, decoded like [VMState::run] would,
+    /// so the output a long program had built up so far isn't lost when it fails near the end
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub partial_output: Option<Box<str>>,
+
+    /// the addresses the program took up on the stack, including the exit after it,
+    /// which the code shown around the instruction that failed is kept within
+    #[serde(skip)]
+    pub program_range: Range<usize>,
+
+    /// the source map of the program, if the VM had one, for showing where in the assembly the error happened
+    #[serde(skip)]
+    pub source_map: Option<Arc<StackSourceMap>>,
+
+    /// when the error is colored when it's displayed, from the [VMState::color] of the VM that threw it.
+    /// it's colored as if it's going to stderr, since that's where errors are usually printed
+    #[serde(skip)]
+    pub color: ColorMode,
+}
+
+impl ChickenError {
+    /// how big the stack was when the error was thrown, including the values that weren't captured
+    pub fn stack_len(&self) -> usize {
+        self.stack.len() + self.stack_omitted
+    }
+
+    /// the addresses of the values that weren't captured
+    pub fn stack_omitted_range(&self) -> Range<usize> {
+        let start = self.stack.len() / 2;
+        start..start + self.stack_omitted
+    }
+
+    /// the value at the given address on the stack when the error was thrown, or None if it wasn't captured or is past the end of the stack
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{StackCapture, VMBuilder, Value};
+    ///
+    /// // pushes 1 three times, then tries to jump by "chicken"
+    /// let err = VMBuilder::from_opcodes([11, 11, 11, 1, 8])
+    ///     .stack_capture(StackCapture::Ends(2))
+    ///     .build()
+    ///     .run()
+    ///     .unwrap_err();
+    ///
+    /// assert_eq!(err.stack_len(), 11);
+    /// assert_eq!(err.stack_cell(1), Some(&Value::Undefined));
+    /// assert_eq!(err.stack_cell(5), None);
+    /// assert_eq!(err.stack_cell(10), Some(&Value::Num(1)));
+    /// ```
+    pub fn stack_cell(&self, address: usize) -> Option<&Value> {
+        let omitted = self.stack_omitted_range();
+
+        if address < omitted.start {
+            self.stack.get(address)
+        } else if address >= omitted.end {
+            self.stack.get(address - self.stack_omitted)
+        } else {
+            None
+        }
+    }
 }
 
 impl fmt::Display for ChickenError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "{}{}", "error: ".red().bold(), self.message.bold())?;
+        // how many values from each end of the stack are shown in the stack dump
+        const DUMP_CELLS: usize = 16;
+
+        let color = self.color.enabled(Stream::Stderr);
+
+        if self.message.is_empty() {
+            return writeln!(
+                f,
+                "{}{:?} error at program counter {}",
+                color::paint("error: ".red().bold(), color),
+                self.kind,
+                self.program_counter
+            );
+        }
+
         writeln!(
             f,
-            "    program counter: {} ({:?})",
-            self.program_counter, self.stack[self.program_counter]
+            "{}{}",
+            color::paint("error: ".red().bold(), color),
+            color::paint(self.message.bold(), color)
         )?;
-        writeln!(f, "    stack dump: {:?}", self.stack)
+        writeln!(f, "    program counter: {}", self.program_counter)?;
+
+        // the program counter has usually moved past the instruction that failed, but the last one executed is still the one to blame
+        let source_map = self.source_map.as_deref();
+        let blamed = (self.recent_program_counters.last()).unwrap_or(&self.program_counter);
+        if let Some(source) = source_map.and_then(|map| map.describe(*blamed)) {
+            writeln!(f, "    source: {}", source)?;
+        }
+
+        // the window is centered on the blamed instruction, and doesn't show cells around the program like the input as code,
+        // unless execution left the program and the cells it ran into are what's interesting.
+        // it can only be shown if every cell in it was captured
+        let stack_len = self.stack_len();
+        let (low, high) = match self.program_range.contains(blamed) {
+            true => (self.program_range.start, self.program_range.end),
+            false => (0, stack_len),
+        };
+        let start =
+            (blamed.saturating_sub(CONTEXT_RADIUS).max(low)).min(stack_len.saturating_sub(1));
+        let end = (blamed + CONTEXT_RADIUS + 2).min(high).min(stack_len);
+        match (start..end)
+            .map(|address| self.stack_cell(address).cloned())
+            .collect::<Option<Vec<_>>>()
+        {
+            Some(cells) if !cells.is_empty() => {
+                write_window_at(f, &cells, start, *blamed, source_map)?
+            }
+            _ => writeln!(f, "    the code around the program counter wasn't captured")?,
+        }
+
+        // long stacks are cut down to their ends, with the number of values left out in between
+        let omitted = self.stack_omitted_range();
+        let mut values = self
+            .stack
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                let address = match index < omitted.start {
+                    true => index,
+                    false => index + self.stack_omitted,
+                };
+
+                match source_map.and_then(|map| map.symbol(address)) {
+                    Some(symbol) => format!("{}: {:?}", symbol, value),
+                    None => format!("{:?}", value),
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut gap = (self.stack.len() / 2, self.stack_omitted);
+        if values.len() > DUMP_CELLS * 2 {
+            let hidden = values.len() - DUMP_CELLS * 2;
+            values.drain(DUMP_CELLS..DUMP_CELLS + hidden);
+            gap = (DUMP_CELLS, gap.1 + hidden);
+        }
+        if gap.1 > 0 {
+            values.insert(gap.0.min(values.len()), format!("... {} more", gap.1));
+        }
+        writeln!(f, "    stack dump: [{}]", values.join(", "))?;
+
+        if !self.recent_program_counters.is_empty() {
+            writeln!(
+                f,
+                "    last {} pcs: {}",
+                self.recent_program_counters.len(),
+                self.recent_program_counters
+                    .iter()
+                    .map(|pc| pc.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )?;
+        }
+
+        if let Some(partial_output) = &self.partial_output {
+            writeln!(f, "    partial output: {:?}", partial_output)?;
+        }
+
+        Ok(())
     }
 }
 
 /// allows for easy construction of a Chicken VM
 pub struct VMBuilder {
     opcodes: Vec<isize>,
-    input: Value,
+    inputs: Vec<Value>,
     debug: bool,
     normal_char: bool,
+    byte_char: bool,
+    char_entity: CharEntity,
+    entity_decoding: EntityDecoding,
+    output_processors: Vec<OutputProcessor>,
+    detect_loops: bool,
+    freeze_program: bool,
+    strict: bool,
+    exit_policy: ExitPolicy,
+    stack_capture: StackCapture,
+    lean_errors: bool,
+    fuse_instructions: bool,
+    loop_window: Option<usize>,
+    profile: bool,
+    coverage: bool,
+    warnings: bool,
+    observers: Vec<Box<dyn Observer + Send>>,
+    limits: Limits,
+    cost_model: Option<CostModel>,
+    snapshot: Option<Snapshot>,
+    stack_capacity: Option<usize>,
+    data: Vec<Value>,
+    start_offset: usize,
+    lazy_input: Option<LazyInput>,
+    color: ColorMode,
+    debug_io: Option<DebugIo>,
+    debug_mi: bool,
+    debug_pause: bool,
+    progress_reporter: Option<ProgressReporter>,
+    checkpointer: Option<Checkpointer>,
+    cancellation_token: Option<CancellationToken>,
+    source_map: Option<SourceMap>,
+    host_fns: BTreeMap<isize, HostFn>,
+    channels: BTreeMap<isize, Channel>,
+    extensions: bool,
+    seed: u64,
+    io: Option<SharedIo>,
 }
 
 impl VMBuilder {
@@ -272,13 +1002,31 @@ impl VMBuilder {
     /// assert_eq!(builder.build().run(), Ok("chicken".to_string()))
     /// ```
     pub fn from_chicken<T: AsRef<str>>(chicken: T) -> Self {
-        Self::from_opcodes(
-            chicken
-                .as_ref()
-                .split('\n')
-                .map(|l| l.matches("chicken").count() as isize)
-                .collect::<Vec<_>>(),
-        )
+        Self::from_program(Program::from_chicken(chicken))
+    }
+
+    /// creates a new VMBuilder from a file, guessing its format from the extension like [Program::from_path] does
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    ///
+    /// let builder = VMBuilder::from_path("examples/helloworld.chicken").unwrap();
+    /// assert_eq!(builder.build().run(), Ok("Hello world".to_string()));
+    /// ```
+    pub fn from_path<P: AsRef<std::path::Path>>(path: P) -> Result<Self, LoadError> {
+        Ok(Self::from_program(Program::from_path(path)?))
+    }
+
+    /// creates a new VMBuilder from Chicken code read from a reader
+    pub fn from_reader<R: std::io::Read>(reader: R) -> Result<Self, LoadError> {
+        Ok(Self::from_program(Program::from_reader(reader)?))
+    }
+
+    /// creates a new VMBuilder from an already parsed [Program]
+    pub fn from_program(program: Program) -> Self {
+        Self::from_opcodes(program.into_opcodes())
     }
 
     /// creates a new VMBuilder from the individual opcodes of a Chicken program
@@ -299,13 +1047,102 @@ impl VMBuilder {
     pub fn from_opcodes<T: Into<Vec<isize>>>(opcodes: T) -> Self {
         Self {
             opcodes: opcodes.into(),
-            input: Undefined,
+            inputs: vec![Undefined],
             debug: false,
             normal_char: false,
+            byte_char: false,
+            char_entity: CharEntity::Decimal,
+            entity_decoding: EntityDecoding::All,
+            output_processors: vec![OutputProcessor::DecodeEntities],
+            detect_loops: false,
+            freeze_program: false,
+            strict: false,
+            exit_policy: ExitPolicy::Error,
+            stack_capture: StackCapture::Full,
+            lean_errors: false,
+            fuse_instructions: true,
+            loop_window: None,
+            profile: false,
+            coverage: false,
+            warnings: false,
+            observers: Vec::new(),
+            limits: Limits::default(),
+            cost_model: None,
+            snapshot: None,
+            stack_capacity: None,
+            data: Vec::new(),
+            start_offset: 0,
+            lazy_input: None,
+            color: ColorMode::Auto,
+            debug_io: None,
+            debug_mi: false,
+            debug_pause: true,
+            progress_reporter: None,
+            checkpointer: None,
+            cancellation_token: None,
+            source_map: None,
+            host_fns: BTreeMap::new(),
+            channels: BTreeMap::new(),
+            extensions: false,
+            seed: 0,
+            io: None,
+        }
+    }
+
+    /// creates a new VMBuilder from opcodes as they come out of an iterator, so a program that's being generated
+    /// doesn't have to be collected into a list of its own first
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    ///
+    /// // pushes "chicken" three times, then exits
+    /// let opcodes = std::iter::repeat(1).take(3).chain([0]);
+    /// assert_eq!(VMBuilder::from_opcode_iter(opcodes).build().run(), Ok("chicken".to_string()));
+    /// ```
+    pub fn from_opcode_iter<I: IntoIterator<Item = isize>>(opcodes: I) -> Self {
+        Self::from_opcodes(opcodes.into_iter().collect::<Vec<_>>())
+    }
+
+    /// creates a new VMBuilder from raw opcodes separated by whitespace read from a reader, see [Program::from_opcode_reader]
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    ///
+    /// let builder = VMBuilder::from_opcode_reader("11 6\n0".as_bytes()).unwrap();
+    /// assert_eq!(builder.input("meow").build().run(), Ok("meow".to_string()));
+    /// ```
+    pub fn from_opcode_reader<R: std::io::Read>(reader: R) -> Result<Self, LoadError> {
+        Ok(Self::from_program(Program::from_opcode_reader(reader)?))
+    }
+
+    /// creates a new VMBuilder that resumes from a [Snapshot] of another VM, instead of starting a program from the beginning
+    pub fn from_snapshot(snapshot: Snapshot) -> Self {
+        Self {
+            normal_char: snapshot.normal_char,
+            byte_char: snapshot.byte_char,
+            char_entity: snapshot.char_entity,
+            extensions: snapshot.extensions,
+            seed: snapshot.random_state,
+            snapshot: Some(snapshot),
+            ..Self::from_opcodes([])
         }
     }
 
-    /// sets the debug flag, causing the resulting VM to single step through the program and provide debug information
+    /// creates a new VMBuilder that resumes from a checkpoint written by [VMBuilder::checkpoint_every] or [VMState::save_checkpoint]
+    pub fn from_checkpoint<P: AsRef<std::path::Path>>(path: P) -> Result<Self, LoadError> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let snapshot = serde_json::from_reader(file)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+
+        Ok(Self::from_snapshot(snapshot))
+    }
+
+    /// sets the debug flag, causing the resulting VM to single step through the program, printing how the stack changed and reading [DebugCommand]s from stdin after every instruction.
+    /// the debug information itself is always logged through the [log] crate, at the debug level (and the trace level for stack dumps)
     pub fn debug(mut self) -> Self {
         self.debug = true;
         self
@@ -317,316 +1154,2573 @@ impl VMBuilder {
         self
     }
 
-    /// sets the normal_char flag, causing the resulting VM to convert characters to their proper ASCII representations instead of to HTML entities
-    pub fn normal_char(mut self) -> Self {
-        self.normal_char = true;
+    /// sets the debug flag, with the debugger reading its commands from `input` and writing to `output` instead of using the terminal.
+    /// commands read from `input` are written to `output` too, so it ends up with a transcript of the whole session.
+    /// see [VMState::run_debug_script] for an easier way to run a script of commands
+    pub fn debug_io<R: BufRead + Send + 'static, W: Write + Send + 'static>(
+        mut self,
+        input: R,
+        output: W,
+    ) -> Self {
+        self.debug = true;
+        self.debug_io = Some(DebugIo {
+            input: Some(Box::new(input)),
+            output: Some(Box::new(output)),
+        });
         self
     }
 
-    /// sets the value of the normal_char flag in the resulting VM
-    pub fn set_normal_char(mut self, normal_char: bool) -> Self {
-        self.normal_char = normal_char;
+    /// makes the resulting VM do all of its reading and writing through `io` instead of the terminal: the debugger reads its commands from it
+    /// and writes to it, the output of the program is written to it by [VMState::run], and if the program wasn't given an input
+    /// it's read from it the first time the program reads it. this takes the place of [VMBuilder::debug_io] and [VMBuilder::debug_output].
+    /// forks of the VM share it. see [TestIo] for an example
+    pub fn io<I: Io + 'static>(mut self, io: I) -> Self {
+        self.io = Some(Arc::new(Mutex::new(io)));
         self
     }
 
-    /// passes the provided input to the VM
-    pub fn input<T: Into<Value>>(mut self, input: T) -> Self {
-        self.input = input.into();
+    /// makes the debugger write to `output` instead of stdout, while still reading commands from stdin.
+    /// this doesn't set the debug flag, so it only matters once something else does
+    pub fn debug_output<W: Write + Send + 'static>(mut self, output: W) -> Self {
+        self.debug_io.get_or_insert_with(DebugIo::default).output = Some(Box::new(output));
         self
     }
 
-    /// consumes this VMBuilder and builds a VMState, which can then be run with [VMState::run] or stepped through with [VMState::step]
-    pub fn build(self) -> VMState {
-        let mut stack: Vec<Value> = vec![
-            // reference to the stack
-            Ptr(0),
-            // the input from the user, usually a string
-            self.input,
-        ];
-
-        // push the program onto the stack
-        stack.append(&mut self.opcodes.iter().map(|c| Num(*c)).collect());
-
-        // push the axe opcode to the stack right after the program, to ensure that we'll exit cleanly unless shenanigans occur
-        stack.push(Num(0));
-
-        // return our new VM state
-        VMState {
-            stack,
-            program_counter: 2, // start the program counter at the start of the program
-            debug: self.debug,
-            normal_char: self.normal_char,
-            exited: false,
-        }
+    /// sets whether the debugger waits for commands after each instruction, which it does by default.
+    /// without pausing it just writes how each instruction changed the stack and carries on, for a verbose trace that doesn't need anyone at the keyboard
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// #[derive(Clone, Default)]
+    /// struct Output(Arc<Mutex<Vec<u8>>>);
+    ///
+    /// impl std::io::Write for Output {
+    ///     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    ///         self.0.lock().unwrap().write(buf)
+    ///     }
+    ///
+    ///     fn flush(&mut self) -> std::io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let output = Output::default();
+    /// let mut vm = VMBuilder::from_opcodes([11, 6, 0])
+    ///     .input("abc")
+    ///     .debug()
+    ///     .debug_output(output.clone())
+    ///     .set_debug_pause(false)
+    ///     .build();
+    ///
+    /// assert_eq!(vm.run(), Ok("abc".to_string()));
+    /// assert!(String::from_utf8_lossy(&output.0.lock().unwrap()).contains("6: 1 -> \"abc\""));
+    /// ```
+    pub fn set_debug_pause(mut self, debug_pause: bool) -> Self {
+        self.debug_pause = debug_pause;
+        self
     }
-}
 
-/// the state of the Chicken VM
+    /// sets the debug flag, with the debugger speaking line delimited JSON instead of text so other programs can drive it.
+    /// whenever it stops it writes an event like `{"event": "stopped", "reason": "step", "pc": 3, "stack": [...]}`,
+    /// where the reason is `start`, `step`, `breakpoint`, `instruction` or `watch`. `self_modification` is included after a store into the program,
+    /// and `watches` lists each watched expression with its `expr`, `value` and whether it `changed`.
+    /// it then reads commands like `{"command": "step", "count": 5}`, `{"command": "break", "address": 3}`, `{"command": "break_on", "instruction": "store"}` or `{"command": "set", "address": 1, "value": "meow"}`,
+    /// which are the same as [DebugCommand]s, answering with `{"event": "output", "text": ...}` or `{"event": "error", "message": ...}` for those that don't resume the program
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    ///
+    /// let commands = r#"{"command": "print", "address": 1}
+    /// {"command": "continue"}
+    /// "#;
+    ///
+    /// let mut vm = VMBuilder::from_opcodes([11, 6, 0])
+    ///     .input("abc")
+    ///     .debug_io(commands.as_bytes(), std::io::sink())
+    ///     .debug_mi()
+    ///     .build();
+    ///
+    /// assert_eq!(vm.run(), Ok("abc".to_string()));
+    /// ```
+    pub fn debug_mi(mut self) -> Self {
+        self.debug = true;
+        self.debug_mi = true;
+        self
+    }
+
+    /// sets the value of the debug_mi flag in the resulting VM, which also sets the debug flag if it's true
+    pub fn set_debug_mi(mut self, debug_mi: bool) -> Self {
+        self.debug |= debug_mi;
+        self.debug_mi = debug_mi;
+        self
+    }
+
+    /// sets the normal_char flag, causing the resulting VM to convert characters to their proper ASCII representations instead of to HTML entities
+    pub fn normal_char(mut self) -> Self {
+        self.normal_char = true;
+        self
+    }
+
+    /// sets the value of the normal_char flag in the resulting VM
+    pub fn set_normal_char(mut self, normal_char: bool) -> Self {
+        self.normal_char = normal_char;
+        self
+    }
+
+    /// sets the byte_char flag, causing the Char instruction to produce single bytes so programs can output binary data with [VMState::run_bytes].
+    /// a byte is stored as the character with the same value, from `\0` to `ÿ`, and Char fails on numbers that aren't between 0 and 255.
+    /// this takes priority over the normal_char flag
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    ///
+    /// // pushes 255, converts it to a byte and exits
+    /// let mut vm = VMBuilder::from_opcodes([265, 9, 0]).byte_char().build();
+    /// assert_eq!(vm.run_bytes(), Ok(vec![255]));
+    /// ```
+    pub fn byte_char(mut self) -> Self {
+        self.byte_char = true;
+        self
+    }
+
+    /// sets the value of the byte_char flag in the resulting VM
+    pub fn set_byte_char(mut self, byte_char: bool) -> Self {
+        self.byte_char = byte_char;
+        self
+    }
+
+    /// sets how the Char instruction writes the HTML entities it produces, see [CharEntity]
+    pub fn char_entity(mut self, char_entity: CharEntity) -> Self {
+        self.char_entity = char_entity;
+        self
+    }
+
+    /// sets which HTML entities are converted back into characters in the output when the program exits.
+    /// by default all of them are, which also mangles programs that output things like `&amp;` on purpose
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{EntityDecoding, VMBuilder};
+    ///
+    /// let result = VMBuilder::from_opcodes([11, 6, 0])
+    ///     .input("&#104;&amp;")
+    ///     .entity_decoding(EntityDecoding::Numeric)
+    ///     .build()
+    ///     .run();
+    /// assert_eq!(result, Ok("h&amp;".to_string()));
+    /// ```
+    pub fn entity_decoding(mut self, entity_decoding: EntityDecoding) -> Self {
+        self.entity_decoding = entity_decoding;
+        self
+    }
+
+    /// sets the steps the output goes through when the program exits, in order, replacing the default of only decoding HTML entities.
+    /// see [OutputProcessor] for what each one does, and [VMState::run_raw] for skipping them all
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{OutputProcessor, VMBuilder};
+    ///
+    /// let result = VMBuilder::from_opcodes([11, 6, 0])
+    ///     .input("  &quot;hi&quot;\n")
+    ///     .post_process([OutputProcessor::DecodeEntities, OutputProcessor::Trim, OutputProcessor::JsonEscape])
+    ///     .build()
+    ///     .run();
+    /// assert_eq!(result, Ok("\\\"hi\\\"".to_string()));
+    /// ```
+    pub fn post_process(mut self, processors: impl Into<Vec<OutputProcessor>>) -> Self {
+        self.output_processors = processors.into();
+        self
+    }
+
+    /// sets the detect_loops flag, causing the resulting VM to return an error instead of running forever when it gets stuck in an infinite loop.
+    /// this hashes the entire stack after every step, so it slows execution down considerably
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    ///
+    /// // jumps back to the start of the program forever
+    /// let result = VMBuilder::from_opcodes([11, 10, 15, 3, 8]).detect_loops().build().run();
+    ///
+    /// assert_eq!(result.unwrap_err().message, "infinite loop detected at pc 2");
+    /// ```
+    pub fn detect_loops(mut self) -> Self {
+        self.detect_loops = true;
+        self
+    }
+
+    /// sets the value of the detect_loops flag in the resulting VM
+    pub fn set_detect_loops(mut self, detect_loops: bool) -> Self {
+        self.detect_loops = detect_loops;
+        self
+    }
+
+    /// sets the freeze_program flag, making any Store into the program or the exit after it an error instead of silently modifying the program.
+    /// this catches Stores to the wrong address right away, but only works with programs that don't reuse their own code as variables like the example programs do
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{ErrorKind, VMBuilder};
+    ///
+    /// // stores "chicken" over the first instruction
+    /// let result = VMBuilder::from_opcodes([1, 12, 7]).freeze_program().build().run();
+    ///
+    /// assert_eq!(result.unwrap_err().kind, ErrorKind::ProgramWrite);
+    /// ```
+    pub fn freeze_program(mut self) -> Self {
+        self.freeze_program = true;
+        self
+    }
+
+    /// sets the value of the freeze_program flag in the resulting VM
+    pub fn set_freeze_program(mut self, freeze_program: bool) -> Self {
+        self.freeze_program = freeze_program;
+        self
+    }
+
+    /// sets the strict flag, making an instruction that pops more values than there are above the program an [ErrorKind::StackUnderflow] error
+    /// instead of silently popping the program itself, or undefined once the stack is empty.
+    /// loading from outside of the stack or a string, or from something that isn't either, is an error too instead of loading undefined.
+    /// the Char instruction fails on values that aren't numbers too, instead of wrapping them in an entity like `&#undefined;`.
+    /// this catches broken programs where they go wrong, instead of wherever the undefined or NaN they made ends up,
+    /// but like [VMBuilder::freeze_program] it breaks programs that do these things on purpose, like 99chickens popping the exit after the program or deadfish loading undefined from a number
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{ErrorKind, VMBuilder};
+    ///
+    /// // adds 1 to nothing
+    /// let result = VMBuilder::from_opcodes([11, 2]).strict().build().run();
+    ///
+    /// assert_eq!(result.unwrap_err().kind, ErrorKind::StackUnderflow);
+    /// ```
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// sets the value of the strict flag in the resulting VM
+    pub fn set_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// sets what [VMState::run] does when the program exits with something other than a string, which is an error by default.
+    /// see [VMState::run_output] for getting the value itself
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{ExitPolicy, VMBuilder};
+    ///
+    /// // pushes 3 and 4, multiplies them and exits
+    /// let vm = || VMBuilder::from_opcodes([13, 14, 4, 0]);
+    ///
+    /// assert!(vm().build().run().is_err());
+    /// assert_eq!(vm().exit_policy(ExitPolicy::Stringify).build().run(), Ok("12".to_string()));
+    /// ```
+    pub fn exit_policy(mut self, exit_policy: ExitPolicy) -> Self {
+        self.exit_policy = exit_policy;
+        self
+    }
+
+    /// sets how much of the stack a [ChickenError] keeps a copy of, which is all of it by default.
+    /// copying the whole stack into every error can be slow for big programs, especially when errors are expected, like when fuzzing
+    pub fn stack_capture(mut self, stack_capture: StackCapture) -> Self {
+        self.stack_capture = stack_capture;
+        self
+    }
+
+    /// sets the lean_errors flag, making errors from the resulting VM only have their [ErrorKind] and program counter,
+    /// without a message, a copy of the stack or anything else that takes time to put together.
+    /// this is for services that run lots of untrusted programs and only need to know what kind of error they had
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{ErrorKind, VMBuilder};
+    ///
+    /// // tries to jump by "chicken"
+    /// let err = VMBuilder::from_opcodes([1, 1, 8]).lean_errors().build().run().unwrap_err();
+    ///
+    /// assert_eq!(err.kind, ErrorKind::Runtime);
+    /// assert_eq!(err.program_counter, 5);
+    /// assert!(err.message.is_empty());
+    /// assert!(err.stack.is_empty());
+    /// ```
+    pub fn lean_errors(mut self) -> Self {
+        self.lean_errors = true;
+        self
+    }
+
+    /// sets the value of the lean_errors flag in the resulting VM
+    pub fn set_lean_errors(mut self, lean_errors: bool) -> Self {
+        self.lean_errors = lean_errors;
+        self
+    }
+
+    /// sets whether [VMState::run] executes common pairs of instructions, like a literal followed by an Add or a Load from the stack, in one go.
+    /// this is enabled by default and doesn't change anything about how the program runs, including its [Stats],
+    /// but can be turned off to rule it out when debugging the interpreter itself
+    pub fn set_fuse_instructions(mut self, fuse_instructions: bool) -> Self {
+        self.fuse_instructions = fuse_instructions;
+        self
+    }
+
+    /// limits loop detection to the last `window` states of the VM, keeping memory usage bounded for long running programs.
+    /// loops that take more steps than this to repeat won't be detected
+    pub fn loop_window(mut self, window: usize) -> Self {
+        self.loop_window = Some(window);
+        self
+    }
+
+    /// sets the profile flag, causing the resulting VM to collect a [Profile] of which instructions were executed and how long they took
+    pub fn profile(mut self) -> Self {
+        self.profile = true;
+        self
+    }
+
+    /// sets the value of the profile flag in the resulting VM
+    pub fn set_profile(mut self, profile: bool) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// sets the coverage flag, causing the resulting VM to track which instructions of the program were executed in a [Coverage] report
+    pub fn coverage(mut self) -> Self {
+        self.coverage = true;
+        self
+    }
+
+    /// sets the value of the coverage flag in the resulting VM
+    pub fn set_coverage(mut self, coverage: bool) -> Self {
+        self.coverage = coverage;
+        self
+    }
+
+    /// sets the warnings flag, causing the resulting VM to collect the [Warning]s a program causes in [VMState::warnings]
+    pub fn warnings(mut self) -> Self {
+        self.warnings = true;
+        self
+    }
+
+    /// sets the value of the warnings flag in the resulting VM
+    pub fn set_warnings(mut self, warnings: bool) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// adds an [Observer] to the resulting VM, which will be told about every instruction that gets executed
+    pub fn observer<O: Observer + Send + 'static>(mut self, observer: O) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// calls `callback` every `every_n_steps` instructions with how many have been executed and how big the stack is,
+    /// for showing a progress bar or logging a heartbeat while a long program runs. unlike an [Observer], this doesn't slow the program down
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let reports = Arc::new(Mutex::new(Vec::new()));
+    /// let reports_clone = reports.clone();
+    ///
+    /// let mut vm = VMBuilder::from_path("examples/99chickens.chicken")
+    ///     .unwrap()
+    ///     .input(9)
+    ///     .on_progress(1000, move |progress| reports_clone.lock().unwrap().push(progress.steps))
+    ///     .build();
+    /// vm.run().unwrap();
+    ///
+    /// let reports = reports.lock().unwrap();
+    /// assert_eq!(reports.len() as u64, vm.stats().steps / 1000);
+    /// ```
+    pub fn on_progress<F: FnMut(Progress) + Send + 'static>(
+        mut self,
+        every_n_steps: u64,
+        callback: F,
+    ) -> Self {
+        self.progress_reporter = Some(ProgressReporter::new(every_n_steps, Box::new(callback)));
+        self
+    }
+
+    /// makes the resulting VM write a checkpoint to `path` every `every_n_steps` steps with [VMState::save_checkpoint],
+    /// replacing the one before it, so a very long run that crashes, runs out of memory or is cancelled can be resumed from the last one
+    /// with [VMBuilder::from_checkpoint] instead of starting over. failing to write a checkpoint stops the VM with an [ErrorKind::Io] error
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    ///
+    /// let path = std::env::temp_dir().join("chicken-checkpoint-every-example.json");
+    ///
+    /// let mut vm = VMBuilder::from_path("examples/99chickens.chicken")
+    ///     .unwrap()
+    ///     .input(9)
+    ///     .checkpoint_every(100, &path)
+    ///     .build();
+    /// let output = vm.run().unwrap();
+    ///
+    /// // carries on from the last checkpoint, written less than 100 steps before the end
+    /// let mut resumed = VMBuilder::from_checkpoint(&path).unwrap().build();
+    /// assert_eq!(resumed.run(), Ok(output));
+    /// # std::fs::remove_file(path).unwrap();
+    /// ```
+    pub fn checkpoint_every<P: Into<std::path::PathBuf>>(
+        mut self,
+        every_n_steps: u64,
+        path: P,
+    ) -> Self {
+        self.checkpointer = Some(Checkpointer::new(every_n_steps, path.into()));
+        self
+    }
+
+    /// makes the resulting VM stop with an [ErrorKind::Interrupted] error once `token` is cancelled, so it can be stopped from another thread or a Ctrl-C handler
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{CancellationToken, ErrorKind, VMBuilder};
+    ///
+    /// let token = CancellationToken::new();
+    /// let mut vm = VMBuilder::from_opcodes([11, 6, 0])
+    ///     .input("abc")
+    ///     .cancellation_token(token.clone())
+    ///     .build();
+    ///
+    /// vm.step().unwrap();
+    /// token.cancel();
+    ///
+    /// assert_eq!(vm.run().unwrap_err().kind, ErrorKind::Interrupted);
+    /// ```
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+
+    /// gives the resulting VM a [SourceMap] of where its program came from, so errors and the debugger can show the assembly line
+    /// and label of each instruction, and breakpoints can be set on labels
+    pub fn source_map(mut self, source_map: SourceMap) -> Self {
+        self.source_map = Some(source_map);
+        self
+    }
+
+    /// makes the resulting VM write a line of JSON describing every executed instruction to the given writer. see [JsonTracer]
+    pub fn trace<W: std::io::Write + Send + 'static>(self, writer: W) -> Self {
+        self.observer(JsonTracer::new(writer))
+    }
+
+    /// the same as [VMBuilder::trace], but only writing the steps picked by the given [TraceSampling]
+    pub fn trace_sampled<W: std::io::Write + Send + 'static>(
+        self,
+        writer: W,
+        sampling: TraceSampling,
+    ) -> Self {
+        self.observer(JsonTracer::new(writer).sampling(sampling))
+    }
+
+    /// binds a Rust function to an opcode, so chicken programs can call into the application running them.
+    /// when the resulting VM runs into the opcode, it pops `arity` values, calls `function` with them, bottom first,
+    /// and pushes the values it returns, instead of pushing the opcode minus 10 like it usually would.
+    /// if the function returns an error the program stops with an [ErrorKind::Runtime] error, leaving the arguments on the stack.
+    /// forks of the VM share its host functions, so any state they keep has to be shared too.
+    /// panics if the opcode is one of the built in instructions
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{VMBuilder, Value};
+    ///
+    /// // pushes the input, calls opcode 100 and exits
+    /// let mut vm = VMBuilder::from_opcodes([11, 6, 0, 100, 0])
+    ///     .input("world")
+    ///     .host_fn(100, 1, |args| Ok(vec![Value::from(format!("hello {}", args[0]))]))
+    ///     .build();
+    ///
+    /// assert_eq!(vm.run(), Ok("hello world".to_string()));
+    /// ```
+    pub fn host_fn<F>(mut self, opcode: isize, arity: usize, function: F) -> Self
+    where
+        F: Fn(Vec<Value>) -> Result<Vec<Value>, std::string::String> + Send + Sync + 'static,
+    {
+        assert!(
+            opcode > CHAR,
+            "opcode {} is a built in instruction, host functions have to use 10 or more",
+            opcode
+        );
+
+        self.host_fns.insert(
+            opcode,
+            HostFn {
+                arity,
+                function: Arc::new(function),
+            },
+        );
+        self
+    }
+
+    /// turns on the [Extension] instructions, which aren't part of chicken itself. without this their opcodes push numbers like usual
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{Extension, Output, VMBuilder, Value};
+    ///
+    /// // pushes 6, calls random and exits with the number it pushed
+    /// let program = [16, Extension::Random.opcode(), 0];
+    ///
+    /// let output = VMBuilder::from_opcodes(program).extensions().build().run_output();
+    /// assert!(matches!(output, Ok(Output::Value(Value::Num(0..=5)))));
+    ///
+    /// let output = VMBuilder::from_opcodes(program).build().run_output();
+    /// assert_eq!(output, Ok(Output::Value(Value::Num(990))));
+    /// ```
+    pub fn extensions(mut self) -> Self {
+        self.extensions = true;
+        self
+    }
+
+    /// sets the value of the extensions flag in the resulting VM
+    pub fn set_extensions(mut self, extensions: bool) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    /// binds a [Channel] to a number, so the resulting VM can send values to it and receive values from it with
+    /// [Extension::Send] and [Extension::Recv]. binding the same channel to more than one VM lets them pass values to each other
+    pub fn channel(mut self, number: isize, channel: Channel) -> Self {
+        self.channels.insert(number, channel);
+        self
+    }
+
+    /// sets the seed of the pseudo-random numbers pushed by [Extension::Random], which is 0 by default.
+    /// the same seed always gives the same numbers, so a run can be repeated by building the VM with the seed it used
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// sets the resource [Limits] of the resulting VM
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// sets the [CostModel] the resulting VM counts fuel with, for [Limits::max_fuel] and [Stats::fuel]
+    pub fn cost_model(mut self, cost_model: CostModel) -> Self {
+        self.cost_model = Some(cost_model);
+        self
+    }
+
+    /// sets up the resulting VM for running a program that can't be trusted, with [Limits::untrusted] and strict mode,
+    /// so it can't run forever, use up all the memory or read undefined values from outside of the stack
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{ErrorKind, VMBuilder};
+    ///
+    /// // adds with nothing on the stack
+    /// let err = VMBuilder::from_opcodes([2]).sandbox().build().run().unwrap_err();
+    /// assert_eq!(err.kind, ErrorKind::StackUnderflow);
+    /// ```
+    pub fn sandbox(self) -> Self {
+        self.limits(Limits::untrusted()).strict()
+    }
+
+    /// sets up the resulting VM for running inside another program, like [VMBuilder::sandbox] but without ever touching the terminal.
+    /// the debugger is turned off, and reads no commands and writes nowhere if it's turned back on, colors are turned off,
+    /// and errors only keep a copy of the ends of the stack, so they stay small enough to pass around and log.
+    /// programs that aren't given an input get undefined instead of reading stdin, unless [VMBuilder::io] is used
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{ErrorKind, VMBuilder};
+    ///
+    /// // pushes "chicken" forever
+    /// let err = VMBuilder::from_opcodes([1, 11, 10, 16, 3, 8]).embedded().build().run().unwrap_err();
+    ///
+    /// assert_eq!(err.kind, ErrorKind::Limit);
+    /// assert!(err.stack.len() <= 32);
+    /// assert!(!err.to_string().contains('\x1b'));
+    /// ```
+    pub fn embedded(self) -> Self {
+        self.sandbox()
+            .set_debug(false)
+            .debug_io(std::io::empty(), std::io::sink())
+            .color(ColorMode::Never)
+            .stack_capture(StackCapture::Ends(16))
+    }
+
+    /// sets up the resulting VM for running from a command line, reading an input that wasn't given from stdin the first time the program reads it,
+    /// printing the output to stdout once the program exits, and coloring errors and debugger output when they go to a terminal
+    pub fn interactive(self) -> Self {
+        self.io(StdIo).color(ColorMode::Auto)
+    }
+
+    /// makes room on the stack for the given number of values up front, so programs that grow the stack a lot don't have to keep reallocating it.
+    /// by default there's room for twice the size of the program and its inputs, since straight line code pushes at most one value per instruction.
+    /// the capacity the stack ended up with is in [Stats::stack_capacity]
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    ///
+    /// let mut vm = VMBuilder::from_opcodes([1]).stack_capacity(1000).build();
+    /// vm.run().unwrap();
+    /// assert!(vm.stats().stack_capacity >= 1000);
+    /// ```
+    pub fn stack_capacity(mut self, capacity: usize) -> Self {
+        self.stack_capacity = Some(capacity);
+        self
+    }
+
+    /// passes the provided input to the VM
+    pub fn input<T: Into<Value>>(mut self, input: T) -> Self {
+        self.inputs = vec![input.into()];
+        self
+    }
+
+    /// passes several inputs to the VM, which are placed on the stack one after another starting at address 1, with the program after them.
+    /// an empty list is the same as not passing any input
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{Value, VMBuilder};
+    ///
+    /// // pushes the second input
+    /// let mut vm = VMBuilder::from_opcodes([12, 6, 0])
+    ///     .inputs(vec![Value::from("first"), Value::from("second")])
+    ///     .build();
+    ///
+    /// assert_eq!(vm.program_start, 3);
+    /// assert_eq!(vm.run(), Ok("second".to_string()));
+    /// ```
+    pub fn inputs<T: Into<Vec<Value>>>(mut self, inputs: T) -> Self {
+        self.inputs = inputs.into();
+
+        if self.inputs.is_empty() {
+            self.inputs.push(Undefined);
+        }
+
+        self
+    }
+
+    /// places a data segment on the stack right after the exit at the end of the program, so a compiler can lay out constants and tables
+    /// at known addresses without emitting pushes for every one of them. the first value is at [VMState::program_end] + 1,
+    /// and the program pushes its own values on top of them
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{Value, VMBuilder};
+    ///
+    /// // loads the first value of the data segment, right after the exit at address 5
+    /// let mut vm = VMBuilder::from_opcodes([16, 6, 0])
+    ///     .data(vec![Value::from("hello"), Value::from("world")])
+    ///     .build();
+    ///
+    /// assert_eq!(vm.data(), &[Value::from("hello"), Value::from("world")]);
+    /// assert_eq!(vm.run(), Ok("hello".to_string()));
+    /// ```
+    pub fn data<T: Into<Vec<Value>>>(mut self, data: T) -> Self {
+        self.data = data.into();
+        self
+    }
+
+    /// starts the program counter this many opcodes into the program instead of at its first instruction,
+    /// so what comes before the entry point can be used for data too
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    ///
+    /// // skips the exit at the start of the program
+    /// let mut vm = VMBuilder::from_opcodes([0, 1]).start_offset(1).build();
+    ///
+    /// assert_eq!(vm.program_counter, vm.program_start + 1);
+    /// assert_eq!(vm.run(), Ok("chicken".to_string()));
+    /// ```
+    pub fn start_offset(mut self, offset: usize) -> Self {
+        self.start_offset = offset;
+        self
+    }
+
+    /// takes the first input from the given function, which is only called the first time the program actually reads that input,
+    /// so input can be asked for interactively without bothering the user when the program doesn't need any.
+    /// until then the input is undefined, and it's never asked for if the program overwrites it first
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{Value, VMBuilder};
+    ///
+    /// // the quine never reads its input
+    /// let mut vm = VMBuilder::from_chicken("chicken")
+    ///     .lazy_input(|| panic!("shouldn't be asked for"))
+    ///     .build();
+    /// assert_eq!(vm.run(), Ok("chicken".to_string()));
+    ///
+    /// let mut vm = VMBuilder::from_opcodes([11, 6, 0])
+    ///     .lazy_input(|| Ok(Value::from("meow")))
+    ///     .build();
+    /// assert_eq!(vm.run(), Ok("meow".to_string()));
+    /// ```
+    pub fn lazy_input<F: FnOnce() -> std::io::Result<Value> + Send + 'static>(
+        mut self,
+        input: F,
+    ) -> Self {
+        self.lazy_input = Some(Box::new(input));
+        self
+    }
+
+    /// takes the first input from everything read from the given reader, which is only read the first time the program actually reads that input.
+    /// see [VMBuilder::lazy_input]
+    pub fn lazy_input_reader<R: Read + Send + 'static>(self, mut reader: R) -> Self {
+        self.lazy_input(move || {
+            let mut input = std::string::String::new();
+            reader.read_to_string(&mut input)?;
+            Ok(String(input.into()))
+        })
+    }
+
+    /// takes the first input from the given [InputSource], which is only read the first time the program actually reads that input.
+    /// see [VMBuilder::lazy_input]
+    pub fn input_source<S: InputSource + 'static>(self, mut source: S) -> Self {
+        self.lazy_input(move || source.read())
+    }
+
+    /// sets when errors and debugger output from the resulting VM are colored, see [VMState::color].
+    /// this only affects this VM, not anything else printed by the process
+    pub fn color(mut self, color: ColorMode) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// consumes this VMBuilder and builds a VMState, which can then be run with [VMState::run] or stepped through with [VMState::step]
+    pub fn build(mut self) -> VMState {
+        let coverage = self
+            .coverage
+            .then(|| Coverage::new(Program::from_opcodes(self.opcodes.clone())));
+
+        // a program that wasn't given an input reads it from the io
+        if let Some(io) = self.io.clone() {
+            if self.lazy_input.is_none() && matches!(self.inputs.first(), Some(Undefined)) {
+                self.lazy_input = Some(Box::new(move || {
+                    io.lock()
+                        .unwrap_or_else(|err| err.into_inner())
+                        .read_input()
+                }));
+            }
+        }
+
+        let debug_io = (self.io.clone())
+            .or_else(|| self.debug_io.map(|io| Arc::new(Mutex::new(io)) as SharedIo));
+
+        let (stack, program_counter, program_start, program_end, exited, return_stack, trap_slot) =
+            match self.snapshot.take() {
+                Some(mut snapshot) => {
+                    if let Some(capacity) = self.stack_capacity {
+                        snapshot
+                            .stack
+                            .reserve(capacity.saturating_sub(snapshot.stack.len()));
+                    }
+
+                    (
+                        snapshot.stack,
+                        snapshot.program_counter,
+                        snapshot.program_start,
+                        snapshot.program_end,
+                        snapshot.exited,
+                        snapshot.return_stack,
+                        snapshot.trap_slot,
+                    )
+                }
+                None => {
+                    // the stack pointer, the inputs, the program, the exit after it and the data segment
+                    let size = 1 + self.inputs.len() + self.opcodes.len() + 1 + self.data.len();
+                    let mut stack: Vec<Value> =
+                        Vec::with_capacity(self.stack_capacity.unwrap_or(size * 2).max(size));
+
+                    // reference to the stack
+                    stack.push(Ptr(0));
+
+                    // the inputs from the user, usually strings. a lazy input stays undefined until it's read
+                    if self.lazy_input.is_some() {
+                        self.inputs[LAZY_INPUT_ADDRESS - 1] = Undefined;
+                    }
+                    stack.append(&mut self.inputs);
+                    let program_start = stack.len();
+
+                    // push the program onto the stack
+                    stack.append(&mut self.opcodes.iter().map(|c| Num(*c)).collect());
+                    let program_end = stack.len();
+
+                    // push the axe opcode to the stack right after the program, to ensure that we'll exit cleanly unless shenanigans occur
+                    stack.push(Num(0));
+
+                    stack.extend(self.data.iter().cloned());
+
+                    // start the program counter at the start of the program, or the entry point inside it
+                    (
+                        stack,
+                        program_start + self.start_offset,
+                        program_start,
+                        program_end,
+                        false,
+                        Vec::new(),
+                        None,
+                    )
+                }
+            };
+
+        let mut stats = Stats::new(&stack);
+        stats.stack_capacity = stack.capacity();
+        let initial_program = stack
+            .get(program_start..program_end)
+            .unwrap_or_default()
+            .into();
+
+        // the initial state counts as a visited state too, so a program that jumps back to the start is caught right away
+        let loop_detector = match (self.detect_loops, self.loop_window) {
+            (false, _) => None,
+            (true, Some(window)) => Some(LoopDetector::with_window(window)),
+            (true, None) => Some(LoopDetector::new()),
+        }
+        .map(|mut detector| {
+            detector.insert(program_counter, &stack);
+            detector
+        });
+
+        // return our new VM state
+        VMState {
+            stack,
+            program_counter,
+            program_start,
+            program_end,
+            debug: self.debug,
+            normal_char: self.normal_char,
+            byte_char: self.byte_char,
+            char_entity: self.char_entity,
+            entity_decoding: self.entity_decoding,
+            output_processors: self.output_processors,
+            freeze_program: self.freeze_program,
+            strict: self.strict,
+            exit_policy: self.exit_policy,
+            stack_capture: self.stack_capture,
+            lean_errors: self.lean_errors,
+            color: self.color,
+            fuse_instructions: self.fuse_instructions,
+            exited,
+            loop_detector,
+            profile: self.profile.then(Profile::default),
+            coverage,
+            warnings: self.warnings.then(Vec::new),
+            stats,
+            observers: self.observers,
+            limits: self.limits,
+            cost_model: self.cost_model,
+            started: false,
+            recording: false,
+            popped: Vec::new(),
+            pushed: Vec::new(),
+            self_modification: None,
+            modified_program: BTreeSet::new(),
+            step_warnings: Vec::new(),
+            lazy_input: self
+                .lazy_input
+                .map(|input| Arc::new(SharedInput::new(input))),
+            pc_history: [0; PC_HISTORY_LEN],
+            pc_history_len: 0,
+            progress_reporter: self.progress_reporter,
+            checkpointer: self.checkpointer,
+            cancellation_token: self.cancellation_token,
+            source_map: self.source_map.map(|source_map| {
+                Arc::new(StackSourceMap {
+                    source_map: Arc::new(source_map),
+                    program_start,
+                    program_end,
+                })
+            }),
+            last_jump: None,
+            faulted: false,
+            deadline: Deadline::default(),
+            host_fns: Arc::new(self.host_fns),
+            channels: Arc::new(self.channels),
+            waiting: false,
+            extensions: self.extensions,
+            seed: self.seed,
+            rng: Rng { state: self.seed },
+            return_stack,
+            trap_slot,
+            io: self.io,
+            initial_program,
+            initial_data: self.data.into(),
+            start_offset: self.start_offset,
+            debugger: Debugger::new(debug_io, self.debug_mi, self.debug_pause),
+        }
+    }
+}
+
+/// the state of the Chicken VM
 pub struct VMState {
     /// the stack of the VM
     pub stack: Vec<Value>,
 
-    /// the program counter, or instruction pointer of the VM
-    pub program_counter: usize,
+    /// the program counter, or instruction pointer of the VM
+    pub program_counter: usize,
+
+    /// the address on the stack the program starts at, right after the stack pointer and inputs
+    pub program_start: usize,
+
+    /// the address of the exit appended right after the program
+    pub program_end: usize,
+
+    /// whether to single step through the program or not
+    pub debug: bool,
+
+    /// whether the Char instruction should produce an actual character instead of an HTML entity string
+    pub normal_char: bool,
+
+    /// whether the Char instruction should produce a single byte, stored as the character with the same value
+    pub byte_char: bool,
+
+    /// how the Char instruction writes HTML entities
+    pub char_entity: CharEntity,
+
+    /// which HTML entities are converted back into characters in the output by [VMState::run]
+    pub entity_decoding: EntityDecoding,
+
+    /// the steps the output goes through in [VMState::run] when the program exits, in order
+    pub output_processors: Vec<OutputProcessor>,
+
+    /// whether a Store into the program is an error
+    pub freeze_program: bool,
+
+    /// whether popping more values than there are above the program is an error
+    pub strict: bool,
+
+    /// whether [Extension] instructions are decoded, instead of pushing their opcode minus 10
+    pub extensions: bool,
+
+    /// what [VMState::run] does when the program exits with something other than a string
+    pub exit_policy: ExitPolicy,
+
+    /// how much of the stack errors keep a copy of
+    pub stack_capture: StackCapture,
+
+    /// whether errors only have their kind and program counter
+    pub lean_errors: bool,
+
+    /// when errors and debugger output are colored. errors are colored as if they're going to stderr
+    /// and the debugger as if it's going to stdout
+    pub color: ColorMode,
+
+    /// whether [VMState::run] executes common pairs of instructions in one go
+    pub fuse_instructions: bool,
+
+    /// whether this VM has finished execution
+    pub exited: bool,
+
+    /// detects infinite loops, if enabled
+    pub loop_detector: Option<LoopDetector>,
+
+    /// profiling information about the program, if enabled
+    pub profile: Option<Profile>,
+
+    /// which instructions of the program were executed, if enabled
+    pub coverage: Option<Coverage>,
+
+    /// the warnings caused by the program so far, if enabled. each kind of warning is only kept once per instruction,
+    /// so a loop doesn't fill this up with copies of the same one
+    pub warnings: Option<Vec<Warning>>,
+
+    stats: Stats,
+
+    observers: Vec<Box<dyn Observer + Send>>,
+
+    /// the resource limits placed on this VM
+    pub limits: Limits,
+
+    // how much fuel each instruction uses up, if it isn't 1 for all of them
+    cost_model: Option<CostModel>,
+
+    // whether the observers have been told that execution started
+    started: bool,
+
+    // the values popped and pushed by the current instruction, only tracked when recording
+    recording: bool,
+    popped: Vec<Value>,
+    pushed: Vec<Value>,
+
+    // the Store into the program done by the current instruction, only tracked when recording or debugging
+    self_modification: Option<SelfModification>,
+
+    // every address in the program that's been stored into, which can't be more than the size of the program
+    modified_program: BTreeSet<usize>,
+
+    // the warnings caused by the current instruction, only tracked when collecting warnings or there are observers
+    step_warnings: Vec<Warning>,
+
+    // the input at LAZY_INPUT_ADDRESS, if it hasn't been read yet
+    lazy_input: Option<Arc<SharedInput>>,
+
+    // the program counters of the last PC_HISTORY_LEN instructions, as a ring buffer.
+    // this is written on every step, so it's a plain array instead of a VecDeque
+    pc_history: [usize; PC_HISTORY_LEN],
+
+    // how many program counters have ever been written to pc_history
+    pc_history_len: usize,
+
+    // calls the progress callback, if there is one
+    progress_reporter: Option<ProgressReporter>,
+
+    // writes checkpoints, if enabled
+    checkpointer: Option<Checkpointer>,
+
+    // stops the VM once it's cancelled
+    cancellation_token: Option<CancellationToken>,
+
+    // where the program came from, if it was assembled
+    source_map: Option<Arc<StackSourceMap>>,
+
+    // the address of the last jump that was taken and how far it jumped, for explaining how execution left the program
+    last_jump: Option<(usize, isize)>,
+
+    // whether the last step failed partway through an instruction, which was undone
+    faulted: bool,
+
+    // when the time limit runs out, if there is one
+    deadline: Deadline,
+
+    // the functions bound to opcodes with VMBuilder::host_fn
+    host_fns: HostFns,
+
+    // the channels bound to numbers with VMBuilder::channel, which forks share, and whether the last step waited on one
+    channels: Arc<BTreeMap<isize, Channel>>,
+    waiting: bool,
+
+    // where the random numbers pushed by Extension::Random come from, and the seed it started with for VMState::reset
+    seed: u64,
+    rng: Rng,
+
+    // the addresses Extension::Call returns to
+    return_stack: Vec<usize>,
+
+    // the slot holding the trap handler, set by Extension::Trap
+    trap_slot: Option<usize>,
+
+    // where the output of the program goes, see VMBuilder::io
+    io: Option<SharedIo>,
+
+    // the program and data segment as they were when the VM was built, for VMState::reset
+    initial_program: Arc<[Value]>,
+    initial_data: Arc<[Value]>,
+
+    // how far into the program execution starts, see VMBuilder::start_offset
+    start_offset: usize,
+
+    debugger: Debugger,
+}
+
+/// how far [VMState::run_steps] or [VMState::run_until] got
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunProgress {
+    /// the VM stopped before the program exited, and can keep going with another call
+    Running,
+
+    /// the program exited with this output, decoded the same way [VMState::run] would
+    Exited(std::string::String),
+}
+
+/// the same as [VMState::fork], so observers aren't copied
+impl Clone for VMState {
+    fn clone(&self) -> Self {
+        self.fork()
+    }
+}
+
+impl VMState {
+    /// runs the VM until it finishes execution, then returns the top value on the stack if it's a string, or an error if it's not.
+    /// the output then goes through [VMState::output_processors], which by default converts HTML entities back to the characters they stand for
+    /// according to [VMState::entity_decoding], see [VMState::run_raw] for getting the output as is.
+    /// any error that occurs during execution will also be returned, along with hopefully useful debug information
+    pub fn run(&mut self) -> Result<std::string::String, ChickenError> {
+        let output = self.run_to_output()?;
+        let output = self.process_output(&output).into_owned();
+        self.write_output(&output)?;
+        Ok(output)
+    }
+
+    /// the same as [VMState::run] with [EntityDecoding::None], not converting any HTML entities in the output,
+    /// for programs that intentionally output text like `&#104;` or HTML that's already escaped
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    ///
+    /// // pushes 104, converts it to a character and exits
+    /// let mut vm = VMBuilder::from_opcodes([114, 9, 0]).build();
+    /// assert_eq!(vm.run_raw(), Ok("&#104;".to_string()));
+    ///
+    /// let mut vm = VMBuilder::from_opcodes([114, 9, 0]).build();
+    /// assert_eq!(vm.run(), Ok("h".to_string()));
+    /// ```
+    pub fn run_raw(&mut self) -> Result<std::string::String, ChickenError> {
+        let output = self.run_to_output()?;
+        self.write_output(&output)?;
+        Ok(output)
+    }
+
+    // runs the program until it exits, then pops what it exited with
+    fn run_to_output(&mut self) -> Result<std::string::String, ChickenError> {
+        self.run_until_exit()?;
+
+        // return the top value of the stack if it's a string
+        match self.pop() {
+            Some(String(s)) => Ok(s.into()),
+            s => self
+                .non_string_exit(s.as_ref())
+                .map_err(|err| self.unpop(err, [s])),
+        }
+    }
+
+    // writes the output of the program to the Io given to VMBuilder::io, if there is one
+    fn write_output(&self, output: &str) -> Result<(), ChickenError> {
+        let Some(io) = self.io.as_ref() else {
+            return Ok(());
+        };
+
+        (io.lock().unwrap_or_else(|err| err.into_inner()))
+            .write_output(output)
+            .map_err(|err| self.error(ErrorKind::Io, format_args!("error writing output: {}", err)))
+    }
+
+    // runs the program until it exits, leaving what it exited with on the stack
+    fn run_until_exit(&mut self) -> Result<(), ChickenError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("run", stack_depth = self.stack.len()).entered();
+
+        log::debug!("no opcode");
+        log::debug!("program counter {:?}", self.program_counter);
+        log::trace!("stack {:?}", self.stack);
+
+        if self.debug {
+            self.debug_start()?;
+        }
+
+        while !self.exited {
+            if !self.step_fast() {
+                self.step()?;
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(steps = self.stats.steps, "program exited");
+
+        Ok(())
+    }
+
+    // what to output when the program exits with something other than a string, according to the exit policy
+    fn non_string_exit(&self, value: Option<&Value>) -> Result<std::string::String, ChickenError> {
+        match self.exit_policy {
+            ExitPolicy::Error => Err(self.error(
+                ErrorKind::Runtime,
+                format_args!("invalid value {:?} on exit", value),
+            )),
+            ExitPolicy::Stringify => Ok(value.unwrap_or(&Undefined).to_string()),
+        }
+    }
+
+    /// runs at most the given number of instructions, so execution can be spread out over the frames of a GUI or game loop.
+    /// unlike [VMState::run] the output is left on the stack once the program exits, so calling this again just returns it again
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{RunProgress, VMBuilder};
+    ///
+    /// let mut vm = VMBuilder::from_path("examples/helloworld.chicken").unwrap().build();
+    ///
+    /// let mut frames = 1;
+    /// while vm.run_steps(10).unwrap() == RunProgress::Running {
+    ///     frames += 1;
+    /// }
+    ///
+    /// assert_eq!(vm.run_steps(10), Ok(RunProgress::Exited("Hello world".to_string())));
+    /// assert!(frames > 1);
+    /// ```
+    pub fn run_steps(&mut self, steps: u64) -> Result<RunProgress, ChickenError> {
+        let mut remaining = steps;
+        while remaining > 0 && !self.exited {
+            // fused instructions take two steps, so this leaves room for them
+            let steps_before = self.stats.steps;
+            if remaining >= 2 && self.step_fast() {
+                remaining -= self.stats.steps - steps_before;
+            } else {
+                self.step()?;
+                remaining -= 1;
+            }
+        }
+
+        self.progress()
+    }
+
+    /// runs as many instructions as fit before `until`, then hands control back, so lots of VMs can take turns on one thread.
+    /// the clock is only read every so many instructions, so this can run a little past `until`, and always runs at least that many.
+    /// like [VMState::run_steps] the output is left on the stack once the program exits, so polling again just returns it again
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    /// use std::{task::Poll, time::{Duration, Instant}};
+    ///
+    /// let mut farm: Vec<_> = ["cluck", "bawk", "chirp"]
+    ///     .into_iter()
+    ///     .map(|input| VMBuilder::from_path("examples/cat.chicken").unwrap().input(input).build())
+    ///     .collect();
+    ///
+    /// let mut outputs = vec![None; farm.len()];
+    /// while outputs.iter().any(Option::is_none) {
+    ///     for (vm, output) in farm.iter_mut().zip(&mut outputs) {
+    ///         if let Poll::Ready(result) = vm.poll(Instant::now() + Duration::from_millis(1)) {
+    ///             *output = Some(result.unwrap());
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(outputs, [Some("cluck".to_string()), Some("bawk".to_string()), Some("chirp".to_string())]);
+    /// ```
+    pub fn poll(&mut self, until: Instant) -> Poll<Result<std::string::String, ChickenError>> {
+        loop {
+            match self.run_steps(POLL_INTERVAL) {
+                Ok(RunProgress::Running) => {}
+                Ok(RunProgress::Exited(output)) => return Poll::Ready(Ok(output)),
+                Err(err) => return Poll::Ready(Err(err)),
+            }
+
+            if Instant::now() >= until {
+                return Poll::Pending;
+            }
+        }
+    }
+
+    /// runs one instruction at a time until `condition` returns true for the state after an instruction, or the program exits.
+    /// at least one instruction is always run, so calling this again with the same condition finds the next time it's true.
+    /// like [VMState::run_steps] the output is left on the stack once the program exits
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{RunProgress, VMBuilder, Value};
+    ///
+    /// let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    ///
+    /// assert_eq!(vm.run_until(|vm| vm.stack.last() == Some(&Value::from("abc"))), Ok(RunProgress::Running));
+    /// assert_eq!(vm.program_counter, 5);
+    /// ```
+    pub fn run_until<F: FnMut(&Self) -> bool>(
+        &mut self,
+        mut condition: F,
+    ) -> Result<RunProgress, ChickenError> {
+        while !self.exited {
+            self.step()?;
+
+            if condition(self) {
+                break;
+            }
+        }
+
+        self.progress()
+    }
+
+    /// runs until the program counter reaches `pc` or the program exits, without having to set a breakpoint.
+    /// see [VMState::run_until]
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{RunProgress, VMBuilder};
+    ///
+    /// let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    ///
+    /// assert_eq!(vm.run_to(5), Ok(RunProgress::Running));
+    /// assert_eq!(vm.run_to(5), Ok(RunProgress::Exited("abc".to_string())));
+    /// ```
+    ///
+    /// the program counter never lands on the operand of a Load, so stopping there stops at the Load itself instead,
+    /// decoding the program the same way the debugger shows it
+    ///
+    /// ```rust
+    /// use chicken::{RunProgress, VMBuilder};
+    ///
+    /// let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    ///
+    /// assert_eq!(vm.run_to(4), Ok(RunProgress::Running));
+    /// assert_eq!(vm.program_counter, 3);
+    /// ```
+    pub fn run_to(&mut self, pc: usize) -> Result<RunProgress, ChickenError> {
+        let pc = self.instruction_containing(pc);
+        self.run_until(|vm| vm.program_counter == pc)
+    }
+
+    // the address of the instruction in the program that the given address is part of, which is the Load before it if it's an operand.
+    // addresses outside of the program are left alone, since there's nothing to decode them from
+    fn instruction_containing(&self, address: usize) -> usize {
+        if !(self.program_start..self.program_end).contains(&address) {
+            return address;
+        }
+
+        let mut start = self.program_start;
+        while let Some(cell) = self.stack.get(start) {
+            let next = start
+                + Instruction::from_values(cell, self.stack.get(start + 1))
+                    .map_or(1, |instruction| instruction.width());
+
+            if next > address {
+                return start;
+            }
+            start = next;
+        }
+
+        address
+    }
+
+    /// runs until the program counter leaves `range` or the program exits, to skip over a loop or some other block of code.
+    /// nothing is run if the program counter is already outside of it
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{RunProgress, VMBuilder};
+    ///
+    /// let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    ///
+    /// assert_eq!(vm.step_until_leaves(2..5), Ok(RunProgress::Running));
+    /// assert_eq!(vm.program_counter, 5);
+    /// ```
+    pub fn step_until_leaves(&mut self, range: Range<usize>) -> Result<RunProgress, ChickenError> {
+        if !range.contains(&self.program_counter) {
+            return self.progress();
+        }
+
+        self.run_until(|vm| !range.contains(&vm.program_counter))
+    }
+
+    // whether the program has exited, with its output if it has
+    fn progress(&self) -> Result<RunProgress, ChickenError> {
+        if !self.exited {
+            return Ok(RunProgress::Running);
+        }
+
+        match self.stack.last() {
+            Some(String(s)) => Ok(RunProgress::Exited(
+                self.process_output(s.as_str()).into_owned(),
+            )),
+
+            s => self.non_string_exit(s).map(RunProgress::Exited),
+        }
+    }
+
+    /// runs the VM like [VMState::run], but returns the output as bytes.
+    /// if the byte_char flag is set, every character in the output is turned back into the byte it stands for without decoding HTML entities,
+    /// which fails if the output contains characters past `ÿ` that didn't come from Char. otherwise this is the output of [VMState::run] as UTF-8
+    pub fn run_bytes(&mut self) -> Result<Vec<u8>, ChickenError> {
+        if !self.byte_char {
+            return self.run().map(std::string::String::into_bytes);
+        }
+
+        let output = self.run_raw()?;
+        output
+            .chars()
+            .map(|c| u8::try_from(c).ok())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| {
+                self.error(
+                    ErrorKind::Runtime,
+                    format_args!("output {:?} has characters that aren't bytes", output),
+                )
+            })
+    }
+
+    /// skips the instruction that caused the last error, so a debugger can carry on exploring the program as if it wasn't there.
+    /// an instruction that fails puts back anything it popped and leaves the program counter pointing at it, as if it never ran,
+    /// though it's still counted in the stats. other errors, like hitting a limit, leave the VM after the last instruction that ran,
+    /// so there's nothing to skip and this returns false
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{VMBuilder, Value};
+    ///
+    /// // pushes "chicken", tries to jump by it, then exits
+    /// let mut vm = VMBuilder::from_opcodes([1, 8, 0]).build();
+    ///
+    /// assert!(vm.run().is_err());
+    /// assert_eq!(vm.program_counter, 3);
+    /// assert_eq!(vm.stack.last(), Some(&Value::from("chicken")));
+    ///
+    /// assert!(vm.resume_after_error());
+    /// assert_eq!(vm.run(), Ok("chicken".to_string()));
+    /// ```
+    pub fn resume_after_error(&mut self) -> bool {
+        if !std::mem::take(&mut self.faulted) {
+            return false;
+        }
+
+        let width = self.stack.get(self.program_counter).map_or(1, |op| {
+            Instruction::from_values(op, self.stack.get(self.program_counter + 1))
+                .map_or(1, |instruction| instruction.width())
+        });
+        self.program_counter += width;
+
+        true
+    }
+
+    /// single steps the VM, running one instruction at a time
+    pub fn step(&mut self) -> Result<(), ChickenError> {
+        self.step_with_info(false).map(|_| ())
+    }
+
+    /// single steps the VM like [VMState::step], returning a description of the instruction that was run, or None if the VM had already exited
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{Instruction, Value, VMBuilder};
+    ///
+    /// let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    /// vm.step().unwrap();
+    ///
+    /// let info = vm.step_info().unwrap().unwrap();
+    /// assert_eq!(info.instruction, Some(Instruction::Load(0)));
+    /// assert_eq!(info.mnemonic, "load");
+    /// assert_eq!((info.program_counter, info.next_program_counter), (3, 5));
+    /// assert_eq!(info.popped, vec![Value::Num(1)]);
+    /// assert_eq!(info.pushed, vec![Value::from("abc")]);
+    /// assert!(!info.jumped);
+    /// ```
+    pub fn step_info(&mut self) -> Result<Option<StepInfo>, ChickenError> {
+        self.step_with_info(true)
+    }
+
+    fn step_with_info(&mut self, record: bool) -> Result<Option<StepInfo>, ChickenError> {
+        let result = self.try_step(record);
+
+        // the error is what stops the run, so an observer failing to hear about it doesn't change anything
+        if let Err(err) = &result {
+            if self.started {
+                let _ = self.notify(|observer, state| observer.on_error(state, err));
+            }
+        }
+
+        result
+    }
+
+    fn try_step(&mut self, record: bool) -> Result<Option<StepInfo>, ChickenError> {
+        if self.exited {
+            return Ok(None);
+        }
+
+        self.faulted = false;
+
+        if self.is_cancelled() {
+            Err(self.error(ErrorKind::Interrupted, format_args!("cancelled")))?
+        }
+
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.stats.steps >= max_steps {
+                Err(self.error(
+                    ErrorKind::Limit,
+                    format_args!("step limit of {} exceeded", max_steps),
+                ))?
+            }
+        }
+
+        if let Some(max_fuel) = self.limits.max_fuel {
+            if self.stats.fuel >= max_fuel {
+                Err(self.error(
+                    ErrorKind::Limit,
+                    format_args!("fuel limit of {} exceeded", max_fuel),
+                ))?
+            }
+        }
+
+        self.check_time()?;
+        self.checkpoint()?;
+
+        if !self.started {
+            self.started = true;
+            self.notify(|observer, state| observer.on_start(state))?;
+        }
+
+        self.resolve_lazy_input(self.program_counter)?;
+        let op = self.decode(self.stack.get(self.program_counter));
+
+        log::debug!("program counter {:?}", self.program_counter);
+        log::debug!(
+            "opcode {:?} ({})",
+            self.stack.get(self.program_counter),
+            self.describe_op(op)
+        );
+
+        let program_counter = self.program_counter;
+        let start = self.profile.is_some().then(Instant::now);
+
+        self.record_program_counter(program_counter);
+
+        if let Some(coverage) = self.coverage.as_mut() {
+            if let Some(index) = program_counter.checked_sub(self.program_start) {
+                coverage.record(index);
+            }
+        }
+
+        self.program_counter += 1;
+
+        // calls and returns jump too, so they're counted as taken jumps like Jump is
+        let is_jump = op == Op::Instruction(JUMP as usize)
+            || matches!(op, Op::Extension(Extension::Call | Extension::Ret));
+        let mnemonic = op.mnemonic();
+
+        self.popped.clear();
+        self.waiting = false;
+        self.pushed.clear();
+        self.self_modification = None;
+        self.step_warnings.clear();
+        self.recording = record || self.tracking_warnings();
+
+        let info = self.recording.then(|| {
+            let opcode = self.stack.get(program_counter).cloned();
+            StepInfo {
+                program_counter,
+                next_program_counter: 0,
+                instruction: opcode.as_ref().and_then(|opcode| {
+                    Instruction::from_values(opcode, self.stack.get(program_counter + 1))
+                }),
+                opcode,
+                mnemonic,
+                popped: Vec::new(),
+                pushed: Vec::new(),
+                jumped: false,
+                self_modification: None,
+                stack_depth: 0,
+            }
+        });
+
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("step", pc = program_counter, opcode = mnemonic).entered();
+
+        match op {
+            Op::Extension(extension) => self.stats.record_step(extension.mnemonic()),
+            _ => self.stats.record_opcode(op.opcode()),
+        }
+
+        let result = self.execute(op);
+
+        // a trap handler catches the errors it can before they stop the program
+        let result = match result {
+            Err(err) if self.trap_slot.is_some() => self.handle_trap(err),
+            result => result,
+        };
+
+        // a failed instruction puts back what it popped, so this leaves the VM just before it
+        if result.is_err() {
+            self.program_counter = program_counter;
+            self.faulted = true;
+        }
+
+        #[cfg(feature = "tracing")]
+        if let Err(err) = &result {
+            tracing::debug!(message = %err.message, "instruction failed");
+        }
+
+        result?;
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            pc = self.program_counter,
+            stack_depth = self.stack.len(),
+            exited = self.exited,
+            "executed {}",
+            mnemonic
+        );
+
+        let jumped = is_jump && self.program_counter != program_counter + 1;
+
+        // the step was already counted as costing 1
+        if let Some(cost_model) = &self.cost_model {
+            let string_len = match self.stack.last() {
+                Some(String(s)) => Some(s.len()),
+                _ => None,
+            };
+            self.stats.fuel = (self.stats.fuel.saturating_sub(1))
+                .saturating_add(cost_model.fuel(mnemonic, string_len));
+        }
+
+        // loads only copy a NaN that was already there
+        if op != Op::Instruction(LOAD as usize) && self.pushed.iter().any(|v| matches!(v, NaN)) {
+            self.warn(
+                WarningKind::NaN,
+                program_counter,
+                format_args!("{} produced NaN", mnemonic),
+            );
+        }
+
+        let info = info.map(|mut info| {
+            info.next_program_counter = self.program_counter;
+            info.popped = std::mem::take(&mut self.popped);
+            info.pushed = std::mem::take(&mut self.pushed);
+            info.jumped = jumped;
+            info.self_modification = self.self_modification.clone();
+            info.stack_depth = self.stack.len();
+            info
+        });
+
+        if let Some(info) = &info {
+            self.notify(|observer, state| observer.on_step(state, info))?;
+
+            if self.exited {
+                self.notify(|observer, state| observer.on_exit(state))?;
+            }
+        }
+
+        if !self.step_warnings.is_empty() {
+            self.report_warnings()?;
+        }
+
+        if let (Some(profile), Some(start)) = (self.profile.as_mut(), start) {
+            profile.record(program_counter, mnemonic, start.elapsed());
 
-    /// whether to run the debugger or not
-    pub debug: bool,
+            if jumped {
+                profile.record_jump(self.program_counter);
+            }
+        }
 
-    /// whether the Char instruction should produce an actual character instead of an HTML entity string
-    pub normal_char: bool,
+        if let Some(max_stack) = self.limits.max_stack {
+            if self.stack.len() > max_stack {
+                Err(self.error(
+                    ErrorKind::Limit,
+                    format_args!("stack limit of {} exceeded", max_stack),
+                ))?
+            }
+        }
 
-    /// whether this VM has finished execution
-    pub exited: bool,
-}
+        // stores can make strings longer without pushing anything, so this is recorded after every instruction
+        self.stats.record_memory(self.stack.len());
+        if let Some(max_memory) = self.limits.max_memory {
+            if self.memory_usage() > max_memory {
+                Err(self.error(
+                    ErrorKind::Limit,
+                    format_args!("memory limit of {} bytes exceeded", max_memory),
+                ))?
+            }
+        }
+
+        // the output is whatever string is left at the top of the stack, so it's checked in the same place as strings in general
+        if let Some(max_output_len) = self.limits.max_output_len {
+            if let Some(String(s)) = self.stack.last() {
+                if s.len() > max_output_len {
+                    Err(self.error(
+                        ErrorKind::OutputLimitExceeded,
+                        format_args!("output limit of {} bytes exceeded", max_output_len),
+                    ))?
+                }
+            }
+        }
+
+        // every string ends up at the top of the stack before it can be stored anywhere else, so that's the only one worth checking
+        if let Some(max_string_len) = self.limits.max_string_len {
+            if let Some(String(s)) = self.stack.last() {
+                if s.len() > max_string_len {
+                    Err(self.error(
+                        ErrorKind::Limit,
+                        format_args!("string length limit of {} exceeded", max_string_len),
+                    ))?
+                }
+            }
+        }
+
+        // waiting on a receive repeats the same state until something's sent, which isn't a loop the program is stuck in
+        if let Some(detector) = self.loop_detector.as_mut().filter(|_| !self.waiting) {
+            if !detector.insert(self.program_counter, &self.stack) {
+                Err(self.error(
+                    ErrorKind::Limit,
+                    format_args!("infinite loop detected at pc {}", self.program_counter),
+                ))?
+            }
+        }
+
+        log::debug!("program counter now {:?}", self.program_counter);
+        log::trace!("stack now {:?}", self.stack);
+
+        self.report_progress();
 
-impl VMState {
-    /// runs the VM until it finishes execution, then returns the top value on the stack if it's a string, or an error if it's not.
-    /// any error that occurs during execution will also be returned, along with hopefully useful debug information
-    pub fn run(&mut self) -> Result<std::string::String, ChickenError> {
         if self.debug {
-            // print some debug info
-            println!("no opcode");
-            println!("program counter {:?}", self.program_counter);
-            println!("stack {:?}", self.stack);
-            println!("press enter to step, ctrl+c to exit");
+            self.debug_step(program_counter, mnemonic, jumped)?;
+        }
 
-            // wait for enter to be pressed
-            stdout().flush().unwrap();
-            stdin().read_exact(&mut [0]).unwrap();
+        Ok(info)
+    }
+
+    /// executes a literal without the bookkeeping only the debugger, observers and limits need, returning whether it could.
+    /// literals are most of the instructions in most programs, so they're worth a shortcut, and if they're followed by an instruction that
+    /// pops them right back off, both are executed in one go. either way the stack, stats and program counter history are left
+    /// exactly as they'd be after executing the instructions one at a time.
+    /// anything that needs to see every instruction, like the debugger or observers, turns this off
+    fn step_fast(&mut self) -> bool {
+        if self.exited
+            || self.debug
+            || !self.observers.is_empty()
+            || self.warnings.is_some()
+            || self.profile.is_some()
+            || self.coverage.is_some()
+            || self.loop_detector.is_some()
+            || self.lazy_input.is_some()
+            || self
+                .limits
+                .max_steps
+                .is_some_and(|max_steps| self.stats.steps >= max_steps)
+            || self
+                .limits
+                .max_fuel
+                .is_some_and(|max_fuel| self.stats.fuel >= max_fuel)
+            || self.cost_model.is_some()
+            || self
+                .limits
+                .max_stack
+                .is_some_and(|max_stack| self.stack.len() + 1 > max_stack)
+            || self.limits.max_string_len.is_some()
+            || self.limits.max_output_len.is_some()
+            || self.limits.max_memory.is_some()
+            || self.time_check_due()
+            || self.checkpoint_due()
+            || self.strict
+            || self.is_cancelled()
+        {
+            return false;
         }
 
-        while !self.exited {
-            self.step()?;
+        let pc = self.program_counter;
+        let Op::Push(literal) = self.decode(self.stack.get(pc)) else {
+            return false;
+        };
+
+        self.started = true;
+        self.recording = false;
+        self.faulted = false;
+
+        if !self.step_fused(literal) {
+            self.stats.record_opcode(Some(literal.wrapping_add(10)));
+            self.record_program_counter(pc);
+            self.program_counter = pc + 1;
+            self.push(Num(literal));
+            self.report_progress();
         }
 
-        // return the top value of the stack if it's a string
-        // also converts all HTML entities back to their normal character representations
-        match self.stack.pop() {
-            Some(String(s)) => Ok(html_escape::decode_html_entities(&s).to_string()),
+        true
+    }
+
+    /// executes the literal at the program counter and the instruction after it in one go if they can be, returning whether they were.
+    /// this skips pushing the literal only to pop it right back off
+    fn step_fused(&mut self, literal: isize) -> bool {
+        let pc = self.program_counter;
+        let op = match self.stack.get(pc + 1) {
+            Some(Num(op)) => *op,
+            _ => return false,
+        };
+
+        // loads are only fused when they load from the stack itself, like `push n; load 0`
+        let fusable = match op {
+            ADD | SUBTRACT | MULTIPLY => true,
+            LOAD => matches!(
+                (self.stack.get(pc + 2), self.stack.first()),
+                (Some(Num(0)), Some(Ptr(_)))
+            ),
+            _ => false,
+        };
+        if !fusable
+            || !self.fuse_instructions
+            || self
+                .limits
+                .max_steps
+                .is_some_and(|max_steps| self.stats.steps + 2 > max_steps)
+            || self
+                .limits
+                .max_fuel
+                .is_some_and(|max_fuel| self.stats.fuel.saturating_add(2) > max_fuel)
+        {
+            return false;
+        }
+
+        // account for the literal as if it had been pushed and popped
+        let literal_opcode = literal.wrapping_add(10);
+        let literal = Num(literal);
+        self.stats.record_opcode(Some(literal_opcode));
+        self.stats.record_push(&literal, self.stack.len() + 1);
+        self.stats.record_opcode(Some(op));
+        self.stats.record_pop(&literal);
+
+        let (next_pc, value) = match op {
+            ADD => (pc + 2, self.pop().unwrap_or(Undefined) + literal),
+            SUBTRACT => (pc + 2, &self.pop().unwrap_or(Undefined) - &literal),
+            MULTIPLY => (pc + 2, &self.pop().unwrap_or(Undefined) * &literal),
+            _ => {
+                let value = match self.stack.first() {
+                    Some(Ptr(p)) => literal
+                        .to_num_option()
+                        .and_then(|index| usize::try_from(index).ok())
+                        .and_then(|index| self.stack.get(p.saturating_add(index)))
+                        .cloned(),
+                    _ => None,
+                };
+                (pc + 3, value.unwrap_or(Undefined))
+            }
+        };
+
+        self.record_program_counter(pc);
+        self.record_program_counter(pc + 1);
+
+        self.program_counter = next_pc;
+        self.push(value);
+        self.report_progress();
+
+        true
+    }
+
+    /// explains how execution ended up at an address outside of the program that can't be executed
+    fn left_program_error(&self, address: usize) -> ChickenError {
+        let location = if address >= self.stack.len() {
+            format!("ran off the end of the stack at {}", address)
+        } else if address > self.program_end {
+            format!(
+                "ran into {:?} at {}, past the exit at {}",
+                self.stack[address], address, self.program_end
+            )
+        } else {
+            format!(
+                "ran into {:?} at {}, before the start of the program at {}",
+                self.stack[address], address, self.program_start
+            )
+        };
+
+        let cause = match self.last_jump {
+            Some((from, offset)) => format!("the last jump taken was from {} by {}", from, offset),
+            None => "no jumps were taken".to_string(),
+        };
+
+        self.error(
+            ErrorKind::Runtime,
+            format_args!("execution left the program: {}, and {}", location, cause),
+        )
+    }
+
+    /// pushes a value onto the stack, keeping track of it in the stats
+    fn push(&mut self, value: Value) {
+        self.stats.record_push(&value, self.stack.len() + 1);
+
+        if self.recording {
+            self.pushed.push(value.clone());
+        }
+
+        self.stack.push(value);
+        self.stats.stack_capacity = self.stack.capacity();
+    }
+
+    /// pops a value off of the stack, keeping track of it in the stats
+    fn pop(&mut self) -> Option<Value> {
+        let value = self.stack.pop();
+
+        if let Some(value) = &value {
+            self.stats.record_pop(value);
+
+            if self.recording {
+                self.popped.push(value.clone());
+            }
+        }
+
+        value
+    }
+
+    /// puts the values an instruction popped back on the stack, bottom first, so an instruction that fails doesn't change the stack.
+    /// this is done after the error is made, so the error shows the stack as it was when the instruction failed
+    fn unpop<I: IntoIterator<Item = Option<Value>>>(
+        &mut self,
+        err: ChickenError,
+        popped: I,
+    ) -> ChickenError {
+        for value in popped.into_iter().flatten() {
+            self.stats.record_unpop(&value);
+            self.stack.push(value);
+        }
+
+        err
+    }
+
+    /// adds a program counter to the history of recently executed instructions
+    fn record_program_counter(&mut self, program_counter: usize) {
+        self.pc_history[self.pc_history_len % PC_HISTORY_LEN] = program_counter;
+        self.pc_history_len += 1;
+    }
 
-            s => Err(ChickenError {
-                message: format!("invalid value {:?} on exit", s),
+    /// creates an error of the given kind, with the current state of the VM attached for debugging.
+    /// the message is only formatted if it's needed, so lean errors don't cost anything to make
+    fn error(&self, kind: ErrorKind, message: fmt::Arguments) -> ChickenError {
+        if self.lean_errors {
+            return ChickenError {
+                kind,
+                message: std::string::String::new(),
                 program_counter: self.program_counter,
-                stack: self.stack.to_vec(),
-            })?,
+                stack: Vec::new(),
+                stack_omitted: self.stack.len(),
+                recent_program_counters: Vec::new(),
+                partial_output: None,
+                program_range: self.program_start..self.program_end + 1,
+                source_map: None,
+                color: self.color,
+            };
+        }
+
+        let (stack, stack_omitted) = self.stack_capture.capture(&self.stack);
+
+        ChickenError {
+            kind,
+            message: message.to_string(),
+            program_counter: self.program_counter,
+            stack,
+            stack_omitted,
+            recent_program_counters: self.recent_program_counters(),
+            partial_output: self
+                .data()
+                .last()
+                .and_then(Value::as_str)
+                .map(|s| self.process_output(s).into()),
+            program_range: self.program_start..self.program_end + 1,
+            source_map: self.source_map.clone(),
+            color: self.color,
         }
     }
 
-    /// single steps the VM, running one instruction at a time
-    pub fn step(&mut self) -> Result<(), ChickenError> {
-        if self.exited {
+    /// the program counters of the last few instructions executed, oldest first
+    fn recent_program_counters(&self) -> Vec<usize> {
+        (self.pc_history_len.saturating_sub(PC_HISTORY_LEN)..self.pc_history_len)
+            .map(|i| self.pc_history[i % PC_HISTORY_LEN])
+            .collect()
+    }
+
+    /// fills in the lazy input if it's at the given address and hasn't been read yet
+    fn resolve_lazy_input(&mut self, address: usize) -> Result<(), ChickenError> {
+        if address != LAZY_INPUT_ADDRESS {
             return Ok(());
         }
 
-        let op = self.stack.get(self.program_counter);
+        if let Some(input) = self.lazy_input.take() {
+            let value = input.read().clone().map_err(|err| {
+                self.error(ErrorKind::Io, format_args!("error reading input: {}", err))
+            })?;
 
-        if self.debug {
-            // print some debug information
-            println!("program counter {:?}", self.program_counter);
-            print!("opcode {:?}", op);
-            println!(
-                " ({})",
-                match &op {
-                    Some(Num(EXIT)) => "axe/exit".to_string(),
-                    Some(Num(CHICKEN)) => "chicken".to_string(),
-                    Some(Num(ADD)) => "add".to_string(),
-                    Some(Num(SUBTRACT)) => "fox/subtract".to_string(),
-                    Some(Num(MULTIPLY)) => "rooster/multiply".to_string(),
-                    Some(Num(COMPARE)) => "compare".to_string(),
-                    Some(Num(LOAD)) => format!(
-                        "pick/load from {:?}",
-                        self.stack
-                            .get(self.program_counter + 1)
-                            .unwrap_or(&Undefined)
-                    ),
-                    Some(Num(STORE)) => "peck/store".to_string(),
-                    Some(Num(JUMP)) => "fr/jump".to_string(),
-                    Some(Num(CHAR)) => "bbq/chr".to_string(),
-                    Some(Num(n)) => format!("literal {}", n),
-                    _ => "unknown".to_string(),
-                }
-            );
+            self.stats.record_store(&self.stack[address], &value);
+            self.stack[address] = value;
         }
 
-        self.program_counter += 1;
+        Ok(())
+    }
 
-        match &op {
-            // terminates the program
-            Some(Num(EXIT)) => self.exited = true,
-
-            // pushes the string "chicken" onto the stack
-            Some(Num(CHICKEN)) => self.stack.push(String("chicken".to_string())),
-
-            // pops the two values off the stack, adds them together, then pushes the result back on the stack
-            // all math operations have the 2nd value from the top as the right hand value, and the top value as the left hand value
-            // if one of the values is a string, the two values are concatenated like in javascript and any numbers are converted to decimal strings
-            // if both of the values are numbers, they will be added like normal
-            Some(Num(ADD)) => {
-                let b = self.stack.pop().unwrap_or(Undefined);
-                let a = self.stack.pop().unwrap_or(Undefined);
-                self.stack.push(a + b)
-            }
-
-            // subtracts the two values at the top of the stack
-            // if either or both of the values are strings, they will be converted to numbers then subtracted
-            Some(Num(SUBTRACT)) => {
-                let b = self.stack.pop().unwrap_or(Undefined);
-                let a = self.stack.pop().unwrap_or(Undefined);
-                self.stack.push(a - b)
-            }
-
-            // multiplies the two values at the top of the stack
-            // if either or both of the values are strings, they will be converted to numbers then multiplied
-            Some(Num(MULTIPLY)) => {
-                let b = self.stack.pop().unwrap_or(Undefined);
-                let a = self.stack.pop().unwrap_or(Undefined);
-                self.stack.push(a * b)
-            }
-
-            // pops the two stack values, compares them for equality, then pushes the result as a truthy or falsy value
-            Some(Num(COMPARE)) => {
-                let b = self.stack.pop() == self.stack.pop();
-                self.stack.push(b.into())
-            }
-
-            // double wide instruction. next opcode indicates the address on the stack to load from
-            // the top value on the stack is popped and used as an index into that address
-            // the address of 0 is a pointer to the entire stack, and as such indexing into it will index into the stack
-            // any other address will index into the stack at that address, and if there's a string there you can access the individual characters in it
-            // the behavior of indexing into numbers is not yet known
-            Some(Num(LOAD)) => {
-                let addr: usize = match self
-                    .stack
-                    .get(self.program_counter)
-                    .unwrap_or(&Undefined)
-                    .to_num_option()
-                    .and_then(|n| n.try_into().ok())
-                {
-                    Some(n) => n,
-                    None => {
-                        self.program_counter += 1;
-                        self.stack.push(Undefined);
-                        return Ok(());
-                    }
-                };
-                self.program_counter += 1;
-
-                let index: usize = match self
-                    .stack
-                    .pop()
-                    .unwrap_or(Undefined)
-                    .to_num_option()
-                    .and_then(|n| n.try_into().ok())
-                {
-                    Some(n) => n,
-                    None => {
-                        self.stack.push(Undefined);
-                        return Ok(());
-                    }
-                };
+    /// calls a function on every observer, giving it access to the VM state
+    fn notify<F: FnMut(&mut Box<dyn Observer + Send>, &Self) -> std::io::Result<()>>(
+        &mut self,
+        mut f: F,
+    ) -> Result<(), ChickenError> {
+        // the observers have to be moved out of the VM so they can borrow it
+        let mut observers = std::mem::take(&mut self.observers);
+        let result = observers.iter_mut().try_for_each(|o| f(o, self));
+        self.observers = observers;
+
+        result.map_err(|err| self.error(ErrorKind::Io, format_args!("observer failed: {}", err)))
+    }
+
+    /// whether warnings are worth looking for, because they're being collected or there are observers to tell about them
+    pub(crate) fn tracking_warnings(&self) -> bool {
+        self.warnings.is_some() || !self.observers.is_empty()
+    }
+
+    /// records a warning caused by the instruction at the given address, if warnings are being tracked
+    pub(crate) fn warn(
+        &mut self,
+        kind: WarningKind,
+        program_counter: usize,
+        message: fmt::Arguments,
+    ) {
+        if self.tracking_warnings() {
+            self.step_warnings.push(Warning {
+                kind,
+                program_counter,
+                message: message.to_string(),
+            });
+        }
+    }
 
-                match self.stack.get(addr) {
-                    Some(String(s)) => match s.chars().nth(index) {
-                        Some(c) => self.stack.push(String(c.to_string())),
-                        None => self.stack.push(Undefined),
-                    },
-                    Some(Ptr(p)) => match self.stack.get(p + index) {
-                        Some(v) => self.stack.push(v.clone()),
-                        None => self.stack.push(Undefined),
-                    },
-                    _ => self.stack.push(Undefined),
+    /// tells the observers about the warnings caused by the instruction that just finished, and collects the new ones
+    fn report_warnings(&mut self) -> Result<(), ChickenError> {
+        let step_warnings = std::mem::take(&mut self.step_warnings);
+
+        for warning in step_warnings.iter() {
+            self.notify(|observer, state| observer.on_warning(state, warning))?;
+
+            if let Some(warnings) = self.warnings.as_mut() {
+                if !warnings
+                    .iter()
+                    .any(|w| w.kind == warning.kind && w.program_counter == warning.program_counter)
+                {
+                    warnings.push(warning.clone());
                 }
             }
+        }
 
-            // top of the stack contains the address on the stack to store to. the second topmost value on the stack gets stored at that address
-            // both values are popped off the stack
-            Some(Num(STORE)) => {
-                let val = self.stack.pop();
-                match val.as_ref().and_then(|v| v.to_num_option()) {
-                    Some(n) => {
-                        // TODO: add error checking here
-                        self.stack[n as usize] = self
-                            .stack
-                            .pop()
-                            .ok_or_else(|| ChickenError {
-                                message: "no more items in stack".to_string(),
-                                program_counter: self.program_counter,
-                                stack: self.stack.to_vec(),
-                            })?
-                    }
-                    None => Err(ChickenError {
-                        message: format!("invalid address {:?}", val),
-                        program_counter: self.program_counter,
-                        stack: self.stack.to_vec(),
-                    })?,
-                }
-            },
+        self.step_warnings = step_warnings;
+        Ok(())
+    }
 
-            // top of the stack is a relative offset to jump to. the value below that is the condition. jumps only occur if the condition is truthy
-            Some(Num(JUMP)) => {
-                let val = self.stack.pop();
-                match val.as_ref().and_then(|v| v.to_num_option()) {
-                    Some(rel) => {
-                        if self.stack.pop().map(|v| v.is_truthy()).unwrap_or(false) {
-                            self.program_counter = self
-                                .program_counter
-                                .checked_add_signed(rel)
-                                .ok_or_else(|| ChickenError {
-                                    message: format!("jump to relative addr {:?} overflowed", val),
-                                    program_counter: self.program_counter,
-                                    stack: self.stack.to_vec(),
-                                })?;
-                        }
-                    }
-                    None => Err(ChickenError {
-                        message: format!("invalid relative address {:?}", val),
-                        program_counter: self.program_counter,
-                        stack: self.stack.to_vec(),
-                    })?,
-                }
-            },
+    /// takes a [Snapshot] of the current state of this VM, which can be turned back into a VM with [VMBuilder::from_snapshot]
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            stack: self.stack.clone(),
+            program_counter: self.program_counter,
+            program_start: self.program_start,
+            program_end: self.program_end,
+            exited: self.exited,
+            normal_char: self.normal_char,
+            byte_char: self.byte_char,
+            char_entity: self.char_entity,
+            extensions: self.extensions,
+            random_state: self.rng.state,
+            return_stack: self.return_stack.clone(),
+            trap_slot: self.trap_slot,
+        }
+    }
 
-            // interprets the value at the top of the stack as ASCII and either pushes its corresponding HTML entity or character
-            Some(Num(CHAR)) => {
-                if self.normal_char {
-                    let val = self.stack.pop();
-                    match val
-                        .as_ref()
-                        .and_then(|v| v.to_num_option())
-                        .and_then(|n| n.try_into().ok())
-                        .and_then(char::from_u32)
-                    {
-                        Some(c) => self.stack.push(String(c.to_string())),
-                        None => Err(ChickenError {
-                            message: format!("{:?} not a number", val),
-                            program_counter: self.program_counter,
-                            stack: self.stack.to_vec(),
-                        })?,
-                    }
-                } else {
-                    let s = self.stack.pop().unwrap_or(Undefined).to_string();
-                    self.stack.push(String(format!("&#{};", s)))
+    /// makes a copy of this VM that runs separately from it, so other paths through the program can be explored without starting over.
+    /// observers and the progress callback can't be copied, so the fork has none. a lazy input that hasn't been read yet is shared with the fork, so it's still only read once
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    ///
+    /// let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    /// vm.step().unwrap();
+    ///
+    /// let mut fork = vm.fork();
+    /// fork.stack[1] = "xyz".into();
+    ///
+    /// assert_eq!(fork.run(), Ok("xyz".to_string()));
+    /// assert_eq!(vm.run(), Ok("abc".to_string()));
+    /// ```
+    pub fn fork(&self) -> Self {
+        Self {
+            stack: self.stack.clone(),
+            program_counter: self.program_counter,
+            program_start: self.program_start,
+            program_end: self.program_end,
+            debug: self.debug,
+            normal_char: self.normal_char,
+            byte_char: self.byte_char,
+            char_entity: self.char_entity,
+            entity_decoding: self.entity_decoding,
+            output_processors: self.output_processors.clone(),
+            freeze_program: self.freeze_program,
+            strict: self.strict,
+            exit_policy: self.exit_policy,
+            stack_capture: self.stack_capture,
+            lean_errors: self.lean_errors,
+            color: self.color,
+            fuse_instructions: self.fuse_instructions,
+            exited: self.exited,
+            loop_detector: self.loop_detector.clone(),
+            profile: self.profile.clone(),
+            coverage: self.coverage.clone(),
+            warnings: self.warnings.clone(),
+            stats: self.stats.clone(),
+            observers: Vec::new(),
+            limits: self.limits,
+            cost_model: self.cost_model.clone(),
+            started: self.started,
+            recording: false,
+            popped: Vec::new(),
+            pushed: Vec::new(),
+            self_modification: None,
+            modified_program: self.modified_program.clone(),
+            step_warnings: Vec::new(),
+            lazy_input: self.lazy_input.clone(),
+            pc_history: self.pc_history,
+            pc_history_len: self.pc_history_len,
+            progress_reporter: None,
+            checkpointer: None,
+            cancellation_token: self.cancellation_token.clone(),
+            source_map: self.source_map.clone(),
+            last_jump: self.last_jump,
+            faulted: self.faulted,
+            deadline: self.deadline,
+            host_fns: self.host_fns.clone(),
+            channels: self.channels.clone(),
+            waiting: self.waiting,
+            extensions: self.extensions,
+            seed: self.seed,
+            rng: self.rng,
+            return_stack: self.return_stack.clone(),
+            trap_slot: self.trap_slot,
+            io: self.io.clone(),
+            initial_program: self.initial_program.clone(),
+            initial_data: self.initial_data.clone(),
+            start_offset: self.start_offset,
+            debugger: self.debugger.clone(),
+        }
+    }
+
+    /// puts the VM back the way it was when it was built, with `input` as its only input, so the same program can be run over lots of inputs
+    /// without building a new VM for each one. the stack keeps its allocation, and everything set on the builder stays the same.
+    /// the stats and warnings start over, but profiling and coverage information keep adding up over every run
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    ///
+    /// let mut vm = VMBuilder::from_path("examples/99chickens.chicken").unwrap().build();
+    ///
+    /// vm.reset(1);
+    /// assert_eq!(vm.run().unwrap(), "1 chicken\nno chickens\n");
+    ///
+    /// vm.reset(0);
+    /// assert_eq!(vm.run().unwrap(), "no chickens\n");
+    /// ```
+    pub fn reset<T: Into<Value>>(&mut self, input: T) {
+        self.stack.clear();
+        self.stack.push(Ptr(0));
+        self.stack.push(input.into());
+        self.program_start = self.stack.len();
+        self.stack.extend(self.initial_program.iter().cloned());
+        self.program_end = self.stack.len();
+        self.stack.push(Num(0));
+        self.stack.extend(self.initial_data.iter().cloned());
+
+        // the program moves if the VM was built with a different number of inputs
+        if let Some(source_map) = self.source_map.as_mut() {
+            if source_map.program_start != self.program_start {
+                *source_map = Arc::new(StackSourceMap {
+                    source_map: source_map.source_map.clone(),
+                    program_start: self.program_start,
+                    program_end: self.program_end,
+                });
+            }
+        }
+
+        self.program_counter = self.program_start + self.start_offset;
+        self.exited = false;
+        self.stats = Stats::new(&self.stack);
+        self.stats.stack_capacity = self.stack.capacity();
+
+        if let Some(detector) = self.loop_detector.as_mut() {
+            detector.clear();
+            detector.insert(self.program_counter, &self.stack);
+        }
+
+        if let Some(reporter) = self.progress_reporter.as_mut() {
+            reporter.reset();
+        }
+
+        if let Some(checkpointer) = self.checkpointer.as_mut() {
+            checkpointer.reset();
+        }
+
+        self.started = false;
+        self.self_modification = None;
+        self.modified_program.clear();
+
+        if let Some(warnings) = self.warnings.as_mut() {
+            warnings.clear();
+        }
+        self.lazy_input = None;
+        self.pc_history_len = 0;
+        self.last_jump = None;
+        self.faulted = false;
+        self.deadline = Deadline::default();
+        self.rng = Rng { state: self.seed };
+        self.return_stack.clear();
+        self.trap_slot = None;
+        self.waiting = false;
+    }
+
+    /// decodes the instructions on the stack from `radius` cells before `center` to `radius` cells after it, for showing the code around an address.
+    /// cells that can't be decoded are None, and the center is always decoded as an instruction even if it's the operand of a Load just before it
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{Instruction, VMBuilder};
+    ///
+    /// let vm = VMBuilder::from_opcodes([11, 6, 0]).build();
+    /// assert_eq!(
+    ///     vm.disassemble_window(3, 1),
+    ///     vec![(2, Some(Instruction::Push(1))), (3, Some(Instruction::Load(0)))]
+    /// );
+    /// ```
+    pub fn disassemble_window(
+        &self,
+        center: usize,
+        radius: usize,
+    ) -> Vec<(usize, Option<Instruction>)> {
+        disassemble_stack(&self.stack, center, radius)
+    }
+
+    /// a short description of where the VM is up to, for when it's stopped partway through: the program counter, the code around it and the top `max_cells` values on the stack.
+    /// unlike the [Display](fmt::Display) impl, this stays short however big the stack gets
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    ///
+    /// let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    /// vm.step().unwrap();
+    ///
+    /// let dump = vm.dump(2);
+    /// assert!(dump.starts_with("program counter: 3\n"));
+    /// assert!(dump.contains("    ->      3: load 0\n"));
+    /// assert!(dump.ends_with("top of the stack:\n    ... 5 more\n         5: 0\n         6: 1\n"));
+    /// ```
+    pub fn dump(&self, max_cells: usize) -> std::string::String {
+        use std::fmt::Write;
+
+        let mut text = format!("program counter: {}\n", self.program_counter);
+        if let Some(source) = (self.source_map()).and_then(|map| map.describe(self.program_counter))
+        {
+            let _ = writeln!(text, "source: {}", source);
+        }
+        let _ = write_window(
+            &mut text,
+            &self.stack,
+            self.program_counter,
+            self.source_map(),
+        );
+
+        text.push_str("top of the stack:\n");
+        let start = self.stack.len().saturating_sub(max_cells);
+        if start > 0 {
+            let _ = writeln!(text, "    ... {} more", start);
+        }
+
+        for (address, value) in self.stack.iter().enumerate().skip(start) {
+            match (self.source_map()).and_then(|map| map.symbol(address)) {
+                Some(symbol) => {
+                    let _ = writeln!(
+                        text,
+                        "    {:>6} ({}): {}",
+                        address,
+                        symbol,
+                        display_cell(value)
+                    );
+                }
+                None => {
+                    let _ = writeln!(text, "    {:>6}: {}", address, display_cell(value));
                 }
             }
+        }
 
-            // pushes n - 10 to the stack
-            Some(Num(n)) => self.stack.push(Num(n - 10)),
+        text
+    }
 
-            s => Err(ChickenError {
-                message: format!("invalid opcode {:?}", s),
-                program_counter: self.program_counter,
-                stack: self.stack.to_vec(),
-            })?,
+    // whether the cancellation token, if there is one, has been cancelled
+    fn is_cancelled(&self) -> bool {
+        (self.cancellation_token.as_ref()).is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// a hash of the program counter, whether the VM has exited and the entire stack.
+    /// it's the same on every platform and version of chicken, so tests can compare states against hashes written down ahead of time
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    ///
+    /// let mut a = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    /// let b = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    /// assert_eq!(a.state_hash(), b.state_hash());
+    ///
+    /// a.step().unwrap();
+    /// assert_ne!(a.state_hash(), b.state_hash());
+    /// ```
+    pub fn state_hash(&self) -> u64 {
+        loops::stable_state_hash(self.program_counter, self.exited, &self.stack)
+    }
+
+    /// the first input passed to the program, at address 1
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{Value, VMBuilder};
+    ///
+    /// let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    /// assert_eq!(vm.input(), Some(&Value::from("abc")));
+    /// assert_eq!(vm.program(), &[Value::Num(11), Value::Num(6), Value::Num(0)]);
+    /// assert!(vm.data().is_empty());
+    ///
+    /// vm.step().unwrap();
+    /// assert_eq!(vm.data(), &[Value::Num(1)]);
+    /// ```
+    pub fn input(&self) -> Option<&Value> {
+        self.inputs().first()
+    }
+
+    /// all of the inputs passed to the program, between the stack pointer and the program
+    pub fn inputs(&self) -> &[Value] {
+        self.stack.get(1..self.program_start).unwrap_or_default()
+    }
+
+    /// the opcodes of the program as they are on the stack, which can differ from the original program if it modified itself.
+    /// the exit appended after the program isn't included
+    pub fn program(&self) -> &[Value] {
+        let end = self.program_end.min(self.stack.len());
+        self.stack.get(self.program_start..end).unwrap_or_default()
+    }
+
+    /// everything on the stack after the program and the exit appended to it, where the program keeps its data
+    pub fn data(&self) -> &[Value] {
+        self.stack.get(self.program_end + 1..).unwrap_or_default()
+    }
+
+    /// compares the stack of this VM to the stack of another one, usually a later state of the same program
+    pub fn diff(&self, other: &VMState) -> StackDiff {
+        StackDiff::between(&self.stack, &other.stack)
+    }
+
+    /// statistics about this run of the program so far
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// roughly how many bytes the stack takes up right now, counting each value on it and every byte of its strings.
+    /// strings shared between several values are counted once for each of them, and arrays only count as a single value,
+    /// so this is only an estimate, but it's the number [Limits::max_memory] is checked against
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{Value, VMBuilder};
+    ///
+    /// let mut vm = VMBuilder::from_chicken("chicken").build();
+    /// let before = vm.memory_usage();
+    ///
+    /// vm.step().unwrap();
+    /// assert_eq!(vm.memory_usage(), before + std::mem::size_of::<Value>() + "chicken".len());
+    /// assert_eq!(vm.stats().peak_memory, vm.memory_usage());
+    /// ```
+    pub fn memory_usage(&self) -> usize {
+        self.stats.memory(self.stack.len())
+    }
+}
+
+/// how many instructions on either side of the program counter are shown in errors and the debugger
+const CONTEXT_RADIUS: usize = 3;
+
+/// decodes the instructions on the stack from `radius` cells before `center` to `radius` cells after it.
+/// cells that aren't instructions are decoded as None. decoding starts at the start of the window,
+/// but the center is always decoded as an instruction, even if it's the operand of a Load just before it
+fn disassemble_stack(
+    stack: &[Value],
+    center: usize,
+    radius: usize,
+) -> Vec<(usize, Option<Instruction>)> {
+    let mut lines = Vec::new();
+    let mut address = center.saturating_sub(radius);
+    let end = center
+        .saturating_add(radius)
+        .min(stack.len().saturating_sub(1));
+
+    while address <= end {
+        let instruction = Instruction::from_values(&stack[address], stack.get(address + 1));
+        lines.push((address, instruction));
+
+        let next = address + instruction.map_or(1, |i| i.width());
+        address = match address < center && next > center {
+            true => center,
+            false => next,
+        };
+    }
+
+    lines
+}
+
+/// writes a disassembly window with an arrow pointing at the program counter, one instruction per line
+fn write_window(
+    f: &mut impl std::fmt::Write,
+    stack: &[Value],
+    program_counter: usize,
+    source_map: Option<&StackSourceMap>,
+) -> fmt::Result {
+    write_window_at(f, stack, 0, program_counter, source_map)
+}
+
+/// writes a disassembly window like [write_window], from only some of the cells on the stack starting at the given address
+fn write_window_at(
+    f: &mut impl std::fmt::Write,
+    cells: &[Value],
+    first_address: usize,
+    program_counter: usize,
+    source_map: Option<&StackSourceMap>,
+) -> fmt::Result {
+    let center = program_counter - first_address;
+    for (index, instruction) in disassemble_stack(cells, center, CONTEXT_RADIUS) {
+        let instruction = match instruction {
+            Some(instruction) => instruction.to_string(),
+            None => format!("invalid opcode {}", display_cell(&cells[index])),
+        };
+        let marker = if index == center { "->" } else { "  " };
+
+        // labels go on a line of their own before what they point at, like in the assembly
+        if let Some(label) = source_map.and_then(|map| map.label_at(first_address + index)) {
+            writeln!(f, "           {}:", label)?;
         }
 
-        if self.debug {
-            // print some more debug info
-            println!("program counter now {:?}", self.program_counter);
-            println!("stack now {:?}", self.stack);
+        writeln!(
+            f,
+            "    {} {:>6}: {}",
+            marker,
+            first_address + index,
+            instruction
+        )?;
+    }
+
+    if center >= cells.len() {
+        writeln!(
+            f,
+            "    -> {:>6}: past the end of the stack",
+            program_counter
+        )?;
+    }
+
+    Ok(())
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// renders a value on the stack for the debugger, quoting strings so they can be told apart from numbers
+pub(crate) fn display_cell(value: &Value) -> std::string::String {
+    match value {
+        String(s) => format!("{:?}", s),
+        Ptr(p) => format!("&{}", p),
+        value => value.to_string(),
+    }
+}
+
+/// lists every cell on the stack with its address, marking the stack pointer, the inputs, the end of the program, cells of the program it's stored into and the program counter.
+/// values are colored by their [StackRegion] when colors are on
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::VMBuilder;
+///
+/// let vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+/// assert_eq!(
+///     vm.to_string(),
+///     "address  value  notes
+///       0  &0     stack pointer
+///       1  \"abc\"  input
+///       2  11     <- pc
+///       3  6
+///       4  0
+///       5  0      end of program
+/// "
+/// );
+/// ```
+impl fmt::Display for VMState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // very long strings would push every note off the screen, so they don't make the column any wider
+        const MAX_VALUE_WIDTH: usize = 40;
+
+        let cells = self.stack.iter().map(display_cell).collect::<Vec<_>>();
+        let address_width = self.stack.len().saturating_sub(1).to_string().len().max(7);
+        let value_width = cells
+            .iter()
+            .map(|cell| cell.chars().count())
+            .filter(|width| *width <= MAX_VALUE_WIDTH)
+            .max()
+            .unwrap_or(0)
+            .max(5);
+
+        writeln!(
+            f,
+            "{:>address_width$}  {:<value_width$}  notes",
+            "address", "value"
+        )?;
+
+        for (address, cell) in cells.iter().enumerate() {
+            let mut notes = Vec::new();
+
+            // the program and what's above it make up most of the stack, so only the other regions are noted
+            let region = self.region(address);
+            if !matches!(region, StackRegion::Program | StackRegion::Data) {
+                notes.push(region.name());
+            }
+
+            if address == self.program_counter {
+                notes.push("<- pc");
+            }
+
+            // labels are marked where they are, and the rest of the program is named after the closest one before it
+            let label = (self.source_map()).and_then(|map| match map.label_at(address) {
+                Some(label) => Some(format!("label {}", label)),
+                None => map.symbol(address),
+            });
+            if let Some(label) = &label {
+                notes.push(label);
+            }
+
+            // the padding goes outside of the color, so it can still be trimmed off the end
+            let padding = value_width.saturating_sub(cell.chars().count());
+            let line = format!(
+                "{:>address_width$}  {}{}  {}",
+                address,
+                region.paint(cell, self.color.enabled(Stream::Stdout)),
+                " ".repeat(padding),
+                notes.join(", ")
+            );
+            writeln!(f, "{}", line.trim_end())?;
+        }
 
-            // wait for enter to be pressed, effectively single stepping
-            stdout().flush().unwrap();
-            stdin().read_exact(&mut [0]).unwrap();
+        if self.program_counter >= self.stack.len() {
+            writeln!(
+                f,
+                "program counter {} is past the end of the stack",
+                self.program_counter
+            )?;
         }
 
         Ok(())
@@ -0,0 +1,187 @@
+use crate::{ChickenError, ErrorKind, VMState};
+use std::time::{Duration, Instant};
+
+/// limits on how many resources a program is allowed to use, set with [VMBuilder::limits](crate::VMBuilder::limits).
+/// going past any of these stops the program with an [ErrorKind::Limit](crate::ErrorKind::Limit) error,
+/// except for the output limit, which has an [ErrorKind::OutputLimitExceeded](crate::ErrorKind::OutputLimitExceeded) error of its own
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::{ErrorKind, Limits, VMBuilder};
+///
+/// let limits = Limits {
+///     max_steps: Some(1000),
+///     ..Default::default()
+/// };
+///
+/// // jumps back to the start of the program forever
+/// let err = VMBuilder::from_opcodes([11, 10, 15, 3, 8]).limits(limits).build().run().unwrap_err();
+///
+/// assert_eq!(err.kind, ErrorKind::Limit);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// the maximum number of instructions that can be executed
+    pub max_steps: Option<u64>,
+
+    /// the maximum amount of fuel the instructions executed can use up, as counted by [VMBuilder::cost_model](crate::VMBuilder::cost_model).
+    /// without a cost model every instruction costs 1, which makes this the same as the step limit
+    pub max_fuel: Option<u64>,
+
+    /// the maximum number of values that can be on the stack, including the program itself
+    pub max_stack: Option<usize>,
+
+    /// the maximum length of any string on the stack, in bytes
+    pub max_string_len: Option<usize>,
+
+    /// the maximum length of the output, in bytes. the output is built up at the top of the stack, so the string there is checked
+    /// every step, stopping programs that make huge amounts of text, like ones that keep adding a string to itself, long before they'd exit
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{ErrorKind, Limits, VMBuilder};
+    ///
+    /// let limits = Limits {
+    ///     max_output_len: Some(1000),
+    ///     ..Default::default()
+    /// };
+    ///
+    /// // pushes "chicken", then adds it to itself forever
+    /// let err = VMBuilder::from_opcodes([1, 23, 6, 0, 2, 11, 10, 19, 3, 8])
+    ///     .limits(limits)
+    ///     .build()
+    ///     .run()
+    ///     .unwrap_err();
+    ///
+    /// assert_eq!(err.kind, ErrorKind::OutputLimitExceeded);
+    /// ```
+    pub max_output_len: Option<usize>,
+
+    /// the maximum amount of memory the stack can take up, in bytes, as counted by [VMState::memory_usage].
+    /// this catches programs that fill the stack with lots of long strings, which neither the stack limit nor the string length limit stop on their own
+    pub max_memory: Option<usize>,
+
+    /// the maximum amount of wall clock time the program can run for, counted from its first instruction.
+    /// this includes any time spent paused between calls to [VMState::run_steps] or in the debugger,
+    /// and is only checked every so many instructions, so a program can go a little over it
+    pub max_time: Option<Duration>,
+}
+
+impl Limits {
+    /// no limits at all, which is the default
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// conservative limits for running programs that can't be trusted, like ones submitted to an online service.
+    /// use [VMBuilder::sandbox](crate::VMBuilder::sandbox) to set these along with strict mode
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{ErrorKind, Limits, VMBuilder};
+    ///
+    /// // pushes "chicken" forever
+    /// let err = VMBuilder::from_opcodes([1, 11, 10, 16, 3, 8])
+    ///     .limits(Limits::untrusted())
+    ///     .build()
+    ///     .run()
+    ///     .unwrap_err();
+    ///
+    /// assert_eq!(err.kind, ErrorKind::Limit);
+    /// assert_eq!(err.message, "stack limit of 100000 exceeded");
+    /// ```
+    pub fn untrusted() -> Self {
+        Self {
+            max_steps: Some(10_000_000),
+            max_fuel: None,
+            max_stack: Some(100_000),
+            max_string_len: Some(1 << 16),
+            // the output is a string, so the string length limit already covers it
+            max_output_len: None,
+            max_memory: Some(64 << 20),
+            max_time: Some(Duration::from_secs(5)),
+        }
+    }
+
+    /// lowers each of these limits to the one in `max`, taking the ones that aren't set from it,
+    /// so limits asked for by someone else, like a client of `chicken serve`, can never go past what's allowed
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::Limits;
+    ///
+    /// let asked_for = Limits {
+    ///     max_steps: Some(u64::MAX),
+    ///     max_stack: Some(10),
+    ///     ..Default::default()
+    /// };
+    /// let limits = asked_for.clamp_to(Limits::untrusted());
+    ///
+    /// assert_eq!(limits.max_steps, Limits::untrusted().max_steps);
+    /// assert_eq!(limits.max_stack, Some(10));
+    /// assert_eq!(limits.max_string_len, Limits::untrusted().max_string_len);
+    /// ```
+    pub fn clamp_to(self, max: Limits) -> Self {
+        fn lower<T: Ord>(limit: Option<T>, max: Option<T>) -> Option<T> {
+            match (limit, max) {
+                (Some(limit), Some(max)) => Some(limit.min(max)),
+                (limit, max) => limit.or(max),
+            }
+        }
+
+        Self {
+            max_steps: lower(self.max_steps, max.max_steps),
+            max_fuel: lower(self.max_fuel, max.max_fuel),
+            max_stack: lower(self.max_stack, max.max_stack),
+            max_string_len: lower(self.max_string_len, max.max_string_len),
+            max_output_len: lower(self.max_output_len, max.max_output_len),
+            max_memory: lower(self.max_memory, max.max_memory),
+            max_time: lower(self.max_time, max.max_time),
+        }
+    }
+}
+
+// how many steps go by between checks of the time limit
+const TIME_CHECK_INTERVAL: u64 = 1024;
+
+// when the time limit runs out, and the step count to check it again at
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Deadline {
+    at: Option<Instant>,
+    next_check: u64,
+}
+
+impl VMState {
+    // whether the time limit has to be checked before the next step, which the fast path leaves to the slow one
+    pub(crate) fn time_check_due(&self) -> bool {
+        self.limits.max_time.is_some() && self.stats.steps >= self.deadline.next_check
+    }
+
+    // stops the program if it's gone past the time limit. the clock starts the first time this is called,
+    // and is only read every so many steps since reading it takes longer than most instructions
+    pub(crate) fn check_time(&mut self) -> Result<(), ChickenError> {
+        let Some(max_time) = self.limits.max_time else {
+            return Ok(());
+        };
+
+        if self.stats.steps < self.deadline.next_check {
+            return Ok(());
+        }
+        self.deadline.next_check = self.stats.steps + TIME_CHECK_INTERVAL;
+
+        let now = Instant::now();
+        let deadline = *self.deadline.at.get_or_insert(now + max_time);
+        if now >= deadline {
+            return Err(self.error(
+                ErrorKind::Limit,
+                format_args!("time limit of {:?} exceeded", max_time),
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,354 @@
+use crate::{
+    is_label, Instruction, ParseError, Program, SourceLocation, SourceMap, ADD, CHAR, EXIT, JUMP,
+    LOAD,
+};
+use std::{collections::BTreeMap, fmt, path::Path};
+
+/// an error encountered while linking assembly files together with [Program::link]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkError {
+    /// the name of the file the error is in
+    pub file: String,
+
+    /// the line the error is on, starting at 1
+    pub line: usize,
+
+    /// what went wrong
+    pub message: String,
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} line {}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+// a line of assembly that turns into opcodes
+enum Item {
+    Opcodes(Vec<isize>),
+
+    // a jump to a label, which is a push of the offset to it followed by a jump.
+    // offsets that can't be pushed on their own, like -5 which is the opcode of compare, take two pushes and an add instead
+    JumpTo { label: String, long: bool },
+}
+
+// a single file of assembly, before its jumps to labels are worked out
+struct Unit {
+    // each item, with the line it's on and the closest label at or before it
+    items: Vec<(usize, Option<String>, Item)>,
+
+    // every label, and the index of the item after it
+    labels: BTreeMap<String, usize>,
+
+    // the labels other files can jump to, and the ones this file jumps to in other files, with the lines they're on
+    exports: Vec<(usize, String)>,
+    imports: Vec<(usize, String)>,
+}
+
+impl Unit {
+    fn parse(asm: &str) -> Result<Self, ParseError> {
+        let mut unit = Unit {
+            items: Vec::new(),
+            labels: BTreeMap::new(),
+            exports: Vec::new(),
+            imports: Vec::new(),
+        };
+        let mut label = None;
+
+        for (index, line) in asm.lines().enumerate() {
+            let mut line = line.split('#').next().unwrap_or_default().trim();
+            let error = |message| ParseError {
+                line: index + 1,
+                message,
+            };
+
+            if let Some((name, rest)) = line.split_once(':') {
+                let name = name.trim();
+
+                if !is_label(name) {
+                    return Err(error(format!("invalid label {:?}", name)));
+                }
+
+                if unit
+                    .labels
+                    .insert(name.to_string(), unit.items.len())
+                    .is_some()
+                {
+                    return Err(error(format!("label {:?} is defined more than once", name)));
+                }
+
+                label = Some(name.to_string());
+                line = rest.trim();
+            }
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+
+            let item = match word {
+                "export" | "import" if is_label(rest) => {
+                    let list = match word {
+                        "export" => &mut unit.exports,
+                        _ => &mut unit.imports,
+                    };
+                    list.push((index + 1, rest.to_string()));
+                    continue;
+                }
+                "export" | "import" => {
+                    return Err(error(format!("{} needs a label, not {:?}", word, rest)))
+                }
+                "jump" if is_label(rest) => Item::JumpTo {
+                    label: rest.to_string(),
+                    long: false,
+                },
+                _ if line == Instruction::mnemonic_of(LOAD) => Item::Opcodes(vec![LOAD]),
+                _ => match line.parse::<Instruction>().map_err(error)? {
+                    Instruction::Load(operand) => Item::Opcodes(vec![LOAD, operand]),
+                    instruction => Item::Opcodes(vec![instruction.opcode()]),
+                },
+            };
+
+            unit.items.push((index + 1, label.clone(), item));
+        }
+
+        Ok(unit)
+    }
+}
+
+// the opcode of a push of this number, if it has one
+fn push_opcode(n: isize) -> Option<isize> {
+    n.checked_add(10)
+        .filter(|opcode| !(EXIT..=CHAR).contains(opcode))
+}
+
+// assembles files that have been parsed, returning the index of the file an error is in along with it.
+// labels that aren't exported are named after their file when there's more than one, so they don't clash
+fn assemble(
+    names: &[String],
+    mut units: Vec<Unit>,
+) -> Result<(Program, SourceMap), (usize, ParseError)> {
+    // items are numbered across all the files, so jumps can be resolved the same way wherever they land
+    let bases = (units.iter())
+        .scan(0, |base, unit| {
+            let start = *base;
+            *base += unit.items.len();
+            Some(start)
+        })
+        .collect::<Vec<_>>();
+
+    let mut exports = BTreeMap::new();
+    for (file, unit) in units.iter().enumerate() {
+        for (line, label) in unit.exports.iter() {
+            let error = |message| {
+                Err((
+                    file,
+                    ParseError {
+                        line: *line,
+                        message,
+                    },
+                ))
+            };
+
+            let Some(item) = unit.labels.get(label) else {
+                return error(format!("exported label {:?} isn't defined", label));
+            };
+            if let Some((other, _)) = exports.insert(label.clone(), (file, bases[file] + item)) {
+                return error(format!(
+                    "label {:?} is already exported from {}",
+                    label, names[other]
+                ));
+            }
+        }
+    }
+
+    // where each jump to a label goes, as an item number
+    let mut targets = BTreeMap::new();
+    for (file, unit) in units.iter().enumerate() {
+        for (line, label) in unit.imports.iter() {
+            if !exports.contains_key(label) {
+                return Err((
+                    file,
+                    ParseError {
+                        line: *line,
+                        message: format!("imported label {:?} isn't exported by any file", label),
+                    },
+                ));
+            }
+        }
+
+        for (index, (line, _, item)) in unit.items.iter().enumerate() {
+            let Item::JumpTo { label, .. } = item else {
+                continue;
+            };
+
+            let target = match unit.labels.get(label) {
+                Some(item) => bases[file] + item,
+                None if unit.imports.iter().any(|(_, import)| import == label) => exports[label].1,
+                None => {
+                    return Err((
+                        file,
+                        ParseError {
+                            line: *line,
+                            message: format!("label {:?} isn't defined or imported", label),
+                        },
+                    ))
+                }
+            };
+            targets.insert(bases[file] + index, target);
+        }
+    }
+
+    // jumps start out short and only get longer, so this always settles
+    let starts = loop {
+        let mut starts = vec![0];
+        for (_, _, item) in units.iter().flat_map(|unit| unit.items.iter()) {
+            let len = match item {
+                Item::Opcodes(opcodes) => opcodes.len(),
+                Item::JumpTo { long: false, .. } => 2,
+                Item::JumpTo { long: true, .. } => 4,
+            };
+            starts.push(starts[starts.len() - 1] + len);
+        }
+
+        let mut changed = false;
+        let items = units.iter_mut().flat_map(|unit| unit.items.iter_mut());
+        for (number, (_, _, item)) in items.enumerate() {
+            if let Item::JumpTo {
+                long: long @ false, ..
+            } = item
+            {
+                let offset = starts[targets[&number]] as isize - (starts[number] + 2) as isize;
+                if push_opcode(offset).is_none() {
+                    *long = true;
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            break starts;
+        }
+    };
+
+    let multiple = units.len() > 1;
+    let mut opcodes = Vec::new();
+    let mut source_map = SourceMap {
+        file: (!multiple)
+            .then(|| names[0].clone())
+            .filter(|name| !name.is_empty()),
+        ..Default::default()
+    };
+
+    for (file, unit) in units.iter().enumerate() {
+        let stem = Path::new(&names[file])
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let qualify = |label: &String| match multiple
+            && !unit.exports.iter().any(|(_, export)| export == label)
+        {
+            true => format!("{}.{}", stem, label),
+            false => label.clone(),
+        };
+
+        for (label, item) in unit.labels.iter() {
+            source_map
+                .labels
+                .insert(qualify(label), starts[bases[file] + item]);
+        }
+
+        for (index, (line, label, item)) in unit.items.iter().enumerate() {
+            let number = bases[file] + index;
+            source_map.instructions.push(SourceLocation {
+                index: starts[number],
+                line: *line,
+                label: label.as_ref().map(qualify),
+                file: multiple.then(|| names[file].clone()),
+            });
+
+            match item {
+                Item::Opcodes(item) => opcodes.extend(item),
+                Item::JumpTo { long, .. } => {
+                    let next = starts[number + 1] as isize;
+                    let offset = starts[targets[&number]] as isize - next;
+
+                    if *long {
+                        let b = match push_opcode(offset - 15) {
+                            Some(_) => 15,
+                            None => 30,
+                        };
+                        opcodes.extend([offset - b + 10, b + 10, ADD, JUMP]);
+                    } else {
+                        opcodes.extend([offset + 10, JUMP]);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok((Program::from_opcodes(opcodes), source_map))
+}
+
+impl Program {
+    // assembles a single file, which is what Program::from_asm_with_source_map does
+    pub(crate) fn assemble(asm: &str, file: Option<&str>) -> Result<(Self, SourceMap), ParseError> {
+        let names = [file.unwrap_or_default().to_string()];
+        assemble(&names, vec![Unit::parse(asm)?]).map_err(|(_, err)| err)
+    }
+
+    /// assembles several files of assembly into one program, given the name and the text of each one, with the first one at the start of the program.
+    /// labels can only be jumped to from the file they're in, unless that file has an `export <label>` line and the file jumping to them
+    /// has an `import <label>` line. labels that aren't exported are put in the source map with the name of their file in front of them,
+    /// like `lib.loop` for `loop` in lib.asm, so labels in different files can have the same name
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::Program;
+    ///
+    /// let main = "import print\npush 1\njump print";
+    /// let lib = "export print\nloop: exit\nprint: push 1\nload 0\npush 1\njump loop";
+    /// let (program, source_map) = Program::link([("main.asm", main), ("lib.asm", lib)]).unwrap();
+    ///
+    /// assert_eq!(source_map.labels["print"], 4);
+    /// assert_eq!(source_map.labels["lib.loop"], 3);
+    /// assert_eq!(source_map.lookup(4).unwrap().file.as_deref(), Some("lib.asm"));
+    /// assert_eq!(chicken::VMBuilder::from_program(program).input("hi").build().run(), Ok("hi".to_string()));
+    /// ```
+    pub fn link<I, N, T>(files: I) -> Result<(Self, SourceMap), LinkError>
+    where
+        I: IntoIterator<Item = (N, T)>,
+        N: AsRef<str>,
+        T: AsRef<str>,
+    {
+        let mut names = Vec::new();
+        let mut units = Vec::new();
+
+        for (name, asm) in files {
+            let name = name.as_ref().to_string();
+            let unit = Unit::parse(asm.as_ref()).map_err(|err| LinkError {
+                file: name.clone(),
+                line: err.line,
+                message: err.message,
+            })?;
+
+            names.push(name);
+            units.push(unit);
+        }
+
+        if units.is_empty() {
+            return Ok((Program::default(), SourceMap::default()));
+        }
+
+        assemble(&names, units).map_err(|(file, err)| LinkError {
+            file: names[file].clone(),
+            line: err.line,
+            message: err.message,
+        })
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{chicken_lines, Instruction, InvalidLoadKind, Program, Successor};
+use std::fmt;
+
+/// pushes of numbers larger than this can't be turned into characters and are almost certainly a mistake
+pub const MAX_MEANINGFUL_LITERAL: isize = 0x10ffff;
+
+/// the kinds of suspicious things the linter looks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintKind {
+    /// a word that looks like a misspelled `chicken`, which doesn't count towards the opcode
+    Misspelling,
+
+    /// a line with so many chickens that the number it pushes can't mean anything
+    HugeLiteral,
+
+    /// a Load at the very end of the program, which takes its operand from the exit after it
+    TrailingLoad,
+
+    /// a jump that can land outside of the program
+    JumpOutOfBounds,
+
+    /// a jump that can land on the operand of a Load, which then runs as an instruction of its own
+    JumpIntoLoad,
+}
+
+/// something suspicious found in a program by [lint]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    /// the line the problem is on, starting at 1
+    pub line: usize,
+
+    /// what kind of problem it is
+    pub kind: LintKind,
+
+    /// a description of the problem
+    pub message: String,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// looks for suspicious things in Chicken code, returning them sorted by line
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::{lint, LintKind};
+///
+/// let lints = lint("chicken\nchicken Chiken");
+/// assert_eq!(lints.len(), 1);
+/// assert_eq!((lints[0].line, lints[0].kind), (2, LintKind::Misspelling));
+/// ```
+pub fn lint<T: AsRef<str>>(chicken: T) -> Vec<Lint> {
+    let chicken = chicken.as_ref();
+    let mut lints = Vec::new();
+
+    for (index, line) in chicken_lines(chicken).enumerate() {
+        // chickens can be stuck to other text, so only look at what's left over once they're taken out
+        for word in line.split_whitespace().flat_map(|w| w.split("chicken")) {
+            if word.len() >= 4 && edit_distance(&word.to_lowercase(), "chicken") <= 2 {
+                lints.push(Lint {
+                    line: index + 1,
+                    kind: LintKind::Misspelling,
+                    message: format!(
+                        "{:?} looks like a misspelled chicken, which doesn't count",
+                        word
+                    ),
+                });
+            }
+        }
+    }
+
+    let program = Program::from_chicken(chicken);
+
+    for (index, instruction) in program.instructions() {
+        match instruction {
+            Instruction::Push(n) if n > MAX_MEANINGFUL_LITERAL => lints.push(Lint {
+                line: index + 1,
+                kind: LintKind::HugeLiteral,
+                message: format!("push {} is too large to mean anything", n),
+            }),
+            _ => (),
+        }
+    }
+
+    for invalid in program.validate().err().unwrap_or_default() {
+        lints.push(match invalid.kind {
+            InvalidLoadKind::Trailing => Lint {
+                line: invalid.index + 1,
+                kind: LintKind::TrailingLoad,
+                message: "load at the end of the program has no operand, so it loads from the stack reference".to_string(),
+            },
+            InvalidLoadKind::JumpedInto { jump } => Lint {
+                line: jump + 1,
+                kind: LintKind::JumpIntoLoad,
+                message: format!(
+                    "jump lands on the operand of the load on line {}",
+                    invalid.index + 1
+                ),
+            },
+        });
+    }
+
+    // this finds the same jumps as validate does and more, but they shouldn't be reported twice
+    for misaligned in program.jump_alignment().misaligned {
+        let lint = Lint {
+            line: misaligned.jump + 1,
+            kind: LintKind::JumpIntoLoad,
+            message: format!(
+                "jump lands on the operand of the load on line {}",
+                misaligned.load + 1
+            ),
+        };
+
+        if !lints.contains(&lint) {
+            lints.push(lint);
+        }
+    }
+
+    for block in program.cfg().blocks {
+        for successor in block.successors.iter() {
+            if let Successor::OutOfBounds(target) = successor {
+                let (index, _) = block
+                    .instructions
+                    .last()
+                    .copied()
+                    .unwrap_or((block.start, Instruction::Exit));
+
+                lints.push(Lint {
+                    line: index + 1,
+                    kind: LintKind::JumpOutOfBounds,
+                    message: format!(
+                        "jump lands outside of the program, at program index {}",
+                        target
+                    ),
+                });
+            }
+        }
+    }
+
+    lints.sort_by_key(|lint| lint.line);
+    lints
+}
+
+/// the number of single character insertions, deletions and substitutions needed to turn one string into another
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + (ca != *cb) as usize;
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+
+    row[b.len()]
+}
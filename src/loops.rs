@@ -0,0 +1,153 @@
+use crate::Value;
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet, VecDeque},
+    hash::{Hash, Hasher},
+};
+
+/// detects programs that will never terminate by remembering hashes of previous VM states.
+///
+/// since Chicken programs are deterministic, seeing the exact same program counter and stack twice means the program is stuck in a loop forever.
+/// only hashes are stored to keep memory usage down, so a hash collision could (very rarely) cause a false positive
+#[derive(Debug, Clone, Default)]
+pub struct LoopDetector {
+    seen: HashSet<u64>,
+    order: VecDeque<u64>,
+    window: Option<usize>,
+}
+
+impl LoopDetector {
+    /// creates a loop detector that remembers every state it's been given
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// creates a loop detector that only remembers the last `window` states it's been given.
+    /// loops that take more steps than this to repeat won't be detected
+    pub fn with_window(window: usize) -> Self {
+        Self {
+            window: Some(window),
+            ..Self::default()
+        }
+    }
+
+    /// records a VM state, returning false if it's been seen before
+    pub fn insert(&mut self, program_counter: usize, stack: &[Value]) -> bool {
+        let hash = hash_state(program_counter, stack);
+
+        if !self.seen.insert(hash) {
+            return false;
+        }
+
+        if let Some(window) = self.window {
+            self.order.push_back(hash);
+
+            while self.order.len() > window {
+                if let Some(old) = self.order.pop_front() {
+                    self.seen.remove(&old);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// forgets all previously seen states
+    pub fn clear(&mut self) {
+        self.seen.clear();
+        self.order.clear();
+    }
+}
+
+/// hashes a value by its exact contents. [Value]'s equality is too loose for this (`1 == "1"`), so it can't implement [Hash] itself
+pub(crate) fn hash_value<H: Hasher>(value: &Value, state: &mut H) {
+    std::mem::discriminant(value).hash(state);
+
+    match value {
+        Value::Num(n) => n.hash(state),
+        Value::String(s) => s.hash(state),
+        Value::Ptr(p) => p.hash(state),
+        Value::Array(values) => {
+            values.len().hash(state);
+            for value in values.iter() {
+                hash_value(value, state);
+            }
+        }
+        _ => (),
+    }
+}
+
+fn hash_state(program_counter: usize, stack: &[Value]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    program_counter.hash(&mut hasher);
+    stack.len().hash(&mut hasher);
+    for value in stack.iter() {
+        hash_value(value, &mut hasher);
+    }
+
+    hasher.finish()
+}
+
+/// a 64 bit FNV-1a hasher, which unlike [DefaultHasher] gives the same results on every platform and version of Rust
+struct StableHasher(u64);
+
+impl StableHasher {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+}
+
+/// hashes the state of a VM in a way that stays the same across platforms and versions of Rust, so hashes can be written down in tests
+pub(crate) fn stable_state_hash(program_counter: usize, exited: bool, stack: &[Value]) -> u64 {
+    let mut hasher = StableHasher::new();
+
+    hasher.write_u64(program_counter as u64);
+    hasher.write(&[exited as u8]);
+    hasher.write_u64(stack.len() as u64);
+
+    for value in stack.iter() {
+        stable_hash_value(&mut hasher, value);
+    }
+
+    hasher.0
+}
+
+fn stable_hash_value(hasher: &mut StableHasher, value: &Value) {
+    match value {
+        Value::Num(n) => {
+            hasher.write(&[0]);
+            hasher.write_u64(*n as i64 as u64);
+        }
+        Value::String(s) => {
+            hasher.write(&[1]);
+            hasher.write_u64(s.len() as u64);
+            hasher.write(s.as_bytes());
+        }
+        Value::Ptr(p) => {
+            hasher.write(&[2]);
+            hasher.write_u64(*p as u64);
+        }
+        Value::True => hasher.write(&[3]),
+        Value::False => hasher.write(&[4]),
+        Value::Undefined => hasher.write(&[5]),
+        Value::NaN => hasher.write(&[6]),
+        Value::Array(values) => {
+            hasher.write(&[7]);
+            hasher.write_u64(values.len() as u64);
+            for value in values.iter() {
+                stable_hash_value(hasher, value);
+            }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use crate::ParseError;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// information about a program that isn't part of the program itself, like its title and what it needs to run.
+/// assembly and opcode files have it in a header of `# key: value` comments at the top, and JSON programs in a `metadata` object
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::{Metadata, Requirement};
+///
+/// let metadata = Metadata::from_header("# title: dice\n# requires: extensions\npush 6\nrandom").unwrap();
+/// assert_eq!(metadata.title.as_deref(), Some("dice"));
+/// assert_eq!(metadata.requires, vec![Requirement::Extensions]);
+/// assert_eq!(metadata.to_header(), "# title: dice\n# requires: extensions\n");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// the name of the program
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    /// who wrote it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+
+    /// a description of the input the program expects
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<String>,
+
+    /// the options the program has to be run with to work
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<Requirement>,
+}
+
+/// an option a program needs to be run with, listed in its [Metadata]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Requirement {
+    /// the program uses [Extension](crate::Extension) instructions, see [VMBuilder::extensions](crate::VMBuilder::extensions)
+    Extensions,
+
+    /// the program expects to be stopped when it pops values that aren't there, see [VMBuilder::strict](crate::VMBuilder::strict)
+    Strict,
+
+    /// the program expects Char to make actual characters, see [VMBuilder::normal_char](crate::VMBuilder::normal_char)
+    NormalChar,
+
+    /// the program expects Char to make single bytes, see [VMBuilder::byte_char](crate::VMBuilder::byte_char)
+    ByteChar,
+}
+
+impl Requirement {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Extensions => "extensions",
+            Self::Strict => "strict",
+            Self::NormalChar => "normal-char",
+            Self::ByteChar => "byte-char",
+        }
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Requirement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            Self::Extensions,
+            Self::Strict,
+            Self::NormalChar,
+            Self::ByteChar,
+        ]
+        .into_iter()
+        .find(|requirement| requirement.name() == s)
+        .ok_or_else(|| format!("unknown requirement {:?}", s))
+    }
+}
+
+impl Metadata {
+    /// whether there isn't any metadata at all
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// reads the header of `# key: value` comments at the start of an assembly or opcode file, stopping at the first line that isn't a comment.
+    /// the keys are title, author, input and requires, which is a comma separated list of [Requirement]s.
+    /// comments with other keys are ignored, so ordinary comments that happen to have a colon in them don't get in the way
+    pub fn from_header(text: &str) -> Result<Self, ParseError> {
+        let mut metadata = Self::default();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let Some((key, value)) = (line.strip_prefix('#')).and_then(|line| line.split_once(':'))
+            else {
+                match line.starts_with('#') {
+                    true => continue,
+                    false => break,
+                }
+            };
+            let value = value.trim().to_string();
+
+            match key.trim() {
+                "title" => metadata.title = Some(value),
+                "author" => metadata.author = Some(value),
+                "input" => metadata.input = Some(value),
+                "requires" => {
+                    for requirement in value.split(',').map(str::trim) {
+                        metadata
+                            .requires
+                            .push(requirement.parse().map_err(|message| ParseError {
+                                line: index + 1,
+                                message,
+                            })?);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    /// writes the metadata as a header of comments that [Metadata::from_header] reads back, one line per key that's set
+    pub fn to_header(&self) -> String {
+        let mut header = String::new();
+
+        for (key, value) in [
+            ("title", &self.title),
+            ("author", &self.author),
+            ("input", &self.input),
+        ] {
+            if let Some(value) = value {
+                header += &format!("# {}: {}\n", key, value);
+            }
+        }
+
+        if !self.requires.is_empty() {
+            let requires = self.requires.iter().map(Requirement::to_string);
+            header += &format!("# requires: {}\n", requires.collect::<Vec<_>>().join(", "));
+        }
+
+        header
+    }
+}
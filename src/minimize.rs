@@ -0,0 +1,70 @@
+use crate::Program;
+
+impl Program {
+    /// looks for a smaller program that still does whatever `reproduces` checks for, like failing with a certain kind of error,
+    /// for shrinking programs that trip over a bug down to something that fits in a bug report.
+    /// whole instructions are removed, in chunks that start at half of the program and get smaller,
+    /// and a removal is kept whenever `reproduces` still returns true without it. this program is returned as is if it doesn't reproduce in the first place.
+    ///
+    /// jumps aren't adjusted when instructions before them are removed, so `reproduces` should run the program with a step limit
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{ErrorKind, Program, VMBuilder};
+    ///
+    /// // pushes 1, 2 and 3, then adds until there's nothing left to add
+    /// let program = Program::from_opcodes([11, 12, 13, 2, 2, 2, 0]);
+    /// let minimized = program.minimize(|program| {
+    ///     let result = VMBuilder::from_program(program.clone()).strict().build().run();
+    ///     result.is_err_and(|err| err.kind == ErrorKind::StackUnderflow)
+    /// });
+    ///
+    /// assert_eq!(minimized.opcodes(), &[2]);
+    /// ```
+    pub fn minimize<F: FnMut(&Program) -> bool>(&self, mut reproduces: F) -> Program {
+        if !reproduces(self) {
+            return self.clone();
+        }
+
+        // the opcodes of each instruction, so the operand of a Load is never removed without it
+        let mut instructions = (self.instructions().into_iter())
+            .map(|(index, instruction)| {
+                let end = (index + instruction.width()).min(self.len());
+                self.opcodes()[index..end].to_vec()
+            })
+            .collect::<Vec<_>>();
+        let mut chunk = (instructions.len() / 2).max(1);
+
+        while chunk <= instructions.len() {
+            let mut removed = false;
+            let mut start = 0;
+
+            while start < instructions.len() {
+                let end = (start + chunk).min(instructions.len());
+                let candidate = (instructions[..start].iter())
+                    .chain(&instructions[end..])
+                    .flatten()
+                    .copied()
+                    .collect::<Vec<_>>();
+
+                // whatever comes after the removed chunk slides into its place, so the same start is tried again
+                if reproduces(&Program::from_opcodes(candidate)) {
+                    instructions.drain(start..end);
+                    removed = true;
+                } else {
+                    start += chunk;
+                }
+            }
+
+            // a pass that removed something can make room for more removals of the same size
+            match (removed, chunk) {
+                (true, _) => chunk = chunk.min(instructions.len()).max(1),
+                (false, 1) => break,
+                (false, _) => chunk /= 2,
+            }
+        }
+
+        Program::from_opcodes(instructions.concat())
+    }
+}
@@ -0,0 +1,63 @@
+/// converts a floating point number to a string exactly the way JavaScript's `Number.prototype.toString` does,
+/// with the shortest digits that read back as the same number, `Infinity` and `-Infinity`, and both zeroes as `0`.
+/// numbers are integers in chicken-rs, but the original interpreter is JavaScript, where adding a number to a string
+/// concatenates its text, so a mode that does arithmetic in floats has to write them out the same way to give the same output
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::format_number;
+///
+/// assert_eq!(format_number(0.1 + 0.2), "0.30000000000000004");
+/// assert_eq!(format_number(1e21), "1e+21");
+/// assert_eq!(format_number(-0.0), "0");
+/// assert_eq!(format_number(f64::INFINITY), "Infinity");
+/// ```
+pub fn format_number(n: f64) -> String {
+    if n.is_nan() {
+        return "NaN".to_string();
+    }
+    if n == 0.0 {
+        return "0".to_string();
+    }
+    if n.is_infinite() {
+        return match n > 0.0 {
+            true => "Infinity".to_string(),
+            false => "-Infinity".to_string(),
+        };
+    }
+    if n < 0.0 {
+        return format!("-{}", format_number(-n));
+    }
+
+    // rust already finds the shortest digits that round trip, and writes them like 1.2345e-7
+    let scientific = format!("{:e}", n);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let digits = mantissa.replace('.', "");
+    let exponent = exponent.parse::<i32>().unwrap_or_default();
+
+    // where the decimal point goes, counting from the start of the digits, like n in the spec
+    let point = exponent + 1;
+    let len = digits.len() as i32;
+
+    match point {
+        _ if len <= point && point <= 21 => digits + &"0".repeat((point - len) as usize),
+        1..=21 => format!(
+            "{}.{}",
+            &digits[..point as usize],
+            &digits[point as usize..]
+        ),
+        -5..=0 => format!("0.{}{}", "0".repeat(-point as usize), digits),
+        _ => {
+            let sign = match exponent < 0 {
+                true => '-',
+                false => '+',
+            };
+
+            match digits.split_at(1) {
+                (first, "") => format!("{}e{}{}", first, sign, exponent.abs()),
+                (first, rest) => format!("{}.{}e{}{}", first, rest, sign, exponent.abs()),
+            }
+        }
+    }
+}
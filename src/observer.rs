@@ -0,0 +1,456 @@
+use crate::{
+    display_cell, ChickenError, Instruction, TraceHeader, VMState, Value, Warning, TRACE_VERSION,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+/// information about a single instruction that was just executed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StepInfo {
+    /// the address of the instruction on the stack
+    #[serde(rename = "pc")]
+    pub program_counter: usize,
+
+    /// the program counter after the instruction was executed
+    #[serde(rename = "next_pc")]
+    pub next_program_counter: usize,
+
+    /// the raw opcode that was executed, or None if the program counter was past the end of the stack
+    pub opcode: Option<Value>,
+
+    /// the decoded instruction, or None if the opcode couldn't be decoded
+    #[serde(serialize_with = "serialize_instruction")]
+    pub instruction: Option<Instruction>,
+
+    /// the mnemonic of the opcode, or `invalid` if it isn't a number
+    #[serde(skip)]
+    pub mnemonic: &'static str,
+
+    /// the values popped off the stack, in the order they were popped
+    pub popped: Vec<Value>,
+
+    /// the values pushed onto the stack, in the order they were pushed
+    pub pushed: Vec<Value>,
+
+    /// whether the instruction was a jump that was taken
+    pub jumped: bool,
+
+    /// the change the instruction made to the program, if it was a Store into it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub self_modification: Option<SelfModification>,
+
+    /// the size of the stack after the instruction was executed
+    pub stack_depth: usize,
+}
+
+/// a Store that wrote into the program itself, or the exit after it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SelfModification {
+    /// the address that was written to
+    pub address: usize,
+
+    /// the opcode that was there before
+    pub old: Value,
+
+    /// the value that replaced it
+    pub new: Value,
+}
+
+impl fmt::Display for SelfModification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "program modified at address {}: {} -> {}",
+            self.address,
+            display_cell(&self.old),
+            display_cell(&self.new)
+        )
+    }
+}
+
+fn serialize_instruction<S: serde::Serializer>(
+    instruction: &Option<Instruction>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match instruction {
+        Some(instruction) => serializer.collect_str(instruction),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// something that wants to be told about every instruction a VM executes, added with [VMBuilder::observer](crate::VMBuilder::observer).
+/// any error returned will stop the VM
+pub trait Observer {
+    /// called right before the first instruction is executed
+    fn on_start(&mut self, _state: &VMState) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// called after every instruction is successfully executed
+    fn on_step(&mut self, state: &VMState, info: &StepInfo) -> io::Result<()>;
+
+    /// called once the VM has exited
+    fn on_exit(&mut self, _state: &VMState) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// called when an instruction fails with an error, which stops the run unless it's resumed
+    fn on_error(&mut self, _state: &VMState, _error: &ChickenError) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// called after [Observer::on_step] for every [Warning] the instruction caused, like popping values that weren't there
+    fn on_warning(&mut self, _state: &VMState, _warning: &Warning) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// lets an observer be shared with the VM, so it can still be accessed after the VM is done with it
+impl<O: Observer> Observer for Arc<Mutex<O>> {
+    fn on_start(&mut self, state: &VMState) -> io::Result<()> {
+        match self.lock() {
+            Ok(mut observer) => observer.on_start(state),
+            Err(_) => Err(io::Error::other("observer lock poisoned")),
+        }
+    }
+
+    fn on_step(&mut self, state: &VMState, info: &StepInfo) -> io::Result<()> {
+        match self.lock() {
+            Ok(mut observer) => observer.on_step(state, info),
+            Err(_) => Err(io::Error::other("observer lock poisoned")),
+        }
+    }
+
+    fn on_exit(&mut self, state: &VMState) -> io::Result<()> {
+        match self.lock() {
+            Ok(mut observer) => observer.on_exit(state),
+            Err(_) => Err(io::Error::other("observer lock poisoned")),
+        }
+    }
+
+    fn on_error(&mut self, state: &VMState, error: &ChickenError) -> io::Result<()> {
+        match self.lock() {
+            Ok(mut observer) => observer.on_error(state, error),
+            Err(_) => Err(io::Error::other("observer lock poisoned")),
+        }
+    }
+
+    fn on_warning(&mut self, state: &VMState, warning: &Warning) -> io::Result<()> {
+        match self.lock() {
+            Ok(mut observer) => observer.on_warning(state, warning),
+            Err(_) => Err(io::Error::other("observer lock poisoned")),
+        }
+    }
+}
+
+/// writes a JSON object for every executed instruction to a writer, one per line.
+/// the first line is a header holding a [Snapshot](crate::Snapshot) of the VM before it started, so the run can be replayed with [Trace](crate::Trace)
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::{JsonTracer, VMBuilder};
+/// use std::sync::{Arc, Mutex};
+///
+/// let tracer = Arc::new(Mutex::new(JsonTracer::new(Vec::new())));
+/// VMBuilder::from_chicken("chicken").observer(tracer.clone()).build().run().unwrap();
+///
+/// let trace = String::from_utf8(tracer.lock().unwrap().get_ref().clone()).unwrap();
+/// assert_eq!(
+///     trace.lines().nth(1).unwrap(),
+///     r#"{"step":1,"pc":2,"next_pc":3,"opcode":1,"instruction":"chicken","popped":[],"pushed":["chicken"],"jumped":false,"stack_depth":5}"#
+/// );
+/// ```
+pub struct JsonTracer<W: Write> {
+    writer: W,
+    steps: u64,
+    sampling: TraceSampling,
+}
+
+impl<W: Write> JsonTracer<W> {
+    /// creates a new tracer that writes every step to the given writer
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            steps: 0,
+            sampling: TraceSampling::All,
+        }
+    }
+
+    /// makes this tracer only write the steps picked by the given sampling, keeping traces of very long runs small.
+    /// the sampling is written into the header, so sampled traces can still be replayed with [Trace](crate::Trace)
+    pub fn sampling(mut self, sampling: TraceSampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// gets a reference to the writer this tracer is writing to
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// consumes this tracer, returning the writer it was writing to
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[derive(Serialize)]
+struct TraceLine<'a> {
+    step: u64,
+
+    #[serde(flatten)]
+    info: &'a StepInfo,
+}
+
+impl<W: Write> Observer for JsonTracer<W> {
+    fn on_start(&mut self, state: &VMState) -> io::Result<()> {
+        serde_json::to_writer(
+            &mut self.writer,
+            &TraceHeader {
+                chicken_trace: TRACE_VERSION,
+                snapshot: state.snapshot(),
+                sampling: self.sampling,
+            },
+        )?;
+        writeln!(self.writer)
+    }
+
+    fn on_step(&mut self, _state: &VMState, info: &StepInfo) -> io::Result<()> {
+        self.steps += 1;
+
+        if !self.sampling.samples(self.steps, info) {
+            return Ok(());
+        }
+
+        serde_json::to_writer(
+            &mut self.writer,
+            &TraceLine {
+                step: self.steps,
+                info,
+            },
+        )?;
+        writeln!(self.writer)
+    }
+
+    fn on_exit(&mut self, _state: &VMState) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// which steps a [JsonTracer] writes, so tracing runs of millions of steps doesn't write gigabytes of JSON.
+/// it's parsed from and displayed as `all`, a number of steps like `1000`, `jumps` or `stores`
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::{JsonTracer, TraceSampling, VMBuilder};
+/// use std::sync::{Arc, Mutex};
+///
+/// let tracer = JsonTracer::new(Vec::new()).sampling("jumps".parse().unwrap());
+/// let tracer = Arc::new(Mutex::new(tracer));
+/// VMBuilder::from_path("examples/helloworld.chicken").unwrap().observer(tracer.clone()).build().run().unwrap();
+///
+/// // the header, then the 9 times the loop jumped back
+/// let trace = String::from_utf8(tracer.lock().unwrap().get_ref().clone()).unwrap();
+/// assert_eq!(trace.lines().count(), 10);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceSampling {
+    /// every step
+    #[default]
+    All,
+
+    /// every nth step, starting with the first
+    Every(u64),
+
+    /// only the steps that took a jump, which shows the path a run took through the program
+    Jumps,
+
+    /// only the steps that stored something, which shows how a run changed its memory
+    Stores,
+}
+
+impl TraceSampling {
+    /// whether the step with the given number, starting at 1, is one of the steps this sampling picks
+    pub fn samples(&self, step: u64, info: &StepInfo) -> bool {
+        match self {
+            TraceSampling::All => true,
+            TraceSampling::Every(every) => (step - 1).is_multiple_of((*every).max(1)),
+            TraceSampling::Jumps => info.jumped,
+            TraceSampling::Stores => matches!(info.mnemonic, "store" | "astore"),
+        }
+    }
+
+    /// whether this picks every step
+    pub fn is_all(&self) -> bool {
+        matches!(self, TraceSampling::All | TraceSampling::Every(0 | 1))
+    }
+}
+
+impl FromStr for TraceSampling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(TraceSampling::All),
+            "jumps" => Ok(TraceSampling::Jumps),
+            "stores" => Ok(TraceSampling::Stores),
+            _ => match s.parse::<u64>() {
+                Ok(every) if every > 0 => Ok(TraceSampling::Every(every)),
+                _ => Err(format!(
+                    "unknown trace sampling {:?}, expected all, a number of steps, jumps or stores",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+impl fmt::Display for TraceSampling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceSampling::All => write!(f, "all"),
+            TraceSampling::Every(every) => write!(f, "{}", every),
+            TraceSampling::Jumps => write!(f, "jumps"),
+            TraceSampling::Stores => write!(f, "stores"),
+        }
+    }
+}
+
+/// how finely a [ChromeTracer] splits up a run into events
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChromeTraceGranularity {
+    /// one event per executed instruction
+    #[default]
+    Instruction,
+
+    /// one event per run of instructions executed without jumping, which keeps traces of long runs manageable
+    BasicBlock,
+}
+
+/// writes a trace in the chrome://tracing event format, which can be opened in Perfetto or chrome://tracing.
+/// the time of each event is the time between one instruction finishing and the next, so it includes the overhead of tracing itself
+pub struct ChromeTracer<W: Write> {
+    writer: W,
+    granularity: ChromeTraceGranularity,
+    start: Instant,
+    last: Instant,
+    events: u64,
+
+    // the start address, start time and number of instructions of the basic block currently being executed
+    block: Option<(usize, Instant, u64)>,
+    next_program_counter: usize,
+}
+
+impl<W: Write> ChromeTracer<W> {
+    /// creates a new tracer that writes to the given writer
+    pub fn new(writer: W, granularity: ChromeTraceGranularity) -> Self {
+        let now = Instant::now();
+
+        Self {
+            writer,
+            granularity,
+            start: now,
+            last: now,
+            events: 0,
+            block: None,
+            next_program_counter: 0,
+        }
+    }
+
+    /// consumes this tracer, returning the writer it was writing to
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_event(
+        &mut self,
+        name: &str,
+        start: Instant,
+        end: Instant,
+        args: serde_json::Value,
+    ) -> io::Result<()> {
+        let event = serde_json::json!({
+            "name": name,
+            "cat": "chicken",
+            "ph": "X",
+            "ts": start.duration_since(self.start).as_secs_f64() * 1e6,
+            "dur": end.duration_since(start).as_secs_f64() * 1e6,
+            "pid": 1,
+            "tid": 1,
+            "args": args,
+        });
+
+        self.writer
+            .write_all(if self.events == 0 { b"[\n" } else { b",\n" })?;
+        serde_json::to_writer(&mut self.writer, &event)?;
+        self.events += 1;
+
+        Ok(())
+    }
+
+    fn finish_block(&mut self, end: Instant) -> io::Result<()> {
+        match self.block.take() {
+            Some((start_pc, start, instructions)) => self.write_event(
+                &format!("block {}", start_pc),
+                start,
+                end,
+                serde_json::json!({ "pc": start_pc, "instructions": instructions }),
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<W: Write> Observer for ChromeTracer<W> {
+    fn on_step(&mut self, _state: &VMState, info: &StepInfo) -> io::Result<()> {
+        let now = Instant::now();
+        let start = std::mem::replace(&mut self.last, now);
+
+        match self.granularity {
+            ChromeTraceGranularity::Instruction => self.write_event(
+                &info
+                    .instruction
+                    .map_or_else(|| "invalid".to_string(), |i| i.to_string()),
+                start,
+                now,
+                serde_json::json!({ "pc": info.program_counter, "stack_depth": info.stack_depth }),
+            ),
+            ChromeTraceGranularity::BasicBlock => {
+                if info.program_counter != self.next_program_counter {
+                    self.finish_block(start)?;
+                }
+
+                let block = self.block.get_or_insert((info.program_counter, start, 0));
+                block.2 += 1;
+
+                let width = info.instruction.map_or(1, |i| i.width());
+                self.next_program_counter = info.program_counter + width;
+
+                if matches!(
+                    info.instruction,
+                    Some(Instruction::Jump) | Some(Instruction::Exit)
+                ) {
+                    self.finish_block(now)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    fn on_exit(&mut self, _state: &VMState) -> io::Result<()> {
+        self.finish_block(self.last)?;
+        self.writer
+            .write_all(if self.events == 0 { b"[]\n" } else { b"\n]\n" })?;
+        self.writer.flush()
+    }
+}
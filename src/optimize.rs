@@ -0,0 +1,196 @@
+use crate::{Instruction, Program, Successor, Value, FIRST_EXTENSION_OPCODE};
+use std::collections::{BTreeMap, BTreeSet};
+
+// where the program starts on the stack of a VM with a single input, which the absolute addresses in a program assume
+const PROGRAM_START: usize = 2;
+
+// a value pushed inside a basic block that's known ahead of time, along with the index of the push it came straight from, if it did
+#[derive(Debug, Clone)]
+struct Known {
+    value: Value,
+    push: Option<usize>,
+}
+
+impl Program {
+    /// removes the instructions that can never run because nothing reachable from the start of the program leads to them,
+    /// returning how many opcodes were removed. jumps over the removed code and addresses past the end of the program are
+    /// adjusted to match, assuming the program is run with a single input like every address in a program does.
+    ///
+    /// nothing is removed unless it's provably safe, so this does nothing to programs with jumps whose targets aren't known
+    /// ahead of time, programs that read their own code or use extension instructions, and programs with addresses or jump offsets
+    /// that can't be adjusted because they aren't pushed right where they're used. a store could write new code over the parts
+    /// that look dead, so programs that store anything are only changed if `frozen` is set, promising that they're run with
+    /// [VMBuilder::freeze_program](crate::VMBuilder::freeze_program)
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::Program;
+    ///
+    /// // jumps over two instructions that never run and exits with "chicken"
+    /// let mut program = Program::from_asm("push 1\npush 2\njump\nadd\nchar\nchicken").unwrap();
+    /// assert_eq!(program.remove_dead_code(false), 2);
+    /// assert_eq!(program.to_asm(), "push 1\npush 0\njump\nchicken\n");
+    /// ```
+    pub fn remove_dead_code(&mut self, frozen: bool) -> usize {
+        match self.without_dead_code(frozen) {
+            Some(program) => {
+                let removed = self.len() - program.len();
+                *self = program;
+                removed
+            }
+            None => 0,
+        }
+    }
+
+    // this program with its dead code removed, or None if there isn't any or it can't be removed safely
+    fn without_dead_code(&self, frozen: bool) -> Option<Program> {
+        let len = self.len();
+        let cfg = self.cfg();
+
+        // the blocks reachable from the start, giving up on jumps that can't be followed
+        let mut reachable = BTreeSet::new();
+        let mut pending = vec![0];
+        while let Some(start) = pending.pop() {
+            if start >= len || !reachable.insert(start) {
+                continue;
+            }
+
+            for successor in cfg.block_at(start)?.successors.iter() {
+                match successor {
+                    Successor::Next(target) | Successor::Jump(target) => pending.push(*target),
+                    Successor::DynamicJump | Successor::OutOfBounds(_) => return None,
+                }
+            }
+        }
+
+        let blocks: Vec<_> = (reachable.iter())
+            .filter_map(|start| cfg.block_at(*start))
+            .collect();
+
+        let mut live = vec![false; len];
+        for block in blocks.iter() {
+            live[block.start..block.end.min(len)].fill(true);
+        }
+
+        let removed = live.iter().filter(|live| !**live).count();
+        if removed == 0 {
+            return None;
+        }
+
+        // how many dead opcodes come before each index, including the exit after the program
+        let dead_before: Vec<usize> = std::iter::once(0)
+            .chain(live.iter().scan(0, |dead, live| {
+                *dead += usize::from(!live);
+                Some(*dead)
+            }))
+            .collect();
+        let new_index = |index: usize| (index - dead_before[index]) as isize;
+
+        // the new opcodes of the pushes and operands that have to change, by index
+        let mut patches = BTreeMap::new();
+        let relocate = |known: Option<Known>, patches: &mut BTreeMap<usize, isize>| {
+            let known = known?;
+            match known.value.to_num_option() {
+                Some(address) if address >= (PROGRAM_START + len) as isize => {
+                    patch(&known, address - removed as isize, patches)
+                }
+                Some(address) if address >= PROGRAM_START as isize => None,
+                // addresses before the program and ones that aren't valid don't depend on where anything is
+                _ => Some(()),
+            }
+        };
+
+        for block in blocks.iter() {
+            let mut stack: Vec<Option<Known>> = Vec::new();
+            let pop = |stack: &mut Vec<Option<Known>>| stack.pop().flatten();
+            let computed = |value: Option<Value>| value.map(|value| Known { value, push: None });
+
+            for &(index, instruction) in block.instructions.iter() {
+                match instruction {
+                    Instruction::Push(n) if n.saturating_add(10) >= FIRST_EXTENSION_OPCODE => {
+                        return None
+                    }
+                    Instruction::Push(n) => stack.push(Some(Known {
+                        value: n.into(),
+                        push: Some(index),
+                    })),
+                    Instruction::Exit => {}
+                    Instruction::Chicken => stack.push(computed(Some("chicken".into()))),
+                    Instruction::Add
+                    | Instruction::Subtract
+                    | Instruction::Multiply
+                    | Instruction::Compare => {
+                        let b = pop(&mut stack).map(|known| known.value);
+                        let a = pop(&mut stack).map(|known| known.value);
+                        stack.push(computed(a.zip(b).map(|(a, b)| match instruction {
+                            Instruction::Add => a + b,
+                            Instruction::Subtract => a - b,
+                            Instruction::Multiply => a * b,
+                            _ => (a == b).into(),
+                        })))
+                    }
+                    Instruction::Char => {
+                        pop(&mut stack);
+                        stack.push(None)
+                    }
+                    Instruction::Load(operand) => {
+                        let popped = pop(&mut stack);
+                        match usize::try_from(operand) {
+                            // the stack pointer, which makes the popped index an address
+                            Ok(0) => relocate(popped, &mut patches)?,
+                            Ok(address) if address >= PROGRAM_START + len => {
+                                patches.insert(index + 1, operand - removed as isize);
+                            }
+                            Ok(address) if address >= PROGRAM_START => return None,
+                            _ => {}
+                        }
+                        stack.push(None)
+                    }
+                    Instruction::Store => {
+                        let address = pop(&mut stack);
+                        pop(&mut stack);
+
+                        if !frozen {
+                            return None;
+                        }
+                        relocate(address, &mut patches)?
+                    }
+                    Instruction::Jump => {
+                        let offset = pop(&mut stack)?;
+                        let condition = pop(&mut stack).map(|known| known.value.is_truthy());
+
+                        if condition != Some(false) {
+                            let after = index + 1;
+                            let target = after.checked_add_signed(offset.value.to_num_option()?)?;
+                            patch(&offset, new_index(target) - new_index(after), &mut patches)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        let opcodes = (self.opcodes().iter().enumerate())
+            .filter(|(index, _)| live[*index])
+            .map(|(index, opcode)| patches.get(&index).copied().unwrap_or(*opcode))
+            .collect::<Vec<_>>();
+
+        Some(Program::from_opcodes(opcodes))
+    }
+}
+
+// changes the push a known value came from so it pushes `value` instead, if it has to and it can
+fn patch(known: &Known, value: isize, patches: &mut BTreeMap<usize, isize>) -> Option<()> {
+    if known.value.to_num_option() == Some(value) {
+        return Some(());
+    }
+
+    // pushes of -10 to -1 would have the opcodes of other instructions
+    let opcode = value
+        .checked_add(10)
+        .filter(|opcode| *opcode >= 10 || *opcode < 0)?;
+    match patches.insert(known.push?, opcode) {
+        Some(other) if other != opcode => None,
+        _ => Some(()),
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{EntityDecoding, VMState};
+use std::{borrow::Cow, fmt, str::FromStr};
+
+/// a step in the chain of changes made to the output of a program once it exits, set with [VMBuilder::post_process](crate::VMBuilder::post_process).
+/// by default the only step is [OutputProcessor::DecodeEntities], which is what the original interpreter does
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::{OutputProcessor, VMBuilder};
+///
+/// // pushes 10, converts it to a newline, adds "chicken" to it and exits
+/// let vm = || VMBuilder::from_opcodes([20, 9, 1, 2, 0]);
+///
+/// assert_eq!(vm().build().run(), Ok("\nchicken".to_string()));
+/// assert_eq!(
+///     vm().post_process([OutputProcessor::DecodeEntities, OutputProcessor::JsonEscape]).build().run(),
+///     Ok("\\nchicken".to_string())
+/// );
+/// assert_eq!(vm().post_process([OutputProcessor::None]).build().run(), Ok("&#10;chicken".to_string()));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutputProcessor {
+    /// converts HTML entities back into the characters they stand for, according to [VMState::entity_decoding]
+    DecodeEntities,
+
+    /// removes whitespace from the start and end of the output
+    Trim,
+
+    /// escapes the output like a JSON string, without the quotes around it, so it can be put straight into JSON
+    JsonEscape,
+
+    /// leaves the output as it is, for turning off the default decoding with `--post-process none`
+    None,
+}
+
+impl OutputProcessor {
+    /// applies this step to the output, with the entities decoded according to `entity_decoding`.
+    /// the output is only copied by steps that actually change it
+    pub fn apply<'a>(self, output: Cow<'a, str>, entity_decoding: EntityDecoding) -> Cow<'a, str> {
+        match (self, output) {
+            (Self::DecodeEntities, Cow::Borrowed(output)) => entity_decoding.decode(output),
+            (Self::DecodeEntities, Cow::Owned(output)) => match entity_decoding.decode(&output) {
+                Cow::Borrowed(_) => Cow::Owned(output),
+                Cow::Owned(decoded) => Cow::Owned(decoded),
+            },
+            (Self::Trim, Cow::Borrowed(output)) => Cow::Borrowed(output.trim()),
+            (Self::Trim, Cow::Owned(output)) if output.trim().len() == output.len() => {
+                Cow::Owned(output)
+            }
+            (Self::Trim, Cow::Owned(output)) => Cow::Owned(output.trim().to_string()),
+            (Self::JsonEscape, output) => {
+                let quoted = serde_json::to_string(&*output).unwrap_or_default();
+                Cow::Owned(quoted[1..quoted.len() - 1].to_string())
+            }
+            (Self::None, output) => output,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::DecodeEntities => "decode-entities",
+            Self::Trim => "trim",
+            Self::JsonEscape => "json-escape",
+            Self::None => "none",
+        }
+    }
+}
+
+impl FromStr for OutputProcessor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Self::DecodeEntities, Self::Trim, Self::JsonEscape, Self::None]
+            .into_iter()
+            .find(|processor| processor.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown output processor {:?}, expected decode-entities, trim, json-escape or none",
+                    s
+                )
+            })
+    }
+}
+
+impl fmt::Display for OutputProcessor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl VMState {
+    /// runs the output of a program through every step of [VMState::output_processors] in order, like [VMState::run] does once the program exits
+    pub fn process_output<'a>(&self, output: &'a str) -> Cow<'a, str> {
+        (self.output_processors.iter()).fold(Cow::Borrowed(output), |output, processor| {
+            processor.apply(output, self.entity_decoding)
+        })
+    }
+}
@@ -0,0 +1,105 @@
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+/// timing information for every instruction with the same mnemonic
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpcodeTiming {
+    /// how many times instructions with this mnemonic were executed
+    pub count: u64,
+
+    /// the total time spent executing instructions with this mnemonic
+    pub time: Duration,
+}
+
+/// profiling information collected while running a program, enabled with [VMBuilder::profile](crate::VMBuilder::profile)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    /// how many times the instruction at each address on the stack was executed
+    pub counts: BTreeMap<usize, u64>,
+
+    /// how long was spent executing each kind of instruction, keyed by mnemonic
+    pub opcodes: BTreeMap<&'static str, OpcodeTiming>,
+
+    /// how many times each address was jumped to by a taken jump
+    pub jump_targets: BTreeMap<usize, u64>,
+}
+
+impl Profile {
+    /// records a single executed instruction
+    pub fn record(&mut self, program_counter: usize, mnemonic: &'static str, time: Duration) {
+        *self.counts.entry(program_counter).or_default() += 1;
+
+        let timing = self.opcodes.entry(mnemonic).or_default();
+        timing.count += 1;
+        timing.time += time;
+    }
+
+    /// records a taken jump
+    pub fn record_jump(&mut self, target: usize) {
+        *self.jump_targets.entry(target).or_default() += 1;
+    }
+
+    /// the total number of instructions executed
+    pub fn total_steps(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// the `n` most executed addresses along with how many times they were executed, most executed first
+    pub fn hottest(&self, n: usize) -> Vec<(usize, u64)> {
+        top(&self.counts, n)
+    }
+
+    /// the `n` most jumped to addresses along with how many times they were jumped to, most jumped to first
+    pub fn hottest_jump_targets(&self, n: usize) -> Vec<(usize, u64)> {
+        top(&self.jump_targets, n)
+    }
+}
+
+fn top(map: &BTreeMap<usize, u64>, n: usize) -> Vec<(usize, u64)> {
+    let mut entries = map.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+
+    // ties are broken by address so the output is stable
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    entries.truncate(n);
+
+    entries
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total_steps().max(1);
+
+        writeln!(f, "{} instructions executed", self.total_steps())?;
+
+        writeln!(f, "\nhottest addresses:")?;
+        for (address, count) in self.hottest(10) {
+            writeln!(
+                f,
+                "    {:>6}: {:>10} ({:.1}%)",
+                address,
+                count,
+                count as f64 * 100.0 / total as f64
+            )?;
+        }
+
+        writeln!(f, "\ntime per instruction:")?;
+        let mut opcodes = self.opcodes.iter().collect::<Vec<_>>();
+        opcodes.sort_by(|a, b| b.1.time.cmp(&a.1.time).then(a.0.cmp(b.0)));
+        for (mnemonic, timing) in opcodes {
+            writeln!(
+                f,
+                "    {:>8}: {:>10} executed, {:?} total, {:?} average",
+                mnemonic,
+                timing.count,
+                timing.time,
+                Duration::from_nanos((timing.time.as_nanos() / timing.count.max(1) as u128) as u64)
+            )?;
+        }
+
+        writeln!(f, "\nhottest jump targets:")?;
+        for (address, count) in self.hottest_jump_targets(10) {
+            writeln!(f, "    {:>6}: {:>10}", address, count)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1214 @@
+use crate::{
+    Extension, Metadata, ProgramFile, SourceMap, Value, ADD, CHAR, CHICKEN, COMPARE, EXIT, JUMP,
+    LOAD, MULTIPLY, STORE, SUBTRACT,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Write},
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+    str::FromStr,
+};
+
+/// a Chicken program in opcode form, independent of any VM state.
+/// it's serialized as an array of its opcodes
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Program {
+    opcodes: Vec<isize>,
+}
+
+impl Program {
+    /// parses a Chicken program, counting the number of `chicken`s on each line.
+    /// lines can end with `\n`, `\r\n` or a lone `\r`, and a byte order mark at the start is ignored
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::Program;
+    ///
+    /// assert_eq!(Program::from_chicken("chicken\n\nchicken chicken").opcodes(), &[1, 0, 2]);
+    /// assert_eq!(Program::from_chicken("\u{feff}chicken\r\n\rchicken\tchicken").opcodes(), &[1, 0, 2]);
+    /// ```
+    pub fn from_chicken<T: AsRef<str>>(chicken: T) -> Self {
+        Self::from_opcodes(
+            chicken_lines(chicken.as_ref())
+                .map(|l| l.matches("chicken").count() as isize)
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// parses a Chicken program that has to follow the spec to the letter: nothing but the word `chicken` on each line,
+    /// separated by single spaces and ending with `\n`. the first thing that breaks the rules is reported with its exact byte offset,
+    /// unlike [Program::from_chicken] which counts `chicken`s wherever they are and ignores everything else
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::Program;
+    ///
+    /// assert_eq!(Program::from_chicken_strict("chicken chicken\n\nchicken\n").unwrap().opcodes(), &[2, 0, 1, 0]);
+    ///
+    /// let err = Program::from_chicken_strict("chicken\nchicken  chicken").unwrap_err();
+    /// assert_eq!((err.offset, err.line), (16, 2));
+    /// assert_eq!(err.message, "more than one space between chickens");
+    /// ```
+    pub fn from_chicken_strict<T: AsRef<str>>(chicken: T) -> Result<Self, SpecViolation> {
+        let mut opcodes = Vec::new();
+        let mut line_start = 0;
+
+        for (index, line) in chicken.as_ref().split('\n').enumerate() {
+            let violation = |position: usize, message: std::string::String| SpecViolation {
+                offset: line_start + position,
+                line: index + 1,
+                message,
+            };
+            let unexpected = |position: usize| {
+                let c = line[position..].chars().next().unwrap_or_default();
+                violation(
+                    position,
+                    format!(
+                        "unexpected {:?}, only chickens separated by single spaces are allowed",
+                        c
+                    ),
+                )
+            };
+
+            let mut count = 0;
+            let mut position = 0;
+            while position < line.len() {
+                let rest = &line[position..];
+
+                if count > 0 {
+                    // a chicken was just read, so a space has to come next
+                    if rest.starts_with("chicken") {
+                        return Err(violation(
+                            position,
+                            "chickens have to be separated by a space".to_string(),
+                        ));
+                    }
+                    if !rest.starts_with(' ') {
+                        return Err(unexpected(position));
+                    }
+                    position += 1;
+
+                    match line[position..].chars().next() {
+                        None => {
+                            return Err(violation(
+                                position - 1,
+                                "line ends with a space".to_string(),
+                            ))
+                        }
+                        Some(' ') => {
+                            return Err(violation(
+                                position,
+                                "more than one space between chickens".to_string(),
+                            ))
+                        }
+                        _ => {}
+                    }
+                } else if rest.starts_with(' ') {
+                    return Err(violation(position, "line starts with a space".to_string()));
+                }
+
+                if !line[position..].starts_with("chicken") {
+                    return Err(unexpected(position));
+                }
+                position += "chicken".len();
+                count += 1;
+            }
+
+            opcodes.push(count);
+            line_start += line.len() + 1;
+        }
+
+        Ok(Self::from_opcodes(opcodes))
+    }
+
+    /// parses Chicken code from the bytes of a file, which can be UTF-8 or UTF-16, see [decode_source]
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::Program;
+    ///
+    /// // "chicken\r\n" saved as UTF-16 by a Windows text editor
+    /// let bytes: Vec<u8> = [0xfeff].into_iter()
+    ///     .chain("chicken\r\n".encode_utf16())
+    ///     .flat_map(u16::to_le_bytes)
+    ///     .collect();
+    ///
+    /// assert_eq!(Program::from_bytes(bytes).unwrap().opcodes(), &[1, 0]);
+    /// ```
+    pub fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self, ParseError> {
+        decode_source(bytes.as_ref()).map(Self::from_chicken)
+    }
+
+    /// parses a program written as raw opcodes separated by whitespace or commas. `#` starts a comment that runs to the end of the line
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::Program;
+    ///
+    /// assert_eq!(Program::from_opcode_text("11, 6 0 # cat").unwrap().opcodes(), &[11, 6, 0]);
+    /// assert_eq!(Program::from_opcode_text("11\nsix").unwrap_err().line, 2);
+    /// ```
+    pub fn from_opcode_text<T: AsRef<str>>(text: T) -> Result<Self, ParseError> {
+        let mut opcodes = Vec::new();
+
+        for (index, line) in text.as_ref().lines().enumerate() {
+            parse_opcode_line(line, index + 1, &mut opcodes)?;
+        }
+
+        Ok(Self::from_opcodes(opcodes))
+    }
+
+    /// reads a program written as raw opcodes from a reader, in the same format as [Program::from_opcode_text].
+    /// the reader is parsed a line at a time, so a generated program can be streamed in without reading all of it into a string first
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{LoadError, Program};
+    ///
+    /// assert_eq!(Program::from_opcode_reader("11 6\n0".as_bytes()).unwrap().opcodes(), &[11, 6, 0]);
+    /// assert!(matches!(Program::from_opcode_reader("11\nsix".as_bytes()), Err(LoadError::Parse(err)) if err.line == 2));
+    /// ```
+    pub fn from_opcode_reader<R: Read>(reader: R) -> Result<Self, LoadError> {
+        let mut reader = BufReader::new(reader);
+        let mut opcodes = Vec::new();
+        let mut line = String::new();
+
+        for number in 1.. {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+
+            parse_opcode_line(&line, number, &mut opcodes)?;
+        }
+
+        Ok(Self::from_opcodes(opcodes))
+    }
+
+    /// parses a program written in assembly, with one instruction per line in the same syntax as disassembly listings.
+    /// `#` starts a comment that runs to the end of the line, and a `load` without an operand is just the Load opcode on its own.
+    /// a line can start with a label like `loop:`, which names the instruction after it, and `jump <label>` jumps to a label
+    /// if the value on top of the stack is truthy, assembling to a push of the offset to it followed by a jump.
+    /// `export` and `import` lines share labels between files linked together with [Program::link]
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::Program;
+    ///
+    /// let program = Program::from_asm("push 1\nload 0 # the input\nexit").unwrap();
+    /// assert_eq!(program.opcodes(), &[11, 6, 0, 0]);
+    /// assert_eq!(Program::from_asm(program.to_asm()).unwrap(), program);
+    ///
+    /// // push 1, then jump forwards by 1 over the exit
+    /// let program = Program::from_asm("push 1\njump end\nexit\nend: chicken").unwrap();
+    /// assert_eq!(program.opcodes(), &[11, 11, 8, 0, 1]);
+    /// ```
+    pub fn from_asm<T: AsRef<str>>(asm: T) -> Result<Self, ParseError> {
+        Self::from_asm_with_source_map(asm, None).map(|(program, _)| program)
+    }
+
+    /// parses a program written in assembly like [Program::from_asm], along with a [SourceMap] of where each instruction came from.
+    /// `file` is the name of the file the assembly was read from, if it was read from one
+    pub fn from_asm_with_source_map<T: AsRef<str>>(
+        asm: T,
+        file: Option<&str>,
+    ) -> Result<(Self, SourceMap), ParseError> {
+        Self::assemble(asm.as_ref(), file)
+    }
+
+    /// reads Chicken code from a reader and parses it, which can be UTF-8 or UTF-16 like [Program::from_bytes]
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, LoadError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Ok(Self::from_bytes(bytes)?)
+    }
+
+    /// reads a program from a file, guessing its format from the extension.
+    /// `.opcodes` files are read as raw opcodes, `.asm` files as assembly, `.json` files as an array of opcodes and anything else as Chicken code.
+    /// files can be UTF-8 or UTF-16, see [decode_source]
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::Program;
+    ///
+    /// assert_eq!(Program::from_path("examples/cat.chicken").unwrap().opcodes(), &[11, 6, 0, 0]);
+    /// assert!(Program::from_path("examples/nonexistent.chicken").is_err());
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        Self::from_path_with_source_map(path).map(|file| file.program)
+    }
+
+    /// reads a program from a file like [Program::from_path], along with its [SourceMap] if it's assembly or JSON with a source map in it,
+    /// and its [Metadata](crate::Metadata) if it has any
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::Program;
+    ///
+    /// let file = Program::from_path_with_source_map("examples/cat.chicken").unwrap();
+    /// assert_eq!((file.program.len(), file.source_map), (4, None));
+    /// ```
+    pub fn from_path_with_source_map<P: AsRef<Path>>(path: P) -> Result<ProgramFile, LoadError> {
+        let path = path.as_ref();
+        let text = decode_source(&std::fs::read(path)?)?;
+
+        let program = match path.extension().and_then(|e| e.to_str()) {
+            Some("opcodes") => {
+                return Ok(ProgramFile {
+                    metadata: Metadata::from_header(&text)?,
+                    program: Self::from_opcode_text(text)?,
+                    source_map: None,
+                })
+            }
+            Some("asm") => {
+                let metadata = Metadata::from_header(&text)?;
+                let (program, source_map) =
+                    Self::from_asm_with_source_map(text, Some(&path.to_string_lossy()))?;
+
+                return Ok(ProgramFile {
+                    program,
+                    source_map: Some(source_map),
+                    metadata,
+                });
+            }
+            // a plain array is parsed on its own so errors in it point at the right place
+            Some("json") if text.trim_start().starts_with('{') => {
+                return serde_json::from_str(&text).map_err(json_error)
+            }
+            Some("json") => serde_json::from_str(&text).map_err(json_error)?,
+            _ => Self::from_chicken(text),
+        };
+
+        Ok(ProgramFile {
+            program,
+            ..Default::default()
+        })
+    }
+
+    /// creates a program from its raw opcodes
+    pub fn from_opcodes<T: Into<Vec<isize>>>(opcodes: T) -> Self {
+        Self {
+            opcodes: opcodes.into(),
+        }
+    }
+
+    /// the raw opcodes of this program
+    pub fn opcodes(&self) -> &[isize] {
+        &self.opcodes
+    }
+
+    /// consumes this program, returning its raw opcodes
+    pub fn into_opcodes(self) -> Vec<isize> {
+        self.opcodes
+    }
+
+    /// the number of opcodes in this program, counting the operands of Load instructions
+    pub fn len(&self) -> usize {
+        self.opcodes.len()
+    }
+
+    /// whether this program has no opcodes at all
+    pub fn is_empty(&self) -> bool {
+        self.opcodes.is_empty()
+    }
+
+    /// writes this program as canonical Chicken code, with one line per opcode and single spaces between chickens,
+    /// or returns None if it has negative opcodes, which can't be written as Chicken.
+    /// parsing the code gives back the same program, as long as it isn't empty, since empty Chicken code has a single exit in it
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::Program;
+    ///
+    /// assert_eq!(Program::from_opcodes([1, 0, 2]).to_chicken().unwrap(), "chicken\n\nchicken chicken");
+    ///
+    /// let program = Program::from_chicken("chicken  chicken \n\nchicken");
+    /// assert_eq!(Program::from_chicken(program.to_chicken().unwrap()), program);
+    /// assert_eq!(Program::from_asm("push -20").unwrap().to_chicken(), None);
+    /// ```
+    pub fn to_chicken(&self) -> Option<String> {
+        let mut chicken = String::new();
+
+        for (index, &opcode) in self.opcodes.iter().enumerate() {
+            if index > 0 {
+                chicken.push('\n');
+            }
+
+            if opcode < 0 {
+                return None;
+            }
+
+            for word in 0..opcode {
+                if word > 0 {
+                    chicken.push(' ');
+                }
+                chicken.push_str("chicken");
+            }
+        }
+
+        Some(chicken)
+    }
+
+    /// writes this program as raw opcodes, one per line, which can be read back with [Program::from_opcode_text]
+    pub fn to_opcode_text(&self) -> String {
+        self.opcodes.iter().fold(String::new(), |mut text, opcode| {
+            let _ = writeln!(text, "{}", opcode);
+            text
+        })
+    }
+
+    /// writes this program as assembly, one instruction per line, which can be read back with [Program::from_asm]
+    pub fn to_asm(&self) -> String {
+        let mut asm = String::new();
+
+        for (index, instruction) in self.instructions() {
+            // a Load at the very end of the program takes its operand from the exit after it, which isn't part of the program
+            if let (Instruction::Load(_), true) = (instruction, index + 1 == self.len()) {
+                let _ = writeln!(asm, "{}", instruction.mnemonic());
+            } else {
+                let _ = writeln!(asm, "{}", instruction);
+            }
+        }
+
+        asm
+    }
+
+    /// decodes the instruction at the given program index.
+    /// the operand of a Load at the very end of the program is read from the exit opcode appended after it, just like at runtime
+    pub fn decode(&self, index: usize) -> Option<Instruction> {
+        let opcode = *self.opcodes.get(index)?;
+        let operand = self.opcodes.get(index + 1).copied().unwrap_or(EXIT);
+
+        Some(Instruction::from_opcode(opcode, operand))
+    }
+
+    /// linearly decodes this program from the start, returning each instruction along with its program index
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{Instruction, Program};
+    ///
+    /// assert_eq!(
+    ///     Program::from_opcodes([11, 6, 0, 0]).instructions(),
+    ///     vec![(0, Instruction::Push(1)), (1, Instruction::Load(0)), (3, Instruction::Exit)]
+    /// );
+    /// ```
+    pub fn instructions(&self) -> Vec<(usize, Instruction)> {
+        let mut instructions = Vec::new();
+        let mut index = 0;
+
+        while let Some(instruction) = self.decode(index) {
+            instructions.push((index, instruction));
+            index += instruction.width();
+        }
+
+        instructions
+    }
+
+    /// builds a control flow graph of this program.
+    ///
+    /// jump offsets are resolved by constant folding the values pushed inside each basic block,
+    /// so jumps whose offsets are computed elsewhere (or loaded from the stack) will show up as [Successor::DynamicJump]
+    pub fn cfg(&self) -> Cfg {
+        let len = self.len();
+        let mut leaders = BTreeSet::from([0]);
+
+        // jump targets can only be found once the blocks containing the jumps are known, so keep splitting until nothing changes
+        loop {
+            let blocks = self.blocks_from_leaders(&leaders);
+            let mut new_leaders = leaders.clone();
+
+            for block in blocks.iter() {
+                for successor in block.successors.iter() {
+                    if let Successor::Next(target) | Successor::Jump(target) = successor {
+                        if *target < len {
+                            new_leaders.insert(*target);
+                        }
+                    }
+                }
+            }
+
+            if new_leaders == leaders {
+                return Cfg { blocks, len };
+            }
+
+            leaders = new_leaders;
+        }
+    }
+
+    /// looks for Loads that can't get the operand they were meant to, since Load is the only instruction that takes up two words.
+    /// a Load at the very end of the program reads its operand from the exit after it, and a jump that lands on the operand of a Load
+    /// runs it as an instruction of its own, decoding everything after it differently.
+    /// jumps are found with [Program::cfg], so only jumps with offsets known ahead of time are checked
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{InvalidLoadKind, Program};
+    ///
+    /// assert_eq!(Program::from_opcodes([11, 6, 0]).validate(), Ok(()));
+    ///
+    /// // push 1, load
+    /// let invalid = Program::from_opcodes([11, 6]).validate().unwrap_err();
+    /// assert_eq!((invalid[0].index, invalid[0].kind), (1, InvalidLoadKind::Trailing));
+    ///
+    /// // push 1, push 1, jump forwards by 1 into the operand of the load after it
+    /// let invalid = Program::from_opcodes([11, 11, 8, 6, 0]).validate().unwrap_err();
+    /// assert_eq!((invalid[0].index, invalid[0].kind), (3, InvalidLoadKind::JumpedInto { jump: 2 }));
+    /// ```
+    pub fn validate(&self) -> Result<(), Vec<InvalidLoad>> {
+        let mut invalid = Vec::new();
+        let mut operands = BTreeSet::new();
+
+        for (index, instruction) in self.instructions() {
+            if let Instruction::Load(_) = instruction {
+                if index + 1 == self.len() {
+                    invalid.push(InvalidLoad {
+                        index,
+                        kind: InvalidLoadKind::Trailing,
+                    });
+                } else {
+                    operands.insert(index + 1);
+                }
+            }
+        }
+
+        for block in self.cfg().blocks {
+            let Some(&(jump, _)) = block.instructions.last() else {
+                continue;
+            };
+
+            for successor in block.successors.iter() {
+                if let Successor::Jump(target) = successor {
+                    if operands.contains(target) {
+                        invalid.push(InvalidLoad {
+                            index: target - 1,
+                            kind: InvalidLoadKind::JumpedInto { jump },
+                        });
+                    }
+                }
+            }
+        }
+
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            invalid.sort_by_key(|invalid| invalid.index);
+            Err(invalid)
+        }
+    }
+
+    fn blocks_from_leaders(&self, leaders: &BTreeSet<usize>) -> Vec<BasicBlock> {
+        let len = self.len();
+        let mut blocks = Vec::new();
+
+        for &start in leaders.iter() {
+            let mut index = start;
+            let mut instructions = Vec::new();
+
+            // values pushed inside this block that are known ahead of time, None if they aren't
+            let mut stack: Vec<Option<Value>> = Vec::new();
+            let pop = |stack: &mut Vec<Option<Value>>| stack.pop().flatten();
+
+            let successors = loop {
+                let instruction = match self.decode(index) {
+                    Some(instruction) => instruction,
+                    None => break vec![Successor::Next(len)],
+                };
+                instructions.push((index, instruction));
+                index += instruction.width();
+
+                match instruction {
+                    Instruction::Exit => break vec![],
+                    Instruction::Chicken => stack.push(Some("chicken".into())),
+                    Instruction::Add | Instruction::Subtract | Instruction::Multiply => {
+                        let b = pop(&mut stack);
+                        let a = pop(&mut stack);
+                        stack.push(a.zip(b).map(|(a, b)| match instruction {
+                            Instruction::Add => a + b,
+                            Instruction::Subtract => a - b,
+                            _ => a * b,
+                        }))
+                    }
+                    Instruction::Compare => {
+                        let b = pop(&mut stack);
+                        let a = pop(&mut stack);
+                        stack.push(a.zip(b).map(|(a, b)| (a == b).into()))
+                    }
+                    Instruction::Load(_) | Instruction::Char => {
+                        pop(&mut stack);
+                        stack.push(None)
+                    }
+                    Instruction::Store => {
+                        pop(&mut stack);
+                        pop(&mut stack);
+                    }
+                    Instruction::Jump => {
+                        let offset = pop(&mut stack).and_then(|v| v.to_num_option());
+                        let condition = pop(&mut stack).map(|v| v.is_truthy());
+
+                        let jump = match offset {
+                            Some(offset) => match (index as isize).checked_add(offset) {
+                                Some(target) if target >= 0 && target as usize <= len => {
+                                    Successor::Jump(target as usize)
+                                }
+                                target => Successor::OutOfBounds(target.unwrap_or(isize::MAX)),
+                            },
+                            None => Successor::DynamicJump,
+                        };
+
+                        break match condition {
+                            Some(true) => vec![jump],
+                            Some(false) => vec![Successor::Next(index)],
+                            None => vec![jump, Successor::Next(index)],
+                        };
+                    }
+                    Instruction::Push(n) => stack.push(Some(n.into())),
+                }
+
+                if leaders.contains(&index) {
+                    break vec![Successor::Next(index)];
+                }
+            };
+
+            blocks.push(BasicBlock {
+                start,
+                end: index,
+                instructions,
+                successors,
+            });
+        }
+
+        blocks
+    }
+}
+
+/// parses Chicken code, exactly like [Program::from_chicken]. this can't fail, since any text is a Chicken program
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::Program;
+///
+/// let program: Program = "chicken\n\nchicken chicken".parse().unwrap();
+/// assert_eq!(program.opcodes(), &[1, 0, 2]);
+/// ```
+impl FromStr for Program {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from_chicken(s))
+    }
+}
+
+impl From<Vec<isize>> for Program {
+    fn from(opcodes: Vec<isize>) -> Self {
+        Self::from_opcodes(opcodes)
+    }
+}
+
+/// generates programs that are mostly made of real instructions and small literals, so fuzzing spends its time running programs
+/// instead of exiting on the first unknown opcode
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Program {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let len = u.arbitrary_len::<u8>()?;
+        let mut opcodes = Vec::with_capacity(len);
+
+        for _ in 0..len {
+            opcodes.push(match u.arbitrary::<u8>()? {
+                // instructions
+                byte @ 0..=127 => (byte % 10) as isize,
+
+                // small literals, which are also used as addresses and jump offsets
+                byte @ 128..=251 => (byte - 128) as isize % 32 + 10,
+
+                // anything at all, to hit overflows and unknown opcodes
+                _ => u.arbitrary()?,
+            });
+        }
+
+        Ok(Self::from_opcodes(opcodes))
+    }
+}
+
+/// a single decoded Chicken instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    /// exits the program
+    Exit,
+
+    /// pushes the string "chicken"
+    Chicken,
+
+    /// adds (or concatenates) the top two values
+    Add,
+
+    /// subtracts the top two values
+    Subtract,
+
+    /// multiplies the top two values
+    Multiply,
+
+    /// compares the top two values for loose equality
+    Compare,
+
+    /// indexes into the given address with the value on the top of the stack
+    Load(isize),
+
+    /// stores the second value from the top at the address on the top of the stack
+    Store,
+
+    /// jumps by the relative offset on the top of the stack if the value below it is truthy
+    Jump,
+
+    /// converts the value on the top of the stack to a character
+    Char,
+
+    /// pushes a literal number
+    Push(isize),
+}
+
+impl Instruction {
+    /// decodes an opcode into an instruction. the operand is only used by Load
+    pub fn from_opcode(opcode: isize, operand: isize) -> Self {
+        match opcode {
+            EXIT => Instruction::Exit,
+            CHICKEN => Instruction::Chicken,
+            ADD => Instruction::Add,
+            SUBTRACT => Instruction::Subtract,
+            MULTIPLY => Instruction::Multiply,
+            COMPARE => Instruction::Compare,
+            LOAD => Instruction::Load(operand),
+            STORE => Instruction::Store,
+            JUMP => Instruction::Jump,
+            CHAR => Instruction::Char,
+            n => Instruction::Push(n.wrapping_sub(10)),
+        }
+    }
+
+    /// decodes an instruction from values on the stack at runtime.
+    /// returns None if the opcode isn't a number, or if it's a Load whose operand isn't a number
+    pub fn from_values(opcode: &Value, operand: Option<&Value>) -> Option<Self> {
+        match opcode {
+            Value::Num(LOAD) => Some(Instruction::Load(operand?.to_num_option()?)),
+            Value::Num(n) => Some(Self::from_opcode(*n, EXIT)),
+            _ => None,
+        }
+    }
+
+    /// the number of opcodes this instruction takes up, which is 2 for Load and 1 for everything else
+    pub fn width(&self) -> usize {
+        match self {
+            Instruction::Load(_) => 2,
+            _ => 1,
+        }
+    }
+
+    /// the mnemonic of the instruction with the given opcode, without needing to decode its operand
+    pub fn mnemonic_of(opcode: isize) -> &'static str {
+        match opcode {
+            EXIT => "exit",
+            CHICKEN => "chicken",
+            ADD => "add",
+            SUBTRACT => "subtract",
+            MULTIPLY => "multiply",
+            COMPARE => "compare",
+            LOAD => "load",
+            STORE => "store",
+            JUMP => "jump",
+            CHAR => "char",
+            _ => "push",
+        }
+    }
+
+    /// the mnemonic of this instruction, as used in disassembly listings
+    pub fn mnemonic(&self) -> &'static str {
+        Self::mnemonic_of(self.opcode())
+    }
+
+    /// the opcode of this instruction, without its operand
+    pub fn opcode(&self) -> isize {
+        match self {
+            Instruction::Exit => EXIT,
+            Instruction::Chicken => CHICKEN,
+            Instruction::Add => ADD,
+            Instruction::Subtract => SUBTRACT,
+            Instruction::Multiply => MULTIPLY,
+            Instruction::Compare => COMPARE,
+            Instruction::Load(_) => LOAD,
+            Instruction::Store => STORE,
+            Instruction::Jump => JUMP,
+            Instruction::Char => CHAR,
+            Instruction::Push(n) => n.wrapping_add(10),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Load(n) | Instruction::Push(n) => write!(f, "{} {}", self.mnemonic(), n),
+            _ => write!(f, "{}", self.mnemonic()),
+        }
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = String;
+
+    /// parses an instruction in the same syntax it's displayed in, like `push 5` or `load 0`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let mnemonic = words.next().unwrap_or_default();
+        let operand = words.next();
+
+        if let Some(extra) = words.next() {
+            return Err(format!("unexpected {:?} after instruction", extra));
+        }
+
+        let instruction = match mnemonic {
+            "exit" => Instruction::Exit,
+            "chicken" => Instruction::Chicken,
+            "add" => Instruction::Add,
+            "subtract" => Instruction::Subtract,
+            "multiply" => Instruction::Multiply,
+            "compare" => Instruction::Compare,
+            "store" => Instruction::Store,
+            "jump" => Instruction::Jump,
+            "char" => Instruction::Char,
+            "load" | "push" => {
+                let operand = match operand.map(str::parse::<isize>) {
+                    Some(Ok(n)) => n,
+                    Some(Err(err)) => {
+                        return Err(format!("invalid operand for {}: {}", mnemonic, err))
+                    }
+                    None => return Err(format!("{} needs an operand", mnemonic)),
+                };
+
+                return match mnemonic {
+                    "load" => Ok(Instruction::Load(operand)),
+                    // operands from -10 to -1 would have the opcodes of other instructions
+                    _ => match operand.checked_add(10) {
+                        Some(opcode) if !(EXIT..=CHAR).contains(&opcode) => {
+                            Ok(Instruction::Push(operand))
+                        }
+                        _ => Err(format!("push {} can't be encoded", operand)),
+                    },
+                };
+            }
+            // extension instructions are written by their mnemonic too, and assemble to the push that has their opcode
+            _ => match mnemonic.parse::<Extension>() {
+                Ok(extension) => Instruction::Push(extension.opcode() - 10),
+                Err(_) => return Err(format!("unknown instruction {:?}", mnemonic)),
+            },
+        };
+
+        match operand {
+            Some(operand) => Err(format!(
+                "{} doesn't take an operand, found {:?}",
+                mnemonic, operand
+            )),
+            None => Ok(instruction),
+        }
+    }
+}
+
+/// where execution can go after a basic block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Successor {
+    /// execution falls through to the given program index. an index equal to the length of the program is the exit appended after it
+    Next(usize),
+
+    /// a jump to the given program index is taken
+    Jump(usize),
+
+    /// a jump whose offset couldn't be determined statically
+    DynamicJump,
+
+    /// a jump that lands outside of the program, at the given program index
+    OutOfBounds(isize),
+}
+
+/// what's wrong with an [InvalidLoad]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidLoadKind {
+    /// the Load is at the very end of the program, so its operand is the exit after it
+    Trailing,
+
+    /// the jump at the given program index lands on the operand of the Load
+    JumpedInto {
+        /// the program index of the jump
+        jump: usize,
+    },
+}
+
+/// a Load that doesn't get the operand it was meant to, found by [Program::validate]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidLoad {
+    /// the program index of the Load
+    pub index: usize,
+
+    /// what's wrong with it
+    pub kind: InvalidLoadKind,
+}
+
+impl fmt::Display for InvalidLoad {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            InvalidLoadKind::Trailing => write!(
+                f,
+                "load at program index {} is at the end of the program, so it has no operand",
+                self.index
+            ),
+            InvalidLoadKind::JumpedInto { jump } => write!(
+                f,
+                "jump at program index {} lands on the operand of the load at program index {}",
+                jump, self.index
+            ),
+        }
+    }
+}
+
+/// a run of instructions that are always executed in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// the program index of the first instruction in this block
+    pub start: usize,
+
+    /// the program index right after the last instruction in this block
+    pub end: usize,
+
+    /// the instructions in this block, along with their program indices
+    pub instructions: Vec<(usize, Instruction)>,
+
+    /// the places execution can continue at after this block
+    pub successors: Vec<Successor>,
+}
+
+/// the control flow graph of a Chicken program, built with [Program::cfg]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfg {
+    /// all the basic blocks in the program, ordered by their start index
+    pub blocks: Vec<BasicBlock>,
+
+    /// the length of the program this graph was built from
+    pub len: usize,
+}
+
+impl Cfg {
+    /// finds the block starting at the given program index
+    pub fn block_at(&self, start: usize) -> Option<&BasicBlock> {
+        self.blocks
+            .binary_search_by_key(&start, |b| b.start)
+            .ok()
+            .map(|i| &self.blocks[i])
+    }
+
+    /// renders this graph in the Graphviz DOT format
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+
+        // writing to a String can't fail
+        let _ = self.write_dot(&mut dot);
+
+        dot
+    }
+
+    fn write_dot(&self, f: &mut String) -> fmt::Result {
+        writeln!(f, "digraph chicken {{")?;
+        writeln!(f, "    node [shape=box, fontname=\"monospace\"];")?;
+        writeln!(f, "    exit [label=\"exit\", shape=ellipse];")?;
+
+        let mut dynamic = false;
+        let mut out_of_bounds = false;
+
+        for block in self.blocks.iter() {
+            write!(f, "    b{} [label=\"", block.start)?;
+            for (index, instruction) in block.instructions.iter() {
+                write!(f, "{}: {}\\l", index, instruction)?;
+            }
+            writeln!(f, "\"];")?;
+
+            if block.successors.is_empty() {
+                writeln!(f, "    b{} -> exit;", block.start)?;
+            }
+
+            for successor in block.successors.iter() {
+                match successor {
+                    Successor::Next(n) if *n == self.len => {
+                        writeln!(f, "    b{} -> exit;", block.start)?
+                    }
+                    Successor::Next(n) => writeln!(f, "    b{} -> b{};", block.start, n)?,
+                    Successor::Jump(n) if *n == self.len => {
+                        writeln!(f, "    b{} -> exit [label=\"jump\"];", block.start)?
+                    }
+                    Successor::Jump(n) => {
+                        writeln!(f, "    b{} -> b{} [label=\"jump\"];", block.start, n)?
+                    }
+                    Successor::DynamicJump => {
+                        dynamic = true;
+                        writeln!(
+                            f,
+                            "    b{} -> dynamic [label=\"jump\", style=dashed];",
+                            block.start
+                        )?
+                    }
+                    Successor::OutOfBounds(n) => {
+                        out_of_bounds = true;
+                        writeln!(
+                            f,
+                            "    b{} -> outside [label=\"jump to {}\", color=red];",
+                            block.start, n
+                        )?
+                    }
+                }
+            }
+        }
+
+        if dynamic {
+            writeln!(
+                f,
+                "    dynamic [label=\"unknown target\", shape=ellipse, style=dashed];"
+            )?;
+        }
+
+        if out_of_bounds {
+            writeln!(
+                f,
+                "    outside [label=\"outside program\", shape=ellipse, color=red];"
+            )?;
+        }
+
+        writeln!(f, "}}")
+    }
+}
+
+/// whether a name can be used as a label in assembly
+pub(crate) fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// parses a line of raw opcodes separated by whitespace or commas onto the end of `opcodes`, for [Program::from_opcode_text]
+fn parse_opcode_line(
+    line: &str,
+    number: usize,
+    opcodes: &mut Vec<isize>,
+) -> Result<(), ParseError> {
+    let line = line.split('#').next().unwrap_or_default();
+
+    for word in line.split(|c: char| c == ',' || c.is_whitespace()) {
+        if word.is_empty() {
+            continue;
+        }
+
+        match word.parse::<isize>() {
+            Ok(opcode) => opcodes.push(opcode),
+            Err(err) => {
+                return Err(ParseError {
+                    line: number,
+                    message: format!("invalid opcode {:?}: {}", word, err),
+                })
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// turns an error reading a JSON program into a load error, keeping the line it was on
+fn json_error(err: serde_json::Error) -> LoadError {
+    LoadError::Parse(ParseError {
+        line: err.line(),
+        message: err.to_string(),
+    })
+}
+
+/// splits Chicken code into its lines, which are its opcodes, the same way [Program::from_chicken] does
+pub(crate) fn chicken_lines(chicken: &str) -> impl Iterator<Item = &str> {
+    let chicken = chicken.strip_prefix('\u{feff}').unwrap_or(chicken);
+
+    chicken
+        .split("\r\n")
+        .flat_map(|line| line.split(['\r', '\n']))
+}
+
+/// decodes the text of a program file, which can be UTF-8 or UTF-16 with or without a byte order mark.
+/// UTF-16 without a byte order mark is recognized by its zero bytes, which text in any other encoding wouldn't have.
+/// a byte order mark is left out of the decoded text
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::decode_source;
+///
+/// assert_eq!(decode_source(b"\xef\xbb\xbfchicken").unwrap(), "chicken");
+/// assert_eq!(decode_source(b"\0c\0h\0i\0c\0k\0e\0n").unwrap(), "chicken");
+/// assert_eq!(decode_source(b"chicken\n\xff").unwrap_err().line, 2);
+/// ```
+pub fn decode_source(bytes: &[u8]) -> Result<String, ParseError> {
+    let utf16 = |bytes: &[u8], from_bytes: fn([u8; 2]) -> u16| {
+        let units = bytes.chunks(2).map(|pair| match *pair {
+            [a, b] => from_bytes([a, b]),
+            // an odd byte out can't be valid
+            _ => 0xdc00,
+        });
+
+        let mut text = String::with_capacity(bytes.len() / 2);
+        for c in char::decode_utf16(units) {
+            match c {
+                Ok(c) => text.push(c),
+                Err(_) => {
+                    return Err(ParseError {
+                        line: text.matches('\n').count() + 1,
+                        message: "invalid UTF-16".to_string(),
+                    })
+                }
+            }
+        }
+
+        Ok(text)
+    };
+
+    // whether the bytes look like UTF-16 with the zero bytes of ASCII characters at the given offset, 1 for little endian.
+    // about half of the bytes of UTF-16 text that's mostly ASCII are zeros, all on the same side of each pair
+    let zeros = |offset: usize| {
+        (bytes.iter().skip(offset).step_by(2))
+            .filter(|b| **b == 0)
+            .count()
+    };
+    let looks_utf16 = |offset: usize| {
+        bytes.len().is_multiple_of(2)
+            && zeros(1 - offset) == 0
+            && zeros(offset) * 2 >= bytes.len() / 2
+    };
+
+    match bytes {
+        [0xef, 0xbb, 0xbf, rest @ ..] => decode_source(rest),
+        [0xff, 0xfe, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xfe, 0xff, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        _ if looks_utf16(1) => utf16(bytes, u16::from_le_bytes),
+        _ if looks_utf16(0) => utf16(bytes, u16::from_be_bytes),
+        _ => match std::str::from_utf8(bytes) {
+            Ok(text) => Ok(text.to_string()),
+            Err(err) => Err(ParseError {
+                line: bytes[..err.valid_up_to()]
+                    .iter()
+                    .filter(|b| **b == b'\n')
+                    .count()
+                    + 1,
+                message: "invalid UTF-8".to_string(),
+            }),
+        },
+    }
+}
+
+/// an error encountered while parsing a program from text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// the line the error is on, starting at 1
+    pub line: usize,
+
+    /// what went wrong
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// where Chicken code breaks the spec, from [Program::from_chicken_strict]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpecViolation {
+    /// the byte offset of the first thing that breaks the spec
+    pub offset: usize,
+
+    /// the line it's on, starting at 1
+    pub line: usize,
+
+    /// what's wrong
+    pub message: String,
+}
+
+impl fmt::Display for SpecViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {} (byte {}): {}",
+            self.line, self.offset, self.message
+        )
+    }
+}
+
+impl std::error::Error for SpecViolation {}
+
+impl From<SpecViolation> for ParseError {
+    fn from(err: SpecViolation) -> Self {
+        Self {
+            line: err.line,
+            message: format!("{} at byte {}", err.message, err.offset),
+        }
+    }
+}
+
+/// an error encountered while loading a program from a file or reader
+#[derive(Debug)]
+pub enum LoadError {
+    /// the program couldn't be read
+    Io(io::Error),
+
+    /// the program was read but wasn't valid
+    Parse(ParseError),
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<ParseError> for LoadError {
+    fn from(err: ParseError) -> Self {
+        Self::Parse(err)
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "couldn't read program: {}", err),
+            Self::Parse(err) => write!(f, "invalid program on {}", err),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Parse(err) => Some(err),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use crate::VMState;
+
+/// how far a program has got, passed to the callback given to [VMBuilder::on_progress](crate::VMBuilder::on_progress)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// how many instructions have been executed so far
+    pub steps: u64,
+
+    /// the size of the stack
+    pub stack_depth: usize,
+}
+
+// calls a callback every so many steps
+pub(crate) struct ProgressReporter {
+    every_n_steps: u64,
+
+    // the step count to call the callback at next
+    next: u64,
+
+    callback: Box<dyn FnMut(Progress) + Send>,
+}
+
+impl ProgressReporter {
+    pub(crate) fn new(every_n_steps: u64, callback: Box<dyn FnMut(Progress) + Send>) -> Self {
+        let every_n_steps = every_n_steps.max(1);
+
+        Self {
+            every_n_steps,
+            next: every_n_steps,
+            callback,
+        }
+    }
+
+    // starts counting from the first step again
+    pub(crate) fn reset(&mut self) {
+        self.next = self.every_n_steps;
+    }
+}
+
+impl VMState {
+    // calls the progress callback if enough steps have been executed since it was last called.
+    // fused instructions count as two steps, so the step count can go past the next report by one
+    pub(crate) fn report_progress(&mut self) {
+        let steps = self.stats.steps;
+        let stack_depth = self.stack.len();
+
+        if let Some(reporter) = self.progress_reporter.as_mut() {
+            if steps >= reporter.next {
+                reporter.next = (steps / reporter.every_n_steps + 1) * reporter.every_n_steps;
+                (reporter.callback)(Progress { steps, stack_depth });
+            }
+        }
+    }
+}
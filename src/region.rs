@@ -0,0 +1,101 @@
+use crate::VMState;
+use colored::{Color, Colorize};
+use serde::Serialize;
+use std::fmt;
+
+/// which part of the memory layout a cell on the stack is in, from [VMState::region].
+/// the debugger, the stack animation, the HTML report and the [Display](fmt::Display) impl of [VMState] all color cells the same way by region,
+/// so the layout can be seen at a glance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StackRegion {
+    /// address 0, which points at the stack itself
+    Pointer,
+
+    /// the inputs, starting at address 1
+    Input,
+
+    /// the program, as it was loaded
+    Program,
+
+    /// a cell of the program, or the exit after it, that the program has stored something into
+    SelfModified,
+
+    /// the exit appended after the program
+    Sentinel,
+
+    /// everything above the program, from the data segment to whatever the program has pushed
+    Data,
+}
+
+impl StackRegion {
+    /// a short name for the region, which is what the [Display](fmt::Display) impl of [VMState] writes next to the cells that aren't program or data
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Pointer => "stack pointer",
+            Self::Input => "input",
+            Self::Program => "program",
+            Self::SelfModified => "self-modified",
+            Self::Sentinel => "end of program",
+            Self::Data => "data",
+        }
+    }
+
+    /// the terminal color cells in this region are shown in, if they're colored at all
+    pub fn color(self) -> Option<Color> {
+        match self {
+            Self::Pointer => Some(Color::Magenta),
+            Self::Input => Some(Color::Cyan),
+            Self::Program => Some(Color::Blue),
+            Self::SelfModified => Some(Color::Red),
+            Self::Sentinel => Some(Color::BrightBlack),
+            Self::Data => None,
+        }
+    }
+
+    // colors some text the way cells in this region are colored, if colors are enabled
+    pub(crate) fn paint(self, text: &str, enabled: bool) -> String {
+        match self.color() {
+            Some(color) => crate::color::paint(text.color(color), enabled),
+            None => text.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for StackRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl VMState {
+    /// which part of the memory layout the cell at the given address is in.
+    /// addresses past the end of the stack are data, since that's where anything pushed there would go
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{StackRegion, VMBuilder};
+    ///
+    /// // pushes "chicken", then stores it over the first opcode of the program
+    /// let mut vm = VMBuilder::from_opcodes([1, 13, 7]).input("abc").build();
+    /// vm.run().ok();
+    ///
+    /// assert_eq!(vm.region(0), StackRegion::Pointer);
+    /// assert_eq!(vm.region(1), StackRegion::Input);
+    /// assert_eq!(vm.region(2), StackRegion::Program);
+    /// assert_eq!(vm.region(3), StackRegion::SelfModified);
+    /// assert_eq!(vm.region(5), StackRegion::Sentinel);
+    /// assert_eq!(vm.region(6), StackRegion::Data);
+    /// ```
+    pub fn region(&self, address: usize) -> StackRegion {
+        match address {
+            0 => StackRegion::Pointer,
+            _ if address < self.program_start => StackRegion::Input,
+            _ if self.modified_program.contains(&address) => StackRegion::SelfModified,
+            _ if address < self.program_end => StackRegion::Program,
+            _ if address == self.program_end => StackRegion::Sentinel,
+            _ => StackRegion::Data,
+        }
+    }
+}
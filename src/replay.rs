@@ -0,0 +1,317 @@
+use crate::{Observer, Snapshot, StepInfo, TraceSampling, VMBuilder, VMState};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    io::{self, BufRead},
+    sync::{Arc, Mutex},
+};
+
+/// the version of the trace format written by [JsonTracer](crate::JsonTracer)
+pub const TRACE_VERSION: u32 = 2;
+
+/// the first line of a trace, describing the VM before it started
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceHeader {
+    /// the version of the trace format, see [TRACE_VERSION]
+    pub chicken_trace: u32,
+
+    /// the state of the VM right before the first instruction was executed
+    pub snapshot: Snapshot,
+
+    /// which steps were recorded
+    #[serde(default, skip_serializing_if = "TraceSampling::is_all")]
+    pub sampling: TraceSampling,
+}
+
+/// a recorded run of a program, as written by [JsonTracer](crate::JsonTracer), which can be replayed to inspect it later
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::{JsonTracer, Trace, VMBuilder};
+/// use std::sync::{Arc, Mutex};
+///
+/// let tracer = Arc::new(Mutex::new(JsonTracer::new(Vec::new())));
+/// VMBuilder::from_chicken("chicken").observer(tracer.clone()).build().run().unwrap();
+///
+/// let trace = Trace::from_reader(&tracer.lock().unwrap().get_ref()[..]).unwrap();
+/// let (result, divergence) = trace.replay(trace.builder());
+/// assert_eq!(result.unwrap(), "chicken");
+/// assert_eq!(divergence, None);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    /// the state of the VM right before the first instruction was executed
+    pub snapshot: Snapshot,
+
+    /// every recorded step, as the JSON objects they were written as
+    pub steps: Vec<serde_json::Value>,
+
+    /// which steps were recorded, so a replay only checks those
+    pub sampling: TraceSampling,
+}
+
+impl Trace {
+    /// reads a trace from a reader
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, TraceError> {
+        let mut lines = reader.lines();
+
+        let header = match lines.next() {
+            Some(line) => serde_json::from_str::<TraceHeader>(&line?)
+                .map_err(|err| TraceError::Json { line: 1, err })?,
+            None => return Err(TraceError::MissingHeader),
+        };
+
+        if header.chicken_trace != TRACE_VERSION {
+            return Err(TraceError::Version(header.chicken_trace));
+        }
+
+        let mut steps = Vec::new();
+
+        for (index, line) in lines.enumerate() {
+            let line = line?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            steps.push(serde_json::from_str(&line).map_err(|err| TraceError::Json {
+                line: index + 2,
+                err,
+            })?);
+        }
+
+        Ok(Self {
+            snapshot: header.snapshot,
+            steps,
+            sampling: header.sampling,
+        })
+    }
+
+    /// creates a VMBuilder that starts from the same state as the recorded run
+    pub fn builder(&self) -> VMBuilder {
+        VMBuilder::from_snapshot(self.snapshot.clone())
+    }
+
+    /// runs a VM built from the given builder (usually from [Trace::builder], with debugging or other options turned on),
+    /// checking every recorded step against the same step of the replay. returns the result of the run and the first step that didn't match, if any
+    pub fn replay(
+        &self,
+        builder: VMBuilder,
+    ) -> (Result<String, crate::ChickenError>, Option<Divergence>) {
+        let verifier = Arc::new(Mutex::new(Verifier {
+            steps: self.steps.clone(),
+            sampling: self.sampling,
+            step: 0,
+            recorded: 0,
+            divergence: None,
+        }));
+
+        let result = builder.observer(verifier.clone()).build().run();
+        let mut verifier = verifier.lock().unwrap();
+
+        if verifier.divergence.is_none() && verifier.recorded < verifier.steps.len() {
+            let expected = verifier.steps.get(verifier.recorded).cloned();
+            verifier.divergence = Some(Divergence {
+                step: step_number(expected.as_ref()).unwrap_or(verifier.step + 1),
+                expected,
+                actual: None,
+            });
+        }
+
+        (result, verifier.divergence.take())
+    }
+
+    /// compares this trace to another one step by step, returning the first place they differ, if any.
+    /// unlike [Trace::replay] this doesn't run anything, so it can compare runs recorded by different versions of chicken or with different options
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{JsonTracer, Limits, Trace, VMBuilder};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let record = |limits| {
+    ///     let tracer = Arc::new(Mutex::new(JsonTracer::new(Vec::new())));
+    ///     let builder = VMBuilder::from_path("examples/helloworld.chicken").unwrap();
+    ///     builder.limits(limits).observer(tracer.clone()).build().run().ok();
+    ///     let trace = Trace::from_reader(&tracer.lock().unwrap().get_ref()[..]).unwrap();
+    ///     trace
+    /// };
+    ///
+    /// let full = record(Limits::default());
+    /// let cut_short = record(Limits { max_steps: Some(10), ..Default::default() });
+    ///
+    /// let diff = full.diff(&cut_short).unwrap();
+    /// assert_eq!(diff.step, 11);
+    /// assert!(diff.first.is_some() && diff.second.is_none());
+    /// assert_eq!(full.diff(&full), None);
+    /// ```
+    pub fn diff(&self, other: &Trace) -> Option<TraceDiff> {
+        if self.snapshot != other.snapshot {
+            return Some(TraceDiff {
+                step: 0,
+                first: serde_json::to_value(&self.snapshot).ok(),
+                second: serde_json::to_value(&other.snapshot).ok(),
+            });
+        }
+
+        let steps = self.steps.len().max(other.steps.len());
+
+        (0..steps).find_map(|step| {
+            let first = self.steps.get(step);
+            let second = other.steps.get(step);
+
+            (first != second).then(|| TraceDiff {
+                step: step_number(first.or(second)).unwrap_or(step as u64 + 1),
+                first: first.cloned(),
+                second: second.cloned(),
+            })
+        })
+    }
+}
+
+/// the first place two recorded runs differ, from [Trace::diff]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceDiff {
+    /// the number of the step, starting at 1, or 0 if the runs started from different states
+    pub step: u64,
+
+    /// the step from the first run, or its starting snapshot if `step` is 0. None if the first run stopped before this step
+    pub first: Option<serde_json::Value>,
+
+    /// the step from the second run, or its starting snapshot if `step` is 0. None if the second run stopped before this step
+    pub second: Option<serde_json::Value>,
+}
+
+impl fmt::Display for TraceDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |step: &Option<serde_json::Value>| match step {
+            Some(step) => step.to_string(),
+            None => "nothing, it had already stopped".to_string(),
+        };
+
+        if self.step == 0 {
+            writeln!(f, "runs started from different states")?;
+        } else {
+            writeln!(f, "runs diverged at step {}", self.step)?;
+        }
+
+        writeln!(f, "first:  {}", show(&self.first))?;
+        write!(f, "second: {}", show(&self.second))
+    }
+}
+
+/// the first step where a replayed run did something different than the recorded one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// the number of the step, starting at 1
+    pub step: u64,
+
+    /// the recorded step, or None if the replay ran for longer than the recording
+    pub expected: Option<serde_json::Value>,
+
+    /// the replayed step, or None if the replay stopped before the recording did
+    pub actual: Option<serde_json::Value>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |step: &Option<serde_json::Value>| match step {
+            Some(step) => step.to_string(),
+            None => "nothing".to_string(),
+        };
+
+        write!(
+            f,
+            "trace diverged at step {}: expected {}, got {}",
+            self.step,
+            show(&self.expected),
+            show(&self.actual)
+        )
+    }
+}
+
+// the number of a recorded step, which is in the step itself since sampled traces skip some
+fn step_number(step: Option<&serde_json::Value>) -> Option<u64> {
+    step?.get("step")?.as_u64()
+}
+
+/// compares every step of a replayed run against the recorded ones, keeping track of the first one that doesn't match
+struct Verifier {
+    steps: Vec<serde_json::Value>,
+    sampling: TraceSampling,
+
+    // how many steps have been replayed, and how many of them were recorded
+    step: u64,
+    recorded: usize,
+
+    divergence: Option<Divergence>,
+}
+
+impl Observer for Verifier {
+    fn on_step(&mut self, _state: &VMState, info: &StepInfo) -> io::Result<()> {
+        self.step += 1;
+        let step = self.step;
+
+        // a step that was recorded has to be sampled again, so a replay that stops jumping or storing where the
+        // recorded run did diverges right there instead of at the next step that does
+        let recorded = step_number(self.steps.get(self.recorded)) == Some(step);
+        if self.divergence.is_some() || !(recorded || self.sampling.samples(step, info)) {
+            return Ok(());
+        }
+
+        let mut actual = serde_json::to_value(info)?;
+        if let Some(object) = actual.as_object_mut() {
+            object.insert("step".to_string(), step.into());
+        }
+
+        let expected = self.steps.get(self.recorded);
+        self.recorded += 1;
+
+        if expected != Some(&actual) {
+            self.divergence = Some(Divergence {
+                step,
+                expected: expected.cloned(),
+                actual: Some(actual),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// an error encountered while reading a trace
+#[derive(Debug)]
+pub enum TraceError {
+    /// the trace couldn't be read
+    Io(io::Error),
+
+    /// a line of the trace wasn't valid
+    Json { line: usize, err: serde_json::Error },
+
+    /// the trace was empty
+    MissingHeader,
+
+    /// the trace was written in a format version this version of chicken doesn't understand
+    Version(u32),
+}
+
+impl From<io::Error> for TraceError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "couldn't read trace: {}", err),
+            Self::Json { line, err } => write!(f, "invalid trace on line {}: {}", line, err),
+            Self::MissingHeader => write!(f, "trace is empty"),
+            Self::Version(version) => write!(f, "unsupported trace version {}", version),
+        }
+    }
+}
+
+impl std::error::Error for TraceError {}
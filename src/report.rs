@@ -0,0 +1,346 @@
+use crate::{display_cell, ChickenError, Observer, Program, StackRegion, StepInfo, VMState};
+use serde::Serialize;
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+};
+
+/// writes a self contained HTML page showing a run of a program, with its disassembly, a slider to move between steps and the stack at each step.
+/// only every `every`th step is recorded, and recording stops after `max_frames` of them, since every frame holds a copy of the whole stack.
+/// cells are colored by their [StackRegion], the same way the debugger colors them.
+/// the report is written once the run ends, whether the program exited or failed with an error
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::{HtmlReport, VMBuilder};
+/// use std::sync::{Arc, Mutex};
+///
+/// let report = Arc::new(Mutex::new(HtmlReport::new(Vec::new(), 1, 1000)));
+/// VMBuilder::from_chicken("chicken").observer(report.clone()).build().run().unwrap();
+///
+/// let html = String::from_utf8(report.lock().unwrap().get_ref().clone()).unwrap();
+/// assert!(html.starts_with("<!DOCTYPE html>"));
+/// ```
+pub struct HtmlReport<W: Write> {
+    writer: W,
+    every: u64,
+    max_frames: usize,
+    steps: u64,
+    program_start: usize,
+    program_end: usize,
+    disassembly: Vec<(usize, String)>,
+    frames: Vec<Frame>,
+    written: bool,
+}
+
+/// the state of the VM after a single step
+#[derive(Serialize)]
+struct Frame {
+    step: u64,
+    pc: usize,
+    instruction: String,
+    stack: Vec<String>,
+    regions: Vec<StackRegion>,
+}
+
+impl<W: Write> HtmlReport<W> {
+    /// creates a new report that writes to the given writer once the run ends
+    pub fn new(writer: W, every: u64, max_frames: usize) -> Self {
+        Self {
+            writer,
+            every: every.max(1),
+            max_frames,
+            steps: 0,
+            program_start: 0,
+            program_end: 0,
+            disassembly: Vec::new(),
+            frames: Vec::new(),
+            written: false,
+        }
+    }
+
+    /// gets a reference to the writer this report is writing to
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// consumes this report, returning the writer it was writing to
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_html(&mut self) -> io::Result<()> {
+        // a run resumed after an error would otherwise write a second page after the first
+        if std::mem::replace(&mut self.written, true) {
+            return Ok(());
+        }
+
+        let data = serde_json::json!({
+            "programStart": self.program_start,
+            "programEnd": self.program_end,
+            "disassembly": self.disassembly,
+            "frames": self.frames,
+            "steps": self.steps,
+        });
+
+        // the data goes inside a script tag, which mustn't be closed early by a string on the stack
+        let data = data.to_string().replace("</", "<\\/");
+
+        write!(self.writer, "{}", TEMPLATE.replace("/*DATA*/", &data))?;
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> Observer for HtmlReport<W> {
+    fn on_start(&mut self, state: &VMState) -> io::Result<()> {
+        self.program_start = state.program_start;
+
+        self.program_end = state.program_end;
+        let program = Program::from_opcodes(
+            state
+                .program()
+                .iter()
+                .map(|v| v.to_num_option().unwrap_or_default())
+                .collect::<Vec<_>>(),
+        );
+
+        self.disassembly = program
+            .instructions()
+            .into_iter()
+            .map(|(index, instruction)| (index + state.program_start, instruction.to_string()))
+            .collect();
+
+        Ok(())
+    }
+
+    fn on_step(&mut self, state: &VMState, info: &StepInfo) -> io::Result<()> {
+        self.steps += 1;
+
+        if (self.steps - 1).is_multiple_of(self.every) && self.frames.len() < self.max_frames {
+            self.frames.push(Frame {
+                step: self.steps,
+                pc: info.program_counter,
+                instruction: info
+                    .instruction
+                    .map_or_else(|| "invalid".to_string(), |i| i.to_string()),
+                stack: state.stack.iter().map(display_cell).collect(),
+                regions: (0..state.stack.len())
+                    .map(|address| state.region(address))
+                    .collect(),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn on_exit(&mut self, _state: &VMState) -> io::Result<()> {
+        self.write_html()
+    }
+
+    fn on_error(&mut self, _state: &VMState, _error: &ChickenError) -> io::Result<()> {
+        self.write_html()
+    }
+}
+
+impl VMState {
+    /// writes a markdown document describing a finished run, with its output or error, its stats and an annotated disassembly
+    /// of the program, so a run can be shared in an issue or with a class without screenshots.
+    /// the disassembly shows how many times each instruction was executed if the VM was built with
+    /// [VMBuilder::coverage](crate::VMBuilder::coverage) or [VMBuilder::profile](crate::VMBuilder::profile),
+    /// and the time spent on each kind of instruction is included with a profile
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::VMBuilder;
+    ///
+    /// let mut vm = VMBuilder::from_chicken("chicken").coverage().build();
+    /// let result = vm.run();
+    ///
+    /// let report = vm.markdown_report(result.as_deref());
+    /// assert!(report.contains("| 2 | chicken | 1 | 50.0% |"));
+    /// ```
+    pub fn markdown_report(&self, result: Result<&str, &ChickenError>) -> String {
+        let mut report = String::new();
+
+        // writing to a String can't fail
+        let _ = self.write_markdown_report(&mut report, result);
+
+        report
+    }
+
+    fn write_markdown_report(
+        &self,
+        f: &mut String,
+        result: Result<&str, &ChickenError>,
+    ) -> std::fmt::Result {
+        writeln!(f, "# chicken run report\n")?;
+
+        match result {
+            Ok(output) => writeln!(f, "## output\n\n{}", fenced(output))?,
+            Err(err) => writeln!(f, "## error\n\n{}", fenced(&err.to_string()))?,
+        }
+
+        writeln!(f, "## stats\n\n{}", fenced(&self.stats.to_string()))?;
+
+        let program = Program::from_opcodes(
+            (self.initial_program.iter())
+                .map(|v| v.to_num_option().unwrap_or_default())
+                .collect::<Vec<_>>(),
+        );
+
+        // how many times the instruction at each program index was executed, if that was kept track of
+        let hits = |index: usize| match (&self.coverage, &self.profile) {
+            (Some(coverage), _) => coverage.hits.get(index).copied(),
+            (None, Some(profile)) => Some(
+                (profile.counts)
+                    .get(&(index + self.program_start))
+                    .copied()
+                    .unwrap_or_default(),
+            ),
+            (None, None) => None,
+        };
+
+        writeln!(f, "## disassembly\n")?;
+        writeln!(f, "| address | instruction | executed | of all steps |")?;
+        writeln!(f, "| ---: | --- | ---: | ---: |")?;
+
+        let mut covered = 0;
+        let instructions = program.instructions();
+        for (index, instruction) in instructions.iter() {
+            let address = index + self.program_start;
+
+            match hits(*index) {
+                Some(hits) => {
+                    covered += usize::from(hits > 0);
+                    let share = hits as f64 * 100.0 / self.stats.steps.max(1) as f64;
+                    writeln!(
+                        f,
+                        "| {} | {} | {} | {:.1}% |",
+                        address, instruction, hits, share
+                    )?
+                }
+                None => writeln!(f, "| {} | {} | | |", address, instruction)?,
+            }
+        }
+
+        if hits(0).is_some() {
+            writeln!(
+                f,
+                "\n{} of {} instructions were executed",
+                covered,
+                instructions.len()
+            )?;
+        }
+
+        if let Some(profile) = &self.profile {
+            writeln!(f, "\n## time per instruction\n")?;
+            writeln!(f, "| instruction | executed | total time | average time |")?;
+            writeln!(f, "| --- | ---: | ---: | ---: |")?;
+
+            let mut opcodes = profile.opcodes.iter().collect::<Vec<_>>();
+            opcodes.sort_by(|a, b| b.1.time.cmp(&a.1.time).then(a.0.cmp(b.0)));
+            for (mnemonic, timing) in opcodes {
+                writeln!(
+                    f,
+                    "| {} | {} | {:?} | {:?} |",
+                    mnemonic,
+                    timing.count,
+                    timing.time,
+                    timing.time / u32::try_from(timing.count).unwrap_or(u32::MAX).max(1)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// puts text in a markdown code block, fenced with more backticks than there are in a row anywhere in it
+fn fenced(text: &str) -> String {
+    let longest = (text.split(|c| c != '`'))
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest.max(2) + 1);
+
+    format!(
+        "{}text\n{}\n{}\n",
+        fence,
+        text.trim_end_matches('\n'),
+        fence
+    )
+}
+
+const TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>chicken execution report</title>
+<style>
+body { font-family: sans-serif; margin: 1em; }
+main { display: flex; gap: 2em; }
+#disassembly, #stack { font-family: monospace; white-space: pre; }
+#disassembly div, #stack div { padding: 0 0.5em; }
+.current { background: #ffe08a; }
+.pointer { color: #a0a; }
+.input { color: #0aa; }
+.program { color: #36c; }
+.self-modified { color: #c33; }
+.sentinel { color: #888; }
+#legend span { margin-right: 1em; }
+#slider { width: 100%; }
+</style>
+</head>
+<body>
+<h1>chicken execution report</h1>
+<p id="summary"></p>
+<input type="range" id="slider" min="0" value="0">
+<p id="step"></p>
+<main>
+<section><h2>disassembly</h2><div id="disassembly"></div></section>
+<section><h2>stack</h2><p id="legend"><span class="pointer">stack pointer</span><span class="input">input</span><span class="program">program</span><span class="self-modified">self-modified</span><span class="sentinel">end of program</span><span>data</span></p><div id="stack"></div></section>
+</main>
+<script>
+const data = /*DATA*/;
+const slider = document.getElementById("slider");
+const disassembly = document.getElementById("disassembly");
+const stack = document.getElementById("stack");
+
+document.getElementById("summary").textContent =
+    data.steps + " steps, showing " + data.frames.length + " of them";
+slider.max = Math.max(data.frames.length - 1, 0);
+
+const lines = data.disassembly.map(([pc, instruction]) => {
+    const line = document.createElement("div");
+    line.textContent = String(pc).padStart(5) + ": " + instruction;
+    disassembly.appendChild(line);
+    return [pc, line];
+});
+
+function show(index) {
+    const frame = data.frames[index];
+    if (!frame) return;
+
+    document.getElementById("step").textContent =
+        "step " + frame.step + ": " + frame.instruction + " at pc " + frame.pc;
+
+    for (const [pc, line] of lines) {
+        line.className = pc === frame.pc ? "current" : "";
+    }
+
+    stack.replaceChildren(...frame.stack.map((value, address) => {
+        const cell = document.createElement("div");
+        cell.textContent = String(address).padStart(5) + ": " + value;
+        cell.className = address === frame.pc ? "current" : frame.regions[address];
+        return cell;
+    }).reverse());
+}
+
+slider.addEventListener("input", () => show(Number(slider.value)));
+show(0);
+</script>
+</body>
+</html>
+"#;
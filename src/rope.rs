@@ -0,0 +1,320 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+// the longest string that can be parsed as an isize without leading zeros, like -9223372036854775808
+const MAX_NUM_LEN: usize = 20;
+
+// strings shorter than this are copied when concatenated instead of being joined into a tree, since they're cheap to copy anyway
+const SMALL_LEN: usize = 64;
+
+/// the string inside a [Value::String](crate::Value::String).
+/// strings are shared instead of copied when they're cloned, and concatenating them just joins them together into a tree,
+/// which is only flattened into one string the first time it's actually needed.
+/// this keeps programs that build up their output by repeatedly adding onto it from copying the whole output every time
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::Rope;
+///
+/// let mut output = Rope::from("");
+/// for i in 0..1000 {
+///     output = output.concat(Rope::from(format!("{} chickens\n", i)));
+/// }
+///
+/// assert!(output.starts_with("0 chickens\n1 chickens\n"));
+/// assert_eq!(output.len(), output.as_str().len());
+/// ```
+#[derive(Clone, Default)]
+pub struct Rope(Arc<Node>);
+
+#[derive(Default)]
+struct Node {
+    len: usize,
+
+    // the whole string. always set for leaves, and set for joined strings once they've been flattened
+    flat: OnceLock<String>,
+
+    // the two strings that were joined together, until this string is flattened
+    children: Mutex<Option<(Rope, Rope)>>,
+
+    // the string parsed as a number, filled in the first time it's needed
+    num: OnceLock<Option<isize>>,
+}
+
+impl Rope {
+    /// the length of the string in bytes, which doesn't need it to be flattened
+    pub fn len(&self) -> usize {
+        self.0.len
+    }
+
+    /// whether the string is empty
+    pub fn is_empty(&self) -> bool {
+        self.0.len == 0
+    }
+
+    /// gets the string as a str, flattening it first if it hasn't been already
+    pub fn as_str(&self) -> &str {
+        let flat = self.0.flat.get_or_init(|| {
+            let mut flat = String::with_capacity(self.0.len);
+            self.push_to(&mut flat);
+            flat
+        });
+
+        // the pieces aren't needed anymore, so don't keep them around
+        let children = self.0.lock_children().take();
+        drop(children);
+
+        flat
+    }
+
+    /// parses the string as a number, or None if it isn't one.
+    /// the result is remembered, so loops that keep using the same string as a number only parse it once
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::Rope;
+    ///
+    /// assert_eq!(Rope::from("-42").to_num(), Some(-42));
+    /// assert_eq!(Rope::from("chicken").to_num(), None);
+    /// ```
+    pub fn to_num(&self) -> Option<isize> {
+        *self.0.num.get_or_init(|| {
+            // long strings can only be numbers if they're padded with zeros, so make sure they're all digits before flattening them
+            if self.len() > MAX_NUM_LEN && !self.is_digits() {
+                return None;
+            }
+
+            self.as_str().parse().ok()
+        })
+    }
+
+    /// joins another string onto the end of this one
+    pub fn concat(mut self, other: Rope) -> Rope {
+        if other.is_empty() {
+            return self;
+        }
+
+        if self.is_empty() {
+            return other;
+        }
+
+        // nothing else can see this string, so it can just be added onto
+        if let Some(node) = Arc::get_mut(&mut self.0) {
+            if node.children.get_mut().is_ok_and(|c| c.is_none()) {
+                if let Some(flat) = node.flat.get_mut() {
+                    other.push_to(flat);
+                    node.len = flat.len();
+                    node.num = OnceLock::new();
+                    return self;
+                }
+            }
+        }
+
+        // strings added to themselves over and over can claim to be longer than could ever fit in memory without being flattened
+        let len = self.len().saturating_add(other.len());
+
+        if len < SMALL_LEN {
+            let mut flat = String::with_capacity(len);
+            self.push_to(&mut flat);
+            other.push_to(&mut flat);
+            return flat.into();
+        }
+
+        Rope(Arc::new(Node {
+            len,
+            flat: OnceLock::new(),
+            children: Mutex::new(Some((self, other))),
+            num: OnceLock::new(),
+        }))
+    }
+
+    /// calls `f` with each piece of the string in order, without flattening it
+    pub fn try_for_each_chunk<E, F: FnMut(&str) -> Result<(), E>>(
+        &self,
+        mut f: F,
+    ) -> Result<(), E> {
+        // walked with a stack instead of recursion, since strings built one piece at a time make very deep trees
+        let mut pending = vec![self.clone()];
+
+        while let Some(rope) = pending.pop() {
+            if let Some(flat) = rope.0.flat.get() {
+                f(flat)?;
+                continue;
+            }
+
+            let children = rope.0.lock_children().clone();
+            match children {
+                Some((left, right)) => {
+                    pending.push(right);
+                    pending.push(left);
+                }
+
+                // flattened by something else in the meantime
+                None => f(rope.as_str())?,
+            }
+        }
+
+        Ok(())
+    }
+
+    // whether the string is all digits, other than a sign at the start
+    fn is_digits(&self) -> bool {
+        let mut start = true;
+
+        self.try_for_each_chunk(|chunk| {
+            let mut bytes = chunk.as_bytes();
+            if std::mem::take(&mut start) {
+                bytes = bytes
+                    .strip_prefix(b"-")
+                    .or(bytes.strip_prefix(b"+"))
+                    .unwrap_or(bytes);
+            }
+
+            match bytes.iter().all(u8::is_ascii_digit) {
+                true => Ok(()),
+                false => Err(()),
+            }
+        })
+        .is_ok()
+    }
+
+    fn push_to(&self, string: &mut String) {
+        let _ = self.try_for_each_chunk(|chunk| {
+            string.push_str(chunk);
+            Ok::<_, ()>(())
+        });
+    }
+}
+
+impl Node {
+    fn lock_children(&self) -> std::sync::MutexGuard<'_, Option<(Rope, Rope)>> {
+        self.children.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// dropped with a stack instead of recursion, for the same reason [Rope::try_for_each_chunk] walks it with one
+impl Drop for Node {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+
+        if let Some((left, right)) = self.lock_children().take() {
+            pending.push(left);
+            pending.push(right);
+        }
+
+        while let Some(rope) = pending.pop() {
+            if let Some(node) = Arc::into_inner(rope.0) {
+                if let Some((left, right)) = node.lock_children().take() {
+                    pending.push(left);
+                    pending.push(right);
+                }
+            }
+        }
+    }
+}
+
+impl Deref for Rope {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Rope {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<String> for Rope {
+    fn from(s: String) -> Self {
+        Rope(Arc::new(Node {
+            len: s.len(),
+            flat: OnceLock::from(s),
+            children: Mutex::new(None),
+            num: OnceLock::new(),
+        }))
+    }
+}
+
+impl From<&str> for Rope {
+    fn from(s: &str) -> Self {
+        s.to_string().into()
+    }
+}
+
+impl From<Rope> for String {
+    fn from(rope: Rope) -> Self {
+        // avoid copying the string if nothing else is using it
+        match Arc::try_unwrap(rope.0) {
+            Ok(mut node) => match node.flat.take() {
+                Some(flat) => flat,
+                None => Rope(Arc::new(node)).to_string(),
+            },
+            Err(node) => Rope(node).to_string(),
+        }
+    }
+}
+
+impl PartialEq for Rope {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && (Arc::ptr_eq(&self.0, &other.0) || self.as_str() == other.as_str())
+    }
+}
+
+impl Eq for Rope {}
+
+impl PartialEq<str> for Rope {
+    fn eq(&self, other: &str) -> bool {
+        self.len() == other.len() && self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Rope {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl PartialEq<Rope> for str {
+    fn eq(&self, other: &Rope) -> bool {
+        other == self
+    }
+}
+
+impl PartialEq<Rope> for &str {
+    fn eq(&self, other: &Rope) -> bool {
+        other == *self
+    }
+}
+
+/// hashes the same as the equivalent str
+impl Hash for Rope {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl fmt::Debug for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.width().is_some() || f.precision().is_some() {
+            f.pad(self.as_str())
+        } else {
+            self.try_for_each_chunk(|chunk| f.write_str(chunk))
+        }
+    }
+}
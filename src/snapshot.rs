@@ -0,0 +1,116 @@
+use crate::{CharEntity, Value, EXIT, LOAD};
+use serde::{Deserialize, Serialize};
+
+/// a copy of the state of a VM at some point in time, taken with [VMState::snapshot](crate::VMState::snapshot)
+/// and turned back into a VM with [VMBuilder::from_snapshot](crate::VMBuilder::from_snapshot)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "SnapshotRepr")]
+pub struct Snapshot {
+    /// the entire stack, including the program itself
+    pub stack: Vec<Value>,
+
+    /// the program counter
+    pub program_counter: usize,
+
+    /// the address on the stack the program starts at
+    pub program_start: usize,
+
+    /// the address of the exit appended right after the program.
+    /// snapshots taken before this was saved get the first exit instruction after the start of the program instead
+    pub program_end: usize,
+
+    /// whether the VM had finished execution
+    pub exited: bool,
+
+    /// whether the Char instruction produces actual characters instead of HTML entity strings
+    pub normal_char: bool,
+
+    /// whether the Char instruction produces single bytes, see [VMBuilder::byte_char](crate::VMBuilder::byte_char)
+    #[serde(default)]
+    pub byte_char: bool,
+
+    /// how the Char instruction writes entities, see [VMBuilder::char_entity](crate::VMBuilder::char_entity)
+    #[serde(default)]
+    pub char_entity: CharEntity,
+
+    /// whether [Extension](crate::Extension) instructions are decoded
+    #[serde(default)]
+    pub extensions: bool,
+
+    /// the state of the pseudo-random numbers pushed by [Extension::Random](crate::Extension::Random),
+    /// which is used as the seed of the resumed VM so it carries on with the same numbers
+    #[serde(default)]
+    pub random_state: u64,
+
+    /// the addresses [Extension::Call](crate::Extension::Call) returns to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub return_stack: Vec<usize>,
+
+    /// the slot holding the trap handler set by [Extension::Trap](crate::Extension::Trap)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trap_slot: Option<usize>,
+}
+
+// the same fields as Snapshot, but with program_end being optional so older snapshots without it still load
+#[derive(Deserialize)]
+struct SnapshotRepr {
+    stack: Vec<Value>,
+    program_counter: usize,
+    program_start: usize,
+    #[serde(default)]
+    program_end: Option<usize>,
+    exited: bool,
+    normal_char: bool,
+    #[serde(default)]
+    byte_char: bool,
+    #[serde(default)]
+    char_entity: CharEntity,
+    #[serde(default)]
+    extensions: bool,
+    #[serde(default)]
+    random_state: u64,
+    #[serde(default)]
+    return_stack: Vec<usize>,
+    #[serde(default)]
+    trap_slot: Option<usize>,
+}
+
+impl From<SnapshotRepr> for Snapshot {
+    fn from(repr: SnapshotRepr) -> Self {
+        let program_end = repr
+            .program_end
+            .unwrap_or_else(|| find_program_end(&repr.stack, repr.program_start));
+
+        Self {
+            stack: repr.stack,
+            program_counter: repr.program_counter,
+            program_start: repr.program_start,
+            program_end,
+            exited: repr.exited,
+            normal_char: repr.normal_char,
+            byte_char: repr.byte_char,
+            char_entity: repr.char_entity,
+            extensions: repr.extensions,
+            random_state: repr.random_state,
+            return_stack: repr.return_stack,
+            trap_slot: repr.trap_slot,
+        }
+    }
+}
+
+// walks the instructions from the start of the program, skipping over the operands of loads, until it finds an exit.
+// programs that exit before their last instruction end up shorter than they were, but the exit appended after them
+// can't be told apart from one of theirs, and if there isn't one at all the program is assumed to go up to the top of the stack
+fn find_program_end(stack: &[Value], program_start: usize) -> usize {
+    let mut address = program_start;
+
+    while let Some(cell) = stack.get(address) {
+        match cell {
+            Value::Num(EXIT) => return address,
+            Value::Num(LOAD) => address += 2,
+            _ => address += 1,
+        }
+    }
+
+    stack.len()
+}
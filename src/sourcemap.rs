@@ -0,0 +1,239 @@
+use crate::{Metadata, Program, VMState};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::Arc};
+
+/// where an instruction of an assembled program came from, see [SourceMap]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    /// the program index of the instruction
+    pub index: usize,
+
+    /// the line of the assembly the instruction was on, starting at 1
+    pub line: usize,
+
+    /// the closest label at or before the instruction, if there is one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+
+    /// the file the instruction was in, for programs linked together from several files with [Program::link](crate::Program::link).
+    /// the file of the whole [SourceMap] is used otherwise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+/// maps the instructions of a program back to the assembly they were assembled from, made by [Program::from_asm_with_source_map]
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::Program;
+///
+/// let (program, source_map) = Program::from_asm_with_source_map("push 1\nloop:\nload 0\nexit", Some("cat.asm")).unwrap();
+/// assert_eq!(program.opcodes(), &[11, 6, 0, 0]);
+///
+/// // the operand of the load is part of the instruction on line 3
+/// let location = source_map.lookup(2).unwrap();
+/// assert_eq!((location.index, location.line), (1, 3));
+/// assert_eq!(location.label.as_deref(), Some("loop"));
+/// assert_eq!(source_map.labels["loop"], 1);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceMap {
+    /// the file the assembly was read from, if it was read from a file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+
+    /// every instruction of the program, in order of program index
+    pub instructions: Vec<SourceLocation>,
+
+    /// every label, and the program index of the instruction after it
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, usize>,
+}
+
+impl SourceMap {
+    /// finds the instruction covering the given program index, which can be the operand of a Load
+    pub fn lookup(&self, index: usize) -> Option<&SourceLocation> {
+        let position = self
+            .instructions
+            .partition_point(|location| location.index <= index);
+
+        self.instructions.get(position.checked_sub(1)?)
+    }
+}
+
+/// a [SourceMap] for a program that's been put on the stack, so addresses on the stack can be looked up in it.
+/// VMs built with [VMBuilder::source_map](crate::VMBuilder::source_map) have one, and so do their errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackSourceMap {
+    /// the source map itself, which uses program indexes instead of addresses
+    pub source_map: Arc<SourceMap>,
+
+    /// the address on the stack the program starts at
+    pub program_start: usize,
+
+    /// the address of the exit appended right after the program
+    pub program_end: usize,
+}
+
+impl StackSourceMap {
+    /// finds the instruction covering the given address on the stack, or None if it's outside of the program
+    pub fn location(&self, address: usize) -> Option<&SourceLocation> {
+        self.source_map
+            .lookup(address.checked_sub(self.program_start)?)
+    }
+
+    /// the address on the stack of the instruction after a label
+    pub fn label_address(&self, label: &str) -> Option<usize> {
+        Some(self.source_map.labels.get(label)? + self.program_start)
+    }
+
+    /// the label right before the instruction at the given address, if there is one
+    pub fn label_at(&self, address: usize) -> Option<&str> {
+        let index = address.checked_sub(self.program_start)?;
+
+        (self.source_map.labels.iter())
+            .find(|(_, target)| **target == index)
+            .map(|(label, _)| label.as_str())
+    }
+
+    /// names an address after the closest label at or before it, like `loop` or `loop+2`,
+    /// or None if it's outside of the program or comes before its first label
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{Program, VMBuilder};
+    ///
+    /// let (program, source_map) = Program::from_asm_with_source_map("push 1\nloop: load 0\njump", None).unwrap();
+    /// let vm = VMBuilder::from_program(program).source_map(source_map).build();
+    ///
+    /// let source_map = vm.source_map().unwrap();
+    /// assert_eq!(source_map.symbol(2), None);
+    /// assert_eq!(source_map.symbol(3).as_deref(), Some("loop"));
+    /// assert_eq!(source_map.symbol(5).as_deref(), Some("loop+2"));
+    /// assert_eq!(source_map.symbol(7), None);
+    /// ```
+    pub fn symbol(&self, address: usize) -> Option<String> {
+        if address > self.program_end {
+            return None;
+        }
+        let index = address.checked_sub(self.program_start)?;
+
+        let (label, target) = (self.source_map.labels.iter())
+            .filter(|(_, target)| **target <= index)
+            .max_by_key(|(_, target)| **target)?;
+
+        Some(match index - target {
+            0 => label.clone(),
+            offset => format!("{}+{}", label, offset),
+        })
+    }
+
+    /// describes where in the assembly the instruction at the given address came from, like `cat.asm line 3, in loop`
+    pub fn describe(&self, address: usize) -> Option<String> {
+        let location = self.location(address)?;
+
+        let mut description = match location.file.as_ref().or(self.source_map.file.as_ref()) {
+            Some(file) => format!("{} line {}", file, location.line),
+            None => format!("line {}", location.line),
+        };
+        if let Some(label) = &location.label {
+            description += &format!(", in {}", label);
+        }
+
+        Some(description)
+    }
+}
+
+impl VMState {
+    /// the source map of the program, if it was given one with [VMBuilder::source_map](crate::VMBuilder::source_map)
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::{Program, VMBuilder};
+    ///
+    /// let (program, source_map) = Program::from_asm_with_source_map("push 1\nread: load 0\nexit", None).unwrap();
+    /// let vm = VMBuilder::from_program(program).source_map(source_map).build();
+    ///
+    /// let source_map = vm.source_map().unwrap();
+    /// assert_eq!(source_map.label_address("read"), Some(3));
+    /// assert_eq!(source_map.describe(4).unwrap(), "line 2, in read");
+    /// ```
+    pub fn source_map(&self) -> Option<&StackSourceMap> {
+        self.source_map.as_deref()
+    }
+}
+
+/// a program in the JSON container format, which is either a plain array of opcodes or an object that can also have a source map and [Metadata]
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::ProgramFile;
+///
+/// let file: ProgramFile = serde_json::from_str("[11, 6, 0, 0]").unwrap();
+/// assert_eq!((file.program.len(), file.source_map), (4, None));
+///
+/// let file: ProgramFile = serde_json::from_str(r#"{"opcodes": [1], "source_map": {"instructions": [{"index": 0, "line": 1}]}}"#).unwrap();
+/// assert_eq!(file.source_map.unwrap().lookup(0).unwrap().line, 1);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "ProgramFileRepr", into = "ProgramFileRepr")]
+pub struct ProgramFile {
+    /// the program itself
+    pub program: Program,
+
+    /// where its instructions came from, if it was assembled
+    pub source_map: Option<SourceMap>,
+
+    /// its title, what it needs to run and so on, which is empty if it doesn't have any
+    pub metadata: Metadata,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ProgramFileRepr {
+    Mapped {
+        opcodes: Program,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        source_map: Option<SourceMap>,
+        #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+        metadata: Metadata,
+    },
+    Plain(Program),
+}
+
+impl From<ProgramFileRepr> for ProgramFile {
+    fn from(repr: ProgramFileRepr) -> Self {
+        match repr {
+            ProgramFileRepr::Mapped {
+                opcodes,
+                source_map,
+                metadata,
+            } => Self {
+                program: opcodes,
+                source_map,
+                metadata,
+            },
+            ProgramFileRepr::Plain(program) => Self {
+                program,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl From<ProgramFile> for ProgramFileRepr {
+    fn from(file: ProgramFile) -> Self {
+        match file.source_map.is_none() && file.metadata.is_empty() {
+            true => Self::Plain(file.program),
+            false => Self::Mapped {
+                opcodes: file.program,
+                source_map: file.source_map,
+                metadata: file.metadata,
+            },
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use crate::{Value, ADD, CHAR, CHICKEN, COMPARE, EXIT, JUMP, LOAD, MULTIPLY, STORE, SUBTRACT};
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use std::{fmt, ops::Index};
+
+/// statistics about a run of a program, available from [VMState::stats](crate::VMState::stats)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Stats {
+    /// the number of instructions executed
+    pub steps: u64,
+
+    /// the fuel used up by the instructions executed, as counted by the [CostModel](crate::CostModel) the VM was built with.
+    /// without one every instruction costs 1, so this is the same as steps
+    pub fuel: u64,
+
+    /// the number of values pushed onto the stack
+    pub pushes: u64,
+
+    /// the number of values popped off of the stack
+    pub pops: u64,
+
+    /// the largest the stack has ever been
+    pub max_stack_depth: usize,
+
+    /// how many values the stack has room for, see [VMBuilder::stack_capacity](crate::VMBuilder::stack_capacity)
+    pub stack_capacity: usize,
+
+    /// the number of bytes taken up by all the strings currently on the stack
+    pub string_bytes: usize,
+
+    /// the largest string_bytes has ever been
+    pub peak_string_bytes: usize,
+
+    /// the most memory the stack has ever taken up, in bytes, as counted by [VMState::memory_usage](crate::VMState::memory_usage)
+    pub peak_memory: usize,
+
+    /// the number of Stores into the program itself
+    pub self_modifications: u64,
+
+    /// how many times each kind of instruction was executed, keyed by mnemonic
+    pub instructions: InstructionCounts,
+}
+
+impl Stats {
+    /// creates a new set of statistics for a freshly built stack
+    pub fn new(stack: &[Value]) -> Self {
+        let string_bytes = (stack.iter().map(string_bytes)).fold(0, usize::saturating_add);
+
+        let mut stats = Self {
+            max_stack_depth: stack.len(),
+            string_bytes,
+            peak_string_bytes: string_bytes,
+            ..Default::default()
+        };
+        stats.record_memory(stack.len());
+        stats
+    }
+
+    /// roughly how many bytes a stack this deep takes up, counting each value and every byte of the strings on it
+    pub fn memory(&self, stack_depth: usize) -> usize {
+        // string lengths saturate, so strings that have been doubled enough times can be usize::MAX bytes long
+        (stack_depth.saturating_mul(std::mem::size_of::<Value>())).saturating_add(self.string_bytes)
+    }
+
+    /// records how deep the stack is, in case it's taking up more memory than ever before
+    pub fn record_memory(&mut self, stack_depth: usize) {
+        self.peak_memory = self.peak_memory.max(self.memory(stack_depth));
+    }
+
+    /// records that an instruction was executed
+    pub fn record_step(&mut self, mnemonic: &'static str) {
+        self.steps += 1;
+        self.fuel = self.fuel.saturating_add(1);
+
+        if let Some(slot) = MNEMONICS.iter().position(|m| *m == mnemonic) {
+            self.instructions.0[slot] += 1;
+        }
+    }
+
+    /// records that the instruction with the given opcode was executed, or an invalid one if it's None.
+    /// this is the same as [Stats::record_step] without having to look up the mnemonic, since it's called for every instruction
+    pub fn record_opcode(&mut self, opcode: Option<isize>) {
+        self.steps += 1;
+        self.fuel = self.fuel.saturating_add(1);
+        self.instructions.0[opcode_slot(opcode)] += 1;
+    }
+
+    /// records that a value was pushed, making the stack the given size
+    pub fn record_push(&mut self, value: &Value, stack_depth: usize) {
+        self.pushes += 1;
+        self.max_stack_depth = self.max_stack_depth.max(stack_depth);
+        self.add_string_bytes(string_bytes(value));
+        self.record_memory(stack_depth);
+    }
+
+    /// records that a value was popped
+    pub fn record_pop(&mut self, value: &Value) {
+        self.pops += 1;
+        self.string_bytes = self.string_bytes.saturating_sub(string_bytes(value));
+    }
+
+    /// records that a popped value was put back on the stack by an instruction that failed
+    pub(crate) fn record_unpop(&mut self, value: &Value) {
+        self.pops = self.pops.saturating_sub(1);
+        self.add_string_bytes(string_bytes(value));
+    }
+
+    /// records that a value on the stack was overwritten with another one
+    pub fn record_store(&mut self, old: &Value, new: &Value) {
+        self.string_bytes = self.string_bytes.saturating_sub(string_bytes(old));
+        self.add_string_bytes(string_bytes(new));
+    }
+
+    /// records that a Store wrote into the program itself
+    pub fn record_self_modification(&mut self) {
+        self.self_modifications += 1;
+    }
+
+    fn add_string_bytes(&mut self, bytes: usize) {
+        self.string_bytes = self.string_bytes.saturating_add(bytes);
+        self.peak_string_bytes = self.peak_string_bytes.max(self.string_bytes);
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "steps: {}", self.steps)?;
+        if self.fuel != self.steps {
+            writeln!(f, "fuel: {}", self.fuel)?;
+        }
+        writeln!(f, "pushes: {}, pops: {}", self.pushes, self.pops)?;
+        writeln!(f, "max stack depth: {}", self.max_stack_depth)?;
+        writeln!(f, "stack capacity: {}", self.stack_capacity)?;
+        writeln!(f, "peak string bytes: {}", self.peak_string_bytes)?;
+        writeln!(f, "peak memory: {} bytes", self.peak_memory)?;
+        writeln!(f, "self modifications: {}", self.self_modifications)?;
+        writeln!(f, "instructions:")?;
+
+        for (mnemonic, count) in self.instructions.iter() {
+            writeln!(f, "    {:>8}: {}", mnemonic, count)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn string_bytes(value: &Value) -> usize {
+    match value {
+        Value::String(s) => s.len(),
+        _ => 0,
+    }
+}
+
+// every mnemonic in alphabetical order, which is the order they're listed in
+pub(crate) const MNEMONICS: [&str; 24] = [
+    "add",
+    "aload",
+    "array",
+    "astore",
+    "call",
+    "char",
+    "charcode",
+    "chicken",
+    "compare",
+    "exit",
+    "invalid",
+    "jump",
+    "length",
+    "load",
+    "multiply",
+    "push",
+    "random",
+    "recv",
+    "ret",
+    "send",
+    "store",
+    "substring",
+    "subtract",
+    "trap",
+];
+
+// where an opcode's count is kept, matching the mnemonics above
+fn opcode_slot(opcode: Option<isize>) -> usize {
+    match opcode {
+        Some(ADD) => 0,
+        Some(CHAR) => 5,
+        Some(CHICKEN) => 7,
+        Some(COMPARE) => 8,
+        Some(EXIT) => 9,
+        Some(JUMP) => 11,
+        Some(LOAD) => 13,
+        Some(MULTIPLY) => 14,
+        Some(STORE) => 20,
+        Some(SUBTRACT) => 22,
+        Some(_) => 15,
+        None => 10,
+    }
+}
+
+/// how many times each kind of instruction was executed, which can be indexed by mnemonic like a map.
+/// only instructions that were executed at least once are listed
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::VMBuilder;
+///
+/// let mut vm = VMBuilder::from_chicken("chicken").build();
+/// vm.run().unwrap();
+///
+/// let instructions = &vm.stats().instructions;
+/// assert_eq!(instructions["chicken"], 1);
+/// assert_eq!(instructions["jump"], 0);
+/// assert_eq!(instructions.iter().collect::<Vec<_>>(), vec![("chicken", 1), ("exit", 1)]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstructionCounts([u64; MNEMONICS.len()]);
+
+impl InstructionCounts {
+    /// how many times the instruction with the given mnemonic was executed
+    pub fn get(&self, mnemonic: &str) -> u64 {
+        self[mnemonic]
+    }
+
+    /// the mnemonic and count of every instruction that was executed at least once, in alphabetical order
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+        MNEMONICS
+            .iter()
+            .zip(self.0.iter())
+            .filter(|(_, count)| **count > 0)
+            .map(|(mnemonic, count)| (*mnemonic, *count))
+    }
+}
+
+impl Index<&str> for InstructionCounts {
+    type Output = u64;
+
+    fn index(&self, mnemonic: &str) -> &u64 {
+        match MNEMONICS.iter().position(|m| *m == mnemonic) {
+            Some(slot) => &self.0[slot],
+            None => &0,
+        }
+    }
+}
+
+/// serialized as an object from mnemonic to count
+impl Serialize for InstructionCounts {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for (mnemonic, count) in self.iter() {
+            map.serialize_entry(mnemonic, &count)?;
+        }
+        map.end()
+    }
+}
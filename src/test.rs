@@ -1,5 +1,23 @@
-use super::VMBuilder;
-use std::fs::read_to_string;
+// the oldest tests borrow the code they read before passing it to from_chicken, which is fine but newer clippy complains about it
+#![allow(clippy::needless_borrows_for_generic_args)]
+
+use super::{
+    format_chicken, format_number, lint, unescape, CancellationToken, Channel, CharEntity,
+    ChromeTraceGranularity, ChromeTracer, ColorMode, ConformanceCase, CostModel, DebugCommand,
+    DebugResponse, EntityDecoding, EnvInput, ErrorKind, EventSender, ExitPolicy, Explanation, Expr,
+    Extension, FileInput, HtmlReport, InstructionBreakpoint, JsonTracer, Limits, LinkError,
+    LintKind, LoadError, Metadata, Observer, Output, OutputProcessor, ParseError, Program,
+    ProgramFile, Requirement, Rope, RunProgress, Snapshot, StackCapture, StackRegion, StepInfo,
+    Stream, Successor, TestIo, Trace, TraceSampling, VMBuilder, VMEvent, VMState, Value, ValueKind,
+    Warning, WarningKind,
+};
+use proptest::prelude::*;
+use std::{
+    collections::BTreeSet,
+    fs::read_to_string,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 #[test]
 fn quine() {
@@ -109,3 +127,3239 @@ fn deadfish() {
         Ok(" 0 ".to_string())
     );
 }
+
+#[test]
+fn inputs() {
+    // loads the first character of the third input
+    let mut vm = VMBuilder::from_opcodes([10, 6, 3])
+        .inputs(vec![Value::from("a"), Value::from("b"), Value::from("cat")])
+        .coverage()
+        .build();
+
+    assert_eq!(vm.program_start, 4);
+    assert_eq!(vm.run(), Ok("c".to_string()));
+    assert_eq!(vm.coverage.unwrap().covered(), 2);
+}
+
+#[test]
+fn data_segment() {
+    // skips the exit at the start, then loads the first value of the data segment right after the exit at address 6
+    let mut vm = VMBuilder::from_opcodes([0, 17, 6, 0])
+        .data(vec![Value::from("x"), Value::from("y")])
+        .start_offset(1)
+        .build();
+
+    assert_eq!(vm.program_counter, 3);
+    assert_eq!(vm.stack[6], Value::Num(0));
+    assert_eq!(vm.run(), Ok("x".to_string()));
+
+    // both are put back the way they were
+    vm.reset(Value::Undefined);
+    assert_eq!(vm.program_counter, 3);
+    assert_eq!(vm.data(), &[Value::from("x"), Value::from("y")]);
+    assert_eq!(vm.run(), Ok("x".to_string()));
+}
+
+#[test]
+fn lazy_input() {
+    let asked = Arc::new(Mutex::new(0));
+
+    let counter = asked.clone();
+    let result = VMBuilder::from_path("examples/helloworld.chicken")
+        .unwrap()
+        .lazy_input(move || {
+            *counter.lock().unwrap() += 1;
+            Ok(Value::from("unused"))
+        })
+        .build()
+        .run();
+    assert_eq!(result, Ok("Hello world".to_string()));
+    assert_eq!(*asked.lock().unwrap(), 0);
+
+    assert_eq!(
+        VMBuilder::from_path("examples/cat.chicken")
+            .unwrap()
+            .lazy_input_reader("meow".as_bytes())
+            .build()
+            .run(),
+        Ok("meow".to_string())
+    );
+
+    // storing over the input means it's never needed
+    let counter = asked.clone();
+    let result = VMBuilder::from_opcodes([1, 11, 7, 11, 6, 0])
+        .lazy_input(move || {
+            *counter.lock().unwrap() += 1;
+            Ok(Value::from("unused"))
+        })
+        .build()
+        .run();
+    assert_eq!(result, Ok("chicken".to_string()));
+    assert_eq!(*asked.lock().unwrap(), 0);
+}
+
+#[test]
+fn input_sources() {
+    assert_eq!(
+        VMBuilder::from_path("examples/cat.chicken")
+            .unwrap()
+            .input_source(FileInput::new("examples/cat.input"))
+            .build()
+            .run(),
+        Ok(read_to_string("examples/cat.input").unwrap())
+    );
+
+    let result = VMBuilder::from_path("examples/cat.chicken")
+        .unwrap()
+        .input_source(EnvInput::new("CHICKEN_SURELY_UNSET_VARIABLE"))
+        .build()
+        .run();
+    assert_eq!(result.unwrap_err().kind, ErrorKind::Io);
+}
+
+#[test]
+fn diff() {
+    let before = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    let mut after = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    after.step().unwrap();
+    after.step().unwrap();
+
+    let diff = before.diff(&after);
+    assert!(diff.changed.is_empty());
+    assert_eq!(diff.pushed, 6..7);
+    assert_eq!(diff.pushed_values(), &[Value::from("abc")]);
+    assert!(diff.popped.is_empty());
+    assert!(after.diff(&after).is_empty());
+}
+
+#[test]
+fn self_modification() {
+    // stores "chicken" over the first instruction
+    let mut vm = VMBuilder::from_opcodes([1, 12, 7]).build();
+    vm.step().unwrap();
+    vm.step().unwrap();
+
+    let info = vm.step_info().unwrap().unwrap();
+    let modification = info.self_modification.unwrap();
+    assert_eq!(modification.address, 2);
+    assert_eq!(modification.old, Value::Num(1));
+    assert_eq!(modification.new, Value::from("chicken"));
+    assert_eq!(vm.stats().self_modifications, 1);
+}
+
+#[test]
+fn store_past_end() {
+    // stores "chicken" at address 20, which grows the stack like a javascript array
+    let run = |limits| {
+        VMBuilder::from_opcodes([1, 30, 7])
+            .limits(limits)
+            .build()
+            .run()
+    };
+    assert_eq!(run(Limits::none()).unwrap(), "chicken");
+
+    let err = run(Limits {
+        max_stack: Some(20),
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Limit);
+
+    // negative addresses are invalid
+    let err = VMBuilder::from_opcodes([17, 10, 11, 3, 7])
+        .build()
+        .run()
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Runtime);
+}
+
+#[test]
+fn debug_commands() {
+    assert_eq!("s".parse(), Ok(DebugCommand::Step(1)));
+    assert_eq!("c".parse(), Ok(DebugCommand::Continue));
+    assert_eq!("b 4".parse(), Ok(DebugCommand::Break(4)));
+    assert_eq!(
+        "b jump  taken".parse(),
+        Ok(DebugCommand::BreakOn(InstructionBreakpoint::TakenJump))
+    );
+    assert_eq!(
+        "b char".parse(),
+        Ok(DebugCommand::BreakOn(InstructionBreakpoint::Mnemonic(
+            "char"
+        )))
+    );
+    assert!("b storee".parse::<DebugCommand>().is_err());
+    assert_eq!(
+        "set 2 null".parse(),
+        Ok(DebugCommand::Set(2, Value::Undefined))
+    );
+    assert_eq!("bt".parse(), Ok(DebugCommand::Backtrace));
+    assert_eq!("leave 2 .. 5".parse(), Ok(DebugCommand::Leave(2..5)));
+    assert!("leave 5".parse::<DebugCommand>().is_err());
+    assert!("s many".parse::<DebugCommand>().is_err());
+    assert!("q now".parse::<DebugCommand>().is_err());
+
+    let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    assert_eq!(
+        vm.debug_command(DebugCommand::Set(1, "xyz".into())),
+        DebugResponse::Output("1 = \"xyz\"".to_string())
+    );
+    assert_eq!(
+        vm.debug_command(DebugCommand::Break(3)),
+        DebugResponse::Output("added breakpoint at 3".to_string())
+    );
+    assert_eq!(vm.debug_command(DebugCommand::Quit), DebugResponse::Quit);
+    assert_eq!(vm.run(), Ok("xyz".to_string()));
+
+    assert_eq!(
+        "push {\"ptr\": 3}".parse(),
+        Ok(DebugCommand::Push(Value::Ptr(3)))
+    );
+    assert_eq!("truncate 7".parse(), Ok(DebugCommand::Truncate(7)));
+    assert_eq!("pc 4".parse(), Ok(DebugCommand::SetProgramCounter(4)));
+    assert!("truncate".parse::<DebugCommand>().is_err());
+    assert!("push".parse::<DebugCommand>().is_err());
+
+    // the program and its exit take up addresses 2 to 5
+    let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    assert_eq!(
+        vm.debug_command(DebugCommand::Truncate(5)),
+        DebugResponse::Output(
+            "the stack can't be shorter than 6, since the program ends at 5".to_string()
+        )
+    );
+    for value in ["one", "two"] {
+        vm.debug_command(DebugCommand::Push(value.into()));
+    }
+    assert_eq!(
+        vm.debug_command(DebugCommand::Truncate(7)),
+        DebugResponse::Output("the stack is now 7 values long".to_string())
+    );
+    assert_eq!(
+        vm.debug_command(DebugCommand::SetProgramCounter(9)),
+        DebugResponse::Output("nothing at address 9".to_string())
+    );
+
+    // skipping straight to the exit leaves what was pushed on top, and the edits last through continuing
+    let (result, transcript) = vm.run_debug_script("pc 5\nc\n");
+    assert!(
+        transcript.contains("(chicken) pc 5\npc = 5\n"),
+        "{}",
+        transcript
+    );
+    assert_eq!(result, Ok("one".to_string()));
+}
+
+#[test]
+fn expressions() {
+    let vm = VMBuilder::from_opcodes([11, 6, 0]).input("7").build();
+    let eval = |expr: &str| expr.parse::<Expr>().map(|expr| expr.eval(&vm));
+
+    assert_eq!(eval("stack[1] * 2 + len(stack)"), Ok(Value::Num(20)));
+    assert_eq!(eval("(1 + 2) * -3"), Ok(Value::Num(-9)));
+    assert_eq!(eval("10 - 4 - 3"), Ok(Value::Num(3)));
+    assert_eq!(eval("stack[pc] == 11"), Ok(Value::True));
+    assert_eq!(eval("stack[1] + \"\\n\""), Ok(Value::from("7\n")));
+    assert_eq!(eval("stack[100]"), Ok(Value::Undefined));
+    assert_eq!(eval("stack[-1]"), Ok(Value::Undefined));
+    assert_eq!(eval("1 / 0"), Ok(Value::NaN));
+
+    assert!(eval("").is_err());
+    assert!(eval("stack[1").is_err());
+    assert!(eval("1 < 2 < 3").is_err());
+    assert!(eval("len(1)").is_err());
+    assert!(eval("chicken").is_err());
+    assert!(eval("\"unterminated").is_err());
+
+    for expr in [
+        "(1 + 2) * 3",
+        "1 - (2 - 3)",
+        "-(1 + 2)",
+        "stack[pc + 1] != \"a\\\"b\"",
+    ] {
+        let parsed = expr.parse::<Expr>().unwrap();
+        assert_eq!(parsed.to_string(), expr);
+        assert_eq!(parsed.to_string().parse(), Ok(parsed));
+    }
+}
+
+#[test]
+fn debug_script() {
+    let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    let (result, transcript) = vm.run_debug_script("s 2\nset 6 \"xyz\"\nbogus\nc\n");
+
+    assert_eq!(result, Ok("xyz".to_string()));
+    assert!(transcript.contains("(chicken) s 2\n"));
+    assert!(transcript.contains("(chicken) set 6 \"xyz\"\n6 = \"xyz\"\n"));
+    assert!(transcript.contains("unknown command \"bogus\""));
+
+    // quitting stops the program, and running out of commands lets it finish
+    let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    assert_eq!(
+        vm.run_debug_script("q\n").0.unwrap_err().kind,
+        ErrorKind::Interrupted
+    );
+
+    let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    assert_eq!(vm.run_debug_script("").0, Ok("abc".to_string()));
+
+    let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    let (_, transcript) = vm.run_debug_script("leave 2..5\np 6\n");
+    assert!(transcript.contains("(chicken) p 6\n\"abc\"\n"));
+
+    // continuing stops as soon as a watch changes
+    let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    let (_, transcript) = vm.run_debug_script("w stack[6]\nc\nbt\nc\n");
+    assert!(transcript.contains("watch 1: stack[6] = undefined\n"));
+    assert!(transcript.contains("watch 1: stack[6] = 1\n"));
+    assert!(transcript.contains("(chicken) bt\n    2\n(chicken) c\n"));
+    assert!(transcript.contains("watch 1: stack[6] = \"abc\"\n"));
+
+    // the loop in 99chickens jumps back to the start for every verse
+    let build = || {
+        VMBuilder::from_path("examples/99chickens.chicken")
+            .unwrap()
+            .input(2)
+            .build()
+    };
+    let (result, transcript) = build().run_debug_script("b jump taken\nc\nc\nb jump taken\nc\n");
+    assert_eq!(result, build().run());
+    assert_eq!(transcript.matches("stopped after jump taken at").count(), 2);
+    assert!(transcript.contains("no longer stopping after every jump taken"));
+}
+
+#[test]
+fn run_with_events() {
+    let builder = || {
+        VMBuilder::from_path("examples/99chickens.chicken")
+            .unwrap()
+            .input(50)
+    };
+
+    // a full queue drops steps instead of waiting, but every step is either received or counted as dropped
+    let (sender, receiver) = std::sync::mpsc::sync_channel(4);
+    let handle = builder()
+        .build()
+        .run_with_events(EventSender::lossy(sender));
+
+    let mut received = 0;
+    let dropped = loop {
+        match receiver.recv().unwrap() {
+            VMEvent::Step(info) => {
+                received += 1;
+                assert!(info.stack_depth > 0);
+            }
+            VMEvent::Finished { dropped_steps } => break dropped_steps,
+        }
+    };
+
+    let (vm, result) = handle.join().unwrap();
+    assert!(result.unwrap().ends_with("no chickens\n"));
+    assert_eq!(received + dropped, vm.stats().steps);
+
+    // hanging up stops the program
+    let (sender, receiver) = std::sync::mpsc::channel();
+    drop(receiver);
+    let (_, result) = builder().build().run_with_events(sender).join().unwrap();
+    assert_eq!(result.unwrap_err().kind, ErrorKind::Io);
+}
+
+// collects what the debugger writes, so it can be looked at after the VM has taken the writer
+#[derive(Clone, Default)]
+struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn debug_mi() {
+    let commands = [
+        r#"{"command": "step", "count": 2}"#,
+        r#"{"command": "print", "address": 6}"#,
+        r#"{"command": "jump"}"#,
+        r#"{"command": "set", "address": 6, "value": "xyz"}"#,
+        r#"{"command": "continue"}"#,
+    ]
+    .join("\n");
+
+    let output = SharedOutput::default();
+    let mut vm = VMBuilder::from_opcodes([11, 6, 0])
+        .input("abc")
+        .debug_io(std::io::Cursor::new(commands), output.clone())
+        .debug_mi()
+        .build();
+    assert_eq!(vm.run(), Ok("xyz".to_string()));
+
+    let output = output.0.lock().unwrap();
+    let events = std::str::from_utf8(&output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(events.len(), 5);
+    assert_eq!(events[0]["event"], "stopped");
+    assert_eq!(events[0]["reason"], "start");
+    assert_eq!(events[0]["pc"], 2);
+    assert_eq!(events[1]["reason"], "step");
+    assert_eq!(events[1]["stack"][6], "abc");
+    assert_eq!(events[2]["text"], "\"abc\"");
+    assert_eq!(events[3]["event"], "error");
+    assert_eq!(events[4]["text"], "6 = \"xyz\"");
+}
+
+#[test]
+fn fork() {
+    let asked = Arc::new(Mutex::new(0));
+
+    let counter = asked.clone();
+    let vm = VMBuilder::from_path("examples/cat.chicken")
+        .unwrap()
+        .lazy_input(move || {
+            *counter.lock().unwrap() += 1;
+            Ok(Value::from("meow"))
+        })
+        .build();
+
+    // both forks see the same input, which is only asked for once
+    assert_eq!(vm.fork().run(), Ok("meow".to_string()));
+    assert_eq!(vm.clone().run(), Ok("meow".to_string()));
+    assert_eq!(*asked.lock().unwrap(), 1);
+    assert!(!vm.exited);
+}
+
+#[test]
+fn state_hash() {
+    let mut vm = VMBuilder::from_path("examples/helloworld.chicken")
+        .unwrap()
+        .build();
+    vm.run().unwrap();
+
+    // written down from a previous run, this should never change
+    assert_eq!(vm.state_hash(), 0xc2466a7514886b2e);
+}
+
+#[test]
+fn recent_program_counters() {
+    // jumps back to the start of the program forever
+    let err = VMBuilder::from_opcodes([11, 10, 15, 3, 8])
+        .limits(Limits {
+            max_steps: Some(100),
+            ..Default::default()
+        })
+        .build()
+        .run()
+        .unwrap_err();
+
+    assert_eq!(err.recent_program_counters.len(), 32);
+    assert_eq!(&err.recent_program_counters[27..], &[2, 3, 4, 5, 6]);
+}
+
+#[test]
+fn cfg() {
+    let cfg = Program::from_chicken(read_to_string("examples/helloworld.chicken").unwrap()).cfg();
+
+    assert_eq!(
+        cfg.blocks.iter().map(|b| b.start).collect::<Vec<_>>(),
+        vec![0, 28, 57]
+    );
+    assert_eq!(
+        cfg.block_at(28).unwrap().successors,
+        vec![Successor::Jump(28), Successor::Next(57)]
+    );
+    assert_eq!(
+        cfg.block_at(57).unwrap().successors,
+        vec![Successor::Next(60)]
+    );
+}
+
+#[test]
+fn remove_dead_code() {
+    // stores 9 past the end of the program, jumps over an add and a char, and loads the 9 back
+    let asm = "push 9\npush 20\nstore\npush 1\npush 2\njump\nadd\nchar\npush 20\nload 0\nchicken";
+    let original = Program::from_asm(asm).unwrap();
+
+    let mut program = original.clone();
+    assert_eq!(program.remove_dead_code(false), 0);
+    assert_eq!(program.remove_dead_code(true), 2);
+    assert_eq!(
+        program.to_asm(),
+        "push 9\npush 18\nstore\npush 1\npush 0\njump\npush 18\nload 0\nchicken\n"
+    );
+    assert_eq!(program.remove_dead_code(true), 0);
+
+    let run = |program: Program| {
+        let builder = VMBuilder::from_program(program).freeze_program();
+        builder.build().run_full().unwrap().remaining_stack
+    };
+    assert_eq!(run(program), run(original));
+
+    // the offset of this jump is loaded from the input, so the add after it could be where it lands
+    let mut program = Program::from_asm("push 1\npush 1\nload 1\njump\nexit\nadd").unwrap();
+    assert_eq!(program.remove_dead_code(true), 0);
+}
+
+#[test]
+fn detect_loops() {
+    let err = VMBuilder::from_opcodes([11, 10, 15, 3, 8])
+        .detect_loops()
+        .build()
+        .run()
+        .unwrap_err();
+    assert_eq!(err.message, "infinite loop detected at pc 2");
+    assert_eq!(err.kind, ErrorKind::Limit);
+    assert_eq!(
+        VMBuilder::from_chicken(read_to_string("examples/deadfish.chicken").unwrap())
+            .input("iissiso")
+            .detect_loops()
+            .loop_window(1024)
+            .build()
+            .run(),
+        Ok(" 289 ".to_string())
+    );
+}
+
+#[test]
+fn profile() {
+    let mut vm = VMBuilder::from_chicken(read_to_string("examples/helloworld.chicken").unwrap())
+        .profile()
+        .build();
+
+    assert_eq!(vm.run(), Ok("Hello world".to_string()));
+
+    let profile = vm.profile.unwrap();
+    assert_eq!(profile.counts[&2], 1);
+    assert_eq!(profile.opcodes["exit"].count, 1);
+    assert_eq!(profile.hottest_jump_targets(1)[0].0, 30);
+    assert_eq!(
+        profile.total_steps(),
+        profile.opcodes.values().map(|t| t.count).sum::<u64>()
+    );
+}
+
+#[test]
+fn coverage() {
+    let mut vm = VMBuilder::from_chicken(read_to_string("examples/deadfish.chicken").unwrap())
+        .input("iso")
+        .coverage()
+        .build();
+
+    assert_eq!(vm.run(), Ok(" 1 ".to_string()));
+
+    let coverage = vm.coverage.unwrap();
+    assert!(coverage.covered() < coverage.total());
+    assert_eq!(coverage.hits[0], 1);
+}
+
+#[test]
+fn stats() {
+    let mut vm = VMBuilder::from_chicken("chicken").build();
+
+    assert_eq!(vm.run(), Ok("chicken".to_string()));
+
+    let stats = vm.stats();
+    assert_eq!(stats.steps, 2);
+    assert_eq!((stats.pushes, stats.pops), (1, 1));
+    assert_eq!(stats.max_stack_depth, 5);
+    assert!(stats.stack_capacity >= 8);
+    assert_eq!((stats.string_bytes, stats.peak_string_bytes), (0, 7));
+    assert_eq!(stats.peak_memory, 5 * std::mem::size_of::<Value>() + 7);
+    assert_eq!(vm.memory_usage(), 4 * std::mem::size_of::<Value>());
+    assert_eq!(stats.instructions["chicken"], 1);
+    assert_eq!(stats.instructions["exit"], 1);
+
+    // pushes "chicken", then adds it to itself until its length saturates, which the byte counts have to as well
+    let mut vm = VMBuilder::from_opcodes([1, 23, 6, 0, 2, 11, 10, 19, 3, 8])
+        .limits(Limits {
+            max_steps: Some(1000),
+            ..Default::default()
+        })
+        .build();
+    assert_eq!(vm.run().unwrap_err().message, "step limit of 1000 exceeded");
+    assert_eq!(vm.stats().peak_string_bytes, usize::MAX);
+    assert_eq!(vm.stats().peak_memory, usize::MAX);
+    assert_eq!(vm.memory_usage(), usize::MAX);
+}
+
+#[test]
+fn trace() {
+    let tracer = Arc::new(Mutex::new(JsonTracer::new(Vec::new())));
+
+    assert_eq!(
+        VMBuilder::from_opcodes([11, 6, 0])
+            .input("abc")
+            .observer(tracer.clone())
+            .build()
+            .run(),
+        Ok("abc".to_string())
+    );
+
+    let trace = std::string::String::from_utf8(tracer.lock().unwrap().get_ref().clone()).unwrap();
+    let lines = trace
+        .lines()
+        .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0]["chicken_trace"], 2);
+    assert_eq!(lines[0]["snapshot"]["program_counter"], 2);
+    assert_eq!(lines[2]["instruction"], "load 0");
+    assert_eq!(lines[2]["popped"], serde_json::json!([1]));
+    assert_eq!(lines[2]["pushed"], serde_json::json!(["abc"]));
+    assert_eq!(lines[2]["next_pc"], 5);
+    assert_eq!(lines[3]["pc"], 5);
+}
+
+#[test]
+fn replay() {
+    let tracer = Arc::new(Mutex::new(JsonTracer::new(Vec::new())));
+
+    assert_eq!(
+        VMBuilder::from_chicken(read_to_string("examples/deadfish.chicken").unwrap())
+            .input("iissiso")
+            .observer(tracer.clone())
+            .build()
+            .run(),
+        Ok(" 289 ".to_string())
+    );
+
+    let recorded = tracer.lock().unwrap().get_ref().clone();
+    let trace = Trace::from_reader(&recorded[..]).unwrap();
+
+    let (result, divergence) = trace.replay(trace.builder());
+    assert_eq!(result, Ok(" 289 ".to_string()));
+    assert_eq!(divergence, None);
+
+    let mut snapshot = trace.snapshot.clone();
+    snapshot.stack[1] = Value::String("iso".into());
+    let (result, divergence) = trace.replay(VMBuilder::from_snapshot(snapshot));
+    assert_eq!(result, Ok(" 1 ".to_string()));
+    assert!(divergence.unwrap().step > 1);
+
+    assert!(Trace::from_reader(&b""[..]).is_err());
+}
+
+#[test]
+fn sampled_trace() {
+    let record = |sampling: &str| {
+        let tracer = JsonTracer::new(Vec::new()).sampling(sampling.parse().unwrap());
+        let tracer = Arc::new(Mutex::new(tracer));
+
+        VMBuilder::from_path("examples/deadfish.chicken")
+            .unwrap()
+            .input("iissiso")
+            .observer(tracer.clone())
+            .build()
+            .run()
+            .unwrap();
+
+        let recorded = tracer.lock().unwrap().get_ref().clone();
+        Trace::from_reader(&recorded[..]).unwrap()
+    };
+
+    let all = record("all");
+    let every = record("10");
+    let jumps = record("jumps");
+
+    assert_eq!(every.sampling, TraceSampling::Every(10));
+    assert_eq!(every.steps.len(), all.steps.len().div_ceil(10));
+    assert_eq!(every.steps[1], all.steps[10]);
+    assert_eq!(every.steps[1]["step"], 11);
+    assert!(jumps.steps.iter().all(|step| step["jumped"] == true));
+    assert!(record("stores")
+        .steps
+        .iter()
+        .all(|step| step["instruction"] == "store"));
+
+    let (result, divergence) = jumps.replay(jumps.builder());
+    assert_eq!(result, Ok(" 289 ".to_string()));
+    assert_eq!(divergence, None);
+
+    let mut snapshot = jumps.snapshot.clone();
+    snapshot.stack[1] = Value::String("iso".into());
+    let divergence = jumps.replay(VMBuilder::from_snapshot(snapshot)).1.unwrap();
+    assert_eq!(divergence.step, divergence.expected.unwrap()["step"]);
+
+    assert_eq!("0".parse::<TraceSampling>().ok(), None);
+    assert_eq!(TraceSampling::Every(10).to_string(), "10");
+}
+
+#[test]
+fn value_json() {
+    let values = vec![
+        Value::Num(-3),
+        Value::String("3".into()),
+        Value::Ptr(0),
+        Value::True,
+        Value::False,
+        Value::Undefined,
+        Value::NaN,
+    ];
+    let json = serde_json::to_string(&values).unwrap();
+
+    assert_eq!(json, r#"[-3,"3",{"ptr":0},true,false,null,{"nan":true}]"#);
+    assert_eq!(
+        format!("{:?}", serde_json::from_str::<Vec<Value>>(&json).unwrap()),
+        format!("{:?}", values)
+    );
+}
+
+#[test]
+fn chrome_trace() {
+    let tracer = Arc::new(Mutex::new(ChromeTracer::new(
+        Vec::new(),
+        ChromeTraceGranularity::BasicBlock,
+    )));
+
+    assert_eq!(
+        VMBuilder::from_chicken(read_to_string("examples/helloworld.chicken").unwrap())
+            .observer(tracer.clone())
+            .build()
+            .run(),
+        Ok("Hello world".to_string())
+    );
+
+    let trace = std::mem::replace(
+        &mut *tracer.lock().unwrap(),
+        ChromeTracer::new(Vec::new(), ChromeTraceGranularity::Instruction),
+    )
+    .into_inner();
+    let events = serde_json::from_slice::<Vec<serde_json::Value>>(&trace).unwrap();
+
+    assert_eq!(events[0]["name"], "block 2");
+    assert_eq!(events[0]["args"]["instructions"], 52);
+    assert_eq!(events.last().unwrap()["name"], "block 59");
+}
+
+#[test]
+fn limits() {
+    let program = read_to_string("examples/99chickens.chicken").unwrap();
+    let run = |limits| {
+        VMBuilder::from_chicken(&program)
+            .input("9")
+            .limits(limits)
+            .build()
+            .run()
+    };
+
+    let err = run(Limits {
+        max_steps: Some(100),
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Limit);
+    assert_eq!(err.message, "step limit of 100 exceeded");
+
+    let err = run(Limits {
+        max_stack: Some(120),
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert_eq!(err.message, "stack limit of 120 exceeded");
+
+    let err = run(Limits {
+        max_string_len: Some(100),
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert_eq!(err.message, "string length limit of 100 exceeded");
+
+    let err = run(Limits {
+        max_output_len: Some(100),
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::OutputLimitExceeded);
+    assert_eq!(err.message, "output limit of 100 bytes exceeded");
+
+    let err = run(Limits {
+        max_memory: Some(1000),
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Limit);
+    assert_eq!(err.message, "memory limit of 1000 bytes exceeded");
+
+    assert!(run(Limits {
+        max_steps: Some(1_000_000),
+        max_fuel: Some(1_000_000),
+        max_stack: Some(1_000),
+        max_string_len: Some(10_000),
+        max_output_len: Some(10_000),
+        max_memory: Some(1_000_000),
+        max_time: Some(Duration::from_secs(60)),
+    })
+    .is_ok());
+}
+
+#[test]
+fn clamped_limits() {
+    // pushes "chicken" right after the program, then doubles it 64 times by loading it back and adding it to itself
+    let len = 1 + 64 * 4 + 1;
+    let bomb = [vec![1], [13 + len, 6, 0, 2].repeat(64), vec![0]].concat();
+
+    // asking for more than the untrusted limits still stops it long before it runs out of memory
+    let asked_for = Limits {
+        max_steps: Some(u64::MAX),
+        max_stack: Some(usize::MAX),
+        ..Default::default()
+    };
+    let limits = asked_for.clamp_to(Limits::untrusted());
+    assert_eq!(limits, Limits::untrusted());
+
+    let err = VMBuilder::from_opcodes(bomb)
+        .limits(limits)
+        .build()
+        .run()
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Limit);
+    assert_eq!(err.message, "string length limit of 65536 exceeded");
+
+    let lower = Limits {
+        max_time: Some(Duration::from_millis(1)),
+        ..Default::default()
+    };
+    assert_eq!(
+        lower.clamp_to(Limits::untrusted()).max_time,
+        Some(Duration::from_millis(1))
+    );
+}
+
+#[test]
+fn poll() {
+    use std::{task::Poll, time::Instant};
+
+    // jumps back to the start of the program forever
+    let mut vm = VMBuilder::from_opcodes([11, 10, 15, 3, 8]).build();
+    assert_eq!(vm.poll(Instant::now()), Poll::Pending);
+    assert!(vm.stats().steps > 0);
+
+    let mut vm = VMBuilder::from_opcodes([10, 0]).build();
+    assert!(matches!(vm.poll(Instant::now()), Poll::Ready(Err(_))));
+
+    // a farm of VMs taking turns, which should end up with the same outputs as running them on their own
+    let program = read_to_string("examples/99chickens.chicken").unwrap();
+    let mut farm: Vec<_> = (1..=5)
+        .map(|n| {
+            VMBuilder::from_chicken(&program)
+                .input((n * 20).to_string())
+                .build()
+        })
+        .collect();
+    let expected: Vec<_> = farm.iter().map(|vm| vm.fork().run().unwrap()).collect();
+
+    let mut outputs = vec![None; farm.len()];
+    while outputs.iter().any(Option::is_none) {
+        for (vm, output) in farm.iter_mut().zip(&mut outputs) {
+            if let Poll::Ready(result) = vm.poll(Instant::now()) {
+                *output = Some(result.unwrap());
+            }
+        }
+    }
+    assert_eq!(
+        outputs.into_iter().map(Option::unwrap).collect::<Vec<_>>(),
+        expected
+    );
+
+    // polling again once it's exited returns the output again
+    assert_eq!(
+        farm[0].poll(Instant::now()),
+        Poll::Ready(Ok(expected[0].clone()))
+    );
+}
+
+#[test]
+fn host_functions() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let build = |opcodes: &[isize]| {
+        let counter = counter.clone();
+        VMBuilder::from_opcodes(opcodes).host_fn(100, 2, move |args| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            match (&args[0], &args[1]) {
+                (Value::Num(a), Value::Num(b)) if *b != 0 => {
+                    Ok(vec![Value::Num(a / b), Value::Num(a % b)])
+                }
+                _ => Err("can't divide".to_string()),
+            }
+        })
+    };
+
+    // divides 17 by 5, then adds the quotient and remainder
+    let mut vm = build(&[27, 15, 100, 2, 0])
+        .exit_policy(ExitPolicy::Stringify)
+        .build();
+    assert_eq!(vm.run(), Ok("5".to_string()));
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+    // a failed call leaves its arguments on the stack
+    let mut vm = build(&[27, 10, 100, 0]).build();
+    let err = vm.run().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Runtime);
+    assert_eq!(err.message, "host function 100 failed: can't divide");
+    assert_eq!(
+        &vm.stack[vm.stack.len() - 2..],
+        [Value::Num(17), Value::Num(0)]
+    );
+
+    // forks share host functions
+    let mut vm = build(&[27, 15, 100, 0]).build();
+    assert!(vm.fork().run().is_err());
+    assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+    let info = vm.step_info().unwrap().unwrap();
+    assert_eq!(info.mnemonic, "push");
+    vm.step().unwrap();
+    let info = vm.step_info().unwrap().unwrap();
+    assert_eq!(info.mnemonic, "host");
+    assert_eq!(info.popped, [Value::Num(5), Value::Num(17)]);
+    assert_eq!(info.pushed, [Value::Num(3), Value::Num(2)]);
+
+    let err = build(&[27, 100]).strict().build().run().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::StackUnderflow);
+    assert_eq!(
+        err.message,
+        "host needs 2 values on the stack, but there is only 1"
+    );
+
+    // other opcodes still push
+    assert_eq!(
+        build(&[101, 0])
+            .exit_policy(ExitPolicy::Stringify)
+            .build()
+            .run(),
+        Ok("91".to_string())
+    );
+}
+
+#[test]
+fn extensions() {
+    let random = Extension::Random.opcode();
+
+    // rolls 20 dice, leaving them all on the stack, and exits with "chicken"
+    let program = [[16, random].repeat(20), vec![1]].concat();
+    let rolls = |builder: VMBuilder| builder.build().run_full().unwrap().remaining_stack;
+
+    let dice = rolls(
+        VMBuilder::from_opcodes(program.clone())
+            .extensions()
+            .seed(7),
+    );
+    assert_eq!(dice.len(), 20);
+    assert!(dice.iter().all(|n| (0..6).contains(&n.as_num().unwrap())));
+
+    // the same seed always rolls the same dice
+    assert_eq!(
+        rolls(
+            VMBuilder::from_opcodes(program.clone())
+                .extensions()
+                .seed(7)
+        ),
+        dice
+    );
+    assert_ne!(
+        rolls(
+            VMBuilder::from_opcodes(program.clone())
+                .extensions()
+                .seed(8)
+        ),
+        dice
+    );
+
+    // without extensions the opcode is just a push
+    assert_eq!(
+        rolls(VMBuilder::from_opcodes([16, random, 1])),
+        [Value::Num(6), Value::Num(random - 10)]
+    );
+
+    // resetting starts the numbers over, while forks and snapshots carry on from where the VM was
+    let mut vm = VMBuilder::from_opcodes(program.clone())
+        .extensions()
+        .seed(7)
+        .build();
+    vm.run_steps(20).unwrap();
+    let mut fork = vm.fork();
+    let mut resumed = VMBuilder::from_snapshot(vm.snapshot()).build();
+    assert_eq!(vm.run_full().unwrap().remaining_stack, dice);
+    assert_eq!(fork.run_full().unwrap().remaining_stack, dice);
+    assert_eq!(resumed.run_full().unwrap().remaining_stack, dice);
+    vm.reset(Value::Undefined);
+    assert_eq!(vm.run_full().unwrap().remaining_stack, dice);
+    assert_eq!(vm.stats().instructions["random"], 20);
+
+    // the bound has to be positive, and is put back if it isn't
+    let mut vm = VMBuilder::from_opcodes([10, random, 0])
+        .extensions()
+        .build();
+    let err = vm.run().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Runtime);
+    assert_eq!(
+        err.message,
+        "random needs a positive upper bound, found Some(Num(0))"
+    );
+    assert_eq!(vm.data(), [Value::Num(0)]);
+
+    let err = VMBuilder::from_opcodes([random])
+        .extensions()
+        .strict()
+        .build()
+        .run()
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::StackUnderflow);
+    assert_eq!(
+        err.message,
+        "random needs a value on the stack, but there aren't any"
+    );
+
+    // the string extensions work on characters, and follow javascript when the indexes are out of range
+    let (length, substring, char_code) = (
+        Extension::Length.opcode(),
+        Extension::Substring.opcode(),
+        Extension::CharCode.opcode(),
+    );
+    let on_input = |opcodes: &[isize]| {
+        let program = [&[11, 6, 0], opcodes, &[1]].concat();
+        let builder = VMBuilder::from_opcodes(program).input("héllo").extensions();
+        builder.build().run_full().unwrap().remaining_stack
+    };
+
+    assert_eq!(on_input(&[length]), [Value::Num(5)]);
+    assert_eq!(on_input(&[11, 14, substring]), [Value::from("éll")]);
+    assert_eq!(on_input(&[14, 11, substring]), [Value::from("éll")]);
+    assert_eq!(
+        on_input(&[10, 11, 3, 110, substring]),
+        [Value::from("héllo")]
+    );
+    assert_eq!(on_input(&[11, char_code]), [Value::Num(233)]);
+    assert_eq!(on_input(&[15, char_code]), [Value::NaN]);
+    assert_eq!(
+        on_input(&[133, length]),
+        [Value::from("héllo"), Value::Num(3)]
+    );
+
+    // makes an array of 3 values at address 15, stores 5 at index 1 of it, then loads it back
+    let (array, aload, astore) = (
+        Extension::Array.opcode(),
+        Extension::ArrayLoad.opcode(),
+        Extension::ArrayStore.opcode(),
+    );
+    let program = [13, array, 15, 11, 25, astore, 25, 6, 0, 11, aload, 1];
+    let stored = Value::Array(Arc::new(vec![
+        Value::Undefined,
+        Value::Num(5),
+        Value::Undefined,
+    ]));
+
+    let mut vm = VMBuilder::from_opcodes(program).extensions().build();
+    vm.run_steps(2).unwrap();
+    let mut fork = vm.fork();
+    assert_eq!(
+        fork.run_full().unwrap().remaining_stack,
+        [stored.clone(), Value::Num(5)]
+    );
+    assert_eq!(
+        vm.stack[15],
+        Value::Array(Arc::new(vec![Value::Undefined; 3]))
+    );
+    assert_eq!(
+        vm.run_full().unwrap().remaining_stack,
+        [stored.clone(), Value::Num(5)]
+    );
+
+    // storing past the end grows the array, and loading past it is undefined
+    let program = [13, array, 15, 14, 25, astore, 25, 6, 0, 115, aload, 1];
+    let remaining = (VMBuilder::from_opcodes(program).extensions().build())
+        .run_full()
+        .unwrap()
+        .remaining_stack;
+    let grown = &remaining[0];
+    assert_eq!(grown.as_array().unwrap().len(), 5);
+    assert_eq!(grown.to_string(), ",,,,5");
+    assert_eq!(&remaining[1], &Value::Undefined);
+
+    // arrays act like their strings with everything but other arrays, and are JSON arrays
+    assert_eq!(stored, Value::from(",5,"));
+    assert_eq!(&stored + &Value::Num(1), Value::from(",5,1"));
+    assert_eq!(serde_json::to_string(&stored).unwrap(), "[null,5,null]");
+    assert_eq!(
+        serde_json::from_str::<Value>("[null,5,null]").unwrap(),
+        stored
+    );
+    assert_eq!(
+        on_input(&[13, array, length]),
+        [Value::from("héllo"), Value::Num(3)]
+    );
+
+    let err = VMBuilder::from_opcodes([13, array, 11, 11, 10, astore])
+        .extensions()
+        .build()
+        .run()
+        .unwrap_err();
+    assert_eq!(
+        err.message,
+        "astore into address 0, which holds Ptr(0) instead of an array"
+    );
+    let err = VMBuilder::from_opcodes([1030, array])
+        .extensions()
+        .limits(Limits {
+            max_stack: Some(100),
+            ..Default::default()
+        })
+        .build()
+        .run()
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Limit);
+    assert_eq!(
+        err.message,
+        "array of 1020 values is bigger than the stack limit of 100"
+    );
+
+    // calls a subroutine at address 7 that pushes 7, which returns to the chicken right after the call
+    let (call, ret) = (Extension::Call.opcode(), Extension::Ret.opcode());
+    let program = Program::from_asm("push 3\ncall\nchicken\nexit\npush 0\npush 7\nret").unwrap();
+    assert_eq!(program.opcodes(), [13, call, 1, 0, 10, 17, ret]);
+
+    let mut vm = VMBuilder::from_program(program).extensions().build();
+    vm.run_steps(2).unwrap();
+    assert_eq!(vm.return_stack(), [4]);
+    let mut resumed = VMBuilder::from_snapshot(vm.snapshot()).build();
+    assert_eq!(resumed.run_full().unwrap().remaining_stack, [Value::Num(7)]);
+    assert_eq!(vm.run_full().unwrap().remaining_stack, [Value::Num(7)]);
+    assert!(vm.return_stack().is_empty());
+    assert_eq!(vm.stats().instructions["call"], 1);
+    assert_eq!(vm.stats().instructions["ret"], 1);
+
+    let err = VMBuilder::from_opcodes([ret])
+        .extensions()
+        .build()
+        .run()
+        .unwrap_err();
+    assert_eq!(err.message, "ret without a call to return from");
+
+    // calling itself forever runs into the stack limit instead of using up all the memory
+    let err = VMBuilder::from_opcodes([10, 14, 3, call])
+        .extensions()
+        .limits(Limits {
+            max_stack: Some(100),
+            ..Default::default()
+        })
+        .build()
+        .run()
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Limit);
+    assert_eq!(err.message, "return stack limit of 100 exceeded");
+
+    // the handler at address 7 is stored in slot 10, and gets the code of the error that jumped to it
+    let trap = Extension::Trap.opcode();
+    let trapped = |opcode: isize, slot: isize| {
+        VMBuilder::from_opcodes([17, slot + 10, trap, opcode, 0, 1, 0])
+            .extensions()
+            .strict()
+            .build()
+            .run_full()
+            .map(|output| output.remaining_stack)
+    };
+    assert_eq!(trapped(ret, 10), Ok(vec![Value::Num(7), Value::Num(1)]));
+    assert_eq!(trapped(2, 10), Ok(vec![Value::Num(7), Value::Num(2)]));
+    assert_eq!(
+        trapped(ret, 99).unwrap_err().message,
+        "ret without a call to return from"
+    );
+
+    // receives two values from channel 0, waiting for each one without it being taken for an infinite loop
+    let (send, recv) = (Extension::Send.opcode(), Extension::Recv.opcode());
+    let channel = Channel::new();
+    let mut vm = VMBuilder::from_opcodes([10, recv, 10, recv, 1])
+        .extensions()
+        .detect_loops()
+        .channel(0, channel.clone())
+        .build();
+    assert_eq!(vm.run_steps(10), Ok(RunProgress::Running));
+    assert!(vm.is_waiting());
+    channel.send(5);
+    assert_eq!(vm.run_steps(10), Ok(RunProgress::Running));
+    assert_eq!(vm.data(), [Value::Num(5), Value::Num(0)]);
+    channel.send("x");
+    assert_eq!(
+        vm.run_full().unwrap().remaining_stack,
+        [Value::Num(5), Value::from("x")]
+    );
+    assert!(!vm.is_waiting() && channel.is_empty());
+
+    let err = VMBuilder::from_opcodes([15, send])
+        .extensions()
+        .build()
+        .run()
+        .unwrap_err();
+    assert_eq!(err.message, "no channel Some(Num(5))");
+
+    // host functions replace extensions with the same opcode
+    let mut vm = VMBuilder::from_opcodes([16, random, 0])
+        .extensions()
+        .host_fn(random, 1, |_| Ok(vec![Value::Num(4)]))
+        .exit_policy(ExitPolicy::Stringify)
+        .build();
+    assert_eq!(vm.run(), Ok("4".to_string()));
+}
+
+#[test]
+fn explanations() {
+    for explanation in Explanation::all() {
+        assert_eq!(Explanation::of(explanation.mnemonic), Some(*explanation));
+        assert_eq!(
+            Explanation::of(&explanation.opcode.to_string()),
+            Some(*explanation)
+        );
+        assert!(
+            explanation.run_example().is_ok(),
+            "example of {} failed",
+            explanation.mnemonic
+        );
+    }
+
+    assert!(Extension::ALL
+        .iter()
+        .all(|extension| Explanation::of(extension.mnemonic()).is_some_and(|e| e.extension)));
+    assert_eq!(Explanation::of("995").unwrap().mnemonic, "push");
+    assert_eq!(Explanation::of("moo"), None);
+}
+
+#[test]
+fn test_io() {
+    let io = TestIo::new().input("abc").commands("s 2\np 1\nc\n");
+    let mut vm = VMBuilder::from_opcodes([11, 6, 0])
+        .debug()
+        .io(io.clone())
+        .build();
+
+    // the input isn't read until the program reads it
+    assert_eq!(vm.stack[1], Value::Undefined);
+    assert_eq!(vm.run(), Ok("abc".to_string()));
+    assert_eq!(io.output(), "abc");
+
+    let transcript = io.debug_output();
+    assert!(transcript.contains("(chicken) s 2\n"));
+    assert!(transcript.contains("(chicken) p 1\n\"abc\"\n"));
+
+    // an input given to the builder takes the place of the io's
+    let io = TestIo::new().input("abc");
+    let mut vm = VMBuilder::from_opcodes([11, 6, 0])
+        .input("xyz")
+        .io(io.clone())
+        .build();
+    assert_eq!(vm.run_raw(), Ok("xyz".to_string()));
+    assert_eq!(io.output(), "xyz");
+
+    let err = VMBuilder::from_opcodes([11, 6, 0])
+        .io(TestIo::new())
+        .build()
+        .run()
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Io);
+    assert_eq!(
+        err.message,
+        "error reading input: the TestIo doesn't have an input"
+    );
+}
+
+#[test]
+fn time_limit_and_sandbox() {
+    // jumps back to the start of the program forever
+    let err = VMBuilder::from_opcodes([11, 10, 15, 3, 8])
+        .limits(Limits {
+            max_time: Some(Duration::from_millis(50)),
+            ..Default::default()
+        })
+        .build()
+        .run()
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Limit);
+    assert_eq!(err.message, "time limit of 50ms exceeded");
+
+    // the clock starts again when the VM is reset
+    let mut vm = VMBuilder::from_opcodes([11, 10, 15, 3, 8])
+        .limits(Limits {
+            max_time: Some(Duration::from_millis(50)),
+            ..Default::default()
+        })
+        .build();
+    assert_eq!(vm.run_steps(10), Ok(RunProgress::Running));
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(vm.run_steps(2000).unwrap_err().kind, ErrorKind::Limit);
+    vm.reset("");
+    assert_eq!(vm.run_steps(2000), Ok(RunProgress::Running));
+
+    let mut vm = VMBuilder::from_path("examples/helloworld.chicken")
+        .unwrap()
+        .sandbox()
+        .build();
+    assert_eq!(vm.limits, Limits::untrusted());
+    assert_eq!(vm.run(), Ok("Hello world".to_string()));
+
+    let err = VMBuilder::from_opcodes([1, 11, 10, 16, 3, 8])
+        .sandbox()
+        .build()
+        .run()
+        .unwrap_err();
+    assert_eq!(err.message, "stack limit of 100000 exceeded");
+}
+
+#[test]
+fn program_formats() {
+    let program = Program::from_chicken(read_to_string("examples/99chickens.chicken").unwrap());
+
+    assert_eq!(Program::from_asm(program.to_asm()), Ok(program.clone()));
+    assert_eq!(
+        Program::from_opcode_text(program.to_opcode_text()),
+        Ok(program.clone())
+    );
+    assert_eq!(
+        Program::from_chicken(program.to_chicken().unwrap()),
+        program
+    );
+    assert_eq!(
+        serde_json::from_str::<Program>(&serde_json::to_string(&program).unwrap()).unwrap(),
+        program
+    );
+
+    // a load at the very end has no operand
+    assert_eq!(Program::from_opcodes([6]).to_asm(), "load\n");
+    assert_eq!(Program::from_opcodes([-1]).to_chicken(), None);
+
+    // a negative literal has a negative opcode, which can't be written as chicken code
+    let negative = Program::from_asm("push -20").unwrap();
+    assert_eq!(negative.opcodes(), &[-10]);
+    assert_eq!(negative.to_chicken(), None);
+    assert!(Program::from_asm("push -3").is_err());
+    assert_eq!(Program::from_asm("add\nfly").unwrap_err().line, 2);
+}
+
+#[test]
+fn source_maps() {
+    let asm = "push 1\n# reads the input\nstart: load 0\n\nchicken\ndone:\nadd\nexit";
+    let (program, source_map) = Program::from_asm_with_source_map(asm, Some("cat.asm")).unwrap();
+
+    assert_eq!(program, Program::from_asm(asm).unwrap());
+    assert_eq!(source_map.file.as_deref(), Some("cat.asm"));
+    assert_eq!(
+        source_map
+            .instructions
+            .iter()
+            .map(|location| (location.index, location.line, location.label.as_deref()))
+            .collect::<Vec<_>>(),
+        [
+            (0, 1, None),
+            (1, 3, Some("start")),
+            (3, 5, Some("start")),
+            (4, 7, Some("done")),
+            (5, 8, Some("done")),
+        ]
+    );
+    assert_eq!(source_map.lookup(2).unwrap().line, 3);
+    assert_eq!(source_map.lookup(100).unwrap().line, 8);
+    assert_eq!(source_map.labels["done"], 4);
+
+    // the source map survives being written into a json program and read back
+    let file = ProgramFile {
+        program: program.clone(),
+        source_map: Some(source_map),
+        metadata: Metadata::default(),
+    };
+    let json = serde_json::to_string(&file).unwrap();
+    assert!(json.starts_with(r#"{"opcodes":[11,6,0,1,2,0],"source_map":"#));
+    assert_eq!(serde_json::from_str::<ProgramFile>(&json).unwrap(), file);
+
+    assert_eq!(
+        Program::from_asm("a:\nexit\na: exit").unwrap_err().message,
+        "label \"a\" is defined more than once"
+    );
+    assert!(Program::from_asm("1st: exit").is_err());
+}
+
+#[test]
+fn load_errors() {
+    assert!(matches!(
+        Program::from_path("examples/nonexistent.chicken"),
+        Err(LoadError::Io(_))
+    ));
+    assert!(matches!(
+        VMBuilder::from_reader(&[b'c', 0xff][..]),
+        Err(LoadError::Parse(_))
+    ));
+    // a byte order mark for UTF-16 on its own is an empty file
+    assert_eq!(
+        Program::from_reader(&[0xff, 0xfe][..]).unwrap(),
+        Program::from_chicken("")
+    );
+    assert_eq!(
+        VMBuilder::from_reader("chicken".as_bytes())
+            .unwrap()
+            .build()
+            .run(),
+        Ok("chicken".to_string())
+    );
+}
+
+#[test]
+fn format() {
+    for example in ["99chickens", "cat", "deadfish", "helloworld", "quine"] {
+        let code = read_to_string(format!("examples/{}.chicken", example)).unwrap();
+        assert_eq!(format_chicken(&code), code);
+    }
+
+    let messy = " chicken  chicken\t\r\n   \nchickenchicken x chicken ";
+    assert_eq!(
+        format_chicken(messy),
+        "chicken chicken\n\nchickenchicken x chicken"
+    );
+    assert_eq!(
+        Program::from_chicken(format_chicken(messy)),
+        Program::from_chicken(messy)
+    );
+
+    // every line is an opcode, so the number of lines and whether the file ends with a newline are left alone
+    for code in ["chicken", "chicken\n", "chicken\n\n", " chicken \r\n\r\n"] {
+        let formatted = format_chicken(code);
+        assert_eq!(
+            formatted.lines().count(),
+            code.lines().count(),
+            "{:?}",
+            code
+        );
+        assert_eq!(
+            Program::from_chicken(&formatted),
+            Program::from_chicken(code)
+        );
+    }
+    assert_eq!(format_chicken("chicken"), "chicken");
+}
+
+#[test]
+fn source_decoding() {
+    let unix = "chicken chicken\n\nchicken\tchicken  chicken\n";
+    let expected = Program::from_chicken(unix);
+    assert_eq!(expected.opcodes(), &[2, 0, 3, 0]);
+
+    // every kind of line ending and a byte order mark give the same opcodes, and format the same way
+    for code in [
+        unix.replace('\n', "\r\n"),
+        unix.replace('\n', "\r"),
+        format!("\u{feff}{}", unix),
+        "chicken chicken\r\n\rchicken chicken chicken\n".to_string(),
+    ] {
+        assert_eq!(Program::from_chicken(&code), expected, "{:?}", code);
+        assert_eq!(format_chicken(&code), format_chicken(unix), "{:?}", code);
+        assert_eq!(Program::from_bytes(code.as_bytes()), Ok(expected.clone()));
+    }
+
+    let misspelled = "chicken\rchicken chiken\r\n";
+    assert_eq!(lint(misspelled)[0].line, 2);
+
+    let utf16 = |bom: bool, to_bytes: fn(u16) -> [u8; 2]| {
+        (bom.then_some(0xfeff).into_iter())
+            .chain(unix.replace('\n', "\r\n").encode_utf16())
+            .flat_map(to_bytes)
+            .collect::<Vec<u8>>()
+    };
+    for bom in [true, false] {
+        assert_eq!(
+            Program::from_bytes(utf16(bom, u16::to_le_bytes)),
+            Ok(expected.clone())
+        );
+        assert_eq!(
+            Program::from_bytes(utf16(bom, u16::to_be_bytes)),
+            Ok(expected.clone())
+        );
+    }
+
+    let mut bytes = utf16(true, u16::to_le_bytes);
+    bytes.extend([0x00, 0xdc]);
+    assert_eq!(
+        Program::from_bytes(bytes).unwrap_err().message,
+        "invalid UTF-16"
+    );
+
+    let err = Program::from_bytes(b"chicken\nchicken\xff\n").unwrap_err();
+    assert_eq!((err.line, err.message.as_str()), (2, "invalid UTF-8"));
+
+    // files saved as UTF-16 load the same as UTF-8 ones
+    let dir = std::env::temp_dir().join(format!("chicken-decoding-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("utf16.chicken");
+    std::fs::write(&path, utf16(true, u16::to_le_bytes)).unwrap();
+    assert_eq!(Program::from_path(&path).unwrap(), expected);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn strict_syntax() {
+    for example in ["99chickens", "cat", "deadfish", "helloworld", "quine"] {
+        let code = read_to_string(format!("examples/{}.chicken", example)).unwrap();
+        assert_eq!(
+            Program::from_chicken_strict(&code),
+            Ok(Program::from_chicken(&code))
+        );
+    }
+
+    for (code, offset, message) in [
+        (" chicken", 0, "line starts with a space"),
+        ("chicken\n ", 8, "line starts with a space"),
+        ("chicken \n", 7, "line ends with a space"),
+        (
+            "chicken  chicken",
+            8,
+            "more than one space between chickens",
+        ),
+        (
+            "chicken\tchicken",
+            7,
+            "unexpected '\\t', only chickens separated by single spaces are allowed",
+        ),
+        (
+            "chickenchicken",
+            7,
+            "chickens have to be separated by a space",
+        ),
+        (
+            "chicken chickens",
+            15,
+            "unexpected 's', only chickens separated by single spaces are allowed",
+        ),
+        (
+            "chicken\r\n",
+            7,
+            "unexpected '\\r', only chickens separated by single spaces are allowed",
+        ),
+        (
+            "\u{feff}chicken",
+            0,
+            "unexpected '\\u{feff}', only chickens separated by single spaces are allowed",
+        ),
+        (
+            "chicken\nChicken",
+            8,
+            "unexpected 'C', only chickens separated by single spaces are allowed",
+        ),
+    ] {
+        let err = Program::from_chicken_strict(code).unwrap_err();
+        assert_eq!(
+            (err.offset, err.message.as_str()),
+            (offset, message),
+            "{:?}",
+            code
+        );
+        assert_eq!(err.line, code[..offset].matches('\n').count() + 1);
+    }
+
+    let err = ParseError::from(Program::from_chicken_strict("chicken \n").unwrap_err());
+    assert_eq!(err.to_string(), "line 1: line ends with a space at byte 7");
+}
+
+#[test]
+fn lints() {
+    for example in ["99chickens", "cat", "deadfish", "helloworld", "quine"] {
+        let code = read_to_string(format!("examples/{}.chicken", example)).unwrap();
+        assert_eq!(lint(code), vec![]);
+    }
+
+    let kinds = |code: &str| lint(code).into_iter().map(|l| l.kind).collect::<Vec<_>>();
+
+    assert_eq!(kinds("chickenchikcen"), vec![LintKind::Misspelling]);
+    assert_eq!(kinds("CHICKEN"), vec![LintKind::Misspelling]);
+    assert_eq!(kinds("kitchen"), vec![]);
+    assert_eq!(
+        kinds("chicken\nchicken chicken chicken chicken chicken chicken"),
+        vec![LintKind::TrailingLoad]
+    );
+    // push 20, push 1, jump
+    assert_eq!(
+        kinds(&Program::from_opcodes([30, 11, 8]).to_chicken().unwrap()),
+        vec![LintKind::JumpOutOfBounds]
+    );
+    // push 1, push 1, jump, load 0
+    assert_eq!(
+        kinds(
+            &Program::from_opcodes([11, 11, 8, 6, 0])
+                .to_chicken()
+                .unwrap()
+        ),
+        vec![LintKind::JumpIntoLoad]
+    );
+    // the same, but the offset of the jump is pushed before another jump that lands on it
+    assert_eq!(
+        kinds(
+            &Program::from_opcodes([11, 11, 11, 10, 8, 8, 6, 0])
+                .to_chicken()
+                .unwrap()
+        ),
+        vec![LintKind::JumpIntoLoad]
+    );
+}
+
+#[test]
+fn jump_alignment() {
+    for example in ["99chickens", "cat", "deadfish", "helloworld", "quine"] {
+        let program = Program::from_path(format!("examples/{}.chicken", example)).unwrap();
+        assert!(program.jump_alignment().is_aligned(), "{}", example);
+    }
+
+    // a jump whose offset is read from the input
+    let program = Program::from_asm("push 1\npush 0\nload 1\njump\nexit").unwrap();
+    let alignment = program.jump_alignment();
+    assert_eq!(alignment.unresolved, BTreeSet::from([4]));
+    assert_eq!(alignment.targets[&4], BTreeSet::new());
+    assert!(alignment.is_aligned());
+    assert_eq!(
+        program.to_asm_with_jump_targets(),
+        "push 1\npush 0\nload 1\njump # can land anywhere\nexit\n"
+    );
+
+    let program = Program::from_opcodes([11, 11, 11, 10, 8, 8, 6, 0]);
+    assert_eq!(
+        program.to_asm_with_jump_targets(),
+        "push 1\npush 1\npush 1\npush 0\njump # can land on 5\njump # can land on 7\nload 0 # jump at 5 lands on its operand\n"
+    );
+    assert_eq!(
+        Program::from_asm(program.to_asm_with_jump_targets()).unwrap(),
+        program
+    );
+}
+
+#[test]
+fn conformance() {
+    let cases = ConformanceCase::load_dir("tests/conformance").unwrap();
+    assert!(!cases.is_empty());
+    assert_eq!(cases, ConformanceCase::builtin());
+
+    let failures = cases
+        .iter()
+        .filter_map(|case| {
+            case.run()
+                .err()
+                .map(|reason| format!("{}: {}", case, reason))
+        })
+        .collect::<Vec<_>>();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+
+    let err = ConformanceCase::parse_file(
+        "bad",
+        "[[case]]\nname = \"both\"\nasm = \"\"\noutput = \"\"\nerror = \"runtime\"\n",
+    )
+    .unwrap_err();
+    assert_eq!(err.line, 2);
+}
+
+#[cfg(feature = "macros")]
+#[test]
+fn chicken_macro() {
+    const HELLO_WORLD: &[isize] = &crate::chicken!("examples/helloworld.chicken");
+
+    assert_eq!(
+        HELLO_WORLD,
+        Program::from_chicken(read_to_string("examples/helloworld.chicken").unwrap()).opcodes()
+    );
+
+    assert_eq!(
+        VMBuilder::from_program(crate::include_chicken!("examples/cat.chicken"))
+            .input("meow")
+            .build()
+            .run(),
+        Ok("meow".to_string())
+    );
+}
+
+#[test]
+fn color_modes() {
+    for mode in [ColorMode::Auto, ColorMode::Always, ColorMode::Never] {
+        assert_eq!(mode.to_string().parse(), Ok(mode));
+    }
+
+    assert!("sometimes".parse::<ColorMode>().is_err());
+
+    // each VM colors its own errors, without changing how any other VM's are colored
+    let error = |mode| {
+        VMBuilder::from_opcodes([11])
+            .color(mode)
+            .build()
+            .run()
+            .unwrap_err()
+            .to_string()
+    };
+    let (always, never) = (error(ColorMode::Always), error(ColorMode::Never));
+    assert!(always.starts_with("\x1b[1;31merror: \x1b[0m"));
+    assert!(never.starts_with("error: "));
+    assert!(!never.contains('\x1b'));
+    assert!(!ColorMode::Never.enabled(Stream::Stderr));
+}
+
+#[test]
+fn raw_output() {
+    let build = || {
+        VMBuilder::from_path("examples/cat.chicken")
+            .unwrap()
+            .input("&lt;b&gt; &#104;")
+    };
+
+    assert_eq!(build().build().run(), Ok("<b> h".to_string()));
+    assert_eq!(
+        build().build().run_raw(),
+        Ok("&lt;b&gt; &#104;".to_string())
+    );
+}
+
+#[test]
+fn entity_decoding() {
+    let run = |entity_decoding| {
+        VMBuilder::from_path("examples/cat.chicken")
+            .unwrap()
+            .input("&#104;&#x69; &amp; &#bad; &#")
+            .entity_decoding(entity_decoding)
+            .build()
+            .run()
+    };
+
+    assert_eq!(run(EntityDecoding::All), Ok("hi & &#bad; &#".to_string()));
+    assert_eq!(
+        run(EntityDecoding::Numeric),
+        Ok("hi &amp; &#bad; &#".to_string())
+    );
+    assert_eq!(
+        run(EntityDecoding::None),
+        Ok("&#104;&#x69; &amp; &#bad; &#".to_string())
+    );
+
+    // signs aren't part of an entity, even though they'd parse as part of a number
+    for entities in ["&#+104;", "&#x+68;", "&#-104;"] {
+        assert_eq!(EntityDecoding::Numeric.decode(entities), entities);
+        assert_eq!(EntityDecoding::All.decode(entities), entities);
+    }
+}
+
+#[test]
+fn escapes() {
+    assert_eq!(
+        unescape(r#"a\nb\r\t\0\\\"\'\x41\u{1F414}"#),
+        Ok("a\nb\r\t\0\\\"'A\u{1F414}".to_string())
+    );
+    assert_eq!(unescape("no escapes"), Ok("no escapes".to_string()));
+
+    for (invalid, message) in [
+        (r"\q", "unknown escape sequence \\q"),
+        (r"\x4", "invalid escape sequence \\x4"),
+        (r"\xzz", "invalid escape sequence \\xzz"),
+        (r"\x+1", "invalid escape sequence \\x+1"),
+        (r"\xff", "invalid escape sequence \\xff"),
+        (r"\u41", "expected { after \\u"),
+        (r"\u{41", "unterminated escape sequence \\u{41"),
+        (r"\u{d800}", "invalid escape sequence \\u{d800}"),
+        (r"\u{+41}", "invalid escape sequence \\u{+41}"),
+        (r"trailing\", "trailing backslash"),
+    ] {
+        assert_eq!(unescape(invalid), Err(message.to_string()), "{}", invalid);
+    }
+}
+
+#[test]
+fn output_processors() {
+    let run = |processors: &[OutputProcessor]| {
+        let vm = VMBuilder::from_path("examples/cat.chicken")
+            .unwrap()
+            .input(" &#104;\t&quot;i&quot;\n ")
+            .post_process(processors)
+            .build();
+        (vm.fork().run(), vm.fork().run_output())
+    };
+
+    let (output, text) = run(&[OutputProcessor::DecodeEntities, OutputProcessor::Trim]);
+    assert_eq!(output, Ok("h\t\"i\"".to_string()));
+    assert_eq!(text, Ok(Output::Text("h\t\"i\"".to_string())));
+
+    let (output, _) = run(&[OutputProcessor::JsonEscape, OutputProcessor::Trim]);
+    assert_eq!(output, Ok("&#104;\\t&quot;i&quot;\\n".to_string()));
+
+    let (output, _) = run(&[]);
+    assert_eq!(output, Ok(" &#104;\t&quot;i&quot;\n ".to_string()));
+    assert_eq!(
+        "decode-entities".parse::<OutputProcessor>(),
+        Ok(OutputProcessor::DecodeEntities)
+    );
+    assert!("decode".parse::<OutputProcessor>().is_err());
+}
+
+#[test]
+fn testing_helpers() {
+    use super::testing::{self, Fixture};
+
+    crate::assert_chicken_output!("chicken", "", "chicken");
+    crate::assert_chicken_output!(vec![11, 6, 0], "abc", "abc");
+    assert!(testing::run([1, 2], "").is_err());
+
+    let fixtures = testing::fixtures("examples").unwrap();
+    assert_eq!(
+        fixtures.iter().map(|f| f.name.as_str()).collect::<Vec<_>>(),
+        ["99chickens", "cat", "deadfish", "helloworld", "quine"]
+    );
+    fixtures.iter().for_each(Fixture::assert);
+
+    let cat = Fixture::load("examples/cat.chicken").unwrap();
+    assert_eq!(cat.input, cat.expected);
+    assert!(Fixture::load("examples/nonexistent.chicken").is_err());
+
+    let result = std::panic::catch_unwind(|| testing::assert_output([11, 6, 0], "abc", "abd"));
+    assert!(result.is_err());
+}
+
+#[test]
+fn char_entities() {
+    let run = |opcodes: &[isize], char_entity, strict| {
+        VMBuilder::from_opcodes(opcodes)
+            .char_entity(char_entity)
+            .set_strict(strict)
+            .build()
+            .run_raw()
+    };
+
+    // pushes 255 and converts it to a character
+    assert_eq!(
+        run(&[265, 9, 0], CharEntity::Decimal, false),
+        Ok("&#255;".to_string())
+    );
+    assert_eq!(
+        run(&[265, 9, 0], CharEntity::Hex, false),
+        Ok("&#xFF;".to_string())
+    );
+
+    // converts "chicken" to a character, which isn't one
+    assert_eq!(
+        run(&[1, 9, 0], CharEntity::Hex, false),
+        Ok("&#chicken;".to_string())
+    );
+    assert_eq!(
+        run(&[1, 9, 0], CharEntity::Hex, true).unwrap_err().kind,
+        ErrorKind::Runtime
+    );
+
+    assert_eq!("hex".parse(), Ok(CharEntity::Hex));
+    assert!("octal".parse::<CharEntity>().is_err());
+}
+
+#[test]
+fn byte_output() {
+    // pushes 255 and 0, converts them to bytes, adds them together and exits
+    let build = || VMBuilder::from_opcodes([265, 9, 10, 9, 2, 0]);
+
+    assert_eq!(build().byte_char().build().run_bytes(), Ok(vec![255, 0]));
+
+    // without byte_char it's just the decoded output as UTF-8
+    assert_eq!(
+        VMBuilder::from_opcodes([265, 9, 0]).build().run_bytes(),
+        Ok("ÿ".as_bytes().to_vec())
+    );
+
+    // chars past 255 aren't bytes
+    let err = VMBuilder::from_opcodes([266, 9, 0])
+        .byte_char()
+        .build()
+        .run_bytes()
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Runtime);
+
+    // and neither is input that isn't Latin-1
+    let err = VMBuilder::from_path("examples/cat.chicken")
+        .unwrap()
+        .input("🐔")
+        .byte_char()
+        .build()
+        .run_bytes()
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Runtime);
+}
+
+#[test]
+fn ropes() {
+    // deep enough to overflow the stack if anything walked it recursively
+    let mut rope = Rope::from("");
+    let mut string = String::new();
+    for i in 0..200_000 {
+        let piece = format!("{}{}", i, "+".repeat(64));
+        let shared = rope.clone();
+        rope = shared.concat(piece.as_str().into());
+        string.push_str(&piece);
+    }
+
+    assert_eq!(rope.len(), string.len());
+    assert_eq!(rope.to_string(), string);
+    assert_eq!(rope, string.as_str());
+    assert_eq!(String::from(rope.clone()), string);
+    assert_eq!(Value::String(rope.clone()), Value::from(string));
+    assert_eq!(format!("{:>5}", Rope::from("ab")), "   ab");
+
+    // strings nothing else can see are added onto in place
+    let added = Rope::from("chicken".repeat(10)).concat(" chicken".into());
+    assert_eq!(added, format!("{} chicken", "chicken".repeat(10)).as_str());
+
+    drop(rope);
+}
+
+#[test]
+fn reference_arithmetic() {
+    let values = [
+        Value::Num(6),
+        Value::from("3"),
+        Value::from("chicken"),
+        Value::True,
+        Value::Undefined,
+    ];
+
+    for a in values.iter() {
+        for b in values.iter() {
+            assert_eq!(a + b, a.clone() + b.clone());
+            assert_eq!(a - b, a.clone() - b.clone());
+            assert_eq!(a * b, a.clone() * b.clone());
+            assert_eq!(a / b, a.clone() / b.clone());
+            assert_eq!(a % b, a.clone() % b.clone());
+        }
+
+        assert_eq!(-a, -a.clone());
+    }
+
+    // overflowing wraps around instead of panicking
+    assert_eq!(
+        Value::Num(isize::MAX) + Value::Num(1),
+        Value::Num(isize::MIN)
+    );
+    assert_eq!(
+        Value::Num(isize::MIN) - Value::Num(1),
+        Value::Num(isize::MAX)
+    );
+    assert_eq!(Value::Num(isize::MAX) * Value::Num(2), Value::Num(-2));
+    assert_eq!(
+        Value::Num(isize::MIN) / Value::Num(-1),
+        Value::Num(isize::MIN)
+    );
+    assert_eq!(Value::Num(isize::MIN) % Value::Num(-1), Value::Num(0));
+    assert_eq!(-Value::Num(isize::MIN), Value::Num(isize::MIN));
+}
+
+// values that are likely to be equal to each other, so the properties below get tested on interesting pairs
+fn value() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        (-3..3isize).prop_map(Value::Num),
+        any::<isize>().prop_map(Value::Num),
+        (-3..3isize).prop_map(|n| Value::from(n.to_string())),
+        "[-+0-9a-c ]{0,4}".prop_map(Value::from),
+        (0..3usize).prop_map(Value::Ptr),
+        Just(Value::True),
+        Just(Value::False),
+        Just(Value::Undefined),
+        Just(Value::NaN),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn add_commutes_without_strings(a in value(), b in value()) {
+        prop_assume!(!matches!(a, Value::String(_)) && !matches!(b, Value::String(_)));
+        prop_assert_eq!(&a + &b, &b + &a);
+    }
+
+    #[test]
+    fn multiply_commutes(a in value(), b in value()) {
+        prop_assert_eq!(&a * &b, &b * &a);
+    }
+
+    #[test]
+    fn numbers_round_trip_through_strings(n in any::<isize>()) {
+        let string = Value::from(Value::Num(n).to_string());
+        prop_assert_eq!(string.to_num(), Value::Num(n));
+        prop_assert_eq!(&string, &Value::Num(n));
+    }
+
+    #[test]
+    fn numeric_strings_round_trip_through_numbers(s in "-?[1-9][0-9]{0,17}|0") {
+        let n = Value::from(s.as_str()).to_num();
+        prop_assert_eq!(n.to_string(), s);
+    }
+
+    #[test]
+    fn ordering_agrees_with_equality(a in value(), b in value()) {
+        prop_assume!(!matches!(a, Value::NaN) && !matches!(b, Value::NaN));
+        prop_assert_eq!(a.partial_cmp(&b) == Some(std::cmp::Ordering::Equal), a == b);
+        prop_assert_eq!(a.partial_cmp(&b), b.partial_cmp(&a).map(|o| o.reverse()));
+    }
+
+    #[test]
+    fn programs_round_trip_through_chicken(opcodes in prop::collection::vec(-30..30isize, 1..20)) {
+        let program = Program::from_opcodes(opcodes);
+
+        // programs with negative opcodes can't be written as chicken code at all, rather than being written as a different program
+        match program.to_chicken() {
+            Some(chicken) => prop_assert_eq!(chicken.parse::<Program>(), Ok(program)),
+            None => prop_assert!(program.opcodes().iter().any(|&opcode| opcode < 0)),
+        }
+    }
+
+    #[test]
+    fn chicken_round_trips_through_programs(chicken in "(chicken| |\n|x)*") {
+        let program = chicken.parse::<Program>().unwrap();
+        prop_assert_eq!(program.to_chicken().unwrap().parse::<Program>(), Ok(program));
+    }
+
+    #[test]
+    fn equality_is_an_equivalence(a in value(), b in value(), c in value()) {
+        prop_assert_eq!(&a, &a);
+        prop_assert_eq!(a == b, b == a);
+        if a == b && b == c {
+            prop_assert_eq!(&a, &c);
+        }
+    }
+}
+
+#[test]
+fn value_kinds() {
+    let value = Value::from("chicken");
+    assert_eq!(value.kind(), ValueKind::String);
+    assert_eq!(value.as_str(), Some("chicken"));
+    assert_eq!(value.as_num(), None);
+    assert_eq!(String::try_from(value.clone()), Ok("chicken".to_string()));
+
+    let err = isize::try_from(value).unwrap_err();
+    assert_eq!(err.expected, ValueKind::Num);
+    assert_eq!(err.to_string(), "expected a number, found string");
+
+    assert_eq!(Value::Ptr(2).as_ptr(), Some(2));
+    assert_eq!(bool::try_from(Value::True), Ok(true));
+    assert!(bool::try_from(Value::Num(1)).is_err());
+}
+
+#[test]
+fn value_ordering() {
+    assert!(Value::Num(2) < Value::Num(10));
+    assert_eq!(
+        Value::Undefined.partial_cmp(&Value::Undefined),
+        Some(std::cmp::Ordering::Equal)
+    );
+
+    // NaN is equal to itself, but isn't ordered against anything
+    assert_eq!(Value::NaN, Value::NaN);
+    assert_eq!(Value::NaN.partial_cmp(&Value::NaN), None);
+    assert_eq!(Value::NaN.partial_cmp(&Value::Num(0)), None);
+    assert_eq!(Value::Num(0).partial_cmp(&Value::NaN), None);
+    assert!(!Value::NaN.le(&Value::NaN) && !Value::NaN.ge(&Value::NaN));
+}
+
+#[test]
+fn stack_capacity() {
+    let build = || {
+        VMBuilder::from_path("examples/99chickens.chicken")
+            .unwrap()
+            .input(9)
+    };
+
+    let mut vm = build().build();
+    vm.run().unwrap();
+    let max_stack_depth = vm.stats().max_stack_depth;
+
+    // with enough room up front the stack never has to grow
+    let mut vm = build().stack_capacity(max_stack_depth).build();
+    let capacity = vm.stats().stack_capacity;
+    assert!(capacity >= max_stack_depth);
+    vm.run().unwrap();
+    assert_eq!(vm.stats().stack_capacity, capacity);
+
+    // storing far past the end grows the stack too
+    let mut vm = VMBuilder::from_opcodes([11, 1010, 7]).build();
+    vm.run().ok();
+    assert!(vm.stats().stack_capacity >= 1001);
+    assert_eq!(vm.stats().stack_capacity, vm.stack.capacity());
+}
+
+#[test]
+fn rope_numbers() {
+    for s in [
+        "0",
+        "-12",
+        "+7",
+        "",
+        "1e3",
+        " 1",
+        "chicken",
+        "99999999999999999999",
+    ] {
+        assert_eq!(Rope::from(s).to_num(), s.parse().ok(), "{:?}", s);
+    }
+
+    // long strings can still be numbers if they're padded with zeros
+    let padded = Rope::from("0".repeat(60)).concat(Rope::from("0".repeat(60)).concat("5".into()));
+    assert_eq!(padded.to_num(), Some(5));
+    assert_eq!(Value::String(padded).to_num(), Value::Num(5));
+
+    let long = Rope::from("1".repeat(60)).concat(" chicken".repeat(60).as_str().into());
+    assert_eq!(long.to_num(), None);
+
+    // adding onto a string in place forgets its old number
+    let mut n = Rope::from("4");
+    assert_eq!(n.to_num(), Some(4));
+    n = n.concat("2".into());
+    assert_eq!(n.to_num(), Some(42));
+}
+
+#[test]
+fn strict() {
+    let run = |opcodes: &[isize]| VMBuilder::from_opcodes(opcodes).strict().build().run();
+
+    // a compare with only one value above the program, which pops the exit after it
+    let err = run(&[11, 5, 1, 0]).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::StackUnderflow);
+    assert_eq!(
+        err.message,
+        "compare needs 2 values on the stack, but there is only 1"
+    );
+    assert_eq!(
+        run(&[9]).unwrap_err().message,
+        "char needs a value on the stack, but there aren't any"
+    );
+
+    // which isn't an error without it
+    assert_eq!(
+        VMBuilder::from_opcodes([11, 5, 1, 0]).build().run(),
+        Ok("chicken".to_string())
+    );
+
+    let helloworld = VMBuilder::from_path("examples/helloworld.chicken").unwrap();
+    assert_eq!(
+        helloworld.strict().build().run(),
+        Ok("Hello world".to_string())
+    );
+
+    // loads that would be undefined
+    for (opcodes, message) in [
+        (
+            &[12, 6, 1][..],
+            "load from index 2 of the string at address 1, which only has 1 characters",
+        ),
+        (
+            &[11, 6, 1000],
+            "load from address 1000, past the end of the stack",
+        ),
+        (
+            &[10, 6, 2],
+            "load from address 2, which holds Num(10) instead of a string or pointer",
+        ),
+        (
+            &[10, 11, 3, 6, 0],
+            "load from address 0 at invalid index Some(Num(-1))",
+        ),
+    ] {
+        let result = VMBuilder::from_opcodes(opcodes)
+            .input("a")
+            .strict()
+            .build()
+            .run();
+        assert_eq!(result.unwrap_err().message, message);
+    }
+
+    let err = VMBuilder::from_path("examples/99chickens.chicken")
+        .unwrap()
+        .input(3)
+        .strict()
+        .build()
+        .run()
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::StackUnderflow);
+}
+
+#[test]
+fn warnings() {
+    let warnings = |opcodes: &[isize]| {
+        let mut vm = VMBuilder::from_opcodes(opcodes).warnings().build();
+        assert_eq!(vm.run(), Ok("chicken".to_string()));
+        vm.warnings.unwrap()
+    };
+
+    let underflow = warnings(&[11, 5, 1, 0]);
+    assert_eq!(underflow.len(), 1);
+    assert_eq!(underflow[0].kind, WarningKind::Underflow);
+    assert_eq!(
+        underflow[0].to_string(),
+        "compare needs 2 values on the stack, but there is only 1 at pc 3"
+    );
+
+    let program_write = warnings(&[11, 12, 7, 1]);
+    assert_eq!(program_write[0].kind, WarningKind::ProgramWrite);
+    assert_eq!(
+        program_write[0].to_string(),
+        "store into the program at address 2 at pc 4"
+    );
+
+    assert_eq!(warnings(&[1, 1, 3, 1])[0].kind, WarningKind::NaN);
+    assert!(warnings(&[1]).is_empty());
+
+    // observers are told about every warning as it happens, without collecting them
+    struct Counter(usize);
+
+    impl Observer for Counter {
+        fn on_step(&mut self, _state: &VMState, _info: &StepInfo) -> std::io::Result<()> {
+            Ok(())
+        }
+
+        fn on_warning(&mut self, _state: &VMState, _warning: &Warning) -> std::io::Result<()> {
+            self.0 += 1;
+            Ok(())
+        }
+    }
+
+    let counter = Arc::new(Mutex::new(Counter(0)));
+    let mut vm = VMBuilder::from_opcodes([1, 1, 3, 1, 1, 3, 1])
+        .observer(counter.clone())
+        .build();
+    vm.run().unwrap();
+    assert_eq!(counter.lock().unwrap().0, 2);
+    assert_eq!(vm.warnings, None);
+}
+
+#[test]
+fn run_steps() {
+    let build = || {
+        VMBuilder::from_path("examples/99chickens.chicken")
+            .unwrap()
+            .input(9)
+            .build()
+    };
+
+    let mut vm = build();
+    assert_eq!(vm.run_steps(0), Ok(RunProgress::Running));
+    assert_eq!(vm.stats().steps, 0);
+
+    let output = loop {
+        match vm.run_steps(7).unwrap() {
+            RunProgress::Running => assert_eq!(vm.stats().steps % 7, 0),
+            RunProgress::Exited(output) => break output,
+        }
+    };
+
+    assert_eq!(Ok(output), build().run());
+
+    let mut vm = VMBuilder::from_opcodes([11]).build();
+    assert_eq!(vm.run_steps(10).unwrap_err().kind, ErrorKind::Runtime);
+}
+
+#[test]
+fn run_until() {
+    let build = || {
+        VMBuilder::from_path("examples/99chickens.chicken")
+            .unwrap()
+            .input(3)
+            .build()
+    };
+    let expected = build().run().unwrap();
+
+    let mut vm = build();
+    assert_eq!(
+        vm.run_until(|vm| vm.stats().steps == 100),
+        Ok(RunProgress::Running)
+    );
+    assert_eq!(vm.stats().steps, 100);
+
+    // somewhere inside the loop that prints each verse
+    let pc = vm.program_counter;
+
+    let mut vm = build();
+    let mut stops = 0;
+    let output = loop {
+        match vm.run_to(pc).unwrap() {
+            RunProgress::Running => {
+                assert_eq!(vm.program_counter, pc);
+                stops += 1;
+            }
+            RunProgress::Exited(output) => break output,
+        }
+    };
+    assert_eq!(output, expected);
+    assert!(stops > 1);
+
+    let mut vm = build();
+    assert_eq!(vm.step_until_leaves(0..2), Ok(RunProgress::Running));
+    assert_eq!(vm.stats().steps, 0);
+    assert_eq!(
+        vm.step_until_leaves(0..usize::MAX),
+        Ok(RunProgress::Exited(expected.clone()))
+    );
+
+    let mut vm = build();
+    assert_eq!(vm.run_to(100_000), Ok(RunProgress::Exited(expected)));
+
+    // the operand of a load is never run, so running to it stops at the load
+    let mut vm = VMBuilder::from_opcodes([11, 6, 0]).input("abc").build();
+    assert_eq!(vm.run_to(4), Ok(RunProgress::Running));
+    assert_eq!(vm.program_counter, 3);
+    assert_eq!(vm.stats().steps, 1);
+}
+
+#[test]
+fn instruction_fusion() {
+    for (name, input) in [
+        ("99chickens", "9"),
+        ("cat", "meow"),
+        ("deadfish", "iissiso"),
+        ("helloworld", ""),
+        ("quine", ""),
+    ] {
+        let run = |fuse_instructions| {
+            let mut vm = VMBuilder::from_path(format!("examples/{}.chicken", name))
+                .unwrap()
+                .input(input)
+                .set_fuse_instructions(fuse_instructions)
+                .build();
+            let output = vm.run();
+            (output, vm.stats().clone(), vm.state_hash())
+        };
+
+        assert_eq!(run(true), run(false), "{}", name);
+
+        // stepping one instruction at a time never takes a shortcut, and ends up in the same place
+        let mut vm = VMBuilder::from_path(format!("examples/{}.chicken", name))
+            .unwrap()
+            .input(input)
+            .build();
+        while !vm.exited {
+            vm.step().unwrap();
+        }
+        let stepped = (vm.run(), vm.stats().clone(), vm.state_hash());
+        assert_eq!(stepped, run(true), "{}", name);
+    }
+
+    // stopping partway through ends up in the same place too
+    let run = |fuse_instructions| {
+        let mut vm = VMBuilder::from_path("examples/99chickens.chicken")
+            .unwrap()
+            .input(9)
+            .limits(Limits {
+                max_steps: Some(201),
+                ..Default::default()
+            })
+            .set_fuse_instructions(fuse_instructions)
+            .build();
+        let err = vm.run().unwrap_err();
+        (err, vm.stats().clone())
+    };
+
+    assert_eq!(run(true), run(false));
+}
+
+#[test]
+fn exit_policy() {
+    // pushes 3 and 4, multiplies them and exits
+    let vm = || VMBuilder::from_opcodes([13, 14, 4, 0]);
+
+    let err = vm().build().run().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Runtime);
+    assert_eq!(err.message, "invalid value Some(Num(12)) on exit");
+
+    let stringify = || vm().exit_policy(ExitPolicy::Stringify);
+    assert_eq!(stringify().build().run(), Ok("12".to_string()));
+    assert_eq!(
+        stringify().build().run_to(1000),
+        Ok(RunProgress::Exited("12".to_string()))
+    );
+
+    // the policy doesn't change what run_output returns
+    assert_eq!(vm().build().run_output(), Ok(Output::Value(Value::Num(12))));
+    assert_eq!(
+        VMBuilder::from_path("examples/helloworld.chicken")
+            .unwrap()
+            .build()
+            .run_output(),
+        Ok(Output::Text("Hello world".to_string()))
+    );
+
+    assert_eq!("stringify".parse(), Ok(ExitPolicy::Stringify));
+    assert!("ignore".parse::<ExitPolicy>().is_err());
+}
+
+#[test]
+fn run_full() {
+    let program = read_to_string("examples/99chickens.chicken").unwrap();
+    let vm = VMBuilder::from_chicken(&program).input("3").build();
+
+    let mut expected = vm.fork();
+    let text = expected.run().unwrap();
+
+    let output = vm.fork().run_full().unwrap();
+    assert_eq!(output.text, text);
+    assert_eq!(output.raw, vm.fork().run_raw().unwrap());
+    assert!(output.raw.contains("&#10;"));
+    assert_eq!(output.remaining_stack, expected.data());
+    assert_eq!(&output.stats, expected.stats());
+
+    // it stops on the same errors as run
+    let err = VMBuilder::from_opcodes([13, 14, 4, 0])
+        .build()
+        .run_full()
+        .unwrap_err();
+    assert_eq!(err.message, "invalid value Some(Num(12)) on exit");
+}
+
+#[test]
+fn run_output_io() {
+    // run_full and run_output write the output to the io like run does
+    let io = TestIo::new();
+    let output = VMBuilder::from_opcodes([11, 114, 9, 0])
+        .io(io.clone())
+        .build()
+        .run_full()
+        .unwrap();
+    assert_eq!(io.output(), output.text);
+
+    let io = TestIo::new();
+    let output = VMBuilder::from_opcodes([114, 9, 0])
+        .io(io.clone())
+        .build()
+        .run_output();
+    assert_eq!(output, Ok(Output::Text("h".to_string())));
+    assert_eq!(io.output(), "h");
+
+    // values are written the way they'd be stringified
+    let io = TestIo::new();
+    let output = VMBuilder::from_opcodes([13, 14, 4, 0])
+        .io(io.clone())
+        .build()
+        .run_output();
+    assert_eq!(output, Ok(Output::Value(Value::Num(12))));
+    assert_eq!(io.output(), "12");
+}
+
+#[test]
+fn left_program() {
+    let run = |opcodes: &[isize]| VMBuilder::from_opcodes(opcodes).build().run().unwrap_err();
+
+    // pushes "chicken", then jumps over the exit onto it
+    let err = run(&[1, 11, 12, 8, 0]);
+    assert_eq!(err.kind, ErrorKind::Runtime);
+    assert_eq!(
+        err.message,
+        "execution left the program: ran into String(\"chicken\") at 8, past the exit at 7, and the last jump taken was from 5 by 2"
+    );
+
+    assert_eq!(
+        run(&[11, 20, 8, 0]).message,
+        "execution left the program: ran off the end of the stack at 15, and the last jump taken was from 4 by 10"
+    );
+
+    // jumping backwards onto the input, by 0 - 6
+    assert_eq!(
+        run(&[11, 10, 16, 3, 8, 0]).message,
+        "execution left the program: ran into Undefined at 1, before the start of the program at 2, and the last jump taken was from 6 by -6"
+    );
+}
+
+#[test]
+fn debug_without_pausing() {
+    let output = SharedOutput::default();
+    let mut vm = VMBuilder::from_opcodes([11, 6, 0])
+        .input("abc")
+        .debug()
+        .debug_output(output.clone())
+        .set_debug_pause(false)
+        .build();
+
+    // breakpoints don't stop it either
+    vm.debug_command(DebugCommand::Break(3));
+    assert_eq!(vm.run(), Ok("abc".to_string()));
+
+    let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+    assert!(output.contains("breakpoint at 3"));
+    assert!(output.contains("6: 1 -> \"abc\""));
+    assert!(!output.contains("(chicken)"));
+}
+
+#[test]
+fn progress() {
+    let run = |fuse_instructions| {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let reports_clone = reports.clone();
+
+        let mut vm = VMBuilder::from_path("examples/99chickens.chicken")
+            .unwrap()
+            .input(3)
+            .set_fuse_instructions(fuse_instructions)
+            .on_progress(10, move |progress| {
+                reports_clone.lock().unwrap().push(progress)
+            })
+            .build();
+        vm.run().unwrap();
+
+        let reports = reports.lock().unwrap().clone();
+        (reports, vm.stats().steps)
+    };
+
+    let (reports, steps) = run(false);
+    assert_eq!(reports.len() as u64, steps / 10);
+    assert!(reports
+        .iter()
+        .zip(1..)
+        .all(|(progress, n)| progress.steps == n * 10));
+    assert!(reports.iter().all(|progress| progress.stack_depth > 0));
+
+    // fused instructions can skip past a multiple of 10, but never skip a report
+    let (fused, _) = run(true);
+    assert_eq!(fused.len(), reports.len());
+    assert!(fused
+        .iter()
+        .zip(1..)
+        .all(|(progress, n)| progress.steps / 10 == n));
+}
+
+#[test]
+fn checkpoints() {
+    let path = std::env::temp_dir().join(format!("chicken-checkpoint-{}.json", std::process::id()));
+    let limits = |max_steps| Limits {
+        max_steps: Some(max_steps),
+        ..Default::default()
+    };
+
+    // an infinite loop that's stopped by the step limit, then resumed from the checkpoint written 50 steps before that
+    let mut vm = VMBuilder::from_opcodes([11, 10, 15, 3, 8, 0])
+        .checkpoint_every(100, &path)
+        .limits(limits(250))
+        .build();
+    assert_eq!(vm.run().unwrap_err().kind, ErrorKind::Limit);
+
+    let mut resumed = VMBuilder::from_checkpoint(&path)
+        .unwrap()
+        .limits(limits(50))
+        .build();
+    assert_eq!(resumed.run().unwrap_err().kind, ErrorKind::Limit);
+    assert_eq!(
+        (resumed.program_counter, &resumed.stack),
+        (vm.program_counter, &vm.stack)
+    );
+    std::fs::remove_file(&path).unwrap();
+
+    // a checkpoint that can't be written stops the program
+    let err = VMBuilder::from_opcodes([11, 10, 15, 3, 8, 0])
+        .checkpoint_every(
+            10,
+            std::env::temp_dir().join("no such directory/checkpoint.json"),
+        )
+        .build()
+        .run()
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Io);
+    assert!(err.message.starts_with("error writing checkpoint"));
+}
+
+#[test]
+fn cancellation() {
+    // push 1, then jump back to the start forever
+    let token = CancellationToken::new();
+    let callback_token = token.clone();
+    let mut vm = VMBuilder::from_opcodes([11, 10, 15, 3, 8, 0])
+        .cancellation_token(token.clone())
+        .on_progress(1000, move |_| callback_token.cancel())
+        .build();
+
+    let err = vm.run().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Interrupted);
+    assert_eq!(err.message, "cancelled");
+    assert!((1000..=1001).contains(&vm.stats().steps));
+
+    // forks share the token
+    let mut fork = vm.fork();
+    assert_eq!(fork.step().unwrap_err().kind, ErrorKind::Interrupted);
+
+    // the dump only shows the top of the stack
+    let dump = vm.dump(4);
+    let more = format!("top of the stack:\n    ... {} more\n", vm.stack.len() - 4);
+    assert!(dump.contains(&more));
+    assert_eq!(
+        dump.lines()
+            .skip_while(|line| !line.contains("more"))
+            .count(),
+        5
+    );
+}
+
+#[test]
+fn partial_output() {
+    let run = |opcodes: &[isize]| VMBuilder::from_opcodes(opcodes).build().run().unwrap_err();
+
+    // pushes "h" as an entity, then tries to jump by "chicken"
+    let err = run(&[1, 114, 9, 1, 8]);
+    assert_eq!(
+        err.message,
+        "invalid relative address Some(String(\"chicken\"))"
+    );
+    assert_eq!(err.partial_output.as_deref(), Some("h"));
+    assert!(err.to_string().contains("partial output: \"h\"\n"));
+
+    // the program itself doesn't count as output
+    assert_eq!(run(&[1, 8]).partial_output, None);
+    assert_eq!(run(&[11, 1, 8]).partial_output, None);
+}
+
+#[test]
+fn stack_capture() {
+    // pushes 1 three times, then tries to jump by "chicken"
+    let run = |capture| {
+        VMBuilder::from_opcodes([11, 11, 11, 1, 8])
+            .stack_capture(capture)
+            .build()
+            .run()
+            .unwrap_err()
+    };
+
+    let full = run(StackCapture::Full);
+    assert_eq!(full.stack.len(), 11);
+    assert_eq!(full.stack_omitted, 0);
+    assert!(full.to_string().contains("    ->      6: jump\n"));
+
+    // the window points at the jump that failed rather than where the program counter ended up, and only shows the program
+    let err = VMBuilder::from_opcodes([1, 8, 0])
+        .build()
+        .run()
+        .unwrap_err();
+    assert_eq!(err.program_counter, 4);
+    let text = err.to_string();
+    assert!(
+        text.contains("            2: chicken\n    ->      3: jump\n"),
+        "{}",
+        text
+    );
+    assert!(!text.contains("      1: "), "{}", text);
+
+    let ends = run(StackCapture::Ends(2));
+    assert_eq!(
+        ends.stack,
+        vec![
+            Value::Ptr(0),
+            Value::Undefined,
+            Value::Num(1),
+            Value::Num(1)
+        ]
+    );
+    assert_eq!(ends.stack_omitted, 7);
+    assert_eq!(ends.stack_omitted_range(), 2..9);
+    assert_eq!((ends.kind, &ends.message), (full.kind, &full.message));
+
+    let display = ends.to_string();
+    assert!(display.contains("stack dump: [Ptr(0), Undefined, ... 7 more, Num(1), Num(1)]\n"));
+    assert!(display.contains("the code around the program counter wasn't captured"));
+
+    let none = run(StackCapture::None);
+    assert!(none.stack.is_empty());
+    assert_eq!(none.stack_len(), 11);
+    assert!(none.to_string().contains("stack dump: [... 11 more]\n"));
+
+    // big stacks are cut down when displayed, even when all of it was captured
+    let err = VMBuilder::from_path("examples/99chickens.chicken")
+        .unwrap()
+        .input(9)
+        .limits(Limits {
+            max_steps: Some(200),
+            ..Default::default()
+        })
+        .build()
+        .run()
+        .unwrap_err();
+    let more = format!("... {} more", err.stack.len() - 32);
+    assert!(err.to_string().contains(&more));
+
+    assert_eq!("8".parse(), Ok(StackCapture::Ends(8)));
+    assert_eq!("none".parse(), Ok(StackCapture::None));
+    assert!("some".parse::<StackCapture>().is_err());
+}
+
+#[test]
+fn lean_errors() {
+    let run = |lean_errors| {
+        VMBuilder::from_path("examples/99chickens.chicken")
+            .unwrap()
+            .input(9)
+            .limits(Limits {
+                max_steps: Some(200),
+                ..Default::default()
+            })
+            .set_lean_errors(lean_errors)
+            .build()
+            .run()
+            .unwrap_err()
+    };
+
+    let (full, lean) = (run(false), run(true));
+    assert_eq!(lean.kind, full.kind);
+    assert_eq!(lean.program_counter, full.program_counter);
+    assert_eq!(lean.stack_len(), full.stack_len());
+    assert!(lean.recent_program_counters.is_empty());
+    assert_eq!(lean.partial_output, None);
+    assert_eq!(
+        lean.to_string(),
+        format!(
+            "error: Limit error at program counter {}\n",
+            lean.program_counter
+        )
+    );
+}
+
+#[test]
+fn resume_after_error() {
+    // a store that fails puts back both of the values it popped
+    let mut vm = VMBuilder::from_opcodes([1, 13, 7, 0])
+        .freeze_program()
+        .build();
+    let (stack, stats) = {
+        vm.run_to(4).unwrap();
+        (vm.stack.clone(), vm.stats().clone())
+    };
+    let err = vm.run().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::ProgramWrite);
+    assert_eq!((vm.program_counter, &vm.stack), (4, &stack));
+    assert_eq!(vm.stats().pops, stats.pops);
+
+    // trying again fails the same way, until the instruction is skipped
+    assert_eq!(vm.step().unwrap_err().message, err.message);
+    assert!(vm.resume_after_error());
+    assert_eq!(vm.program_counter, 5);
+    assert!(!vm.resume_after_error());
+
+    // loads skip their operand too
+    let mut vm = VMBuilder::from_opcodes([10, 6, 1000, 1, 0])
+        .strict()
+        .build();
+    assert!(vm.run().is_err());
+    assert_eq!(vm.program_counter, 3);
+    assert!(vm.resume_after_error());
+    assert_eq!(vm.run(), Ok("chicken".to_string()));
+
+    // limits are hit after an instruction has finished, so there's nothing to skip
+    let mut vm = VMBuilder::from_opcodes([11, 11, 0])
+        .limits(Limits {
+            max_stack: Some(7),
+            ..Default::default()
+        })
+        .build();
+    assert_eq!(vm.run().unwrap_err().kind, ErrorKind::Limit);
+    assert!(!vm.resume_after_error());
+
+    // neither is a program exiting with the wrong kind of value, which is left on the stack
+    let mut vm = VMBuilder::from_opcodes([13, 0]).build();
+    assert!(vm.run().is_err());
+    assert_eq!(vm.stack.last(), Some(&Value::Num(3)));
+    assert!(!vm.resume_after_error());
+}
+
+#[test]
+fn reset() {
+    let mut vm = VMBuilder::from_path("examples/99chickens.chicken")
+        .unwrap()
+        .build();
+
+    for n in [3, 0, 5] {
+        vm.reset(n);
+        let mut fresh = VMBuilder::from_path("examples/99chickens.chicken")
+            .unwrap()
+            .input(n)
+            .build();
+        assert_eq!(vm.stack, fresh.stack);
+        assert_eq!(vm.run(), fresh.run());
+        assert_eq!(vm.stats(), fresh.stats());
+    }
+
+    // a program that overwrites itself is put back the way it started
+    let mut vm = VMBuilder::from_opcodes([1, 13, 7, 0]).build();
+    let stack = vm.stack.clone();
+    for _ in 0..3 {
+        vm.step().unwrap();
+    }
+    assert_ne!(vm.stack, stack);
+    vm.reset(Value::Undefined);
+    assert_eq!((vm.program_counter, &vm.stack), (2, &stack));
+    assert!(!vm.exited);
+}
+
+#[test]
+fn trace_diff() {
+    let record = |input: &str| {
+        let tracer = Arc::new(Mutex::new(JsonTracer::new(Vec::new())));
+        VMBuilder::from_path("examples/deadfish.chicken")
+            .unwrap()
+            .input(input)
+            .observer(tracer.clone())
+            .build()
+            .run()
+            .unwrap();
+        let recorded = tracer.lock().unwrap().get_ref().clone();
+        Trace::from_reader(&recorded[..]).unwrap()
+    };
+
+    let trace = record("iissiso");
+    assert_eq!(trace.diff(&record("iissiso")), None);
+
+    // different inputs mean the runs didn't start from the same state
+    let diff = trace.diff(&record("iso")).unwrap();
+    assert_eq!(diff.step, 0);
+    assert!(diff
+        .to_string()
+        .starts_with("runs started from different states"));
+
+    let mut changed = trace.clone();
+    changed.steps[4]["pushed"] = serde_json::json!([1234]);
+    let diff = trace.diff(&changed).unwrap();
+    assert_eq!(diff.step, 5);
+    assert_eq!(diff.second.unwrap()["pushed"], serde_json::json!([1234]));
+
+    let mut shorter = trace.clone();
+    shorter.steps.truncate(10);
+    let diff = shorter.diff(&trace).unwrap();
+    assert_eq!((diff.step, diff.first), (11, None));
+}
+
+#[test]
+fn source_level_debugging() {
+    let asm = "push 1\nloop:\nload 0\njump\nend: exit";
+    let (program, source_map) = Program::from_asm_with_source_map(asm, Some("t.asm")).unwrap();
+    let vm = || {
+        VMBuilder::from_program(program.clone())
+            .input("hi")
+            .source_map(source_map.clone())
+            .build()
+    };
+
+    // errors point at the line of the instruction that failed, and name the labels in the stack dump
+    let err = vm().run().unwrap_err().to_string();
+    assert!(
+        err.contains("    source: t.asm line 4, in loop\n"),
+        "{}",
+        err
+    );
+    assert!(
+        err.contains("    ->      5: jump\n           end:\n            6: exit\n"),
+        "{}",
+        err
+    );
+    assert!(
+        err.contains("Num(11), loop: Num(6), loop+1: Num(0), loop+2: Num(8), end: Num(0)"),
+        "{}",
+        err
+    );
+
+    assert_eq!(
+        "b loop:".parse(),
+        Ok(DebugCommand::BreakAtLabel("loop".to_string()))
+    );
+    assert_eq!(
+        "b store".parse::<DebugCommand>().unwrap(),
+        DebugCommand::BreakOn(InstructionBreakpoint::Mnemonic("store"))
+    );
+
+    let (_, transcript) = vm().run_debug_script("b end:\nb nowhere:\nc\nq\n");
+    assert!(
+        transcript.contains("added breakpoint at 6 (end)\n"),
+        "{}",
+        transcript
+    );
+    assert!(
+        transcript.contains("no label named \"nowhere\"\n"),
+        "{}",
+        transcript
+    );
+    assert!(
+        transcript.contains("      3  6      label loop\n      4  0      loop+1\n"),
+        "{}",
+        transcript
+    );
+
+    let mut vm = vm();
+    assert_eq!(
+        vm.debug_command(DebugCommand::Print(4)),
+        DebugResponse::Output("0 (loop+1)".to_string())
+    );
+    assert!(vm.dump(3).contains("         6 (end): 0\n"));
+
+    // the labels follow the program when a reset moves it
+    let mut vm = VMBuilder::from_program(program.clone())
+        .inputs(vec![Value::Undefined, Value::Undefined])
+        .source_map(source_map.clone())
+        .build();
+    assert_eq!(vm.source_map().unwrap().label_address("end"), Some(7));
+    vm.reset("hi");
+    assert_eq!(vm.source_map().unwrap().label_address("end"), Some(6));
+}
+
+#[test]
+fn metadata() {
+    let header = "# title: echo\n# author: someone\n# input: a line of text\n# requires: extensions, byte-char\n";
+    let metadata = Metadata::from_header(&format!(
+        "{}\n# loop: not metadata\npush 1\n# title: too late\n",
+        header
+    ))
+    .unwrap();
+    assert_eq!(metadata.title.as_deref(), Some("echo"));
+    assert_eq!(metadata.author.as_deref(), Some("someone"));
+    assert_eq!(metadata.input.as_deref(), Some("a line of text"));
+    assert_eq!(
+        metadata.requires,
+        vec![Requirement::Extensions, Requirement::ByteChar]
+    );
+    assert_eq!(metadata.to_header(), header);
+    assert!(Metadata::from_header("push 1").unwrap().is_empty());
+
+    let err = Metadata::from_header("# title: x\n# requires: magic").unwrap_err();
+    assert_eq!(err.line, 2);
+
+    // metadata survives a trip through JSON, and doesn't show up when there isn't any
+    let file = ProgramFile {
+        program: Program::from_opcodes([11, 6, 0]),
+        source_map: None,
+        metadata: metadata.clone(),
+    };
+    let json = serde_json::to_string(&file).unwrap();
+    assert_eq!(serde_json::from_str::<ProgramFile>(&json).unwrap(), file);
+
+    let plain = ProgramFile {
+        metadata: Metadata::default(),
+        ..file
+    };
+    assert_eq!(serde_json::to_string(&plain).unwrap(), "[11,6,0]");
+}
+
+#[test]
+fn linking() {
+    // main jumps into a loop in lib, which never ends.
+    // the jump back is too short to push its offset on its own, so it takes two pushes and an add
+    let main = "import countdown\nexport done\npush 0\npush 1\njump countdown\ndone: exit";
+    let lib = "import done\nexport countdown\ncountdown: push 3\nloop: push 1\npush 1\njump loop";
+    let (program, source_map) = Program::link([("main.asm", main), ("lib.asm", lib)]).unwrap();
+    assert_eq!(source_map.labels["countdown"], 5);
+    assert_eq!(source_map.labels["lib.loop"], 6);
+    assert_eq!(source_map.labels["done"], 4);
+    assert_eq!(&program.opcodes()[8..], &[-11, 25, 2, 8]);
+
+    let location = source_map.lookup(9).unwrap();
+    assert_eq!(
+        (
+            location.file.as_deref(),
+            location.line,
+            location.label.as_deref()
+        ),
+        (Some("lib.asm"), 6, Some("lib.loop"))
+    );
+
+    let vm = VMBuilder::from_program(program)
+        .source_map(source_map)
+        .build();
+    assert_eq!(
+        vm.source_map().unwrap().describe(11).unwrap(),
+        "lib.asm line 6, in lib.loop"
+    );
+
+    // labels that aren't exported are only visible in their own file
+    let err = |files: &[(&str, &str)]| Program::link(files.iter().copied()).unwrap_err();
+    assert_eq!(
+        err(&[("a.asm", "jump b"), ("b.asm", "b: exit")]),
+        LinkError {
+            file: "a.asm".to_string(),
+            line: 1,
+            message: "label \"b\" isn't defined or imported".to_string()
+        }
+    );
+    assert_eq!(
+        err(&[("a.asm", "import b\njump b"), ("b.asm", "b: exit")]).to_string(),
+        "a.asm line 1: imported label \"b\" isn't exported by any file"
+    );
+    assert_eq!(
+        err(&[
+            ("a.asm", "export b\nb: exit"),
+            ("b.asm", "export b\nb: exit")
+        ])
+        .to_string(),
+        "b.asm line 1: label \"b\" is already exported from a.asm"
+    );
+    assert_eq!(
+        err(&[("a.asm", "push 1\npeck")]).to_string(),
+        "a.asm line 2: unknown instruction \"peck\""
+    );
+
+    // a single file is the same as assembling it on its own
+    let asm = "push 1\nloop: load 0\njump loop";
+    assert_eq!(
+        Program::link([("", asm)]).unwrap(),
+        Program::from_asm_with_source_map(asm, None).unwrap()
+    );
+}
+
+#[test]
+fn disassemble() {
+    let listing = Program::from_opcodes([11, 6, 0, 8, 6]).disassemble(None);
+    let operands = listing
+        .iter()
+        .map(|instruction| {
+            (
+                instruction.address,
+                instruction.mnemonic,
+                instruction.operand,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        operands,
+        vec![
+            (0, "push", Some(1)),
+            (1, "load", Some(0)),
+            (3, "jump", None),
+            (4, "load", None)
+        ]
+    );
+    assert!(listing
+        .iter()
+        .all(|instruction| instruction.label.is_none()));
+    assert_eq!(listing[3].to_string(), "     4  load");
+    assert_eq!(
+        serde_json::to_string(&listing[2]).unwrap(),
+        r#"{"address":3,"opcode":8,"mnemonic":"jump"}"#
+    );
+}
+
+#[test]
+fn embedded() {
+    // the debugger doesn't wait on stdin even when it's turned back on
+    let mut vm = VMBuilder::from_opcodes([11, 6, 0])
+        .embedded()
+        .debug()
+        .input("meow")
+        .build();
+    assert_eq!(vm.run(), Ok("meow".to_string()));
+
+    // without an input, it's undefined instead of being read from stdin
+    let mut vm = VMBuilder::from_opcodes([11, 6, 0]).embedded().build();
+    assert_eq!(vm.run_output(), Ok(Output::Value(Value::Undefined)));
+
+    let err = VMBuilder::from_opcodes([1, 11, 10, 16, 3, 8])
+        .embedded()
+        .build()
+        .run()
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Limit);
+    assert_eq!(err.stack.len(), 32);
+}
+
+#[test]
+fn opcode_streams() {
+    let text = "11, 6 # push the input\n0\n";
+    let program = Program::from_opcode_text(text).unwrap();
+
+    assert_eq!(
+        Program::from_opcode_reader(text.as_bytes()).unwrap(),
+        program
+    );
+    assert_eq!(
+        VMBuilder::from_opcode_iter(program.opcodes().iter().copied())
+            .input("meow")
+            .build()
+            .run(),
+        Ok("meow".to_string())
+    );
+
+    let Err(LoadError::Parse(err)) = Program::from_opcode_reader("11\n\n6 load".as_bytes()) else {
+        panic!("invalid opcodes were parsed");
+    };
+    assert_eq!(err.line, 3);
+    assert!(matches!(
+        Program::from_opcode_reader(&[0xff, 0xfe][..]),
+        Err(LoadError::Io(_))
+    ));
+}
+
+#[test]
+fn javascript_numbers() {
+    // what node prints for each of these
+    for (n, expected) in [
+        (1.0, "1"),
+        (-1.5, "-1.5"),
+        (0.1 + 0.2, "0.30000000000000004"),
+        (100.0, "100"),
+        (123456789012345680000.0, "123456789012345680000"),
+        (1e21, "1e+21"),
+        (1.5e300, "1.5e+300"),
+        (0.000001, "0.000001"),
+        (0.0000001, "1e-7"),
+        (-1.25e-10, "-1.25e-10"),
+        (5e-324, "5e-324"),
+        (f64::MAX, "1.7976931348623157e+308"),
+        (2f64.powi(53), "9007199254740992"),
+        (0.0, "0"),
+        (-0.0, "0"),
+        (f64::INFINITY, "Infinity"),
+        (f64::NEG_INFINITY, "-Infinity"),
+        (f64::NAN, "NaN"),
+    ] {
+        assert_eq!(format_number(n), expected);
+    }
+}
+
+proptest! {
+    #[test]
+    fn javascript_numbers_round_trip(n in any::<f64>().prop_filter("finite", |n| n.is_finite())) {
+        prop_assert_eq!(format_number(n).parse::<f64>().unwrap(), n);
+    }
+}
+
+#[test]
+fn minimize() {
+    let underflows = |program: &Program| {
+        let result = VMBuilder::from_program(program.clone())
+            .strict()
+            .limits(Limits {
+                max_steps: Some(1000),
+                ..Default::default()
+            })
+            .build()
+            .run();
+        result.is_err_and(|err| err.kind == ErrorKind::StackUnderflow)
+    };
+
+    // pushes a few things, then adds until the stack runs out
+    let program = Program::from_asm("push 3\npush 4\nchicken\nadd\nadd\nadd\nexit").unwrap();
+    let minimized = program.minimize(underflows);
+    assert!(underflows(&minimized));
+    assert_eq!(minimized.opcodes(), &[2]);
+
+    // the operand of a load always goes with it
+    let program = Program::from_opcodes([11, 6, 0, 2, 2]);
+    let minimized = program.minimize(|program| program.opcodes().contains(&0));
+    assert_eq!(minimized.opcodes(), &[6, 0]);
+
+    // programs that don't reproduce in the first place are left alone
+    let cat = Program::from_opcodes([11, 6, 0]);
+    assert_eq!(cat.minimize(underflows), cat);
+}
+
+#[test]
+fn cost_model() {
+    let hello = || VMBuilder::from_path("examples/helloworld.chicken").unwrap();
+
+    // without a cost model, fuel is steps, including the ones the fast path takes
+    let mut vm = hello().build();
+    vm.run().unwrap();
+    assert_eq!(vm.stats().fuel, vm.stats().steps);
+
+    let costs = "push=0, byte=2".parse::<CostModel>().unwrap();
+    assert_eq!(costs, CostModel::new().cost("push", 0).string_byte_cost(2));
+    assert!("shove=1".parse::<CostModel>().is_err());
+    assert!("add".parse::<CostModel>().is_err());
+
+    let mut vm = hello().cost_model(costs).build();
+    vm.run().unwrap();
+    let pushes = vm.stats().instructions["push"];
+    assert!(vm.stats().fuel > vm.stats().steps - pushes);
+
+    let limited = |max_fuel| {
+        hello()
+            .limits(Limits {
+                max_fuel: Some(max_fuel),
+                ..Default::default()
+            })
+            .build()
+            .run()
+    };
+    let steps = vm.stats().steps;
+    assert!(limited(steps).is_ok());
+    assert_eq!(
+        limited(steps - 1).unwrap_err().message,
+        format!("fuel limit of {} exceeded", steps - 1)
+    );
+
+    // a huge cost saturates instead of overflowing and wrapping back under the limit
+    let huge = || {
+        hello()
+            .cost_model(CostModel::new().cost("push", u64::MAX))
+            .limits(Limits {
+                max_fuel: Some(u64::MAX - 1),
+                ..Default::default()
+            })
+            .build()
+    };
+    let mut vm = huge();
+    assert_eq!(vm.run().unwrap_err().kind, ErrorKind::Limit);
+    assert_eq!(vm.stats().fuel, u64::MAX);
+}
+
+#[test]
+fn stack_regions() {
+    // stores 1 over the exit, which then pushes "chicken" like the instruction before it
+    let mut vm = VMBuilder::from_opcodes([11, 16, 7, 1]).input("abc").build();
+    vm.run().ok();
+
+    let regions = (0..vm.stack.len())
+        .map(|address| vm.region(address))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        regions,
+        vec![
+            StackRegion::Pointer,
+            StackRegion::Input,
+            StackRegion::Program,
+            StackRegion::Program,
+            StackRegion::Program,
+            StackRegion::Program,
+            StackRegion::SelfModified,
+            StackRegion::Data,
+            StackRegion::Data,
+        ]
+    );
+    assert!(vm.to_string().contains("self-modified"));
+    assert_eq!(vm.fork().region(6), StackRegion::SelfModified);
+
+    vm.reset("abc");
+    assert_eq!(vm.region(6), StackRegion::Sentinel);
+
+    let report = Arc::new(Mutex::new(HtmlReport::new(Vec::new(), 1, 10)));
+    VMBuilder::from_chicken("chicken")
+        .observer(report.clone())
+        .build()
+        .run()
+        .unwrap();
+    let html = String::from_utf8(report.lock().unwrap().get_ref().clone()).unwrap();
+    assert!(html.contains(r#""regions":["pointer","input","program","sentinel","data"]"#));
+
+    // snapshots from before the end of the program was saved work it out from the stack, skipping over the operands of loads
+    let snapshot = VMBuilder::from_opcodes([11, 6, 0, 1]).build().snapshot();
+    let mut json = serde_json::to_value(&snapshot).unwrap();
+    json.as_object_mut().unwrap().remove("program_end");
+    let old = serde_json::from_value::<Snapshot>(json).unwrap();
+    assert_eq!(old.program_end, 6);
+    assert_eq!(old, snapshot);
+}
+
+#[test]
+fn html_report_on_error() {
+    // jumps back to the start of the program forever, until it runs into the step limit
+    let report = Arc::new(Mutex::new(HtmlReport::new(Vec::new(), 1, 10)));
+    let mut vm = VMBuilder::from_opcodes([11, 10, 15, 3, 8])
+        .limits(Limits {
+            max_steps: Some(100),
+            ..Default::default()
+        })
+        .observer(report.clone())
+        .build();
+    assert_eq!(vm.run().unwrap_err().kind, ErrorKind::Limit);
+
+    let html = String::from_utf8(report.lock().unwrap().get_ref().clone()).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains(r#""steps":100"#));
+
+    // it's only written once, even if the VM is run again afterwards
+    assert!(vm.run().is_err());
+    let html = String::from_utf8(report.lock().unwrap().get_ref().clone()).unwrap();
+    assert_eq!(html.matches("<!DOCTYPE html>").count(), 1);
+}
@@ -0,0 +1,217 @@
+//! helpers for testing Chicken programs from other crates, like the [assert_chicken_output](crate::assert_chicken_output) macro
+//! and [Fixture] for loading programs with their inputs and expected outputs from files.
+//! fixtures follow the same layout as `chicken test`, where `name.chicken` is run with `name.input` as its input if it exists
+//! and should output whatever is in `name.expected`, with a single trailing newline ignored in both
+//!
+//! # Example
+//!
+//! ```rust
+//! use chicken::{assert_chicken_output, testing::Fixture};
+//!
+//! assert_chicken_output!([11, 6, 0], "meow", "meow");
+//!
+//! for fixture in chicken::testing::fixtures("examples").unwrap() {
+//!     fixture.assert();
+//! }
+//! ```
+
+use crate::{ChickenError, Limits, LoadError, Program, VMBuilder, Value};
+use std::path::{Path, PathBuf};
+
+/// how many instructions a program run by this module can execute, so a broken test fails instead of hanging.
+/// this is the same as the default for `chicken test`
+pub const MAX_STEPS: u64 = 100_000_000;
+
+/// something that can be run as a Chicken program in a test: a [Program], chicken code as a string or a list of opcodes
+pub trait TestProgram {
+    /// turns this into a program
+    fn into_program(self) -> Program;
+}
+
+impl TestProgram for Program {
+    fn into_program(self) -> Program {
+        self
+    }
+}
+
+impl TestProgram for &Program {
+    fn into_program(self) -> Program {
+        self.clone()
+    }
+}
+
+impl TestProgram for &str {
+    fn into_program(self) -> Program {
+        Program::from_chicken(self)
+    }
+}
+
+impl TestProgram for String {
+    fn into_program(self) -> Program {
+        Program::from_chicken(self)
+    }
+}
+
+impl TestProgram for Vec<isize> {
+    fn into_program(self) -> Program {
+        Program::from_opcodes(self)
+    }
+}
+
+impl TestProgram for &[isize] {
+    fn into_program(self) -> Program {
+        Program::from_opcodes(self)
+    }
+}
+
+impl<const N: usize> TestProgram for [isize; N] {
+    fn into_program(self) -> Program {
+        Program::from_opcodes(self)
+    }
+}
+
+/// runs a program with the given input and a limit of [MAX_STEPS] instructions, returning its output
+pub fn run<P: TestProgram, I: Into<Value>>(program: P, input: I) -> Result<String, ChickenError> {
+    VMBuilder::from_program(program.into_program())
+        .input(input)
+        .limits(Limits {
+            max_steps: Some(MAX_STEPS),
+            ..Default::default()
+        })
+        .build()
+        .run()
+}
+
+/// runs a program like [run] and panics if it fails or its output isn't what was expected.
+/// this is what [assert_chicken_output](crate::assert_chicken_output) calls
+#[track_caller]
+pub fn assert_output<P: TestProgram, I: Into<Value>>(program: P, input: I, expected: &str) {
+    match run(program, input) {
+        Ok(output) => assert!(
+            output == expected,
+            "program output didn't match\n  expected: {:?}\n    output: {:?}",
+            expected,
+            output
+        ),
+        Err(err) => panic!("program failed: {}", err),
+    }
+}
+
+/// asserts that a Chicken program outputs what's expected when it's given an input, panicking with the output or the error if it doesn't.
+/// the program can be a [Program](crate::Program), chicken code as a string or a list of opcodes, see [TestProgram](crate::testing::TestProgram).
+/// programs are limited to [MAX_STEPS](crate::testing::MAX_STEPS) instructions
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::assert_chicken_output;
+///
+/// assert_chicken_output!(include_str!("../examples/cat.chicken"), "meow", "meow");
+/// assert_chicken_output!(chicken::Program::from_path("examples/helloworld.chicken").unwrap(), "", "Hello world");
+/// ```
+#[macro_export]
+macro_rules! assert_chicken_output {
+    ($program:expr, $input:expr, $expected:expr $(,)?) => {
+        $crate::testing::assert_output($program, $input, $expected)
+    };
+}
+
+/// a program loaded from a file for testing, along with its input and expected output from the files next to it
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    /// the name of the file without its extension
+    pub name: String,
+
+    /// the file the program was loaded from
+    pub path: PathBuf,
+
+    /// the program itself
+    pub program: Program,
+
+    /// the contents of `name.input`, if there is one
+    pub input: Option<String>,
+
+    /// the contents of `name.expected`, if there is one
+    pub expected: Option<String>,
+}
+
+impl Fixture {
+    /// loads a program from a file in any format [Program::from_path] can read, along with the `.input` and `.expected` files next to it
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chicken::testing::Fixture;
+    ///
+    /// let fixture = Fixture::load("examples/cat.chicken").unwrap();
+    /// assert_eq!(fixture.name, "cat");
+    /// assert_eq!(fixture.run().ok(), fixture.expected);
+    /// ```
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+
+        Ok(Self {
+            name: (path.file_stem().unwrap_or_default())
+                .to_string_lossy()
+                .into_owned(),
+            path: path.to_path_buf(),
+            program: Program::from_path(path)?,
+            input: read_sidecar(&path.with_extension("input"))?,
+            expected: read_sidecar(&path.with_extension("expected"))?,
+        })
+    }
+
+    /// runs the program with its input, or an empty one if it doesn't have one, like [run]
+    pub fn run(&self) -> Result<String, ChickenError> {
+        run(&self.program, self.input.clone().unwrap_or_default())
+    }
+
+    /// runs the program and panics if it fails, or if its output isn't what's in its `.expected` file when it has one
+    #[track_caller]
+    pub fn assert(&self) {
+        match (self.run(), &self.expected) {
+            (Ok(output), Some(expected)) => assert!(
+                output == *expected,
+                "{} output didn't match\n  expected: {:?}\n    output: {:?}",
+                self.path.display(),
+                expected,
+                output
+            ),
+            (Ok(_), None) => {}
+            (Err(err), _) => panic!("{} failed: {}", self.path.display(), err),
+        }
+    }
+}
+
+/// loads every `.chicken` file in a directory that has a `.expected` file next to it, sorted by name, like `chicken test` does
+pub fn fixtures<P: AsRef<Path>>(dir: P) -> Result<Vec<Fixture>, LoadError> {
+    let mut fixtures = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.extension().is_some_and(|e| e == "chicken")
+            && path.with_extension("expected").exists()
+        {
+            fixtures.push(Fixture::load(path)?);
+        }
+    }
+
+    fixtures.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(fixtures)
+}
+
+// reads a file next to a fixture if it exists, ignoring a single trailing newline
+fn read_sidecar(path: &Path) -> Result<Option<String>, LoadError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let mut text = std::fs::read_to_string(path)?;
+    if text.ends_with('\n') {
+        text.pop();
+    }
+
+    Ok(Some(text))
+}
@@ -0,0 +1,104 @@
+use crate::{color::paint, display_cell, Observer, StepInfo, VMState};
+use colored::Colorize;
+use std::{
+    io::{self, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// animates the stack in a terminal as a program runs, redrawing it after every step.
+/// the cells around the instruction that was just executed are shown above the stack, with that instruction highlighted in yellow,
+/// and the cells pushed by it are highlighted in green. the rest are colored by their [StackRegion](crate::StackRegion).
+/// steps are slowed down to the given number of steps per second so they can actually be followed
+pub struct Visualizer<W: Write> {
+    writer: W,
+    frame_time: Duration,
+    rows: usize,
+    steps: u64,
+    last_frame: Option<Instant>,
+    color: bool,
+}
+
+impl<W: Write> Visualizer<W> {
+    /// creates a new visualizer that draws to the given writer, showing the topmost `rows` cells of the stack
+    pub fn new(writer: W, steps_per_second: f64, rows: usize) -> Self {
+        Self {
+            writer,
+            frame_time: Duration::from_secs_f64(1.0 / steps_per_second.max(f64::MIN_POSITIVE))
+                .min(Duration::from_secs(3600)),
+            rows,
+            steps: 0,
+            last_frame: None,
+            color: true,
+        }
+    }
+
+    /// sets whether the cells are colored, which they are by default since the screen is cleared with escape codes anyway
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// consumes this visualizer, returning the writer it was drawing to
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Observer for Visualizer<W> {
+    fn on_step(&mut self, state: &VMState, info: &StepInfo) -> io::Result<()> {
+        self.steps += 1;
+
+        if let Some(last_frame) = self.last_frame {
+            if let Some(remaining) = self.frame_time.checked_sub(last_frame.elapsed()) {
+                thread::sleep(remaining);
+            }
+        }
+        self.last_frame = Some(Instant::now());
+
+        let len = state.stack.len();
+        let bottom = len.saturating_sub(self.rows);
+        let pushed_from = len.saturating_sub(info.pushed.len());
+
+        // clear the screen and move the cursor to the top left
+        let mut frame = "\x1b[2J\x1b[H".to_string();
+
+        frame += &format!(
+            "step {}, pc {}: {}\n",
+            self.steps,
+            info.program_counter,
+            paint(
+                (info.instruction)
+                    .map_or_else(|| "invalid".to_string(), |i| i.to_string())
+                    .bold(),
+                self.color
+            )
+        );
+
+        let pc = info.program_counter;
+        frame += "program:\n";
+        for address in pc.saturating_sub(2)..(pc + 3).min(len) {
+            let line = format!("{:>8}: {}", address, display_cell(&state.stack[address]));
+
+            match address == pc {
+                true => frame += &paint(line.black().on_yellow(), self.color),
+                false => frame += &state.region(address).paint(&line, self.color),
+            }
+            frame.push('\n');
+        }
+
+        frame += &format!("stack (top {} of {}):\n", len - bottom, len);
+        for address in (bottom..len).rev() {
+            let line = format!("{:>8}: {}", address, display_cell(&state.stack[address]));
+
+            match address >= pushed_from {
+                true => frame += &paint(line.green(), self.color),
+                false => frame += &state.region(address).paint(&line, self.color),
+            }
+            frame.push('\n');
+        }
+
+        self.writer.write_all(frame.as_bytes())?;
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// the kind of a [Warning]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningKind {
+    /// an instruction popped more values than there were above the program, so it got part of the program or undefined instead.
+    /// this is an [ErrorKind::StackUnderflow](crate::ErrorKind::StackUnderflow) error with [VMBuilder::strict](crate::VMBuilder::strict) set
+    Underflow,
+
+    /// a Store wrote into the program. this is an [ErrorKind::ProgramWrite](crate::ErrorKind::ProgramWrite) error
+    /// with [VMBuilder::freeze_program](crate::VMBuilder::freeze_program) set
+    ProgramWrite,
+
+    /// an instruction made a NaN, like by subtracting a string that isn't a number
+    NaN,
+}
+
+/// something a program did that isn't an error, but usually means it's broken, like popping values that aren't there.
+/// these are passed to [Observer::on_warning](crate::Observer::on_warning) as they happen, and collected in [VMState::warnings](crate::VMState::warnings)
+/// if [VMBuilder::warnings](crate::VMBuilder::warnings) is set
+///
+/// # Example
+///
+/// ```rust
+/// use chicken::{VMBuilder, WarningKind};
+///
+/// // subtracts "chicken" from "chicken", then exits with a string
+/// let mut vm = VMBuilder::from_opcodes([1, 1, 3, 1]).warnings().build();
+/// vm.run().unwrap();
+///
+/// let warnings = vm.warnings.unwrap();
+/// assert_eq!(warnings[0].kind, WarningKind::NaN);
+/// assert_eq!(warnings[0].to_string(), "subtract produced NaN at pc 4");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Warning {
+    /// what kind of warning this is
+    pub kind: WarningKind,
+
+    /// the address of the instruction that caused it
+    #[serde(rename = "pc")]
+    pub program_counter: usize,
+
+    /// a description of what happened
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at pc {}", self.message, self.program_counter)
+    }
+}